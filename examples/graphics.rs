@@ -1,5 +1,3 @@
-#![feature(allocator_api)]
-
 use stupidalloc::StupidAlloc;
 #[global_allocator]
 static GLOBAL: StupidAlloc = StupidAlloc;
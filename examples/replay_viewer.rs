@@ -0,0 +1,155 @@
+//! Plays the evolution of a heap back in graphical windows, from a folder of
+//! snapshots taken one after the other with `StupidAlloc::snapshot_heap()`,
+//! by the same run of a program:
+//!
+//! ```text
+//! recording/
+//!     0000/
+//!     0001/
+//!     ...
+//! ```
+//!
+//! Every allocation of the recording gets a window of its own, which opens
+//! when the allocation shows up, follows its contents from one snapshot to the
+//! next, and closes when it's gone.
+//!
+//! ```sh
+//! cargo run --example replay_viewer --features graphics -- recording 4
+//! ```
+//!
+//! The last argument is the number of snapshots shown per second, 2 by
+//! default. While it plays, type `+` or `-` and enter to go faster or slower,
+//! `p` to pause or carry on, and `q` to stop.
+
+#![cfg_attr(all(feature = "graphics", feature = "nightly"), feature(allocator_api))]
+
+#[cfg(all(feature = "graphics", feature = "nightly"))]
+fn main() -> std::io::Result<()> {
+    use std::{
+        collections::HashMap,
+        io::BufRead,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use stupidalloc::{Snapshot, StupidAlloc};
+
+    let mut args = std::env::args().skip(1);
+    let Some(dir) = args.next().map(PathBuf::from) else {
+        eprintln!("usage: replay_viewer <folder of snapshots> [snapshots per second]");
+        std::process::exit(1);
+    };
+    let per_second = args.next().and_then(|arg| arg.parse().ok()).unwrap_or(2.0);
+
+    let mut steps = std::fs::read_dir(&dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    steps.retain(|path| path.is_dir());
+    steps.sort();
+    let snapshots = steps
+        .iter()
+        .map(Snapshot::load)
+        .collect::<std::io::Result<Vec<_>>>()?;
+    if snapshots.is_empty() {
+        eprintln!("no snapshots in {}", dir.display());
+        std::process::exit(1);
+    }
+
+    // the delay between snapshots in microseconds, and whether it's paused or
+    // stopped, changed from the standard input.
+    let delay = Arc::new(AtomicU64::new((1_000_000.0 / per_second) as u64));
+    let paused = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::new(AtomicBool::new(false));
+    {
+        let (delay, paused, stopped) = (delay.clone(), paused.clone(), stopped.clone());
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line.as_deref().map(str::trim) {
+                    Ok("+") => {
+                        let _ = delay.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |d| {
+                            Some((d / 2).max(1_000))
+                        });
+                    }
+                    Ok("-") => {
+                        let _ = delay.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |d| {
+                            Some(d.saturating_mul(2))
+                        });
+                    }
+                    Ok("p") => {
+                        paused.fetch_xor(true, Ordering::SeqCst);
+                    }
+                    Ok("q") | Err(_) => break,
+                    _ => {}
+                }
+            }
+            stopped.store(true, Ordering::SeqCst);
+        });
+    }
+
+    // the copies of the allocations of the recording, by their address in
+    // the recording.
+    let mut live = HashMap::<usize, Box<[u8], StupidAlloc>>::new();
+
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        while paused.load(Ordering::SeqCst) && !stopped.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+
+        println!(
+            "{}/{}: {} ({} allocations)",
+            i + 1,
+            snapshots.len(),
+            snapshot.dir().display(),
+            snapshot.allocations().len()
+        );
+
+        // freed allocations close their window on their own.
+        live.retain(|addr, _| {
+            snapshot
+                .allocations()
+                .iter()
+                .any(|allocation| allocation.addr == *addr)
+        });
+
+        for allocation in snapshot.allocations() {
+            let contents = snapshot.contents_of(allocation)?;
+            // zero-sized allocations have nothing to show.
+            if contents.is_empty() {
+                live.remove(&allocation.addr);
+                continue;
+            }
+
+            match live.get_mut(&allocation.addr) {
+                Some(copy) if copy.len() == contents.len() => copy.copy_from_slice(&contents),
+                // new, or resized: a new copy, with a window of its own.
+                _ => {
+                    let mut copy = Vec::with_capacity_in(contents.len(), StupidAlloc);
+                    copy.extend_from_slice(&contents);
+                    let copy = copy.into_boxed_slice();
+                    StupidAlloc.open_window_of(&*copy, StupidAlloc::AUTO_COLUMNS);
+                    live.insert(allocation.addr, copy);
+                }
+            }
+        }
+
+        std::thread::sleep(Duration::from_micros(delay.load(Ordering::SeqCst)));
+    }
+
+    println!("done, type q and enter to close the windows");
+    while !stopped.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+#[cfg(not(all(feature = "graphics", feature = "nightly")))]
+fn main() {
+    eprintln!("This example plays snapshots back in graphical windows. Running it without the `graphics` and `nightly` features is useless.");
+}
@@ -0,0 +1,604 @@
+use std::{fmt, io, time::Duration};
+
+#[cfg(target_os = "linux")]
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Condvar, Mutex, Once, OnceLock,
+    },
+    time::Instant,
+};
+
+use crate::StupidAlloc;
+#[cfg(target_os = "linux")]
+use crate::{error, page_size, process_id, residency, system_scope, threads, STUPID_MAP};
+
+/// Samples which memory pages of an allocation get accessed, set with
+/// [`StupidAlloc::profile_accesses_of()`], without touching the code doing
+/// the accesses: every interval, the pages are made inaccessible with
+/// `mprotect`, and the first access to each of them faults, which gets it
+/// counted and accessible again.
+///
+/// ```
+/// use std::time::Duration;
+/// use stupidalloc::AccessProfiler;
+///
+/// // the last minute, ten times a second.
+/// let profiler = AccessProfiler::new().interval(Duration::from_millis(100)).samples(600);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccessProfiler {
+    interval: Duration,
+    samples: usize,
+}
+
+impl AccessProfiler {
+    /// A sample every 100 ms, keeping the last 100 of them.
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_millis(100),
+            samples: 100,
+        }
+    }
+
+    /// Takes a sample every `interval`.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Keeps the last `samples` samples, and forgets older ones.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+}
+
+impl Default for AccessProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What an [`AccessProfiler`] found out about an allocation, returned by
+/// [`StupidAlloc::access_profile_of()`]. Allocations are cut in cells, a page
+/// each, or more for allocations of more than 4096 pages, starting from the
+/// page the data starts on.
+///
+/// It displays as a heatmap, one line per sample and one column per cell, or
+/// per group of cells for big allocations, the darkest ones being the most
+/// accessed, followed by the heat of each column over all samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessProfile {
+    /// The number of bytes of each cell.
+    pub cell_size: usize,
+    /// One sample per interval, oldest first: whether each cell was accessed
+    /// during the interval.
+    pub samples: Vec<Vec<bool>>,
+}
+
+impl AccessProfile {
+    /// Returns the number of samples each cell was accessed in.
+    pub fn heat(&self) -> Vec<usize> {
+        let cells = self.samples.iter().map(Vec::len).max().unwrap_or(0);
+        (0..cells)
+            .map(|cell| {
+                self.samples
+                    .iter()
+                    .filter(|sample| sample.get(cell) == Some(&true))
+                    .count()
+            })
+            .collect()
+    }
+}
+
+// the width of the heatmap, in columns, and the shades of its cells, from
+// never accessed to always accessed.
+const HEATMAP_WIDTH: usize = 64;
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+// the shade of `part` out of `whole`.
+fn shade(part: usize, whole: usize) -> char {
+    let level = (part * (SHADES.len() - 1)).div_ceil(whole.max(1));
+    SHADES[level.min(SHADES.len() - 1)] as char
+}
+
+impl fmt::Display for AccessProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let heat = self.heat();
+        let group = heat.len().div_ceil(HEATMAP_WIDTH).max(1);
+        writeln!(
+            f,
+            "{} samples of {} cells of {} bytes, {group} per column",
+            self.samples.len(),
+            heat.len(),
+            self.cell_size
+        )?;
+
+        for sample in &self.samples {
+            let row = sample
+                .chunks(group)
+                .map(|cells| shade(cells.iter().filter(|&&cell| cell).count(), cells.len()))
+                .collect::<String>();
+            writeln!(f, "|{row}|")?;
+        }
+
+        let total = heat
+            .chunks(group)
+            .map(|cells| shade(cells.iter().sum(), cells.len() * self.samples.len()))
+            .collect::<String>();
+        writeln!(f, "+{}+", "-".repeat(total.chars().count()))?;
+        writeln!(f, "|{total}|")
+    }
+}
+
+// how many allocations can be profiled at once, and how many cells each one
+// gets.
+#[cfg(target_os = "linux")]
+const SLOTS: usize = 64;
+#[cfg(target_os = "linux")]
+const CELLS: usize = 4096;
+
+// the pages of a profiled allocation, as the fault handler sees them. it can't
+// lock anything, so it's all atomics, and the slots are never freed.
+#[cfg(target_os = "linux")]
+struct Slot {
+    // the first page of the armed pages, 0 when they're not armed, and the
+    // end of the last one.
+    start: AtomicUsize,
+    end: AtomicUsize,
+    pages_per_cell: AtomicUsize,
+    // one bit per cell, set when it was accessed since the last sample.
+    touched: [AtomicU64; CELLS / 64],
+}
+
+#[cfg(target_os = "linux")]
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            start: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+            pages_per_cell: AtomicUsize::new(1),
+            touched: [const { AtomicU64::new(0) }; CELLS / 64],
+        }
+    }
+
+    // makes the `len` bytes of data at `addr` inaccessible, to hear of the
+    // first access to each of their pages. must be called with the registry
+    // locked, for them not to be freed in the meantime.
+    fn arm(&self, addr: usize, len: usize) {
+        let start = addr / page_size() * page_size();
+        let pages = residency::pages(addr, len);
+        self.end
+            .store(start + pages * page_size(), Ordering::SeqCst);
+        self.pages_per_cell
+            .store(pages.div_ceil(CELLS).max(1), Ordering::SeqCst);
+        for word in &self.touched {
+            word.store(0, Ordering::SeqCst);
+        }
+        self.start.store(start, Ordering::SeqCst);
+        self.rearm();
+    }
+
+    // makes the armed pages inaccessible again, after a sample.
+    fn rearm(&self) {
+        let start = self.start.load(Ordering::SeqCst);
+        let end = self.end.load(Ordering::SeqCst);
+        if start != 0 && end > start {
+            unsafe { libc::mprotect(start as *mut libc::c_void, end - start, libc::PROT_NONE) };
+        }
+    }
+
+    // the cells accessed since the last sample, out of the first `cells`.
+    fn harvest(&self, cells: usize) -> Vec<bool> {
+        let words = self
+            .touched
+            .iter()
+            .map(|word| word.swap(0, Ordering::SeqCst))
+            .collect::<Vec<_>>();
+        (0..cells)
+            .map(|cell| words[cell / 64] >> (cell % 64) & 1 == 1)
+            .collect()
+    }
+
+    // the number of cells of the armed pages.
+    fn cells(&self) -> usize {
+        let pages =
+            (self.end.load(Ordering::SeqCst) - self.start.load(Ordering::SeqCst)) / page_size();
+        pages.div_ceil(self.pages_per_cell.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(target_os = "linux")]
+static ARMED: [Slot; SLOTS] = [const { Slot::new() }; SLOTS];
+
+// an allocation being profiled, known by its file, which follows it when it
+// moves.
+#[cfg(target_os = "linux")]
+struct Profile {
+    path: PathBuf,
+    slot: usize,
+    profiler: AccessProfiler,
+    cells: usize,
+    cell_size: usize,
+    samples: VecDeque<Vec<bool>>,
+    due: Instant,
+    // the allocation wasn't in the registry on the last sample: it may have
+    // been moving, or it's gone.
+    missing: bool,
+}
+
+// the profiles, which only ever get allocated in System, and the condvar
+// waking the sampler up when there's a new one.
+#[cfg(target_os = "linux")]
+static PROFILES: (Mutex<Vec<Profile>>, Condvar) = (Mutex::new(Vec::new()), Condvar::new());
+
+// the process the sampler thread runs in, like the reaper's.
+#[cfg(target_os = "linux")]
+static SAMPLER_PID: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(target_os = "linux")]
+static INSTALL_HANDLER: Once = Once::new();
+
+// the handler of SIGSEGV that was there before ours, for the faults that
+// aren't ours.
+#[cfg(target_os = "linux")]
+static PREVIOUS: OnceLock<libc::sigaction> = OnceLock::new();
+
+#[cfg(target_os = "linux")]
+thread_local! {
+    // the last fault the handler didn't know about, see on_fault.
+    static UNKNOWN_FAULT: Cell<usize> = const { Cell::new(0) };
+}
+
+// counts an access to the page containing `addr`, and makes it accessible
+// again, if it's armed.
+#[cfg(target_os = "linux")]
+fn touch(addr: usize) -> bool {
+    let Some(slot) = ARMED.iter().find(|slot| {
+        let start = slot.start.load(Ordering::SeqCst);
+        start != 0 && (start..slot.end.load(Ordering::SeqCst)).contains(&addr)
+    }) else {
+        return false;
+    };
+
+    let page = addr / page_size() * page_size();
+    let cell = (page - slot.start.load(Ordering::SeqCst))
+        / page_size()
+        / slot.pages_per_cell.load(Ordering::SeqCst);
+    if let Some(word) = slot.touched.get(cell / 64) {
+        word.fetch_or(1 << (cell % 64), Ordering::SeqCst);
+    }
+    unsafe {
+        libc::mprotect(
+            page as *mut libc::c_void,
+            page_size(),
+            libc::PROT_READ | libc::PROT_WRITE,
+        )
+    };
+    true
+}
+
+// the SIGSEGV handler. it can't allocate nor lock anything.
+#[cfg(target_os = "linux")]
+extern "C" fn on_fault(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let addr = unsafe { (*info).si_addr() } as usize;
+    if touch(addr) {
+        return;
+    }
+
+    // the pages may have been disarmed between the fault and now, so the
+    // access gets one more try, which faults again if it's a real crash.
+    if UNKNOWN_FAULT.with(|fault| fault.replace(addr)) != addr {
+        return;
+    }
+    UNKNOWN_FAULT.with(|fault| fault.set(0));
+
+    // not ours: whoever was there before gets it, like a stack overflow.
+    match PREVIOUS.get() {
+        Some(previous) if previous.sa_sigaction == libc::SIG_IGN => {}
+        Some(previous) if previous.sa_sigaction != libc::SIG_DFL => unsafe {
+            if previous.sa_flags & libc::SA_SIGINFO != 0 {
+                let handler = std::mem::transmute::<
+                    usize,
+                    extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
+                >(previous.sa_sigaction);
+                handler(signal, info, context);
+            } else {
+                let handler =
+                    std::mem::transmute::<usize, extern "C" fn(libc::c_int)>(previous.sa_sigaction);
+                handler(signal);
+            }
+        },
+        // the fault happens again, with nobody to catch it this time.
+        _ => unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        },
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn install_handler() {
+    INSTALL_HANDLER.call_once(|| unsafe {
+        let mut action = std::mem::zeroed::<libc::sigaction>();
+        action.sa_sigaction = on_fault as *const () as usize;
+        // stack overflows are handled on an alternate stack, and so are ours.
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        let mut previous = std::mem::zeroed::<libc::sigaction>();
+        if libc::sigaction(libc::SIGSEGV, &action, &mut previous) == 0 {
+            let _ = PREVIOUS.set(previous);
+        }
+    });
+}
+
+// gives the pages of the allocation at `addr` back to the program for good,
+// before it's freed or resized. no allocations, the registry may well be
+// locked.
+pub fn disarm(addr: usize) {
+    #[cfg(target_os = "linux")]
+    {
+        let start = addr / page_size() * page_size();
+        for slot in &ARMED {
+            let end = slot.end.load(Ordering::SeqCst);
+            if slot
+                .start
+                .compare_exchange(start, 0, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+                && end > start
+            {
+                unsafe {
+                    libc::mprotect(
+                        start as *mut libc::c_void,
+                        end - start,
+                        libc::PROT_READ | libc::PROT_WRITE,
+                    )
+                };
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = addr;
+}
+
+// takes the samples that are due, and arms the pages again. runs in the
+// sampler thread, where everything goes to System anyway.
+#[cfg(target_os = "linux")]
+fn tick(profiles: &mut Vec<Profile>, now: Instant) {
+    let sampled = (0..profiles.len())
+        .filter(|&i| profiles[i].due <= now)
+        .collect::<Vec<_>>();
+
+    for &i in &sampled {
+        let profile = &mut profiles[i];
+        let sample = ARMED[profile.slot].harvest(profile.cells);
+        profile.samples.push_back(sample);
+        while profile.samples.len() > profile.profiler.samples {
+            profile.samples.pop_front();
+        }
+        profile.due = now + profile.profiler.interval;
+    }
+
+    let mut dead = Vec::new();
+    {
+        let map = STUPID_MAP.read_all();
+        for &i in &sampled {
+            let profile = &mut profiles[i];
+            let slot = &ARMED[profile.slot];
+            let armed = slot.start.load(Ordering::SeqCst) != 0;
+            let live = map.iter().find(|(_, handle)| handle.path == profile.path);
+
+            match live {
+                // sampled just now.
+                Some(_) if armed => slot.rearm(),
+                // resized, and disarmed for it.
+                Some((&addr, handle)) => {
+                    slot.arm(addr, handle.layout.size());
+                    profile.cells = slot.cells();
+                    profile.cell_size = slot.pages_per_cell.load(Ordering::SeqCst) * page_size();
+                    profile.missing = false;
+                }
+                // on its way out: the free disarms it.
+                None if armed => {}
+                None if profile.missing => dead.push(i),
+                None => profile.missing = true,
+            }
+        }
+    }
+
+    for i in dead.into_iter().rev() {
+        profiles.remove(i);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn sample() {
+    threads::internal();
+
+    let (profiles, condvar) = &PROFILES;
+    let mut profiles = profiles.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        match profiles.iter().map(|profile| profile.due).min() {
+            None => profiles = condvar.wait(profiles).unwrap(),
+            Some(due) if due > now => {
+                profiles = condvar.wait_timeout(profiles, due - now).unwrap().0;
+            }
+            Some(_) => tick(&mut profiles, now),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn spawn_sampler() {
+    let pid = process_id();
+    if SAMPLER_PID.swap(pid, Ordering::SeqCst) != pid {
+        let spawned = std::thread::Builder::new()
+            .name("stupidalloc sampler".into())
+            .spawn(sample);
+        if let Err(e) = spawned {
+            SAMPLER_PID.store(0, Ordering::SeqCst);
+            error::backend(format!("couldn't spawn the sampler thread: {e}"));
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Starts sampling the accesses to the memory pages of the allocation
+    /// containing `value`, as described by `profiler`, or stops with
+    /// [`None`], forgetting the samples. The profile follows the allocation
+    /// when it's resized, and ends when it's freed. Up to 64 allocations can
+    /// be profiled at once. Only on Linux.
+    ///
+    /// Reads and writes made by the stupid alloc itself, like hexdumps and
+    /// checksums, count too. System calls given the memory while its pages
+    /// are inaccessible, like a read from a file into it, fail with `EFAULT`
+    /// instead of counting: the OS doesn't fault on behalf of the program.
+    ///
+    /// Returns an error of kind [`NotFound`](io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc, and of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported) for pass-through
+    /// allocations, and outside of Linux, and of kind
+    /// [`OutOfMemory`](io::ErrorKind::OutOfMemory) if 64 allocations are
+    /// profiled already.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use std::time::Duration;
+    /// use stupidalloc::{AccessProfiler, StupidAlloc};
+    ///
+    /// let mut data = Box::new_in([0u8; 1 << 16], StupidAlloc);
+    /// if cfg!(target_os = "linux") {
+    ///     let profiler = AccessProfiler::new().interval(Duration::from_millis(10));
+    ///     StupidAlloc.profile_accesses_of(&*data, Some(profiler)).unwrap();
+    ///
+    ///     // only ever touching the third page.
+    ///     for i in 0..20 {
+    ///         data[2 * 4096] = i;
+    ///         std::thread::sleep(Duration::from_millis(5));
+    ///     }
+    ///
+    ///     let profile = StupidAlloc.access_profile_of(&*data).unwrap();
+    ///     println!("{profile}");
+    ///     let heat = profile.heat();
+    ///     assert!(heat[2] > 0);
+    ///     assert_eq!(heat[5], 0);
+    ///     StupidAlloc.profile_accesses_of(&*data, None).unwrap();
+    /// }
+    /// ```
+    pub fn profile_accesses_of<T: ?Sized>(
+        &self,
+        value: &T,
+        profiler: Option<AccessProfiler>,
+    ) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            let addr = value as *const T as *const u8 as usize;
+
+            let result = system_scope(|| {
+                let (profiles, condvar) = &PROFILES;
+                let mut profiles = profiles.lock().unwrap();
+
+                // the allocation may be profiled already, under another name
+                // if it moved since.
+                let Some(handle) = STUPID_MAP.handle_of(addr) else {
+                    return Err(io::ErrorKind::NotFound);
+                };
+                if handle.map.map().is_none() {
+                    return Err(io::ErrorKind::Unsupported);
+                }
+                let (start, len, path) = (handle.start, handle.layout.size(), handle.path.clone());
+                let index = profiles.iter().position(|p| p.path == path);
+
+                let Some(profiler) = profiler else {
+                    if let Some(i) = index {
+                        disarm(start);
+                        drop(handle);
+                        profiles.remove(i);
+                    }
+                    return Ok(());
+                };
+
+                if let Some(i) = index {
+                    profiles[i].profiler = profiler;
+                    profiles[i].due = Instant::now() + profiler.interval;
+                    condvar.notify_one();
+                    return Ok(());
+                }
+
+                let Some(slot) = (0..SLOTS).find(|&slot| profiles.iter().all(|p| p.slot != slot))
+                else {
+                    return Err(io::ErrorKind::OutOfMemory);
+                };
+
+                install_handler();
+                ARMED[slot].arm(start, len);
+                let cells = ARMED[slot].cells();
+                let cell_size = ARMED[slot].pages_per_cell.load(Ordering::SeqCst) * page_size();
+                drop(handle);
+
+                profiles.push(Profile {
+                    path,
+                    slot,
+                    profiler,
+                    cells,
+                    cell_size,
+                    samples: VecDeque::new(),
+                    due: Instant::now() + profiler.interval,
+                    missing: false,
+                });
+                condvar.notify_one();
+                spawn_sampler();
+                Ok(())
+            });
+
+            result.map_err(io::Error::from)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (value, profiler);
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    /// Returns the samples taken so far of the accesses to the allocation
+    /// containing `value`, if it's being profiled, see
+    /// [`StupidAlloc::profile_accesses_of()`].
+    pub fn access_profile_of<T: ?Sized>(&self, value: &T) -> Option<AccessProfile> {
+        #[cfg(target_os = "linux")]
+        {
+            let addr = value as *const T as *const u8 as usize;
+
+            // collected in System, and then copied for the user.
+            let profile = system_scope(|| {
+                let profiles = PROFILES.0.lock().unwrap();
+                let path = STUPID_MAP.handle_of(addr)?.path.clone();
+                profiles
+                    .iter()
+                    .find(|profile| profile.path == path)
+                    .map(|profile| AccessProfile {
+                        cell_size: profile.cell_size,
+                        samples: profile.samples.iter().cloned().collect(),
+                    })
+            });
+
+            let user_profile = profile.clone();
+            system_scope(|| drop(profile));
+            user_profile
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = value;
+            None
+        }
+    }
+}
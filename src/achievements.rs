@@ -0,0 +1,160 @@
+use std::{fmt::Write as _, time::Duration};
+
+#[cfg(unix)]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Once,
+};
+
+use crate::{system_scope, Stats, StupidAlloc, Timestamp, Totals};
+
+/// A silly milestone reached by the program, listed by
+/// [`StupidAlloc::achievements()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Achievement {
+    /// The name of the achievement.
+    pub name: &'static str,
+    /// What it took to unlock it.
+    pub description: &'static str,
+}
+
+const fn achievement(name: &'static str, description: &'static str) -> Achievement {
+    Achievement { name, description }
+}
+
+// every achievement, and whether it's unlocked.
+#[allow(clippy::type_complexity)]
+const ACHIEVEMENTS: &[(Achievement, fn(&Totals, &Stats) -> bool)] = &[
+    (
+        achievement("Hello, heap", "Made a first stupid allocation."),
+        |totals, _| totals.allocations >= 1,
+    ),
+    (
+        achievement("Thousandaire", "Made 1,000 stupid allocations."),
+        |totals, _| totals.allocations >= 1_000,
+    ),
+    (
+        achievement("Millionaire", "Made 1,000,000 stupid allocations. Why?"),
+        |totals, _| totals.allocations >= 1_000_000,
+    ),
+    (
+        achievement("Chonker", "Allocated a mebibyte at once."),
+        |totals, _| totals.largest >= 1 << 20,
+    ),
+    (
+        achievement("Absolute unit", "Allocated a gibibyte at once."),
+        |totals, _| totals.largest >= 1 << 30,
+    ),
+    (
+        achievement("Growing pains", "Resized the same allocation 10 times."),
+        |totals, _| totals.most_resizes >= 10,
+    ),
+    (
+        achievement("Shapeshifter", "Resized the same allocation 100 times."),
+        |totals, _| totals.most_resizes >= 100,
+    ),
+    (
+        achievement("Ancient artifact", "Kept an allocation alive for a minute."),
+        |totals, _| totals.longest_lived >= Duration::from_secs(60),
+    ),
+    (
+        achievement("Clean slate", "Freed every stupid allocation."),
+        |totals, stats| totals.allocations > 0 && stats.allocations == 0,
+    ),
+];
+
+// whether the report card gets printed when the program exits.
+#[cfg(unix)]
+static AT_EXIT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+static REGISTER_AT_EXIT: Once = Once::new();
+
+fn unlocked(totals: &Totals, stats: &Stats) -> impl Iterator<Item = Achievement> {
+    let (totals, stats) = (*totals, *stats);
+    ACHIEVEMENTS
+        .iter()
+        .filter(move |(_, unlocked)| unlocked(&totals, &stats))
+        .map(|(achievement, _)| *achievement)
+}
+
+// writes the report card. must be called in system_scope.
+fn report_card() -> String {
+    let (totals, stats) = (StupidAlloc.totals(), StupidAlloc.stats());
+
+    let mut out = format!("stupid report card, at {}\n", Timestamp::now());
+    // can't fail, it's a string
+    let _ = writeln!(out, "  allocations:       {}", totals.allocations);
+    let _ = writeln!(out, "  frees:             {}", totals.frees);
+    let _ = writeln!(out, "  resizes:           {}", totals.resizes);
+    let _ = writeln!(out, "  still alive:       {}", stats.allocations);
+    let _ = writeln!(out, "  largest:           {} B", totals.largest);
+    let _ = writeln!(out, "  most resized:      {} times", totals.most_resizes);
+    let _ = writeln!(out, "  longest lived:     {:.2?}", totals.longest_lived);
+
+    let achievements = unlocked(&totals, &stats).collect::<Vec<_>>();
+    let _ = writeln!(
+        out,
+        "achievements: {}/{}",
+        achievements.len(),
+        ACHIEVEMENTS.len()
+    );
+    for Achievement { name, description } in achievements {
+        let _ = writeln!(out, "  * {name}: {description}");
+    }
+
+    out
+}
+
+#[cfg(unix)]
+extern "C" fn print_at_exit() {
+    if AT_EXIT.load(Ordering::SeqCst) {
+        StupidAlloc.print_report_card();
+    }
+}
+
+impl StupidAlloc {
+    /// Returns the achievements unlocked so far, from the counters of
+    /// [`StupidAlloc::totals()`] and [`StupidAlloc::stats()`]. There's no
+    /// prize.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in(42u32, StupidAlloc);
+    /// let achievements = StupidAlloc.achievements();
+    ///
+    /// assert!(achievements.iter().any(|a| a.name == "Hello, heap"));
+    /// ```
+    pub fn achievements(&self) -> Vec<Achievement> {
+        unlocked(&self.totals(), &self.stats()).collect()
+    }
+
+    /// Returns a summary of the run: the counters of
+    /// [`StupidAlloc::totals()`], followed by the unlocked achievements.
+    pub fn report_card(&self) -> String {
+        let card = system_scope(report_card);
+
+        // the card was written in System, the user gets their own copy.
+        let user_card = card.as_str().to_owned();
+        system_scope(|| drop(card));
+        user_card
+    }
+
+    /// Prints the summary of [`StupidAlloc::report_card()`] to the standard
+    /// output.
+    pub fn print_report_card(&self) {
+        system_scope(|| print!("{}", report_card()));
+    }
+
+    /// Enables or disables printing the report card when the program exits.
+    #[cfg(unix)]
+    #[cfg_attr(feature = "nightly", doc(cfg(unix)))]
+    pub fn set_report_card_at_exit(&self, value: bool) {
+        AT_EXIT.store(value, Ordering::SeqCst);
+        REGISTER_AT_EXIT.call_once(|| unsafe {
+            libc::atexit(print_at_exit);
+        });
+    }
+}
@@ -0,0 +1,115 @@
+use std::io;
+
+use crate::{AllocHandle, StupidAlloc, STUPID_MAP};
+
+/// What the program is about to do with an allocation, for the OS to page its
+/// file in and out accordingly, see [`StupidAlloc::advise_of()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Advice {
+    /// The data will be needed soon: the OS may start reading it from the file
+    /// right away.
+    WillNeed,
+    /// The data won't be needed for a while: the OS may drop it from memory,
+    /// and read it back from the file when it's used again. Nothing is lost,
+    /// since the file holds it.
+    DontNeed,
+    /// The data will be read in order: the OS may read ahead aggressively, and
+    /// drop what was read soon after.
+    Sequential,
+    /// The data will be read in no particular order: reading ahead is no use.
+    Random,
+    /// The data won't be needed for a long while: the OS writes it back to
+    /// the file and evicts it from RAM right away, which
+    /// [`StupidAlloc::residency_of()`] shows. Only on Linux.
+    PageOut,
+}
+
+#[cfg(unix)]
+impl Advice {
+    fn flag(self) -> Option<libc::c_int> {
+        match self {
+            Advice::WillNeed => Some(libc::MADV_WILLNEED),
+            Advice::DontNeed => Some(libc::MADV_DONTNEED),
+            Advice::Sequential => Some(libc::MADV_SEQUENTIAL),
+            Advice::Random => Some(libc::MADV_RANDOM),
+            #[cfg(target_os = "linux")]
+            Advice::PageOut => Some(libc::MADV_PAGEOUT),
+            #[cfg(not(target_os = "linux"))]
+            Advice::PageOut => None,
+        }
+    }
+}
+
+impl AllocHandle {
+    // gives `advice` about the bytes of the map from `start` to `end`, widened
+    // to whole pages. only file-backed memory is advised: dropping the pages of
+    // memory from another allocator would zero them out.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    pub(crate) fn advise(&self, advice: Advice, start: usize, end: usize) -> io::Result<()> {
+        // no error messages, the registry may well be locked.
+        let Some(map) = self.map.map() else {
+            return Err(io::ErrorKind::Unsupported.into());
+        };
+
+        #[cfg(unix)]
+        {
+            // madvise wants a page-aligned start.
+            let start = start / crate::page_size() * crate::page_size();
+            let end = end.min(map.len());
+            if start >= end {
+                return Ok(());
+            }
+
+            let flag = advice.flag().ok_or(io::ErrorKind::Unsupported)?;
+            let addr = unsafe { map.as_ptr().add(start) } as *mut libc::c_void;
+            // the OS only evicts clean pages, it leaves writing them back to
+            // whoever dirtied them.
+            if advice == Advice::PageOut
+                && unsafe { libc::msync(addr, end - start, libc::MS_SYNC) } != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            if unsafe { libc::madvise(addr, end - start, flag) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Tells the OS what the program is about to do with the allocation
+    /// containing `value`, using `madvise`, for it to page the file in and out
+    /// accordingly: with [`Advice::PageOut`], the next read of the data has
+    /// to go to the file, and takes measurably longer for it. The advice
+    /// covers the whole pages the data is on.
+    ///
+    /// Returns an error of kind [`NotFound`](io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc, and of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported) for pass-through
+    /// allocations, outside of Unix platforms, and for advice the platform
+    /// doesn't take.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{Advice, StupidAlloc};
+    ///
+    /// let data = Box::new_in([42u8; 1 << 16], StupidAlloc);
+    /// StupidAlloc.advise_of(&*data, Advice::DontNeed).unwrap();
+    ///
+    /// // the data comes back from the file.
+    /// assert!(data.iter().all(|&b| b == 42));
+    /// ```
+    pub fn advise_of<T: ?Sized>(&self, value: &T, advice: Advice) -> io::Result<()> {
+        let addr = value as *const T as *const u8 as usize;
+
+        let handle = STUPID_MAP.handle_of(addr).ok_or(io::ErrorKind::NotFound)?;
+        let offset = handle.offset();
+        handle.advise(advice, offset, offset + handle.layout.size())
+    }
+}
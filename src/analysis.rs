@@ -0,0 +1,219 @@
+// there are no windows for the histogram to be drawn in on wasi, only the
+// switch.
+#![cfg_attr(all(feature = "graphics", target_os = "wasi"), allow(dead_code))]
+
+use std::{fmt, mem::size_of};
+
+#[cfg(feature = "graphics")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::StupidAlloc;
+
+/// What the bytes of an allocation look like, as guessed by
+/// [`StupidAlloc::analyze_of()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    /// There are no bytes.
+    Empty,
+    /// Every byte is zero.
+    Zeroed,
+    /// Printable ASCII characters and whitespace.
+    Ascii,
+    /// UTF-8 text, not all of it ASCII.
+    Utf8,
+    /// Mostly words that could be addresses of this process.
+    PointerLike,
+    /// Mostly `f64`s or `f32`s of a reasonable magnitude.
+    FloatLike,
+    /// Anything else.
+    Binary,
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentKind::Empty => write!(f, "empty"),
+            ContentKind::Zeroed => write!(f, "zeroed"),
+            ContentKind::Ascii => write!(f, "ASCII text"),
+            ContentKind::Utf8 => write!(f, "UTF-8 text"),
+            ContentKind::PointerLike => write!(f, "pointers"),
+            ContentKind::FloatLike => write!(f, "floats"),
+            ContentKind::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// The byte distribution of an allocation, returned by
+/// [`StupidAlloc::analyze_of()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// The size of the allocation, in bytes.
+    pub size: usize,
+    /// The number of bytes of each value, from `0x00` to `0xFF`.
+    pub histogram: [usize; 256],
+    /// The Shannon entropy of the bytes, in bits per byte: 0 when they're all
+    /// the same, 8 when they're as random as it gets. Compressed or encrypted
+    /// data comes close to 8, text is around 4 or 5.
+    pub entropy: f64,
+    /// What the bytes look like.
+    pub kind: ContentKind,
+}
+
+pub fn histogram(bytes: &[u8]) -> [usize; 256] {
+    let mut histogram = [0; 256];
+    for &byte in bytes {
+        histogram[byte as usize] += 1;
+    }
+    histogram
+}
+
+fn entropy(histogram: &[usize; 256], len: usize) -> f64 {
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// whether `word` could be an address of this process: aligned, past the first
+// pages, which are never mapped, and in the user half of the address space.
+pub fn plausible_pointer(word: usize) -> bool {
+    word.is_multiple_of(size_of::<usize>()) && word >= 0x10000 && (word as u64) < 1 << 47
+}
+
+fn plausible_float(value: f64) -> bool {
+    value == 0.0 || (1e-9..1e12).contains(&value.abs())
+}
+
+// whether at least three quarters of the words of `bytes` pass `test`, the
+// leftovers at the end not counting.
+fn mostly<const N: usize>(bytes: &[u8], test: impl Fn([u8; N]) -> bool) -> bool {
+    let words = bytes.len() / N;
+    let passing = bytes
+        .chunks_exact(N)
+        .filter(|word| test((*word).try_into().unwrap()))
+        .count();
+    words > 0 && passing * 4 >= words * 3
+}
+
+fn kind(bytes: &[u8]) -> ContentKind {
+    let text = |byte: &u8| byte.is_ascii_graphic() || byte.is_ascii_whitespace();
+
+    if bytes.is_empty() {
+        ContentKind::Empty
+    } else if bytes.iter().all(|&byte| byte == 0) {
+        ContentKind::Zeroed
+    } else if bytes.iter().all(text) {
+        ContentKind::Ascii
+    } else if std::str::from_utf8(bytes)
+        .is_ok_and(|s| s.chars().all(|c| !c.is_control() || c.is_whitespace()))
+    {
+        ContentKind::Utf8
+    } else if mostly::<{ size_of::<usize>() }>(bytes, |word| {
+        let word = usize::from_ne_bytes(word);
+        word == 0 || plausible_pointer(word)
+    }) {
+        ContentKind::PointerLike
+    } else if mostly::<8>(bytes, |word| plausible_float(f64::from_ne_bytes(word)))
+        || mostly::<4>(bytes, |word| {
+            plausible_float(f32::from_ne_bytes(word) as f64)
+        })
+    {
+        ContentKind::FloatLike
+    } else {
+        ContentKind::Binary
+    }
+}
+
+pub fn analyze(bytes: &[u8]) -> Analysis {
+    let histogram = histogram(bytes);
+    Analysis {
+        size: bytes.len(),
+        entropy: entropy(&histogram, bytes.len()),
+        histogram,
+        kind: kind(bytes),
+    }
+}
+
+// whether the windows draw the histogram of their allocation over the bits.
+#[cfg(feature = "graphics")]
+static OVERLAY: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "graphics")]
+const BARS: u32 = 0x00FF8000;
+
+#[cfg(feature = "graphics")]
+pub fn overlay() -> bool {
+    OVERLAY.load(Ordering::SeqCst)
+}
+
+// draws the histogram of `bytes` as bars over `pixels`, `width` pixels wide,
+// blended with what's under them. each bar is one column of pixels, and
+// covers the byte values that fall in it.
+#[cfg(feature = "graphics")]
+pub fn draw_overlay(pixels: &mut [u32], width: usize, bytes: &[u8]) {
+    let height = pixels.len() / width.max(1);
+    if height == 0 || bytes.is_empty() {
+        return;
+    }
+
+    // nothing gets allocated here, since it's called from the window
+    // threads.
+    let histogram = histogram(bytes);
+    let count = |x: usize| {
+        let first = x * 256 / width;
+        let last = ((x + 1) * 256 / width).clamp(first + 1, 256);
+        histogram[first..last].iter().sum::<usize>()
+    };
+    let highest = (0..width).map(count).max().unwrap_or(0).max(1);
+
+    for x in 0..width {
+        let bar = (count(x) * height).div_ceil(highest);
+        for y in height - bar..height {
+            let pixel = &mut pixels[y * width + x];
+            *pixel = (*pixel >> 1 & 0x007F7F7F) + (BARS >> 1 & 0x007F7F7F);
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Analyzes the bytes of the allocation containing `value`: how many
+    /// there are of each value, how random they are, and a guess at what they
+    /// are, or [`None`] if `value` wasn't allocated with the stupid alloc.
+    /// Handy to tell what a mystery allocation is, when everything goes
+    /// through the global allocator.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{ContentKind, StupidAlloc};
+    ///
+    /// let text = Box::new_in(*b"hello, stupid world", StupidAlloc);
+    /// let analysis = StupidAlloc.analyze_of(&*text).unwrap();
+    /// assert_eq!(analysis.kind, ContentKind::Ascii);
+    /// assert_eq!(analysis.histogram[b'l' as usize], 3);
+    ///
+    /// let floats = Box::new_in([1.5f64, -2.25, 3.0, 1e6], StupidAlloc);
+    /// assert_eq!(StupidAlloc.analyze_of(&*floats).unwrap().kind, ContentKind::FloatLike);
+    ///
+    /// let zeroes = Box::new_in([0u8; 64], StupidAlloc);
+    /// assert_eq!(StupidAlloc.analyze_of(&*zeroes).unwrap().entropy, 0.0);
+    /// ```
+    pub fn analyze_of<T: ?Sized>(&self, value: &T) -> Option<Analysis> {
+        let (_, bytes) = self.contents_of(value)?;
+        Some(analyze(&bytes))
+    }
+
+    /// Enables or disables drawing a histogram of the bytes of each
+    /// allocation over the bits of its graphical window, depending on the
+    /// value passed as parameter: one bar per column of pixels, from the
+    /// byte `0x00` on the left to `0xFF` on the right. Windows that are
+    /// already open pick it up on the next frame.
+    #[cfg(feature = "graphics")]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub fn set_window_histogram(&self, value: bool) {
+        OVERLAY.store(value, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    alloc::{Layout, System},
+    path::{Path, PathBuf},
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    dispose, inspect, persist::check_name, stats::stats_of, system_scope, temp_alloc_dir,
+    user_data, AllocError, Allocator, HeapEvent, Stats, StupidAlloc, DEALLOCATING, STUPID_MAP,
+};
+
+// ids of arenas, so that their allocations can be told apart.
+static NEXT_ARENA_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A named group of allocations, created with [`StupidAlloc::arena()`].
+///
+/// An arena is an allocator of its own: the files of the allocations made
+/// through it land in a dedicated folder, and the allocations can be looked at
+/// as a group. When the arena is dropped, the allocations that are still
+/// alive (since they borrow the arena, they can only have been leaked) are
+/// freed along with their files.
+///
+#[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+#[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+/// #![feature(allocator_api)]
+/// use stupidalloc::StupidAlloc;
+///
+/// let arena = StupidAlloc.arena("textures").unwrap();
+/// let texture = Box::new_in([0u8; 64], &arena);
+/// let mut palette = Vec::with_capacity_in(16, &arena);
+/// palette.extend(0..16u32);
+///
+/// assert_eq!(arena.stats().allocations, 2);
+/// assert_eq!(arena.stats().bytes, 64 + palette.capacity() * 4);
+/// assert!(StupidAlloc.file_of(&*texture).unwrap().starts_with(arena.dir()));
+/// ```
+pub struct Arena {
+    pub(crate) id: usize,
+    pub(crate) name: String,
+    pub(crate) dir: PathBuf,
+}
+
+impl StupidAlloc {
+    /// Creates a new [`Arena`] named `name`. Its allocation files are created
+    /// in a folder of the same name, next to the other allocation files. The
+    /// name is used as a folder name, so keep it simple: an error of kind
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput) is returned if it's
+    /// empty, `.` or `..`, or has path separators.
+    pub fn arena(&self, name: &str) -> std::io::Result<Arena> {
+        check_name(name)?;
+        Ok(Arena::new(name, temp_alloc_dir().join(name)))
+    }
+}
+
+impl Arena {
+    pub(crate) fn new(name: &str, dir: PathBuf) -> Self {
+        Arena {
+            id: NEXT_ARENA_ID.fetch_add(1, Ordering::SeqCst),
+            name: name.to_string(),
+            dir,
+        }
+    }
+
+    /// Returns the name of the arena.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the folder where the files of the allocations of the arena are
+    /// created, unless the user picks another one with the `interactive`
+    /// feature.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns statistics about the live allocations of the arena.
+    pub fn stats(&self) -> Stats {
+        stats_of(|handle| handle.arena == Some(self.id))
+    }
+
+    /// Opens a graphical window for every live allocation of the arena that
+    /// doesn't have one already, with `columns` bytes on each row.
+    #[cfg(feature = "graphics")]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub fn open_windows(&self, columns: usize) {
+        STUPID_MAP
+            .write_all()
+            .values_mut()
+            .filter(|handle| handle.arena == Some(self.id))
+            .for_each(|handle| handle.open_window(columns))
+    }
+
+    /// Closes the graphical windows of all the allocations of the arena.
+    #[cfg(feature = "graphics")]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub fn close_windows(&self) {
+        STUPID_MAP
+            .write_all()
+            .values_mut()
+            .filter(|handle| handle.arena == Some(self.id))
+            .for_each(|handle| {
+                if let Some(window) = handle.window.take() {
+                    window.close()
+                }
+            })
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        DEALLOCATING.with(|d| d.fetch_add(1, Ordering::SeqCst));
+
+        // the registry can't stay locked while the allocations are disposed
+        // of, since releasing them talks to the watcher.
+        let leaked = system_scope(|| {
+            let addrs = STUPID_MAP
+                .read_all()
+                .iter()
+                .filter(|(_, handle)| handle.arena == Some(self.id))
+                .map(|(&addr, _)| addr)
+                .collect::<Vec<_>>();
+
+            addrs
+                .into_iter()
+                .filter_map(|addr| Some((addr, STUPID_MAP.remove(addr)?)))
+                .collect::<Vec<_>>()
+        });
+
+        // DEALLOCATING is nonzero, so this vec lives in System.
+        let freed = leaked
+            .into_iter()
+            .map(|(addr, handle)| {
+                let layout = handle.layout;
+                dispose(addr, handle);
+                (addr, layout)
+            })
+            .collect::<Vec<_>>();
+
+        DEALLOCATING.with(|d| d.fetch_sub(1, Ordering::SeqCst));
+
+        user_data::drop_orphans();
+        for &(addr, layout) in &freed {
+            inspect::notify(HeapEvent::Freed { addr, layout });
+        }
+        system_scope(|| drop(freed));
+    }
+}
+
+// the arena only decides where allocations go: once they exist, they're the
+// same as any other stupid allocation.
+unsafe impl Allocator for Arena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        StupidAlloc.inner_allocate(layout, Some(self), false, |layout| System.allocate(layout))
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        StupidAlloc.inner_allocate(layout, Some(self), true, |layout| {
+            System.allocate_zeroed(layout)
+        })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        StupidAlloc.deallocate(ptr, layout)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        StupidAlloc.grow(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        StupidAlloc.grow_zeroed(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        StupidAlloc.shrink(ptr, old_layout, new_layout)
+    }
+}
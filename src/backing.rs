@@ -0,0 +1,182 @@
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    io,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::RwLock,
+};
+
+#[cfg(target_os = "linux")]
+use memmap2::RemapOptions;
+
+#[cfg(feature = "graphics")]
+use std::sync::{Arc, Mutex};
+
+use crate::MmapMut;
+
+#[cfg(feature = "graphics")]
+use crate::graphics::MemoryView;
+
+// an allocator that can be used for pass-through allocations.
+pub type InnerAlloc = &'static (dyn GlobalAlloc + Sync);
+
+// the allocator servicing new allocations in pass-through mode. none means
+// the allocations are file-backed, as they should.
+pub static PASS_THROUGH: RwLock<Option<InnerAlloc>> = RwLock::new(None);
+
+// where the data of an allocation actually lives
+pub enum Backing {
+    // a memory map of the allocation file. the stupid way.
+    Map(MmapMut),
+    // memory from another allocator, in pass-through mode.
+    Memory {
+        ptr: NonNull<u8>,
+        len: usize,
+        // what was asked of the allocator, which is not quite `len` for
+        // zero-sized allocations.
+        layout: Layout,
+        allocator: InnerAlloc,
+        // what graphical windows look at, since there is no file to map.
+        #[cfg(feature = "graphics")]
+        view: MemoryView,
+    },
+}
+
+// SAFETY: the memory is owned by the backing, just like a Box would.
+unsafe impl Send for Backing {}
+unsafe impl Sync for Backing {}
+
+impl Backing {
+    // allocates `len` bytes of zeroed memory aligned to `align` from
+    // `allocator`, which gets called while ALLOCATING is nonzero so that it
+    // can allocate on its own if it needs to.
+    pub fn allocate(allocator: InnerAlloc, len: usize, align: usize) -> Option<Self> {
+        // zero-sized allocations are not allowed with GlobalAlloc.
+        let layout = Layout::from_size_align(len.max(1), align).ok()?;
+        let ptr = NonNull::new(unsafe { allocator.alloc_zeroed(layout) })?;
+
+        Some(Backing::Memory {
+            ptr,
+            len,
+            layout,
+            allocator,
+            #[cfg(feature = "graphics")]
+            view: Arc::new(Mutex::new((ptr.as_ptr() as usize, len))),
+        })
+    }
+
+    // the allocator of a pass-through allocation.
+    pub fn allocator(&self) -> Option<InnerAlloc> {
+        match self {
+            Backing::Map(_) => None,
+            Backing::Memory { allocator, .. } => Some(*allocator),
+        }
+    }
+
+    // resizes the memory map of a file-backed allocation to `len` bytes without
+    // moving it, which only linux knows how to do. returns whether it worked,
+    // the map is left as it was otherwise.
+    #[allow(unused_variables)]
+    pub fn remap_in_place(&mut self, len: usize) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            Backing::Map(map) => unsafe { map.remap(len, RemapOptions::new()) }.is_ok(),
+            _ => false,
+        }
+    }
+
+    // where the memory starts, for the pointers handed out to the user. unlike
+    // one that went through a slice, this one may be written through.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        match self {
+            Backing::Map(map) => map.as_mut_ptr(),
+            Backing::Memory { ptr, .. } => ptr.as_ptr(),
+        }
+    }
+
+    // the memory map of a file-backed allocation.
+    pub fn map(&self) -> Option<&MmapMut> {
+        match self {
+            Backing::Map(map) => Some(map),
+            Backing::Memory { .. } => None,
+        }
+    }
+
+    // tells the windows that pass-through memory moved from `old` to here.
+    // must be called before `old` is dropped.
+    #[cfg(feature = "graphics")]
+    pub fn moved_from(&mut self, old: &mut Backing) {
+        if let (Backing::Memory { ptr, len, view, .. }, Backing::Memory { view: old_view, .. }) =
+            (self, old)
+        {
+            // the windows keep the same view, and the old memory gets the new,
+            // unused one for its last moments.
+            std::mem::swap(view, old_view);
+            *view.lock().unwrap() = (ptr.as_ptr() as usize, *len);
+        }
+    }
+
+    // the memory of a pass-through allocation, as seen by windows.
+    #[cfg(feature = "graphics")]
+    pub fn view(&self) -> Option<&MemoryView> {
+        match self {
+            Backing::Map(_) => None,
+            Backing::Memory { view, .. } => Some(view),
+        }
+    }
+
+    // makes sure the file is up to date with the memory. pass-through memory
+    // has no file, so there's nothing to do.
+    pub fn flush_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        match self {
+            Backing::Map(map) => map.flush_range(offset, len),
+            Backing::Memory { .. } => Ok(()),
+        }
+    }
+}
+
+impl Deref for Backing {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Backing::Map(map) => &map[..],
+            Backing::Memory { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts(ptr.as_ptr(), *len)
+            },
+        }
+    }
+}
+
+impl DerefMut for Backing {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Backing::Map(map) => &mut map[..],
+            Backing::Memory { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts_mut(ptr.as_ptr(), *len)
+            },
+        }
+    }
+}
+
+impl Drop for Backing {
+    fn drop(&mut self) {
+        if let Backing::Memory {
+            ptr,
+            layout,
+            allocator,
+            #[cfg(feature = "graphics")]
+            view,
+            ..
+        } = self
+        {
+            // windows must not look at the memory anymore.
+            #[cfg(feature = "graphics")]
+            {
+                *view.lock().unwrap() = (0, 0);
+            }
+
+            unsafe { allocator.dealloc(ptr.as_ptr(), *layout) };
+        }
+    }
+}
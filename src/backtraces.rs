@@ -0,0 +1,133 @@
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        RwLock,
+    },
+};
+
+use crate::{AllocHandle, StupidAlloc};
+
+/// When the stupid alloc captures backtraces, see
+/// [`StupidAlloc::set_backtraces()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backtraces {
+    /// Whenever the `RUST_BACKTRACE` and `RUST_LIB_BACKTRACE` environment
+    /// variables say so, like [`Backtrace::capture()`].
+    #[default]
+    Environment,
+    /// Never.
+    Never,
+    /// When allocating, but not when freeing or resizing.
+    OnAllocOnly,
+    /// When allocating, freeing and resizing.
+    Full,
+    /// For one allocation, free or resize out of `n`.
+    Sampled(usize),
+}
+
+// what a backtrace is captured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Alloc,
+    Free,
+    Resize,
+}
+
+static POLICY: RwLock<Backtraces> = RwLock::new(Backtraces::Environment);
+
+// the number of events seen by Sampled, to pick one out of n.
+static EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+// the backtrace of `event`, or a disabled one if the policy says so. must be
+// called while ALLOCATING or DEALLOCATING is nonzero, since capturing
+// allocates.
+pub fn capture(event: Event) -> Backtrace {
+    let wanted = match *POLICY.read().unwrap() {
+        Backtraces::Environment => return Backtrace::capture(),
+        Backtraces::Never => false,
+        Backtraces::OnAllocOnly => event == Event::Alloc,
+        Backtraces::Full => true,
+        Backtraces::Sampled(n) => EVENTS
+            .fetch_add(1, Ordering::SeqCst)
+            .is_multiple_of(n.max(1)),
+    };
+
+    if wanted {
+        Backtrace::force_capture()
+    } else {
+        Backtrace::disabled()
+    }
+}
+
+// the frames of the standard library and of the stupid alloc, that are not
+// where the program allocated.
+const INTERNAL: &[&str] = &[
+    "std::",
+    "core::",
+    "alloc::",
+    "stupidalloc::",
+    "<std::",
+    "<core::",
+    "<alloc::",
+    "<stupidalloc::",
+    "__rust",
+];
+
+// where the allocation was made, as told by the macro that made it, or by its
+// backtrace. resolving the backtrace takes a while, but so does everything
+// else the stupid alloc does. must be called while ALLOCATING or DEALLOCATING
+// is nonzero, since it allocates.
+pub fn call_site(handle: &AllocHandle) -> String {
+    if let Some(location) = handle.location {
+        return location.to_string();
+    }
+    if handle.backtrace.status() != BacktraceStatus::Captured {
+        return "unknown".into();
+    }
+
+    // frames look like `  12: my_crate::parse` followed by an optional
+    // `        at ./src/parse.rs:12:5`.
+    let backtrace = handle.backtrace.to_string();
+    let mut lines = backtrace.lines().map(str::trim).peekable();
+    while let Some(line) = lines.next() {
+        let Some((number, symbol)) = line.split_once(": ") else {
+            continue;
+        };
+        if !number.bytes().all(|b| b.is_ascii_digit())
+            || INTERNAL.iter().any(|prefix| symbol.starts_with(prefix))
+        {
+            continue;
+        }
+        return match lines.peek().and_then(|line| line.strip_prefix("at ")) {
+            Some(at) => format!("{symbol} at {at}"),
+            None => symbol.into(),
+        };
+    }
+    "unknown".into()
+}
+
+impl StupidAlloc {
+    /// Sets when backtraces are captured, for the logs, the diagnostics of
+    /// double frees and [`StupidAlloc::history_of()`]. Capturing one on every
+    /// allocation is extremely slow, so this trades detail for speed, without
+    /// having to give up on logging.
+    ///
+    /// Captured backtraces are only symbolized when they are displayed: when
+    /// writing them to the logs, or when something goes wrong, and when
+    /// allocations made without [`stupid_box!`](crate::stupid_box) or
+    /// [`stupid_vec!`](crate::stupid_vec) are freed, to tell where they were
+    /// made. The backtrace
+    /// of where something went wrong, like a double free, always follows the
+    /// environment.
+    ///
+    /// ```
+    /// use stupidalloc::{Backtraces, StupidAlloc};
+    ///
+    /// // only one allocation, free or resize out of a hundred.
+    /// StupidAlloc.set_backtraces(Backtraces::Sampled(100));
+    /// ```
+    pub fn set_backtraces(&self, backtraces: Backtraces) {
+        *POLICY.write().unwrap() = backtraces;
+    }
+}
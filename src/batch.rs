@@ -0,0 +1,115 @@
+use std::{
+    alloc::{Layout, System},
+    ptr::NonNull,
+    sync::atomic::Ordering,
+};
+
+use crate::{
+    confirm_alloc, fault, freeze, inspect, latency, new_allocation, stats, system_scope,
+    use_system, AllocError, Allocator, HeapEvent, StupidAlloc, ALLOCATING, STUPID_MAP,
+};
+
+impl StupidAlloc {
+    /// Allocates memory for every layout of `layouts` at once, for data
+    /// structures that know they need many blocks up front. All the files get
+    /// registered with a single lock of the allocator's bookkeeping instead of
+    /// one per allocation and, with the `interactive` feature, a single
+    /// confirmation dialog lists all of them.
+    ///
+    /// Either every allocation succeeds, or none of them is made and
+    /// [`AllocError`] is returned. The memory is zeroed, whatever the pattern
+    /// set with [`StupidAlloc::set_fill_pattern()`], and must be de-allocated
+    /// one block at a time, like any other allocation.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use std::alloc::{Allocator, Layout};
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let layouts = [Layout::new::<u64>(), Layout::new::<[u8; 100]>()];
+    /// let blocks = StupidAlloc.allocate_batch(&layouts).unwrap();
+    /// assert_eq!(StupidAlloc.stats().allocations, 2);
+    ///
+    /// for (block, layout) in blocks.into_iter().zip(layouts) {
+    ///     assert_eq!(block.len(), layout.size());
+    ///     unsafe { StupidAlloc.deallocate(block.cast(), layout) };
+    /// }
+    /// ```
+    pub fn allocate_batch(&self, layouts: &[Layout]) -> Result<Vec<NonNull<[u8]>>, AllocError> {
+        // same as inner_allocate: when the stupid alloc can't be used, each
+        // block goes to System on its own.
+        if use_system() {
+            let mut ptrs = Vec::with_capacity(layouts.len());
+            for &layout in layouts {
+                stats::record_fallback();
+                match System.allocate_zeroed(layout) {
+                    Ok(ptr) => ptrs.push(ptr),
+                    Err(error) => {
+                        for (ptr, &layout) in ptrs.into_iter().zip(layouts) {
+                            unsafe { System.deallocate(ptr.cast(), layout) };
+                        }
+                        return Err(error);
+                    }
+                }
+            }
+            return Ok(ptrs);
+        }
+
+        // one wait for the whole batch, which is the point of batches.
+        let _world = freeze::enter();
+        latency::wait(layouts.iter().map(|layout| layout.size()).sum());
+
+        ALLOCATING.with(|a| a.fetch_add(1, Ordering::SeqCst));
+        let faulty = layouts.iter().any(|&layout| fault::inject(layout));
+        let result = if !faulty && confirm_alloc(layouts) {
+            let mut batch = Vec::with_capacity(layouts.len());
+            // zeroed like the blocks from System above, so that a batch
+            // doesn't depend on which thread asked for it.
+            for &layout in layouts {
+                match new_allocation(layout, None, true) {
+                    Some(allocation) => batch.push(allocation),
+                    None => break,
+                }
+            }
+
+            if batch.len() == layouts.len() {
+                let ptrs = batch.iter().map(|(ptr, _)| *ptr).collect::<Vec<_>>();
+                STUPID_MAP.insert_batch(
+                    batch
+                        .into_iter()
+                        .map(|(ptr, handle)| (ptr.as_ptr() as *mut u8 as usize, handle)),
+                );
+                Ok(ptrs)
+            } else {
+                // all or nothing: the ones that were made go away. ALLOCATING
+                // is nonzero, which keeps release from recursing just as well
+                // as DEALLOCATING would.
+                batch.into_iter().for_each(|(_ptr, handle)| {
+                    #[cfg(feature = "valgrind")]
+                    crate::valgrind::freelike(_ptr.as_ptr() as *mut u8 as usize);
+                    handle.release()
+                });
+                Err(AllocError)
+            }
+        } else {
+            Err(AllocError)
+        };
+        ALLOCATING.with(|a| a.fetch_sub(1, Ordering::SeqCst));
+
+        if let Ok(ptrs) = &result {
+            for (ptr, &layout) in ptrs.iter().zip(layouts) {
+                inspect::notify(HeapEvent::Allocated {
+                    addr: ptr.as_ptr() as *mut u8 as usize,
+                    layout,
+                });
+            }
+        }
+
+        // the vec was allocated in System, the user gets their own copy.
+        result.map(|ptrs| {
+            let user_ptrs = ptrs.as_slice().to_vec();
+            system_scope(|| drop(ptrs));
+            user_ptrs
+        })
+    }
+}
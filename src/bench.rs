@@ -0,0 +1,216 @@
+//! Measurements of the time the stupid alloc spends doing stupid things.
+//!
+//! The allocator keeps track of how long it spends on each kind of
+//! [`Operation`], for every allocation it makes, resizes or frees, in every
+//! thread. [`report()`] returns the totals so far, and [`run()`] makes a bunch
+//! of allocations to measure them on purpose, which is handy to compare
+//! backends:
+//!
+//! ```
+//! use std::alloc::System;
+//! use stupidalloc::{bench, StupidAlloc};
+//!
+//! let files = bench::run(100, 4096);
+//!
+//! StupidAlloc.set_pass_through(Some(&System));
+//! let memory = bench::run(100, 4096);
+//! StupidAlloc.set_pass_through(None);
+//!
+//! println!("with files:\n{files}\nwith pass-through:\n{memory}");
+//! assert_eq!(memory.get(bench::Operation::FileCreation).count, 0);
+//! ```
+
+use std::{
+    alloc::Layout,
+    fmt,
+    ops::Sub,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{Allocator, StupidAlloc};
+
+/// Something the stupid alloc spends time on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Creating allocation files.
+    FileCreation,
+    /// Giving allocation files their size, when they're created or when the
+    /// allocations are resized.
+    FileResize,
+    /// Mapping allocation files in memory.
+    Mmap,
+    /// Waiting for the allocator's bookkeeping to be available, which is
+    /// mostly about waiting on other threads.
+    RegistryLock,
+    /// Writing to log files, with the `logging` feature.
+    Logging,
+    /// Opening graphical windows and telling them what happened, with the
+    /// `graphics` feature.
+    Graphics,
+}
+
+impl Operation {
+    /// Every operation, in the order they are reported.
+    pub const ALL: [Operation; 6] = [
+        Operation::FileCreation,
+        Operation::FileResize,
+        Operation::Mmap,
+        Operation::RegistryLock,
+        Operation::Logging,
+        Operation::Graphics,
+    ];
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::FileCreation => "file creation",
+            Operation::FileResize => "file resize",
+            Operation::Mmap => "mmap",
+            Operation::RegistryLock => "registry locking",
+            Operation::Logging => "logging",
+            Operation::Graphics => "graphics messaging",
+        })
+    }
+}
+
+/// The time spent on one kind of [`Operation`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// The number of times the operation was done.
+    pub count: u64,
+    /// The total time spent on it.
+    pub total: Duration,
+}
+
+impl Timing {
+    /// The average time spent on the operation, or zero if it was never done.
+    pub fn average(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.total.as_nanos() / count as u128) as u64),
+        }
+    }
+}
+
+/// The time spent on each [`Operation`], returned by [`report()`] and
+/// [`run()`]. Reports can be subtracted from each other to get the time spent
+/// in between.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Report {
+    timings: [Timing; Operation::ALL.len()],
+}
+
+impl Report {
+    /// The time spent on `operation`.
+    pub fn get(&self, operation: Operation) -> Timing {
+        self.timings[operation as usize]
+    }
+
+    /// The time spent on every operation, in the order of [`Operation::ALL`].
+    pub fn iter(&self) -> impl Iterator<Item = (Operation, Timing)> + '_ {
+        Operation::ALL.into_iter().zip(self.timings)
+    }
+
+    /// The time spent on all the operations together.
+    pub fn total(&self) -> Duration {
+        self.timings.iter().map(|timing| timing.total).sum()
+    }
+}
+
+impl Sub for Report {
+    type Output = Report;
+
+    fn sub(self, earlier: Report) -> Report {
+        Report {
+            timings: std::array::from_fn(|i| Timing {
+                count: self.timings[i]
+                    .count
+                    .saturating_sub(earlier.timings[i].count),
+                total: self.timings[i]
+                    .total
+                    .saturating_sub(earlier.timings[i].total),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<20}{:>10}{:>14}{:>14}",
+            "operation", "count", "total", "average"
+        )?;
+        for (operation, timing) in self.iter() {
+            writeln!(
+                f,
+                "{:<20}{:>10}{:>14}{:>14}",
+                operation.to_string(),
+                timing.count,
+                format!("{:.2?}", timing.total),
+                format!("{:.2?}", timing.average())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// the number of times each operation was done, and the nanoseconds spent on
+// it. plain atomics, so that measuring never allocates.
+static COUNTS: [AtomicU64; Operation::ALL.len()] =
+    [const { AtomicU64::new(0) }; Operation::ALL.len()];
+static NANOS: [AtomicU64; Operation::ALL.len()] =
+    [const { AtomicU64::new(0) }; Operation::ALL.len()];
+
+// runs `f`, and counts the time it took towards `operation`.
+pub(crate) fn measure<R>(operation: Operation, f: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = f();
+    let nanos = start.elapsed().as_nanos() as u64;
+
+    COUNTS[operation as usize].fetch_add(1, Ordering::Relaxed);
+    NANOS[operation as usize].fetch_add(nanos, Ordering::Relaxed);
+
+    result
+}
+
+/// Returns the time spent on each operation since the program started, in
+/// every thread.
+pub fn report() -> Report {
+    Report {
+        timings: std::array::from_fn(|i| Timing {
+            count: COUNTS[i].load(Ordering::Relaxed),
+            total: Duration::from_nanos(NANOS[i].load(Ordering::Relaxed)),
+        }),
+    }
+}
+
+/// Makes `iterations` allocations of `size` bytes with the current settings
+/// of the stupid alloc, doubles their size and frees them, then returns the
+/// time spent on each operation in the meantime. Other threads using the
+/// stupid alloc at the same time are measured as well.
+///
+/// The stupid alloc must be enabled in the current thread, see
+/// [`StupidAlloc::enable_in_thread()`]. Otherwise, nothing gets measured.
+pub fn run(iterations: usize, size: usize) -> Report {
+    let layout = Layout::from_size_align(size, 1).unwrap();
+    let grown = Layout::from_size_align(size * 2, 1).unwrap();
+    let before = report();
+
+    for _ in 0..iterations {
+        let ptr = StupidAlloc
+            .allocate(layout)
+            .expect("benchmark allocation failed");
+
+        unsafe {
+            let ptr = StupidAlloc
+                .grow(ptr.cast(), layout, grown)
+                .expect("benchmark resize failed");
+            StupidAlloc.deallocate(ptr.cast(), grown);
+        }
+    }
+
+    report() - before
+}
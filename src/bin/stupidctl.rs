@@ -0,0 +1,459 @@
+//! `stupidctl`, to look at the stupid heap of a running program from the
+//! outside: through its allocation files, or through its control socket (see
+//! `StupidAlloc::start_control_socket()`).
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+const USAGE: &str = "\
+usage: stupidctl [--dir <dir> | --socket <path>] <command>
+
+commands:
+  list                        lists the allocation files, or the allocations
+                              of the program behind the socket
+  hexdump <file | address>    dumps an allocation file, or an allocation of the
+                              program behind the socket
+  tail <file>                 prints the log of an allocation as it grows
+  view <file> [columns]       draws the bits of an allocation file, 8 bytes on
+                              each row by default
+  clean [--dry-run]           removes the files left behind by crashed runs
+  manifest [pid | folder]     tells what each file of a process was, from its
+                              manifest, for every process by default
+  compare <run> <run>         compares the live allocations of two runs by call
+                              site, from their bundles or their manifests
+  send <command>...           sends a command to the socket, `send help` lists
+                              them
+
+files are looked for in the allocation folder when they're not found as is. it
+defaults to the one the stupid alloc uses, in the temporary folder.";
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
+
+// where the allocations go, or the socket to talk to.
+enum Target {
+    Dir(PathBuf),
+    Socket(PathBuf),
+}
+
+impl Target {
+    fn dir(&self) -> io::Result<&Path> {
+        match self {
+            Target::Dir(dir) => Ok(dir),
+            Target::Socket(_) => Err(invalid("this command works on files, not on a socket")),
+        }
+    }
+
+    // the file `name`, as is or in the allocation folder.
+    fn file(&self, name: Option<String>) -> io::Result<PathBuf> {
+        let name = PathBuf::from(name.ok_or_else(|| invalid("missing file"))?);
+        match self.dir()?.join(&name) {
+            in_dir if !name.exists() && in_dir.exists() => Ok(in_dir),
+            _ => Ok(name),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("stupidctl: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut target = Target::Dir(std::env::temp_dir().join("stupidalloc"));
+
+    let command = loop {
+        match args.next().as_deref() {
+            Some("--dir") => {
+                target = Target::Dir(args.next().ok_or_else(|| invalid("missing folder"))?.into())
+            }
+            Some("--socket") => {
+                target =
+                    Target::Socket(args.next().ok_or_else(|| invalid("missing socket"))?.into())
+            }
+            Some("-h" | "--help") | None => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            Some(command) => break command.to_string(),
+        }
+    };
+
+    match (command.as_str(), &target) {
+        ("list", Target::Dir(dir)) => list(dir),
+        ("hexdump", Target::Dir(_)) => hexdump(&target.file(args.next())?),
+        ("list", Target::Socket(socket)) => send(socket, "list"),
+        ("hexdump", Target::Socket(socket)) => {
+            let address = args.next().ok_or_else(|| invalid("missing address"))?;
+            send(socket, &format!("hexdump {address}"))
+        }
+        ("send", Target::Socket(socket)) => send(socket, &args.collect::<Vec<_>>().join(" ")),
+        ("send", Target::Dir(_)) => Err(invalid("`send` needs a --socket")),
+        ("tail", _) => tail(&target.file(args.next())?),
+        ("view", _) => {
+            let path = target.file(args.next())?;
+            let columns = match args.next() {
+                Some(columns) => columns
+                    .parse()
+                    .ok()
+                    .filter(|&columns| columns > 0)
+                    .ok_or_else(|| invalid(format!("invalid number of columns: {columns:?}")))?,
+                None => 8,
+            };
+            view(&path, columns)
+        }
+        ("clean", _) => clean(target.dir()?, args.next().as_deref() == Some("--dry-run")),
+        ("manifest", _) => match args.next() {
+            Some(name) => manifest(&target.file(Some(name))?),
+            None => manifests(target.dir()?),
+        },
+        ("compare", _) => {
+            let a = target.file(args.next())?;
+            let b = target.file(args.next())?;
+            print!("{}", stupidalloc::StupidAlloc.compare_runs(a, b)?);
+            Ok(())
+        }
+        (command, _) => Err(invalid(format!("unknown command: {command:?}"))),
+    }
+}
+
+// the allocation files in `dir`, and in the folders of each process, sorted by
+// name. logs and old versions aren't
+// allocations.
+fn allocation_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "mem") {
+            files.push(path);
+        } else if process_id(&path).is_some() {
+            files.extend(allocation_files(&path)?);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+// the process a folder belongs to.
+fn process_id(path: &Path) -> Option<u32> {
+    match path.is_dir() {
+        true => path.file_name()?.to_str()?.parse().ok(),
+        false => None,
+    }
+}
+
+fn list(dir: &Path) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    for path in allocation_files(dir)? {
+        let size = std::fs::metadata(&path)?.len();
+        writeln!(out, "{size:>12} {}", path.display())?;
+    }
+
+    Ok(())
+}
+
+// the same format as the hexdumps of the library, with offsets in the file
+// instead of addresses, which mean nothing out of the program.
+fn hexdump(path: &Path) -> io::Result<()> {
+    let bytes = std::fs::read(path)?;
+    let mut out = io::stdout().lock();
+
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        write!(out, "0x{:08x}: ", i * 16)?;
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(b) => write!(out, "{b:02x} ")?,
+                None => write!(out, "   ")?,
+            }
+        }
+        let text = chunk
+            .iter()
+            .map(|&b| match b.is_ascii_graphic() || b == b' ' {
+                true => b as char,
+                false => '.',
+            })
+            .collect::<String>();
+        writeln!(out, " {text}")?;
+    }
+
+    Ok(())
+}
+
+// one character per bit, most significant first like in the windows.
+fn view(path: &Path, columns: usize) -> io::Result<()> {
+    let bytes = std::fs::read(path)?;
+    let mut out = io::stdout().lock();
+
+    for (i, row) in bytes.chunks(columns).enumerate() {
+        write!(out, "0x{:08x} ", i * columns)?;
+        for byte in row {
+            let bits = (0..8)
+                .rev()
+                .map(|bit| match byte >> bit & 1 {
+                    1 => '█',
+                    _ => '·',
+                })
+                .collect::<String>();
+            write!(out, "{bits}")?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+// prints the log as it is, then whatever gets appended to it, until it's
+// removed. the log of an allocation file is next to it.
+fn tail(path: &Path) -> io::Result<()> {
+    let path = path.with_extension("md");
+    let mut file = File::open(&path)?;
+    let mut position = 0;
+    let mut out = io::stdout();
+
+    loop {
+        // a new allocation reusing the name starts its log over.
+        let len = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if len < position {
+            file = File::open(&path)?;
+            position = 0;
+        }
+
+        file.seek(SeekFrom::Start(position))?;
+        position += io::copy(&mut (&mut file).take(len - position), &mut out)?;
+        out.flush()?;
+
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+// prints what the manifest in the folder of a process, or the manifest
+// `path` itself, says about the files that were still live.
+fn manifest(path: &Path) -> io::Result<()> {
+    let live = stupidalloc::StupidAlloc.read_manifest(path)?;
+    let mut out = io::stdout().lock();
+
+    for entry in live {
+        let gone = match entry.path.exists() {
+            true => "",
+            false => " (gone)",
+        };
+        writeln!(
+            out,
+            "{:>12} 0x{:x} {}{gone}",
+            entry.size,
+            entry.address,
+            entry.path.display()
+        )?;
+        if let (Some(type_name), Some(location)) = (entry.type_name, entry.location) {
+            writeln!(out, "{:12} {type_name} from {location}", "")?;
+        }
+        match entry.resized {
+            Some(resized) => writeln!(
+                out,
+                "{:12} allocated {}, last resized {resized}",
+                "", entry.allocated
+            )?,
+            None => writeln!(out, "{:12} allocated {}", "", entry.allocated)?,
+        }
+    }
+
+    Ok(())
+}
+
+// the manifests of every process folder in `dir`, one after the other.
+fn manifests(dir: &Path) -> io::Result<()> {
+    let mut folders = std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    folders.sort();
+
+    for folder in folders {
+        if let (Some(pid), true) = (process_id(&folder), folder.join("manifest.tsv").exists()) {
+            println!("process {pid}:");
+            manifest(&folder)?;
+        }
+    }
+
+    Ok(())
+}
+
+// removes the allocation files that no process uses anymore, along with their
+// logs and old versions, and the folders of processes that are gone, unless
+// another process shares one of their allocations. persistent allocations are
+// meant to outlive their program.
+#[cfg(target_os = "linux")]
+fn clean(dir: &Path, dry_run: bool) -> io::Result<()> {
+    let in_use = files_in_use()?;
+    let mut stale = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        match process_id(&path) {
+            Some(pid)
+                if !Path::new("/proc").join(pid.to_string()).exists()
+                    && !shared(&path, &in_use)? =>
+            {
+                stale.push(path)
+            }
+            Some(_) => stale.extend(stale_files(&path, &in_use)?),
+            None => {}
+        }
+    }
+    stale.extend(stale_files(dir, &in_use)?);
+
+    for path in stale {
+        println!("{}", path.display());
+        if dry_run {
+            continue;
+        }
+        match path.is_dir() {
+            true => std::fs::remove_dir_all(&path)?,
+            false => std::fs::remove_file(&path)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clean(_dir: &Path, _dry_run: bool) -> io::Result<()> {
+    Err(invalid("telling stale files apart only works on Linux"))
+}
+
+// whether the file at `path` is locked by a live allocation. allocations lock
+// their files, unless they were told to close them: those only show up in
+// /proc.
+#[cfg(target_os = "linux")]
+fn locked(path: &Path) -> bool {
+    File::open(path)
+        .is_ok_and(|file| matches!(file.try_lock(), Err(std::fs::TryLockError::WouldBlock)))
+}
+
+// whether a file of `dir`, or of its subfolders, is used by a process.
+#[cfg(target_os = "linux")]
+fn shared(dir: &Path, in_use: &std::collections::HashSet<PathBuf>) -> io::Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let used = match path.is_dir() {
+            true => shared(&path, in_use)?,
+            false => in_use.contains(&path.canonicalize()?) || locked(&path),
+        };
+        if used {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// the files of `dir` belonging to allocation files nobody uses.
+#[cfg(target_os = "linux")]
+fn stale_files(
+    dir: &Path,
+    in_use: &std::collections::HashSet<PathBuf>,
+) -> io::Result<Vec<PathBuf>> {
+    let mut stale = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        // versions and bits are named after their allocation file:
+        // `alloc_N.mem.vN` and `alloc_N.mem.bits`.
+        let allocation = match name.split_once(".mem") {
+            Some((stem, _)) => dir.join(format!("{stem}.mem")),
+            None if name.ends_with(".md") => path.with_extension("mem"),
+            None => continue,
+        };
+
+        // the logs of freed allocations stay, that's what they're for.
+        let alive = in_use.contains(&allocation.canonicalize().unwrap_or(allocation.clone()))
+            || locked(&allocation);
+        if !alive && allocation.exists() {
+            stale.push(path);
+        }
+    }
+
+    Ok(stale)
+}
+
+// the files mapped or open in any process we can look at. the files of
+// allocations are mapped, except the ones waiting in a pool, which are open.
+#[cfg(target_os = "linux")]
+fn files_in_use() -> io::Result<std::collections::HashSet<PathBuf>> {
+    let mut files = std::collections::HashSet::new();
+
+    for entry in std::fs::read_dir("/proc")? {
+        let proc = entry?.path();
+        if process_id(&proc).is_none() {
+            continue;
+        }
+
+        // processes come and go, and the ones of other users are none of our
+        // business.
+        if let Ok(maps) = std::fs::read_to_string(proc.join("maps")) {
+            files.extend(
+                maps.lines()
+                    .filter_map(|line| line.splitn(6, ' ').nth(5))
+                    .map(|path| PathBuf::from(path.trim_start())),
+            );
+        }
+        if let Ok(fds) = std::fs::read_dir(proc.join("fd")) {
+            files.extend(fds.filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok()));
+        }
+    }
+
+    Ok(files)
+}
+
+// one command, and its answer up to the line saying how it went.
+#[cfg(unix)]
+fn send(socket: &Path, command: &str) -> io::Result<()> {
+    use std::{
+        io::{BufRead, BufReader},
+        os::unix::net::UnixStream,
+    };
+
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{command}")?;
+
+    let mut out = io::stdout().lock();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line == "ok" {
+            return Ok(());
+        }
+        if let Some(error) = line.strip_prefix("error: ") {
+            return Err(io::Error::other(error));
+        }
+        writeln!(out, "{line}")?;
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "the program hung up",
+    ))
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &Path, _command: &str) -> io::Result<()> {
+    Err(invalid("control sockets only exist on Unix platforms"))
+}
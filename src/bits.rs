@@ -0,0 +1,68 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::AllocHandle;
+
+// the folder holding the bits of the allocation with the file at `path`.
+pub fn bits_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".bits");
+    PathBuf::from(path)
+}
+
+// the file of bit `bit` of byte `byte`, bit 0 being the least significant one.
+fn bit_path(dir: &Path, byte: usize, bit: usize) -> PathBuf {
+    dir.join(format!("{byte:010}.{bit}"))
+}
+
+impl AllocHandle {
+    // writes every bit of the data to a file of its own, and removes the files
+    // of the bits past the end of the data, if it used to be `old_size` bytes.
+    // must be called in system_scope, like everything else touching files.
+    pub(crate) fn write_bits(&self, old_size: usize) -> io::Result<()> {
+        // pass-through allocations are supposed to stay away from the disk.
+        if !self.has_file() {
+            return Ok(());
+        }
+
+        let dir = bits_path(&self.path);
+        std::fs::create_dir_all(&dir)?;
+
+        let offset = self.offset();
+        let data = &self.map[offset..offset + self.layout.size()];
+        for (byte, value) in data.iter().enumerate() {
+            for bit in 0..8 {
+                let digit = if value >> bit & 1 == 0 { b"0" } else { b"1" };
+                std::fs::write(bit_path(&dir, byte, bit), digit)?;
+            }
+        }
+
+        for byte in data.len()..old_size {
+            for bit in 0..8 {
+                std::fs::remove_file(bit_path(&dir, byte, bit))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // removes the bits and their folder, once the allocation is gone. needs to
+    // be called while DEALLOCATING is nonzero.
+    pub(crate) fn remove_bits(&self) {
+        if !self.has_file() {
+            return;
+        }
+
+        // the reaper removes them in order, so the folder is empty by the time
+        // it gets to it.
+        let dir = bits_path(&self.path);
+        for byte in 0..self.layout.size() {
+            for bit in 0..8 {
+                crate::reaper::remove(bit_path(&dir, byte, bit));
+            }
+        }
+        crate::reaper::remove(dir);
+    }
+}
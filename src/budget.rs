@@ -0,0 +1,190 @@
+use std::{
+    cell::Cell,
+    sync::{Mutex, MutexGuard, RwLock},
+};
+
+use allocator_api2::alloc::System;
+use hashbrown::{hash_map::DefaultHashBuilder, HashSet};
+use lazy_static::lazy_static;
+
+use crate::{
+    error::{self, StupidError},
+    stats::stats_of,
+    system_scope, threads, StupidAlloc,
+};
+
+/// What happens to allocations that would go over a [`Budget`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BudgetAction {
+    /// They go to System, like the ones of threads where the stupid alloc is
+    /// disabled.
+    #[default]
+    System,
+    /// They fail.
+    Fail,
+}
+
+/// A limit on the live stupid allocations, of the whole program with
+/// [`StupidAlloc::set_budget()`], or of a thread with
+/// [`StupidAlloc::set_thread_budget()`]: handy to keep the disk from filling
+/// up, or to only give files to one part of the program.
+///
+/// Allocations that would go over it, or resizes that would make one grow
+/// over it, go to System or fail, and the error handler hears of it with a
+/// [`StupidError::OverBudget`].
+///
+/// ```
+/// use stupidalloc::{Budget, BudgetAction, StupidAlloc};
+///
+/// // a hundred mebibytes of files, and not a byte more.
+/// StupidAlloc.set_budget(Some(Budget::new().bytes(100 << 20).action(BudgetAction::Fail)));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Budget {
+    bytes: Option<usize>,
+    allocations: Option<usize>,
+    action: BudgetAction,
+}
+
+impl Budget {
+    /// No limits, and allocations over them would go to System.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the total size of the live allocations to `bytes`.
+    pub fn bytes(mut self, bytes: usize) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    /// Limits the number of live allocations to `allocations`.
+    pub fn allocations(mut self, allocations: usize) -> Self {
+        self.allocations = Some(allocations);
+        self
+    }
+
+    /// Sets what happens to allocations over the budget.
+    pub fn action(mut self, action: BudgetAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    // whether `bytes` in `allocations` more than `live` go over the budget.
+    fn exceeded_by(
+        &self,
+        (live_bytes, live_allocations): (usize, usize),
+        bytes: usize,
+        allocations: usize,
+    ) -> bool {
+        let over = |limit: Option<usize>, live: usize, more: usize| {
+            limit.is_some_and(|limit| live.saturating_add(more) > limit)
+        };
+        over(self.bytes, live_bytes, bytes) || over(self.allocations, live_allocations, allocations)
+    }
+}
+
+static GLOBAL: RwLock<Option<Budget>> = RwLock::new(None);
+
+pub type Spilled = HashSet<usize, DefaultHashBuilder, System>;
+
+lazy_static! {
+    // the addresses of the live allocations that went to System for being over
+    // a budget, so that they can be told apart from pointers that don't come
+    // from here at all. it lives in System, since the stupid alloc is calling.
+    static ref SPILLED: Mutex<Spilled> = Mutex::new(HashSet::new_in(System));
+}
+
+thread_local! {
+    static THREAD: Cell<Option<Budget>> = const { Cell::new(None) };
+}
+
+pub fn lock() -> MutexGuard<'static, Spilled> {
+    SPILLED.lock().unwrap()
+}
+
+// remembers that the allocation at `addr` went to System for being over a
+// budget.
+pub fn spill(addr: usize) {
+    lock().insert(addr);
+}
+
+// forgets the allocation at `addr`, about to be freed or moved, and returns
+// whether it went to System for being over a budget.
+pub fn unspill(addr: usize) -> bool {
+    lock().remove(&addr)
+}
+
+// what to do with `bytes` more in `allocations` more allocations made by the
+// calling thread, if they go over a budget. the thread's own comes first.
+// reports it, and must be called with the registry unlocked.
+pub fn check(bytes: usize, allocations: usize) -> Option<BudgetAction> {
+    let thread = THREAD.try_with(Cell::get).ok().flatten();
+    let global = *GLOBAL.read().unwrap();
+    if thread.is_none() && global.is_none() {
+        return None;
+    }
+
+    let usage = |stats: crate::Stats| (stats.bytes, stats.allocations);
+    let (budget, of_thread) = match thread {
+        Some(budget)
+            if budget.exceeded_by(
+                usage(stats_of(|handle| handle.thread == threads::number())),
+                bytes,
+                allocations,
+            ) =>
+        {
+            (budget, true)
+        }
+        _ => match global {
+            Some(budget) if budget.exceeded_by(usage(stats_of(|_| true)), bytes, allocations) => {
+                (budget, false)
+            }
+            _ => return None,
+        },
+    };
+
+    system_scope(|| {
+        error::report(StupidError::OverBudget {
+            bytes,
+            thread: of_thread,
+        })
+    });
+    Some(budget.action)
+}
+
+impl StupidAlloc {
+    /// Sets the [`Budget`] of the whole program, or removes it with [`None`].
+    /// Allocations that are already live are left alone, even if they're over
+    /// it already.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{Budget, BudgetAction, StupidAlloc, StupidError};
+    ///
+    /// let first = Box::new_in([0u8; 64], StupidAlloc);
+    /// let live = StupidAlloc.stats().bytes;
+    /// StupidAlloc.set_budget(Some(Budget::new().bytes(live + 100).action(BudgetAction::Fail)));
+    ///
+    /// let second = Box::try_new_in([0u8; 64], StupidAlloc).unwrap();
+    /// assert!(Box::try_new_in([0u8; 64], StupidAlloc).is_err());
+    /// assert!(matches!(StupidAlloc.last_error(), Some(StupidError::OverBudget { .. })));
+    ///
+    /// // the one that went to System is freed like any other.
+    /// StupidAlloc.set_budget(Some(Budget::new().bytes(live + 100)));
+    /// let spilled = Box::new_in([0u8; 64], StupidAlloc);
+    /// assert!(StupidAlloc.file_of(&*spilled).is_none());
+    /// drop(spilled);
+    /// StupidAlloc.set_budget(None);
+    /// ```
+    pub fn set_budget(&self, budget: Option<Budget>) {
+        *GLOBAL.write().unwrap() = budget;
+    }
+
+    /// Sets the [`Budget`] of the calling thread, or removes it with [`None`].
+    /// It only counts the live allocations the thread made itself, wherever
+    /// they were freed, and comes on top of the budget of the whole program.
+    pub fn set_thread_budget(&self, budget: Option<Budget>) {
+        let _ = THREAD.try_with(|thread| thread.set(budget));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{fmt::Write as _, io, path::Path};
+
+use crate::{
+    analysis::histogram, backtraces, graph::pointer_graph, system_scope, HexDump, StupidAlloc,
+    Timestamp, STUPID_MAP,
+};
+
+// `field` as a csv field, quoted if it has to be.
+fn csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// the records of a csv file, as written by `csv`: fields may be quoted, and
+// quoted fields may hold anything, line breaks included.
+pub fn records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let (mut quoted, mut chars) = (false, text.chars().peekable());
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
+
+// the smallest power of two at least as big as `size`, the upper bound of its
+// size class. zero-sized allocations get a class of their own.
+fn size_class(size: usize) -> usize {
+    match size {
+        0 => 0,
+        size => size.checked_next_power_of_two().unwrap_or(usize::MAX),
+    }
+}
+
+impl StupidAlloc {
+    /// Writes everything there is to know about the heap into the folder
+    /// `dir` (created if needed), as files that scripts and spreadsheets can
+    /// read: one call to collect it all for a bug report. Zip the folder to
+    /// send it around.
+    ///
+    /// - `stats.json`: when the stupid alloc started and when the bundle was
+    ///   written, and the [`Stats`](crate::Stats),
+    ///   [`Totals`](crate::Totals) and [`Fallbacks`](crate::Fallbacks).
+    /// - `allocations.csv`: one line per live allocation, with its address,
+    ///   layout, thread, age, resizes, type, location, call site, arena and
+    ///   file. The call site is the one of
+    ///   [`StupidAlloc::lifetime_report()`], and bundles of two runs can be
+    ///   compared with [`StupidAlloc::compare_runs()`].
+    /// - `pointers.csv`: the edges of the pointer graph of
+    ///   [`StupidAlloc::export_graph()`], one per pointer, with the offsets of
+    ///   the pointer and of what it points to.
+    /// - `sizes.csv`: the number of allocations and bytes per size class, by
+    ///   powers of two.
+    /// - `bytes.csv`: how many bytes of each value there are, over all the
+    ///   allocations.
+    /// - `hexdumps/`: if `hexdumps` is `true`, the contents of every
+    ///   allocation like `xxd` shows them, one file per address.
+    ///
+    /// Files left over from a previous bundle in `dir` are overwritten.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let value = Box::new_in(42u64, StupidAlloc);
+    /// let reference = Box::new_in(&*value, StupidAlloc);
+    ///
+    /// let dir = std::env::temp_dir().join("stupidalloc_doc_bundle");
+    /// StupidAlloc.export_bundle(&dir, true).unwrap();
+    ///
+    /// let allocations = std::fs::read_to_string(dir.join("allocations.csv")).unwrap();
+    /// assert!(allocations.contains(&format!("0x{:x},8,8,", &*value as *const u64 as usize)));
+    ///
+    /// let pointers = std::fs::read_to_string(dir.join("pointers.csv")).unwrap();
+    /// assert!(pointers.contains(&format!("{:p},0,{:p},0", &*reference, &*value)));
+    /// ```
+    pub fn export_bundle(&self, dir: impl AsRef<Path>, hexdumps: bool) -> io::Result<()> {
+        let dir = dir.as_ref();
+
+        // these lock the registry on their own.
+        let stats = self.stats();
+        let totals = self.totals();
+        let fallbacks = self.fallbacks();
+
+        // while the registry is locked, nothing must be stupid-allocated.
+        system_scope(|| {
+            std::fs::create_dir_all(dir)?;
+            if hexdumps {
+                std::fs::create_dir_all(dir.join("hexdumps"))?;
+            }
+
+            // can't fail, they're strings
+            let mut json = String::from("{\n");
+            let _ = writeln!(json, "  \"started_at\": \"{}\",", self.started_at());
+            let _ = writeln!(json, "  \"exported_at\": \"{}\",", Timestamp::now());
+            let _ = writeln!(
+                json,
+                "  \"stats\": {{\"allocations\": {}, \"bytes\": {}, \"fallbacks\": {}}},",
+                stats.allocations, stats.bytes, stats.fallbacks
+            );
+            let _ = writeln!(
+                json,
+                "  \"totals\": {{\"allocations\": {}, \"frees\": {}, \"resizes\": {}, \
+                 \"largest\": {}, \"most_resizes\": {}, \"longest_lived_seconds\": {}}},",
+                totals.allocations,
+                totals.frees,
+                totals.resizes,
+                totals.largest,
+                totals.most_resizes,
+                totals.longest_lived.as_secs_f64()
+            );
+            let _ = writeln!(
+                json,
+                "  \"fallbacks\": {{\"disabled\": {}, \"recursion\": {}, \"below_threshold\": {}, \
+                 \"late\": {}, \"over_budget\": {}}}",
+                fallbacks.disabled,
+                fallbacks.recursion,
+                fallbacks.below_threshold,
+                fallbacks.late,
+                fallbacks.over_budget
+            );
+            json.push_str("}\n");
+            std::fs::write(dir.join("stats.json"), json)?;
+
+            let mut table = String::from(
+                "address,size,align,thread,age_seconds,resizes,type,location,call_site,arena,path\n",
+            );
+            let mut sizes = std::collections::BTreeMap::<usize, (usize, usize)>::new();
+            let mut bytes = [0; 256];
+            {
+                let map = STUPID_MAP.read_all();
+                let mut handles = map.iter().collect::<Vec<_>>();
+                handles.sort_by_key(|(&addr, _)| addr);
+
+                for (&addr, handle) in handles {
+                    let offset = handle.offset();
+                    let data = &handle.map[offset..offset + handle.layout.size()];
+
+                    let _ = writeln!(
+                        table,
+                        "0x{addr:x},{},{},{},{},{},{},{},{},{},{}",
+                        handle.layout.size(),
+                        handle.layout.align(),
+                        handle.thread,
+                        handle.created.elapsed().as_secs_f64(),
+                        handle.resizes,
+                        csv(handle.type_name.unwrap_or_default()),
+                        csv(&handle.location.map(|l| l.to_string()).unwrap_or_default()),
+                        csv(&backtraces::call_site(handle)),
+                        handle.arena.map(|a| a.to_string()).unwrap_or_default(),
+                        csv(&handle.path.to_string_lossy())
+                    );
+
+                    let class = sizes.entry(size_class(data.len())).or_default();
+                    class.0 += 1;
+                    class.1 += data.len();
+                    for (total, count) in bytes.iter_mut().zip(histogram(data)) {
+                        *total += count;
+                    }
+
+                    if hexdumps {
+                        let dump = HexDump { bytes: data, addr }.to_string();
+                        std::fs::write(dir.join(format!("hexdumps/0x{addr:016x}.txt")), dump)?;
+                    }
+                }
+            }
+            std::fs::write(dir.join("allocations.csv"), table)?;
+
+            let mut classes = String::from("up_to,allocations,bytes\n");
+            for (up_to, (allocations, total)) in sizes {
+                let _ = writeln!(classes, "{up_to},{allocations},{total}");
+            }
+            std::fs::write(dir.join("sizes.csv"), classes)?;
+
+            let mut values = String::from("byte,count\n");
+            for (byte, count) in bytes.iter().enumerate() {
+                let _ = writeln!(values, "0x{byte:02x},{count}");
+            }
+            std::fs::write(dir.join("bytes.csv"), values)?;
+
+            let (_, edges) = pointer_graph();
+            let mut pointers = String::from("from,field,to,target\n");
+            for edge in edges {
+                let _ = writeln!(
+                    pointers,
+                    "0x{:x},{},0x{:x},{}",
+                    edge.from, edge.field, edge.to, edge.target
+                );
+            }
+            std::fs::write(dir.join("pointers.csv"), pointers)
+        })
+    }
+}
@@ -0,0 +1,240 @@
+//! Functions for C and C++ programs, exported from the `cdylib` build of the
+//! crate with the `c-api` feature. The crate is only built as a Rust library
+//! by default, the `cdylib` is asked for on the command line:
+//!
+//! ```sh
+//! cargo rustc --release --lib --crate-type cdylib --features c-api
+//! ```
+//!
+//! ```c
+//! void *stupid_malloc(size_t size);
+//! void *stupid_calloc(size_t count, size_t size);
+//! void *stupid_realloc(void *ptr, size_t size);
+//! void stupid_free(void *ptr);
+//! char *stupid_state_json(void);
+//! ```
+//!
+//! Like with Rust programs, the stupid alloc is only enabled in the first
+//! thread using it, and the others get their memory from the C library's
+//! `malloc`, until they call [`StupidAlloc::enable_in_thread()`], which is
+//! not exported: pick the thread wisely. Memory can be freed and resized from
+//! any thread, stupid or not.
+
+use std::{
+    alloc::Layout,
+    ffi::{c_char, c_void},
+    fmt::Write,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    budget, busy, free_registered, shutdown, stats, system_scope, use_system, Allocator,
+    StupidAlloc, STUPID_MAP,
+};
+
+// the C library's allocator, for everything the stupid alloc leaves alone. the
+// preload library replaces malloc and friends, so it reaches the real ones
+// through glibc's own names for them.
+#[cfg(not(all(feature = "preload", target_os = "linux", target_env = "gnu")))]
+pub(crate) use libc::{
+    calloc as real_calloc, free as real_free, malloc as real_malloc, realloc as real_realloc,
+};
+
+#[cfg(all(feature = "preload", target_os = "linux", target_env = "gnu"))]
+extern "C" {
+    #[link_name = "__libc_malloc"]
+    pub(crate) fn real_malloc(size: usize) -> *mut c_void;
+    #[link_name = "__libc_calloc"]
+    pub(crate) fn real_calloc(count: usize, size: usize) -> *mut c_void;
+    #[link_name = "__libc_realloc"]
+    pub(crate) fn real_realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+    #[link_name = "__libc_free"]
+    pub(crate) fn real_free(ptr: *mut c_void);
+}
+
+// malloc promises memory fit for any type, which means 16 bytes on the
+// platforms that matter.
+const MALLOC_ALIGN: usize = 16;
+
+// the layout of the stupid allocation starting at `ptr`, if that's what it is.
+// pointers from malloc are never in the registry, so this tells them apart.
+pub(crate) fn registered_layout(ptr: *mut c_void) -> Option<Layout> {
+    // looking at the registry while it's already locked in this thread would
+    // deadlock, and busy threads only ever make allocations with malloc anyway.
+    if busy() {
+        return None;
+    }
+
+    let addr = ptr as usize;
+    STUPID_MAP.read(addr).get(&addr).map(|handle| handle.layout)
+}
+
+fn malloc_layout(size: usize) -> Option<Layout> {
+    // zero-sized files can't be mapped. malloc may return a unique pointer for
+    // those anyway.
+    Layout::from_size_align(size.max(1), MALLOC_ALIGN).ok()
+}
+
+/// Allocates `size` bytes, like `malloc`. Threads where
+/// the stupid alloc is disabled get their memory from the C library's
+/// `malloc` instead, see [`StupidAlloc::enable_in_thread()`].
+///
+/// # Safety
+/// Same as `malloc`.
+#[no_mangle]
+pub unsafe extern "C" fn stupid_malloc(size: usize) -> *mut c_void {
+    if use_system() || shutdown::late() {
+        stats::record_fallback();
+        return real_malloc(size);
+    }
+
+    malloc_layout(size)
+        .and_then(|layout| StupidAlloc.allocate(layout).ok())
+        .map_or(ptr::null_mut(), |ptr| ptr.as_ptr() as *mut c_void)
+}
+
+/// Allocates an array of `count` elements of `size` bytes each, like
+/// `calloc`. Returns a null pointer if the total size overflows.
+///
+/// # Safety
+/// Same as `calloc`.
+#[no_mangle]
+pub unsafe extern "C" fn stupid_calloc(count: usize, size: usize) -> *mut c_void {
+    if use_system() || shutdown::late() {
+        stats::record_fallback();
+        return real_calloc(count, size);
+    }
+
+    // zeroed whatever the fill pattern, which only applies to malloc.
+    count
+        .checked_mul(size)
+        .and_then(malloc_layout)
+        .and_then(|layout| StupidAlloc.allocate_zeroed(layout).ok())
+        .map_or(ptr::null_mut(), |ptr| ptr.as_ptr() as *mut c_void)
+}
+
+/// Frees memory returned by the other functions of the C API, like `free`,
+/// from any thread. Does nothing if `ptr` is null.
+///
+/// # Safety
+/// Same as `free`.
+#[no_mangle]
+pub unsafe extern "C" fn stupid_free(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    // the allocation may come from a thread where the stupid alloc is enabled,
+    // so it doesn't matter whether it is in this one.
+    match registered_layout(ptr) {
+        Some(layout) => free_registered(ptr as usize, layout),
+        None => {
+            budget::unspill(ptr as usize);
+            real_free(ptr)
+        }
+    }
+}
+
+/// Resizes memory returned by the other functions of the C API, like
+/// `realloc`, keeping the contents that fit. A null `ptr` allocates new
+/// memory, and a `size` of zero frees `ptr` and returns a null pointer.
+///
+/// # Safety
+/// Same as `realloc`.
+#[no_mangle]
+pub unsafe extern "C" fn stupid_realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return stupid_malloc(size);
+    }
+
+    if size == 0 {
+        stupid_free(ptr);
+        return ptr::null_mut();
+    }
+
+    let Some(old_layout) = registered_layout(ptr) else {
+        // malloc's, maybe for going over a budget, in which case the new
+        // address must be known as such.
+        let new = real_realloc(ptr, size);
+        if !new.is_null() && budget::unspill(ptr as usize) {
+            budget::spill(new as usize);
+        }
+        return new;
+    };
+    let Some(new_layout) = malloc_layout(size) else {
+        return ptr::null_mut();
+    };
+
+    if use_system() || shutdown::late() {
+        // a stupid allocation, resized in a thread where the stupid alloc is
+        // disabled or after it shut down: it moves to malloc.
+        stats::record_fallback();
+        let new = real_malloc(size);
+        if !new.is_null() {
+            ptr::copy_nonoverlapping(
+                ptr as *const u8,
+                new as *mut u8,
+                old_layout.size().min(size),
+            );
+            free_registered(ptr as usize, old_layout);
+        }
+        return new;
+    }
+
+    let old = NonNull::new_unchecked(ptr as *mut u8);
+    let resized = if new_layout.size() >= old_layout.size() {
+        StupidAlloc.grow(old, old_layout, new_layout)
+    } else {
+        StupidAlloc.shrink(old, old_layout, new_layout)
+    };
+
+    resized.map_or(ptr::null_mut(), |ptr| ptr.as_ptr() as *mut c_void)
+}
+
+// writes `s` as a json string.
+fn write_json_string(json: &mut String, s: &str) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Returns the state of the stupid alloc as a JSON array, with one
+/// `{"address": ..., "path": "..."}` object per allocation, like
+/// [`StupidAlloc::state()`]. The string is null-terminated, and must be freed
+/// with the C library's `free`. Returns a null pointer if there is no memory
+/// left for it.
+#[no_mangle]
+pub extern "C" fn stupid_state_json() -> *mut c_char {
+    system_scope(|| {
+        let mut state = StupidAlloc.state().into_iter().collect::<Vec<_>>();
+        state.sort();
+
+        let mut json = String::from("[");
+        for (i, (addr, path)) in state.into_iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(json, "{{\"address\":{addr},\"path\":").unwrap();
+            write_json_string(&mut json, &path.to_string_lossy());
+            json.push('}');
+        }
+        json.push(']');
+
+        // the caller frees it with free, so it has to come from malloc.
+        let c_json = unsafe { real_malloc(json.len() + 1) } as *mut u8;
+        if !c_json.is_null() {
+            unsafe {
+                ptr::copy_nonoverlapping(json.as_ptr(), c_json, json.len());
+                *c_json.add(json.len()) = 0;
+            }
+        }
+        c_json as *mut c_char
+    })
+}
@@ -0,0 +1,177 @@
+// wasi has no windows, the chart never opens there.
+#![cfg_attr(target_os = "wasi", allow(dead_code, unused_imports))]
+
+use std::{
+    alloc::System,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use allocator_api2::vec::Vec;
+
+use crate::{error, hud, placement, system_scope, threads, HeapEvent, HeapInspector, StupidAlloc};
+
+// the size of the chart, in pixels before scaling. one sample per column.
+const WIDTH: usize = 256;
+const HEIGHT: usize = 128;
+
+// how often the chart samples the heap.
+const INTERVAL: Duration = Duration::from_millis(100);
+
+// the rows taken by the labels at the top.
+const LABELS: usize = 2 * hud::LINE_HEIGHT + 1;
+
+const BYTES: u32 = 0x00FFFFFF;
+const ALLOCATIONS: u32 = 0x0040FF40;
+const BIG: u32 = 0x00FF4040;
+
+// one sample of the heap, and the biggest allocation made since the one
+// before, if one was big.
+#[derive(Clone, Copy, Default)]
+struct Sample {
+    bytes: usize,
+    allocations: usize,
+    big: usize,
+}
+
+// the height of `value` in the chart, from the top, when `max` is at the top
+// of the plot.
+fn row(value: usize, max: usize) -> usize {
+    let plot = HEIGHT - LABELS - 1;
+    HEIGHT - 1 - value * plot / max.max(1)
+}
+
+// draws `samples`, oldest first, the newest one on the right.
+fn draw(buffer: &mut [u32], samples: &[Sample]) {
+    buffer.fill(0);
+
+    let first = WIDTH - samples.len();
+    let max_bytes = samples.iter().map(|s| s.bytes).max().unwrap_or(0);
+    let max_allocations = samples.iter().map(|s| s.allocations).max().unwrap_or(0);
+
+    // the markers go under the lines.
+    for (i, sample) in samples.iter().enumerate() {
+        if sample.big > 0 {
+            for y in LABELS..HEIGHT {
+                buffer[y * WIDTH + first + i] = BIG;
+            }
+        }
+    }
+
+    // lines, joining each sample to the one before.
+    let mut line = |value: fn(&Sample) -> usize, max: usize, color: u32| {
+        for (i, pair) in samples.windows(2).enumerate() {
+            let (a, b) = (row(value(&pair[0]), max), row(value(&pair[1]), max));
+            for y in a.min(b)..=a.max(b) {
+                buffer[y * WIDTH + first + i + 1] = color;
+            }
+        }
+    };
+    line(|s| s.allocations, max_allocations, ALLOCATIONS);
+    line(|s| s.bytes, max_bytes, BYTES);
+
+    if let Some(last) = samples.last() {
+        let bytes = format!("bytes {} max {max_bytes}", last.bytes);
+        let allocations = format!("allocations {} max {max_allocations}", last.allocations);
+        hud::write(buffer, WIDTH, (1, 1), &bytes, BYTES);
+        hud::write(
+            buffer,
+            WIDTH,
+            (1, 1 + hud::LINE_HEIGHT),
+            &allocations,
+            ALLOCATIONS,
+        );
+    }
+}
+
+// samples the heap and draws the chart until its window is closed. `big` is
+// the biggest allocation over the threshold since the last sample.
+#[cfg(not(target_os = "wasi"))]
+fn run(big: Arc<AtomicUsize>) {
+    use minifb::{Scale, Window, WindowOptions};
+
+    let options = WindowOptions {
+        scale: Scale::X4,
+        ..Default::default()
+    };
+    let Ok(mut window) = Window::new("Stupid heap usage", WIDTH, HEIGHT, options) else {
+        return;
+    };
+    if let Some((x, y)) = placement::place(window.get_size()) {
+        window.set_position(x, y);
+    }
+    window.limit_update_rate(Some(INTERVAL));
+
+    let mut samples = Vec::<Sample, System>::with_capacity_in(WIDTH, System);
+    let mut buffer = Vec::with_capacity_in(WIDTH * HEIGHT, System);
+    buffer.resize(WIDTH * HEIGHT, 0);
+
+    while window.is_open() {
+        let stats = StupidAlloc.stats();
+        if samples.len() == WIDTH {
+            samples.remove(0);
+        }
+        samples.push(Sample {
+            bytes: stats.bytes,
+            allocations: stats.allocations,
+            big: big.swap(0, Ordering::SeqCst),
+        });
+
+        draw(&mut buffer, &samples);
+        if window.update_with_buffer(&buffer, WIDTH, HEIGHT).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(target_os = "wasi")]
+fn run(_big: Arc<AtomicUsize>) {}
+
+impl StupidAlloc {
+    /// Opens a window charting the heap over the last 25 seconds or so: the
+    /// number of bytes of the live allocations in white, and their number in
+    /// green, each on a scale of its own. Allocations of at least `big` bytes
+    /// are marked by a red line, when they're made or grown that big. The
+    /// chart keeps going until its window is closed.
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub fn open_usage_chart(&self, big: usize) {
+        // the callback and the counter it shares with the chart live in
+        // System, since they're dropped by the thread of the chart.
+        let (counter, subscription) = system_scope(|| {
+            let counter = Arc::new(AtomicUsize::new(0));
+            let seen = Arc::clone(&counter);
+            let subscription = self.subscribe(Box::new(move |event| {
+                let size = match event {
+                    HeapEvent::Allocated { layout, .. } => layout.size(),
+                    HeapEvent::Resized { new_layout, .. } => new_layout.size(),
+                    _ => 0,
+                };
+                if size >= big.max(1) {
+                    seen.fetch_max(size, Ordering::SeqCst);
+                }
+            }));
+            (counter, subscription)
+        });
+
+        // so is the thread, in case it can't be spawned and drops them here.
+        system_scope(|| {
+            let spawned = std::thread::Builder::new()
+                .name("stupid heap usage".into())
+                .spawn(move || {
+                    #[cfg(feature = "valgrind")]
+                    crate::valgrind::disable_errors_in_thread();
+
+                    threads::internal();
+                    run(counter);
+                    system_scope(|| StupidAlloc.unsubscribe(subscription));
+                });
+            if let Err(e) = spawned {
+                error::backend(format!("couldn't spawn the chart thread: {e}"));
+                StupidAlloc.unsubscribe(subscription);
+            }
+        });
+    }
+}
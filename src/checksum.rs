@@ -0,0 +1,157 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    error::{self, StupidError},
+    AllocHandle, StupidAlloc,
+};
+
+static CHECKSUMS: AtomicBool = AtomicBool::new(false);
+
+pub fn enabled() -> bool {
+    CHECKSUMS.load(Ordering::SeqCst)
+}
+
+// fnv-1a, which unlike the hasher of std gives the same result from one build
+// to the next, for the checksums of persistent files to make sense in the next
+// run.
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// where the checksum of the persistent allocation with the file at `path` is
+// kept, for the next run to check it.
+fn sum_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".sum");
+    PathBuf::from(path)
+}
+
+// the checksum of the `len` bytes at `offset` in the file at `path`, as they
+// are on disk rather than in the map. reports it if they can't be read.
+fn checksum_on_disk(path: &Path, offset: usize, len: usize) -> Option<u64> {
+    let file = error::check(std::fs::read(path), "read back the file", path)?;
+    // a file cut short doesn't hold the data anymore, whatever it holds.
+    Some(file.get(offset..offset + len).map_or(!0, checksum))
+}
+
+// checks the file of a persistent allocation against the checksum kept with it
+// by the run that made it, if there is one. must be called in system_scope.
+pub fn check_persistent(path: &Path, data: &[u8]) {
+    if !enabled() {
+        return;
+    }
+    let Some(sum) = std::fs::read_to_string(sum_path(path))
+        .ok()
+        .and_then(|sum| u64::from_str_radix(sum.trim(), 16).ok())
+    else {
+        return;
+    };
+
+    if checksum(data) != sum {
+        error::report(StupidError::ChecksumMismatch {
+            path: path.to_path_buf(),
+            external: false,
+        });
+    }
+}
+
+impl AllocHandle {
+    // checks that the file still holds either the data as of the last flush
+    // point, or the data as it is now, which is what the map shows on most
+    // platforms. anything else got there behind the back of the allocation.
+    // must be called in system_scope, like everything else touching files.
+    pub(crate) fn verify_checksum(&self) {
+        let Some(flushed) = self.checksum.filter(|_| enabled() && self.has_file()) else {
+            return;
+        };
+
+        let offset = self.offset();
+        let Some(on_disk) = checksum_on_disk(&self.path, offset, self.layout.size()) else {
+            return;
+        };
+        if on_disk == flushed || on_disk == checksum(&self.map[offset..offset + self.layout.size()])
+        {
+            return;
+        }
+
+        // the watcher heard of an edit, so someone did it on purpose.
+        #[cfg(feature = "watch")]
+        let external = self.edited;
+        #[cfg(not(feature = "watch"))]
+        let external = false;
+
+        error::report(StupidError::ChecksumMismatch {
+            path: self.path.clone(),
+            external,
+        });
+    }
+
+    // remembers the checksum of the data, right after it was flushed, and keeps
+    // it next to the file of persistent allocations. losing it is not worth
+    // failing over. must be called in system_scope.
+    pub(crate) fn store_checksum(&mut self) {
+        if !enabled() || !self.has_file() {
+            return;
+        }
+
+        let offset = self.offset();
+        let sum = checksum(&self.map[offset..offset + self.layout.size()]);
+        self.checksum = Some(sum);
+
+        if self.persistent {
+            let path = sum_path(&self.path);
+            let _ = error::check(
+                std::fs::write(&path, format!("{sum:016x}\n")),
+                "write the checksum",
+                &path,
+            );
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Enables or disables checksums of the allocation files. When enabled,
+    /// flushing an allocation with [`StupidAlloc::flush_of()`] or
+    /// [`StupidAlloc::flush_all()`] remembers a checksum of its contents, and
+    /// the next flush, or the free, reads the file back to check that it
+    /// holds either what was flushed or what the memory holds now. Anything
+    /// else is reported as a [`StupidError::ChecksumMismatch`]: the file was
+    /// replaced, cut short or corrupted. With the `watch` feature, files that
+    /// another program was heard editing are told apart from corrupted ones.
+    ///
+    /// On most platforms the memory is the file, so that edits made in place
+    /// show up in the memory right away, and only the `watch` feature hears of
+    /// them. Persistent allocations keep their checksum in a `.sum` file next
+    /// to theirs, which [`StupidAlloc::restore()`] checks, to catch changes
+    /// made while the program wasn't running.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{StupidAlloc, StupidError};
+    ///
+    /// StupidAlloc.set_checksums(true);
+    /// let value = Box::new_in([1u8; 16], StupidAlloc);
+    /// StupidAlloc.flush_of(&*value).unwrap();
+    ///
+    /// // some other program replaces the file.
+    /// let path = StupidAlloc.file_of(&*value).unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    /// std::fs::write(&path, [2u8; 16]).unwrap();
+    ///
+    /// StupidAlloc.flush_of(&*value).unwrap();
+    /// assert!(matches!(
+    ///     StupidAlloc.last_error(),
+    ///     Some(StupidError::ChecksumMismatch { .. })
+    /// ));
+    /// StupidAlloc.set_checksums(false);
+    /// ```
+    pub fn set_checksums(&self, value: bool) {
+        CHECKSUMS.store(value, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Bound::{Excluded, Unbounded},
+    sync::Mutex,
+};
+
+use crate::{backtraces, system_scope, AllocHandle, StupidAlloc, STUPID_MAP};
+
+/// The allocations of a call site that were resized too many times, see
+/// [`StupidAlloc::churn_report()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChurnSite {
+    /// Where the allocations were made, like
+    /// [`SiteLifetimes::call_site`](crate::SiteLifetimes::call_site).
+    pub call_site: String,
+    /// The number of allocations made there that were resized too many
+    /// times, live or freed.
+    pub allocations: u64,
+    /// The total number of times they were resized.
+    pub resizes: u64,
+    /// The number of times the most resized of them was resized.
+    pub most_resizes: usize,
+    /// The addresses of the ones that are still live, sorted.
+    pub live: Vec<usize>,
+}
+
+/// The outcome of [`StupidAlloc::churn_report()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChurnReport {
+    /// The number of resizes over which an allocation churns.
+    pub threshold: usize,
+    /// The call sites with churning allocations, the one with the most
+    /// resizes first.
+    pub sites: Vec<ChurnSite>,
+}
+
+impl ChurnReport {
+    /// The total number of times the churning allocations were resized.
+    pub fn resizes(&self) -> u64 {
+        self.sites.iter().map(|site| site.resizes).sum()
+    }
+}
+
+impl fmt::Display for ChurnReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} call sites with allocations resized more than {} times, {} resizes",
+            self.sites.len(),
+            self.threshold,
+            self.resizes()
+        )?;
+        for site in &self.sites {
+            writeln!(
+                f,
+                "  {}: {} allocations, {} live, {} resizes, at most {}",
+                site.call_site,
+                site.allocations,
+                site.live.len(),
+                site.resizes,
+                site.most_resizes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// the freed allocations that were resized, by call site: how many of them were
+// resized how many times.
+static FREED: Mutex<BTreeMap<String, BTreeMap<usize, u64>>> = Mutex::new(BTreeMap::new());
+
+// records an allocation made at `call_site` that is being freed. must be called
+// while DEALLOCATING is nonzero.
+pub fn record(handle: &AllocHandle, call_site: &str) {
+    if handle.resizes == 0 {
+        return;
+    }
+
+    let mut freed = FREED.lock().unwrap();
+    if !freed.contains_key(call_site) {
+        freed.insert(call_site.into(), BTreeMap::new());
+    }
+    if let Some(counts) = freed.get_mut(call_site) {
+        *counts.entry(handle.resizes).or_default() += 1;
+    }
+}
+
+// the number of resizes after which allocations get a warning in their log.
+#[cfg(feature = "logging")]
+static WARN_AFTER: std::sync::RwLock<Option<usize>> = std::sync::RwLock::new(None);
+
+// the warning to log for an allocation that was just resized, if it's one
+// resize too many.
+#[cfg(feature = "logging")]
+pub fn warning(handle: &AllocHandle) -> Option<String> {
+    let threshold = (*WARN_AFTER.read().unwrap())?;
+    (threshold.checked_add(1) == Some(handle.resizes)).then(|| {
+        format!(
+            "## Churn\nResized more than {threshold} times: if the final size is known, \
+             reserve it up front with `with_capacity()` or `reserve()`.\n"
+        )
+    })
+}
+
+// the churn of `call_site` among `sites`, added if it's not there yet.
+fn site(sites: &mut BTreeMap<String, ChurnSite>, call_site: String) -> &mut ChurnSite {
+    sites.entry(call_site.clone()).or_insert(ChurnSite {
+        call_site,
+        allocations: 0,
+        resizes: 0,
+        most_resizes: 0,
+        live: Vec::new(),
+    })
+}
+
+fn report(threshold: usize) -> ChurnReport {
+    let mut sites = BTreeMap::new();
+
+    for (call_site, counts) in FREED.lock().unwrap().iter() {
+        for (&resizes, &count) in counts.range((Excluded(threshold), Unbounded)) {
+            let site = site(&mut sites, call_site.clone());
+            site.allocations += count;
+            site.resizes += resizes as u64 * count;
+            site.most_resizes = site.most_resizes.max(resizes);
+        }
+    }
+
+    for (&addr, handle) in STUPID_MAP
+        .read_all()
+        .iter()
+        .filter(|(_, handle)| handle.resizes > threshold)
+    {
+        let site = site(&mut sites, backtraces::call_site(handle));
+        site.allocations += 1;
+        site.resizes += handle.resizes as u64;
+        site.most_resizes = site.most_resizes.max(handle.resizes);
+        site.live.push(addr);
+    }
+
+    let mut sites = sites.into_values().collect::<Vec<_>>();
+    for site in &mut sites {
+        site.live.sort_unstable();
+    }
+    sites.sort_by_key(|site| std::cmp::Reverse(site.resizes));
+
+    ChurnReport { threshold, sites }
+}
+
+impl StupidAlloc {
+    /// Reports the allocations, live or freed, that were resized more than
+    /// `threshold` times, by call site. That's a `Vec` or a `String` growing
+    /// one push at a time, which means as many new files, or at least as many
+    /// remaps, when `with_capacity()` would have done it in one go.
+    ///
+    /// Call sites are found like in [`StupidAlloc::lifetime_report()`]: from
+    /// the macros, or from the backtraces if they are enabled.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let mut stupid_vec = Vec::new_in(StupidAlloc);
+    /// for i in 0..100u32 {
+    ///     stupid_vec.push(i);
+    /// }
+    ///
+    /// let report = StupidAlloc.churn_report(3);
+    /// let site = report
+    ///     .sites
+    ///     .iter()
+    ///     .find(|site| site.live.contains(&(stupid_vec.as_ptr() as usize)))
+    ///     .unwrap();
+    /// assert!(site.most_resizes > 3);
+    /// println!("{report}");
+    /// ```
+    pub fn churn_report(&self, threshold: usize) -> ChurnReport {
+        // collected in System and then copied for the user.
+        let report = system_scope(|| report(threshold));
+        let user_report = report.clone();
+        system_scope(|| drop(report));
+        user_report
+    }
+
+    /// Writes a warning in the log of allocations as soon as they are resized
+    /// more than `threshold` times, or stops with [`None`]. The warning comes
+    /// right after the log of the resize, and its backtrace.
+    ///
+    /// ```
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// StupidAlloc.set_churn_warning(Some(8));
+    /// ```
+    #[cfg(feature = "logging")]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "logging")))]
+    pub fn set_churn_warning(&self, threshold: Option<usize>) {
+        *WARN_AFTER.write().unwrap() = threshold;
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fmt,
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::StupidAlloc;
+
+// when the stupid alloc started, on the monotonic clock and on the wall.
+static START: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+
+// starts the clock, if it's not running already. called at the first
+// allocation, and by whatever needs the time before that.
+pub fn start() -> (Instant, SystemTime) {
+    *START.get_or_init(|| (Instant::now(), SystemTime::now()))
+}
+
+/// A moment of the run, both on the wall clock and relative to the start of
+/// the stupid alloc, on a monotonic clock. Logs, the heap map and the report
+/// card show both, like `2026-03-14 15:09:26.535 UTC (+12.345s)`, so that the
+/// traces of several runs line up on the offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    /// The time since the first stupid allocation, or since the clock was
+    /// first asked for the time if that was earlier. Moments before that are
+    /// at zero.
+    pub since_start: Duration,
+    /// The time on the wall clock, according to the monotonic one: it doesn't
+    /// jump when the system clock is set.
+    pub wall: SystemTime,
+}
+
+impl Timestamp {
+    /// The current moment.
+    pub fn now() -> Self {
+        Self::of(Instant::now())
+    }
+
+    /// The moment of `instant`, like the one of a
+    /// [`ResizeRecord`](crate::ResizeRecord).
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use stupidalloc::Timestamp;
+    ///
+    /// let earlier = Timestamp::now();
+    /// let later = Timestamp::of(Instant::now() + Duration::from_secs(2));
+    ///
+    /// assert!(later.since_start - earlier.since_start >= Duration::from_secs(2));
+    /// println!("{later}");
+    /// ```
+    pub fn of(instant: Instant) -> Self {
+        let (start, wall) = start();
+        match instant.checked_duration_since(start) {
+            Some(since_start) => Timestamp {
+                since_start,
+                wall: wall + since_start,
+            },
+            None => Timestamp {
+                since_start: Duration::ZERO,
+                wall: wall - start.duration_since(instant),
+            },
+        }
+    }
+}
+
+// the year, month and day of the civil date `days` days after 1970-01-01,
+// from http://howardhinnant.github.io/date_algorithms.html.
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // nobody runs this before 1970.
+        let since_epoch = self.wall.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs() as i64;
+        let (year, month, day) = civil_date(secs.div_euclid(86_400));
+        let secs_of_day = secs.rem_euclid(86_400);
+
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:03} UTC (+{:.3}s)",
+            secs_of_day / 3600,
+            secs_of_day / 60 % 60,
+            secs_of_day % 60,
+            since_epoch.subsec_millis(),
+            self.since_start.as_secs_f64()
+        )
+    }
+}
+
+impl StupidAlloc {
+    /// Returns when the stupid alloc started: at its first allocation, or
+    /// when the time was first asked for, like with this, if that was
+    /// earlier. Every [`Timestamp`] is relative to that moment.
+    pub fn started_at(&self) -> Timestamp {
+        Timestamp::of(start().0)
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    alloc::System,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+};
+
+use crate::{claim_main_thread, system_scope, threads, user_error, StupidAlloc, STUPID_MAP};
+
+const HELP: &str = "\
+list                      lists the allocations: address, size and file
+hexdump <address>         dumps the contents of an allocation
+open <address> [columns]  opens a window for an allocation, columns can be auto
+close <address>           closes the window of an allocation
+enable                    makes new allocations stupid again
+disable                   gives new allocations memory from System instead
+snapshot <dir>            saves a snapshot of the heap in <dir>
+help                      shows this";
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
+
+// addresses are accepted in hex with a 0x prefix, the way they are listed, or
+// in decimal.
+fn parse_address(arg: Option<&str>) -> io::Result<usize> {
+    let arg = arg.ok_or_else(|| invalid("missing address"))?;
+    match arg.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => arg.parse(),
+    }
+    .map_err(|_| invalid(format!("invalid address: {arg:?}")))
+}
+
+// the inspection functions only look at the address of the value they're
+// given, and a reference to a zero-sized value may point anywhere but null.
+fn at<'a>(addr: usize) -> io::Result<&'a ()> {
+    match addr {
+        0 => Err(invalid("null address")),
+        addr => Ok(unsafe { &*(addr as *const ()) }),
+    }
+}
+
+// runs one command, and writes its output to `out`.
+fn run(line: &str, out: &mut impl Write) -> io::Result<()> {
+    let mut args = line.split_whitespace();
+
+    match args.next() {
+        Some("list") => {
+            let mut allocations = STUPID_MAP
+                .read_all()
+                .iter()
+                .map(|(&addr, handle)| (addr, handle.layout.size(), handle.path.clone()))
+                .collect::<Vec<_>>();
+            allocations.sort();
+
+            for (addr, size, path) in allocations {
+                writeln!(out, "0x{addr:016x} {size} {}", path.to_string_lossy())?;
+            }
+        }
+        Some("hexdump") => {
+            let addr = parse_address(args.next())?;
+            let dump = StupidAlloc
+                .hexdump_of(at(addr)?)
+                .ok_or_else(|| invalid(format!("no allocation at 0x{addr:x}")))?;
+            out.write_all(dump.as_bytes())?;
+        }
+        #[cfg(feature = "graphics")]
+        Some("open") => {
+            let addr = parse_address(args.next())?;
+            let columns = match args.next() {
+                Some("auto") => StupidAlloc::AUTO_COLUMNS,
+                Some(columns) => columns
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid number of columns: {columns:?}")))?,
+                None => 8,
+            };
+            StupidAlloc.open_window_of(at(addr)?, columns);
+        }
+        #[cfg(feature = "graphics")]
+        Some("close") => {
+            let addr = parse_address(args.next())?;
+            StupidAlloc.close_graphics_of(at(addr)?);
+        }
+        #[cfg(not(feature = "graphics"))]
+        Some("open" | "close") => {
+            return Err(invalid("windows need the `graphics` feature"));
+        }
+        // turning the stupid alloc off for real would send the allocations
+        // that already exist to System when they're freed.
+        Some("enable") => StupidAlloc.set_pass_through(None),
+        Some("disable") => StupidAlloc.set_pass_through(Some(&System)),
+        Some("snapshot") => {
+            let dir = args.next().ok_or_else(|| invalid("missing folder"))?;
+            let snapshot = StupidAlloc.snapshot_heap(dir)?;
+            writeln!(out, "{} allocations saved", snapshot.allocations().len())?;
+        }
+        Some("help") => writeln!(out, "{HELP}")?,
+        Some(command) => return Err(invalid(format!("unknown command: {command:?}"))),
+        None => {}
+    }
+
+    Ok(())
+}
+
+// answers the commands of one client, one line each. every answer ends with
+// a line saying `ok`, or `error: ` and what went wrong.
+fn serve(stream: UnixStream) -> io::Result<()> {
+    let mut out = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let mut answer = Vec::new();
+        match run(&line?, &mut answer) {
+            Ok(()) => writeln!(answer, "ok")?,
+            Err(e) => writeln!(answer, "error: {e}")?,
+        }
+        out.write_all(&answer)?;
+    }
+
+    Ok(())
+}
+
+fn listen(listener: UnixListener) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        // threads spawned from here are not stupid, since this one isn't.
+        let _ = std::thread::Builder::new()
+            .name("stupidalloc control client".into())
+            .spawn(|| {
+                threads::internal();
+                // the client hanging up is its own business.
+                let _ = serve(stream);
+            });
+    }
+}
+
+impl StupidAlloc {
+    /// Starts listening for commands on a Unix domain socket at `path`, on a
+    /// background thread, for external tools to look at the allocations of
+    /// the program while it runs. A stale socket left at `path` by a previous
+    /// run is replaced.
+    ///
+    /// Commands are lines of text, and so are their answers, which end with
+    /// a line saying `ok`, or `error: ` followed by what went wrong. `help`
+    /// lists the commands: listing the allocations, dumping their contents,
+    /// opening and closing windows, taking snapshots, and turning the stupid
+    /// alloc off, which puts it in pass-through mode with [`System`], see
+    /// [`StupidAlloc::set_pass_through()`]. Allocations are designated by their
+    /// address, as listed by `list`.
+    ///
+    /// ```no_run
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let socket = std::env::temp_dir().join("stupidalloc.sock");
+    /// StupidAlloc.start_control_socket(&socket).unwrap();
+    ///
+    /// // meanwhile, in a shell:
+    /// // $ echo list | nc -U /tmp/stupidalloc.sock
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(unix)))]
+    pub fn start_control_socket(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+
+        system_scope(|| {
+            // someone may still be listening there, in which case it's theirs.
+            let stale = std::fs::symlink_metadata(path)
+                .is_ok_and(|m| m.file_type().is_socket() && UnixStream::connect(path).is_err());
+            if stale {
+                std::fs::remove_file(path)?;
+            }
+
+            let listener = UnixListener::bind(path)?;
+            claim_main_thread();
+            std::thread::Builder::new()
+                .name("stupidalloc control".into())
+                .spawn(move || {
+                    threads::internal();
+                    listen(listener)
+                })?;
+
+            Ok(())
+        })
+        .map_err(user_error)
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    backtrace::BacktraceStatus,
+    collections::HashMap,
+    fmt,
+    hash::{DefaultHasher, Hasher},
+};
+
+use crate::{system_scope, AllocHandle, StupidAlloc, STUPID_MAP};
+
+// the number of backtraces kept as examples for each group.
+const EXAMPLES: usize = 3;
+
+/// Live allocations with the exact same contents, see
+/// [`StupidAlloc::dedup_report()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// The size of each allocation of the group, in bytes.
+    pub size: usize,
+    /// The addresses of the allocations of the group, sorted.
+    pub addrs: Vec<usize>,
+    /// The type of the allocations, if it was recorded for all of them and is
+    /// the same.
+    pub type_name: Option<&'static str>,
+    /// Where a few of the allocations were made, if backtraces are enabled,
+    /// with `RUST_BACKTRACE=1` for example.
+    pub backtraces: Vec<String>,
+}
+
+impl DuplicateGroup {
+    /// The number of allocations of the group.
+    pub fn count(&self) -> usize {
+        self.addrs.len()
+    }
+
+    /// The bytes that would be saved by keeping only one of them.
+    pub fn wasted(&self) -> usize {
+        self.size * (self.count() - 1)
+    }
+}
+
+/// The outcome of [`StupidAlloc::dedup_report()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupReport {
+    /// The groups of identical allocations, the most wasteful first.
+    pub groups: Vec<DuplicateGroup>,
+}
+
+impl DedupReport {
+    /// The bytes that would be saved by keeping only one allocation of each
+    /// group.
+    pub fn wasted(&self) -> usize {
+        self.groups.iter().map(DuplicateGroup::wasted).sum()
+    }
+}
+
+impl fmt::Display for DedupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} groups of identical allocations, {} bytes wasted",
+            self.groups.len(),
+            self.wasted()
+        )?;
+        for group in &self.groups {
+            write!(
+                f,
+                "  {} copies of {} bytes, {} bytes wasted",
+                group.count(),
+                group.size,
+                group.wasted()
+            )?;
+            if let Some(type_name) = group.type_name {
+                write!(f, ", {type_name}")?;
+            }
+            writeln!(f, ", first at 0x{:x}", group.addrs[0])?;
+            for backtrace in &group.backtraces {
+                for line in backtrace.lines() {
+                    writeln!(f, "      {line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// the data of an allocation.
+fn contents(handle: &AllocHandle) -> &[u8] {
+    let offset = handle.offset();
+    &handle.map[offset..offset + handle.layout.size()]
+}
+
+fn hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(data);
+    hasher.finish()
+}
+
+// groups the live allocations by contents. the allocations are hashed first,
+// and only the ones whose hash comes up more than once get compared for real
+// and looked at again for their backtraces. must be called in system_scope.
+fn groups() -> Vec<DuplicateGroup> {
+    let map = STUPID_MAP.read_all();
+
+    // allocations of zero bytes are all the same, and waste nothing.
+    let mut by_hash = HashMap::<(usize, u64), Vec<usize>>::new();
+    for (&addr, handle) in map.iter().filter(|(_, h)| h.layout.size() != 0) {
+        by_hash
+            .entry((handle.layout.size(), hash(contents(handle))))
+            .or_default()
+            .push(addr);
+    }
+
+    let handles = map.iter().collect::<HashMap<_, _>>();
+    let mut groups = Vec::new();
+    for ((size, _), mut addrs) in by_hash.into_iter().filter(|(_, a)| a.len() > 1) {
+        addrs.sort_unstable();
+
+        // in case of collisions, the hash may hide several groups.
+        while let Some(&first) = addrs.first() {
+            let data = contents(handles[&first]);
+            let (same, others) = addrs
+                .into_iter()
+                .partition::<Vec<_>, _>(|addr| contents(handles[addr]) == data);
+            addrs = others;
+            if same.len() < 2 {
+                continue;
+            }
+
+            let type_name = handles[&first].type_name.filter(|&type_name| {
+                same.iter()
+                    .all(|addr| handles[addr].type_name == Some(type_name))
+            });
+
+            let backtraces = same
+                .iter()
+                .map(|addr| &handles[addr].backtrace)
+                .filter(|backtrace| backtrace.status() == BacktraceStatus::Captured)
+                .take(EXAMPLES)
+                .map(ToString::to_string)
+                .collect();
+
+            groups.push(DuplicateGroup {
+                size,
+                addrs: same,
+                type_name,
+                backtraces,
+            });
+        }
+    }
+
+    groups.sort_by_key(|group| (std::cmp::Reverse(group.wasted()), group.addrs[0]));
+    groups
+}
+
+impl StupidAlloc {
+    /// Looks for live allocations with the exact same contents, and reports
+    /// them by group, with the bytes they waste. Why are there 500 copies of
+    /// the same string? The backtraces of a few of them tell, if backtraces
+    /// are enabled, see [`StupidAlloc::set_backtraces()`].
+    ///
+    /// Every live allocation gets hashed, which takes a while on a big heap.
+    /// Allocations of zero bytes are left out.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let copies = (0..3)
+    ///     .map(|_| Box::new_in(*b"the same string, again", StupidAlloc))
+    ///     .collect::<Vec<_>>();
+    ///
+    /// let report = StupidAlloc.dedup_report();
+    /// let group = report
+    ///     .groups
+    ///     .iter()
+    ///     .find(|group| group.addrs.contains(&(&*copies[0] as *const _ as usize)))
+    ///     .unwrap();
+    /// assert_eq!(group.count(), 3);
+    /// assert_eq!(group.wasted(), 44);
+    /// println!("{report}");
+    /// ```
+    pub fn dedup_report(&self) -> DedupReport {
+        // like state, collected in System and then copied for the user.
+        let report = system_scope(|| DedupReport { groups: groups() });
+        let user_report = report.clone();
+        system_scope(|| drop(report));
+        user_report
+    }
+}
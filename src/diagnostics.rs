@@ -0,0 +1,304 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+};
+
+use crate::{
+    backing::PASS_THROUGH, fill, shutdown, system_scope, temp_alloc_dir, FillPattern, StupidAlloc,
+    Timestamp, CANARIES, CLOSE_FILES, FAKE, GUARD_PAGES, LOCAL_SWITCH_OFF, QUARANTINE_LEN,
+    STUPID_MAP,
+};
+
+/// Where the data of new allocations lives, see [`Diagnostics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// In memory mapped files, the stupid way.
+    Files,
+    /// In the memory of the allocator given to
+    /// [`StupidAlloc::set_pass_through()`].
+    PassThrough,
+    /// In memory from System, with the `fake` feature or under Miri.
+    Fake,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Files => write!(f, "files"),
+            Backend::PassThrough => write!(f, "pass-through"),
+            Backend::Fake => write!(f, "fake"),
+        }
+    }
+}
+
+/// How the stupid alloc is set up and what it has to work with, returned by
+/// [`StupidAlloc::diagnostics()`]. Mostly there to answer "why are my
+/// allocations going to System?", and "why are they failing?".
+///
+/// Its [`Display`](fmt::Display) implementation is the banner printed by
+/// [`StupidAlloc::set_banner_at_startup()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// The folder of the allocation files of this process.
+    pub dir: PathBuf,
+    /// When the stupid alloc started.
+    pub started_at: Timestamp,
+    /// The features the crate was built with.
+    pub features: Vec<&'static str>,
+    /// Where the data of new allocations lives.
+    pub backend: Backend,
+    /// Whether stupid allocation is enabled in the calling thread. Only the
+    /// first thread that allocates has it enabled by default, the others
+    /// leave everything to System until they call
+    /// [`StupidAlloc::enable_in_thread()`].
+    pub enabled_in_thread: bool,
+    /// Whether the stupid alloc has shut down, since the program is exiting.
+    /// Windows don't open anymore.
+    pub shut_down: bool,
+    /// Whether new allocations get guard pages.
+    pub guard_pages: bool,
+    /// Whether new allocations get canaries.
+    pub canaries: bool,
+    /// The number of freed allocations kept in quarantine.
+    pub quarantine: usize,
+    /// What new allocations are filled with.
+    pub fill: FillPattern,
+    /// Whether new allocations close their file once it's mapped.
+    pub close_files: bool,
+    /// The size from which allocations get a file when the crate is
+    /// preloaded in a C program, or none without the `preload` feature.
+    pub preload_threshold: Option<usize>,
+    /// The number of files the live allocations keep open.
+    pub open_files: usize,
+    /// The number of files this process may have open at once, or none if
+    /// there is no limit, or if it's unknown. Allocations fail once it's
+    /// reached, unless files are closed after mapping.
+    pub fd_limit: Option<u64>,
+    /// The free space on the disk of the folder, in bytes, or none if it's
+    /// unknown. Allocations fail once it runs out.
+    pub free_space: Option<u64>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn on_off(value: bool) -> &'static str {
+            if value {
+                "on"
+            } else {
+                "off"
+            }
+        }
+
+        writeln!(f, "stupidalloc diagnostics:")?;
+        writeln!(f, "  folder: {}", self.dir.display())?;
+        writeln!(f, "  started: {}", self.started_at)?;
+        match self.features.is_empty() {
+            true => writeln!(f, "  features: none")?,
+            false => writeln!(f, "  features: {}", self.features.join(", "))?,
+        }
+        writeln!(f, "  backend: {}", self.backend)?;
+        match self.enabled_in_thread {
+            true => writeln!(f, "  this thread: stupid")?,
+            false => writeln!(
+                f,
+                "  this thread: System, enable it with StupidAlloc::enable_in_thread(true)"
+            )?,
+        }
+        if self.shut_down {
+            writeln!(f, "  shut down: yes")?;
+        }
+        writeln!(
+            f,
+            "  guard pages: {}, canaries: {}, quarantine: {}, fill: {}, close files: {}",
+            on_off(self.guard_pages),
+            on_off(self.canaries),
+            self.quarantine,
+            self.fill,
+            on_off(self.close_files)
+        )?;
+        if let Some(threshold) = self.preload_threshold {
+            writeln!(f, "  preload threshold: {threshold} bytes")?;
+        }
+        match self.fd_limit {
+            Some(limit) => writeln!(f, "  open files: {} of {limit}", self.open_files)?,
+            None => writeln!(f, "  open files: {}", self.open_files)?,
+        }
+        match self.free_space {
+            Some(space) => writeln!(f, "  free disk space: {space} bytes"),
+            None => writeln!(f, "  free disk space: unknown"),
+        }
+    }
+}
+
+// whether to print the diagnostics when the first allocation is made.
+static AT_STARTUP: AtomicBool = AtomicBool::new(false);
+
+static PRINT_AT_STARTUP: Once = Once::new();
+
+// the features the crate was built with, in the order of the manifest.
+fn features() -> Vec<&'static str> {
+    [
+        ("always-graphics", cfg!(feature = "always-graphics")),
+        ("bit-mode", cfg!(feature = "bit-mode")),
+        ("c-api", cfg!(feature = "c-api")),
+        ("fake", cfg!(feature = "fake")),
+        ("graphics", cfg!(feature = "graphics")),
+        ("interactive", cfg!(feature = "interactive")),
+        ("logging", cfg!(feature = "logging")),
+        ("midi", cfg!(feature = "midi")),
+        ("morse", cfg!(feature = "morse")),
+        ("nightly", cfg!(feature = "nightly")),
+        ("preload", cfg!(feature = "preload")),
+        ("python", cfg!(feature = "python")),
+        ("regex", cfg!(feature = "regex")),
+        ("teaching", cfg!(feature = "teaching")),
+        ("time-travel", cfg!(feature = "time-travel")),
+        ("valgrind", cfg!(feature = "valgrind")),
+        ("wallpaper", cfg!(feature = "wallpaper")),
+        ("watch", cfg!(feature = "watch")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+// the soft limit on open files, which is the one that counts. the types of
+// the limits depend on the platform, hence the casts.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn fd_limit() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0
+        || limit.rlim_cur == libc::RLIM_INFINITY
+    {
+        return None;
+    }
+    Some(limit.rlim_cur as u64)
+}
+
+#[cfg(not(unix))]
+fn fd_limit() -> Option<u64> {
+    None
+}
+
+// the space left for unprivileged users on the disk of `dir`, which may not
+// exist yet, in which case it's the disk of its closest ancestor that does.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn free_space(dir: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let dir = dir.ancestors().find(|dir| dir.exists())?;
+    let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+
+    let mut stats = unsafe { std::mem::zeroed::<libc::statvfs>() };
+    if unsafe { libc::statvfs(dir.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    (stats.f_bavail as u64).checked_mul(stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+// gathers the diagnostics. must be called while ALLOCATING is nonzero, and
+// with the registry unlocked.
+fn collect() -> Diagnostics {
+    let dir = temp_alloc_dir();
+    let backend = if PASS_THROUGH.read().unwrap().is_some() {
+        Backend::PassThrough
+    } else if FAKE {
+        Backend::Fake
+    } else {
+        Backend::Files
+    };
+
+    #[cfg(all(feature = "preload", target_os = "linux", target_env = "gnu"))]
+    let preload_threshold = Some(crate::preload::threshold());
+    #[cfg(not(all(feature = "preload", target_os = "linux", target_env = "gnu")))]
+    let preload_threshold = None;
+
+    Diagnostics {
+        started_at: StupidAlloc.started_at(),
+        features: features(),
+        backend,
+        enabled_in_thread: !LOCAL_SWITCH_OFF
+            .try_with(|l| l.load(Ordering::SeqCst))
+            .unwrap_or(true),
+        shut_down: shutdown::late(),
+        guard_pages: GUARD_PAGES.load(Ordering::SeqCst),
+        canaries: CANARIES.load(Ordering::SeqCst),
+        quarantine: QUARANTINE_LEN.load(Ordering::SeqCst),
+        fill: fill::pattern(false),
+        close_files: CLOSE_FILES.load(Ordering::SeqCst),
+        preload_threshold,
+        open_files: STUPID_MAP
+            .read_all()
+            .values()
+            .filter(|handle| handle.file.is_some())
+            .count(),
+        fd_limit: fd_limit(),
+        free_space: free_space(&dir),
+        dir,
+    }
+}
+
+// prints the diagnostics if asked to, the first time it's called. must be
+// called like collect.
+pub fn on_first_allocation() {
+    if AT_STARTUP.load(Ordering::SeqCst) || banner_from_env() {
+        PRINT_AT_STARTUP.call_once(|| eprint!("{}", collect()));
+    }
+}
+
+// whether the `STUPIDALLOC_BANNER` environment variable asks for the banner,
+// for the programs that allocate before they get a chance to.
+fn banner_from_env() -> bool {
+    static FROM_ENV: Once = Once::new();
+    static BANNER: AtomicBool = AtomicBool::new(false);
+
+    FROM_ENV.call_once(|| {
+        let value = std::env::var_os("STUPIDALLOC_BANNER").unwrap_or_default();
+        BANNER.store(!value.is_empty() && value != "0", Ordering::SeqCst);
+    });
+    BANNER.load(Ordering::SeqCst)
+}
+
+impl StupidAlloc {
+    /// Returns the settings of the stupid alloc, and what it has to work
+    /// with, as seen from the calling thread.
+    ///
+    /// ```
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let diagnostics = StupidAlloc.diagnostics();
+    /// assert!(diagnostics.dir.ends_with(std::process::id().to_string()));
+    /// println!("{diagnostics}");
+    /// ```
+    pub fn diagnostics(&self) -> Diagnostics {
+        // like state, collected in System and then copied for the user.
+        let diagnostics = system_scope(collect);
+        let user_diagnostics = diagnostics.clone();
+        system_scope(|| drop(diagnostics));
+        user_diagnostics
+    }
+
+    /// Enables or disables printing the [diagnostics](StupidAlloc::diagnostics)
+    /// to the standard error, once, at the first stupid allocation made while
+    /// it's enabled, depending on the value passed as parameter. Programs using the stupid
+    /// alloc as their global allocator allocate before they get to call this,
+    /// and can set the `STUPIDALLOC_BANNER` environment variable to `1`
+    /// instead.
+    pub fn set_banner_at_startup(&self, value: bool) {
+        AT_STARTUP.store(value, Ordering::SeqCst);
+    }
+}
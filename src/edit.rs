@@ -0,0 +1,223 @@
+use minifb::Key;
+
+const DIGITS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+const PAD_DIGITS: [Key; 10] = [
+    Key::NumPad0,
+    Key::NumPad1,
+    Key::NumPad2,
+    Key::NumPad3,
+    Key::NumPad4,
+    Key::NumPad5,
+    Key::NumPad6,
+    Key::NumPad7,
+    Key::NumPad8,
+    Key::NumPad9,
+];
+
+const LETTERS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+// the character typed with `key`, if it's one values are made of. letters
+// are lowercase, there's no telling whether shift is down anyway.
+fn typed(key: Key) -> Option<char> {
+    let index = |keys: &[Key]| keys.iter().position(|k| *k == key);
+
+    if let Some(i) = index(&DIGITS).or_else(|| index(&PAD_DIGITS)) {
+        return char::from_digit(i as u32, 10);
+    }
+    if let Some(i) = index(&LETTERS) {
+        return Some((b'a' + i as u8) as char);
+    }
+    match key {
+        Key::Minus | Key::NumPadMinus => Some('-'),
+        Key::Period | Key::NumPadDot => Some('.'),
+        Key::Space => Some(' '),
+        _ => None,
+    }
+}
+
+// the types values can be written as, and their sizes.
+const INTEGERS: [(&str, usize); 8] = [
+    ("u8", 1),
+    ("i8", 1),
+    ("u16", 2),
+    ("i16", 2),
+    ("u32", 4),
+    ("i32", 4),
+    ("u64", 8),
+    ("i64", 8),
+];
+
+// the bytes of the value written in `text`, which looks like a rust literal
+// followed by the byte order: `42`, `-1i16`, `0xdeadbeef u32 be`, `1.5f32`.
+// integers without a type take as few bytes as they fit in, floats are f64,
+// and the byte order is little endian unless told otherwise. integers may be
+// negative or not whatever their type, as long as they fit in its size.
+pub fn parse(text: &str) -> Result<Vec<u8>, &'static str> {
+    let mut words = text.split_whitespace().collect::<Vec<_>>();
+    let big = match words.last().copied() {
+        Some(order @ ("be" | "le")) if words.len() > 1 => {
+            words.pop();
+            order == "be"
+        }
+        _ => cfg!(target_endian = "big"),
+    };
+    let literal = words.concat();
+    let order = |mut bytes: Vec<u8>| {
+        if big {
+            bytes.reverse();
+        }
+        bytes
+    };
+
+    let (negative, unsigned) = match literal.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, literal.as_str()),
+    };
+
+    // hexadecimal digits include f, so only integer types go after them.
+    let (digits, radix, suffix) = match unsigned.strip_prefix("0x") {
+        Some(hex) => {
+            let split = hex.find(['u', 'i']).unwrap_or(hex.len());
+            (&hex[..split], 16, &hex[split..])
+        }
+        None => {
+            let split = unsigned.find(['u', 'i', 'f']).unwrap_or(unsigned.len());
+            (&unsigned[..split], 10, &unsigned[split..])
+        }
+    };
+    if digits.is_empty() {
+        return Err("no value");
+    }
+
+    let float = suffix.starts_with('f') || (suffix.is_empty() && digits.contains('.'));
+    if float {
+        let value = digits.parse::<f64>().map_err(|_| "not a number")?;
+        let value = if negative { -value } else { value };
+        return match suffix {
+            "f32" => Ok(order((value as f32).to_le_bytes().to_vec())),
+            "f64" | "" => Ok(order(value.to_le_bytes().to_vec())),
+            _ => Err("unknown type"),
+        };
+    }
+
+    let value = i128::from_str_radix(digits, radix).map_err(|_| "not a number")?;
+    let value = if negative { -value } else { value };
+    let fits = |size: usize| (-(1 << (8 * size - 1))..1 << (8 * size)).contains(&value);
+    let size = match suffix {
+        "" => [1, 2, 4, 8]
+            .into_iter()
+            .find(|&size| fits(size))
+            .ok_or("too big")?,
+        _ => INTEGERS
+            .iter()
+            .find(|(name, _)| *name == suffix)
+            .map(|&(_, size)| size)
+            .ok_or("unknown type")?,
+    };
+    if !fits(size) {
+        return Err("too big");
+    }
+
+    Ok(order(value.to_le_bytes()[..size].to_vec()))
+}
+
+// what the keys pressed while editing did.
+pub enum Step {
+    Typing,
+    Cancelled,
+    // the value was typed and is valid, here are its bytes.
+    Done(Vec<u8>),
+}
+
+// a value being typed in a window, to be written at the byte `at`. the title
+// shows it as it's typed.
+pub struct Edit {
+    pub at: usize,
+    text: String,
+    error: Option<&'static str>,
+}
+
+impl Edit {
+    pub fn new(at: usize) -> Self {
+        Edit {
+            at,
+            text: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn keys(&mut self, keys: &[Key]) -> Step {
+        for &key in keys {
+            match key {
+                Key::Escape => return Step::Cancelled,
+                Key::Enter | Key::NumPadEnter => match parse(&self.text) {
+                    Ok(bytes) => return Step::Done(bytes),
+                    Err(error) => self.error = Some(error),
+                },
+                Key::Backspace => {
+                    self.text.pop();
+                    self.error = None;
+                }
+                key => {
+                    if let Some(c) = typed(key) {
+                        self.text.push(c);
+                        self.error = None;
+                    }
+                }
+            }
+        }
+        Step::Typing
+    }
+
+    // shows `error` until the text changes.
+    pub fn fail(&mut self, error: &'static str) {
+        self.error = Some(error);
+    }
+
+    // the title of the window `name` while editing.
+    pub fn title(&self, name: &str) -> String {
+        match self.error {
+            Some(error) => format!("{name} | 0x{:x} = {}_ ({error})", self.at, self.text),
+            None => format!("{name} | 0x{:x} = {}_", self.at, self.text),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use crate::{system_scope, StupidAlloc};
+
+/// Something that went wrong inside of the stupid alloc, while allocating or
+/// freeing memory: there is nobody to return an error to there, and panicking
+/// is not an option. The allocation fails instead, or the de-allocation does
+/// what it can, and the error goes to the handler set with
+/// [`StupidAlloc::set_error_handler()`], and to
+/// [`StupidAlloc::last_error()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StupidError {
+    /// Doing something with a file or a folder failed.
+    Io {
+        /// What the stupid alloc was trying to do, like `"create the file"`.
+        operation: &'static str,
+        /// The file or folder involved.
+        path: PathBuf,
+        /// The kind of the underlying error.
+        kind: io::ErrorKind,
+        /// The message of the underlying error.
+        message: String,
+    },
+    /// The file dialog of the `interactive` feature failed to show up.
+    Dialog {
+        /// The message of the underlying error.
+        message: String,
+    },
+    /// Something the stupid alloc relies on failed: the allocator of
+    /// pass-through mode ran out of memory, or a thread couldn't be spawned.
+    Backend {
+        /// What went wrong.
+        message: String,
+    },
+    /// An allocation, or a resize, would have gone over a
+    /// [`Budget`](crate::Budget), and went to System or failed instead.
+    OverBudget {
+        /// The number of bytes it would have added.
+        bytes: usize,
+        /// Whether the budget was the one of the thread, rather than the one
+        /// of the whole program.
+        thread: bool,
+    },
+    /// The file of an allocation doesn't hold what it should anymore, see
+    /// [`StupidAlloc::set_checksums()`].
+    ChecksumMismatch {
+        /// The file of the allocation.
+        path: PathBuf,
+        /// Whether another program was heard editing the file, which takes
+        /// the `watch` feature. Otherwise, it may well be corrupted.
+        external: bool,
+    },
+}
+
+impl fmt::Display for StupidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StupidError::Io {
+                operation,
+                path,
+                message,
+                ..
+            } => write!(f, "couldn't {operation} {}: {message}", path.display()),
+            StupidError::Dialog { message } => write!(f, "file dialog failed: {message}"),
+            StupidError::Backend { message } => f.write_str(message),
+            StupidError::OverBudget { bytes, thread } => {
+                let budget = if *thread { "thread" } else { "global" };
+                write!(f, "{bytes} more bytes would go over the {budget} budget")
+            }
+            StupidError::ChecksumMismatch { path, external } => match external {
+                true => write!(f, "{} was edited by another program", path.display()),
+                false => write!(f, "{} doesn't hold its allocation anymore", path.display()),
+            },
+        }
+    }
+}
+
+impl std::error::Error for StupidError {}
+
+static HANDLER: RwLock<Option<fn(StupidError)>> = RwLock::new(None);
+
+// the last error, allocated in System like everything else made while
+// ALLOCATING or DEALLOCATING is nonzero.
+static LAST_ERROR: Mutex<Option<StupidError>> = Mutex::new(None);
+
+// tells the application about `error`. must be called while ALLOCATING or
+// DEALLOCATING is nonzero, so that the error and whatever the handler does
+// live in System.
+pub fn report(error: StupidError) {
+    let handler = *HANDLER.read().unwrap();
+
+    // the previous error is dropped once the lock is released.
+    let previous = LAST_ERROR.lock().unwrap().replace(error.clone());
+    drop(previous);
+
+    if let Some(handler) = handler {
+        handler(error);
+    }
+}
+
+// reports the error of `result`, if it is one, caused by trying to `operation`
+// on `path`. same rules as report.
+pub fn check<T>(result: io::Result<T>, operation: &'static str, path: &Path) -> Option<T> {
+    result
+        .map_err(|e| {
+            report(StupidError::Io {
+                operation,
+                path: path.to_path_buf(),
+                kind: e.kind(),
+                message: e.to_string(),
+            })
+        })
+        .ok()
+}
+
+// reports that something the stupid alloc relies on failed. same rules as
+// report.
+pub fn backend(message: impl Into<String>) {
+    report(StupidError::Backend {
+        message: message.into(),
+    });
+}
+
+impl StupidAlloc {
+    /// Sets the function called whenever something goes wrong inside of the
+    /// stupid alloc, or removes it with [`None`]. The handler is called from
+    /// inside of the allocator, on the thread that ran into the error: anything
+    /// it allocates comes from [`System`](std::alloc::System), and it must not
+    /// panic.
+    ///
+    /// ```
+    /// use stupidalloc::{StupidAlloc, StupidError};
+    ///
+    /// fn complain(error: StupidError) {
+    ///     eprintln!("stupidalloc is in trouble: {error}");
+    /// }
+    ///
+    /// StupidAlloc.set_error_handler(Some(complain));
+    /// ```
+    pub fn set_error_handler(&self, handler: Option<fn(StupidError)>) {
+        *HANDLER.write().unwrap() = handler;
+    }
+
+    /// Returns the last thing that went wrong inside of the stupid alloc, if
+    /// anything did, see [`StupidError`].
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use std::alloc::{GlobalAlloc, Layout, System};
+    /// use stupidalloc::{StupidAlloc, StupidError};
+    ///
+    /// // an allocator that never has any memory to spare.
+    /// struct Broke;
+    ///
+    /// unsafe impl GlobalAlloc for Broke {
+    ///     unsafe fn alloc(&self, _: Layout) -> *mut u8 {
+    ///         std::ptr::null_mut()
+    ///     }
+    ///
+    ///     unsafe fn dealloc(&self, _: *mut u8, _: Layout) {}
+    /// }
+    ///
+    /// StupidAlloc.set_pass_through(Some(&Broke));
+    /// let stupid_box = Box::try_new_in(42u32, StupidAlloc);
+    /// StupidAlloc.set_pass_through(None);
+    ///
+    /// assert!(stupid_box.is_err());
+    /// assert!(matches!(StupidAlloc.last_error(), Some(StupidError::Backend { .. })));
+    /// ```
+    pub fn last_error(&self) -> Option<StupidError> {
+        let error = system_scope(|| LAST_ERROR.lock().unwrap().clone());
+
+        // the copy was made in System, the user gets their own.
+        let user_error = error.clone();
+        system_scope(|| drop(error));
+        user_error
+    }
+}
@@ -0,0 +1,184 @@
+use std::{any::type_name, mem::size_of};
+
+use crate::{system_scope, Allocator, StupidAlloc, STUPID_MAP};
+
+/// A `Vec` whose buffer [`StupidAlloc::explain_vec()`] can explain: the one of
+/// [`allocator_api2`], like [`StupidVec`](crate::StupidVec), and the one of
+/// std, with any allocator with the `nightly` feature, and with the global
+/// allocator without it.
+pub trait VecLike {
+    /// The type of the elements.
+    type Element;
+
+    /// Where the buffer starts, the number of elements in use, and the number
+    /// of elements it has room for.
+    fn buffer(&self) -> (*const Self::Element, usize, usize);
+}
+
+impl<T, A: Allocator> VecLike for allocator_api2::vec::Vec<T, A> {
+    type Element = T;
+
+    fn buffer(&self) -> (*const T, usize, usize) {
+        (self.as_ptr(), self.len(), self.capacity())
+    }
+}
+
+#[cfg(feature = "nightly")]
+impl<T, A: std::alloc::Allocator> VecLike for Vec<T, A> {
+    type Element = T;
+
+    fn buffer(&self) -> (*const T, usize, usize) {
+        (self.as_ptr(), self.len(), self.capacity())
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+impl<T> VecLike for Vec<T> {
+    type Element = T;
+
+    fn buffer(&self) -> (*const T, usize, usize) {
+        (self.as_ptr(), self.len(), self.capacity())
+    }
+}
+
+// the buffer of a container: `len` of its elements are in use, out of
+// `capacity`. strings are made of bytes, vectors of elements of their own
+// type.
+struct Buffer {
+    addr: usize,
+    len: usize,
+    capacity: usize,
+    element: Option<(&'static str, usize)>,
+}
+
+impl Buffer {
+    fn element_size(&self) -> usize {
+        self.element.map_or(1, |(_, size)| size)
+    }
+}
+
+// what `buffer` holds, in words.
+fn text(buffer: &Buffer) -> String {
+    let Buffer { len, capacity, .. } = *buffer;
+    let (used, size) = (
+        len * buffer.element_size(),
+        capacity * buffer.element_size(),
+    );
+    let spare = capacity - len;
+
+    let mut text = match buffer.element {
+        Some((type_name, element_size)) => format!(
+            "`Vec<{type_name}>` of len {len} and capacity {capacity}, {element_size} bytes per element: "
+        ),
+        None => format!(
+            "`String` of len {len} and capacity {capacity}, in bytes rather than characters: "
+        ),
+    };
+    text.push_str(&match (len, buffer.element) {
+        (0, _) => "nothing is in use yet, ".into(),
+        (1, Some(_)) => format!("bytes 0..{used} hold the element, "),
+        (_, Some(_)) => format!("bytes 0..{used} hold the {len} elements, "),
+        (_, None) => format!("bytes 0..{used} hold the text, "),
+    });
+    text.push_str(&match (spare, buffer.element) {
+        (0, _) => "and there is no spare capacity: the next push reallocates, which usually doubles the capacity.".into(),
+        (_, Some(_)) => format!("and bytes {used}..{size} are spare capacity, allocated but not initialized yet. The next {spare} pushes go there, without reallocating."),
+        (_, None) => format!("and bytes {used}..{size} are spare capacity, allocated but not initialized yet. The next {spare} bytes pushed go there, without reallocating."),
+    });
+    text
+}
+
+// logs what `buffer` holds and shows it in its window. none if it isn't a
+// stupid allocation, or no allocation at all. must be called in system_scope.
+fn explain(buffer: Buffer) -> Option<String> {
+    if buffer.capacity == 0 || buffer.element_size() == 0 {
+        return None;
+    }
+
+    #[cfg_attr(not(feature = "logging"), allow(unused_mut))]
+    #[cfg_attr(
+        not(any(feature = "logging", feature = "graphics")),
+        allow(unused_variables)
+    )]
+    let mut handle = STUPID_MAP.handle_of_mut(buffer.addr)?;
+    let text = text(&buffer);
+
+    #[cfg(feature = "logging")]
+    handle.log(format_args!("## Explained\n{text}\n"));
+
+    #[cfg(feature = "graphics")]
+    if let Some(window) = handle.window.as_ref() {
+        // where the spare capacity starts, from the start of the data.
+        let used = buffer.addr - handle.start + buffer.len * buffer.element_size();
+        // the window might be closed already, and that's fine.
+        let _ = window.tx.send(crate::graphics::Message::Explain {
+            used,
+            summary: format!("len {}, capacity {}", buffer.len, buffer.capacity),
+        });
+    }
+
+    Some(text)
+}
+
+impl StupidAlloc {
+    /// Explains where the elements of `vec` are in its buffer, and where its
+    /// spare capacity is: allocated, but not initialized yet. Returns the
+    /// explanation, which also goes to the log of the allocation with the
+    /// `logging` feature. With the `graphics` feature, the window of the
+    /// allocation, if it's open, draws the spare capacity in amber instead of
+    /// white, and its title says the len and capacity, until the allocation is
+    /// resized: call this again after pushing to see where the new elements
+    /// went.
+    ///
+    /// `vec` is a [`StupidVec`](crate::StupidVec), or any other `Vec` whose
+    /// buffer is a stupid allocation, like all of them with the stupid alloc
+    /// as the global allocator, see [`VecLike`]. Returns [`None`] if `vec`
+    /// has no buffer, like when it's empty, or if it's not a stupid
+    /// allocation.
+    ///
+    /// ```
+    /// use stupidalloc::{stupid_vec, StupidAlloc};
+    ///
+    /// let mut numbers = stupid_vec![1u32, 2, 3];
+    /// numbers.reserve_exact(5);
+    ///
+    /// let text = StupidAlloc.explain_vec(&numbers).unwrap();
+    /// assert!(text.contains("bytes 12..32 are spare capacity"));
+    /// println!("{text}");
+    /// ```
+    pub fn explain_vec<V: VecLike>(&self, vec: &V) -> Option<String> {
+        let (ptr, len, capacity) = vec.buffer();
+        let buffer = Buffer {
+            addr: ptr as usize,
+            len,
+            capacity,
+            element: Some((type_name::<V::Element>(), size_of::<V::Element>())),
+        };
+
+        // like the reports, explained in System and then copied for the user.
+        let text = system_scope(|| explain(buffer));
+        let user_text = text.clone();
+        system_scope(|| drop(text));
+        user_text
+    }
+
+    /// Like [`StupidAlloc::explain_vec()`], for a `String`, whose len and
+    /// capacity count bytes of UTF-8: `"é"` is two of them. A `String` only
+    /// lives in a stupid allocation with the stupid alloc as the global
+    /// allocator.
+    // the capacity is the point, which a `&str` doesn't have.
+    #[allow(clippy::ptr_arg)]
+    pub fn explain_string(&self, string: &String) -> Option<String> {
+        let buffer = Buffer {
+            addr: string.as_ptr() as usize,
+            len: string.len(),
+            capacity: string.capacity(),
+            element: None,
+        };
+
+        let text = system_scope(|| explain(buffer));
+        let user_text = text.clone();
+        system_scope(|| drop(text));
+        user_text
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    alloc::Layout,
+    backtrace::Backtrace,
+    ops::{Bound, RangeBounds},
+    sync::Mutex,
+};
+
+use crate::{random, StupidAlloc};
+
+/// Which allocations fail on purpose, for testing how a program copes with
+/// running out of memory. Set with [`StupidAlloc::set_fault_injection()`].
+///
+/// The size range and the call site pick the allocations that may fail, and
+/// out of those, every `n`th one fails, or each one with some probability.
+/// With neither, all of them fail.
+///
+/// ```
+/// use stupidalloc::FaultInjection;
+///
+/// // one in ten allocations of at least a page, made by the parser.
+/// let faults = FaultInjection::new()
+///     .sizes(4096..)
+///     .call_site("my_crate::parser")
+///     .probability(0.1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FaultInjection {
+    every: Option<usize>,
+    sizes: (Bound<usize>, Bound<usize>),
+    call_site: Option<String>,
+    probability: Option<f64>,
+    seed: u64,
+}
+
+impl Default for FaultInjection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FaultInjection {
+    /// Makes every allocation fail, until told otherwise by the other methods.
+    pub fn new() -> Self {
+        Self {
+            every: None,
+            sizes: (Bound::Unbounded, Bound::Unbounded),
+            call_site: None,
+            probability: None,
+            seed: 0,
+        }
+    }
+
+    /// Fails every `n`th allocation that may fail.
+    ///
+    /// # Panics
+    /// Panics if `n` is 0.
+    pub fn every(mut self, n: usize) -> Self {
+        assert!(n > 0, "can't fail every 0th allocation");
+        self.every = Some(n);
+        self
+    }
+
+    /// Only fails allocations with a size in `range`, in bytes.
+    pub fn sizes(mut self, range: impl RangeBounds<usize>) -> Self {
+        self.sizes = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    /// Only fails allocations whose backtrace contains `pattern`, like the
+    /// name of a function or of a module. Capturing backtraces is slow, so
+    /// is this.
+    pub fn call_site(mut self, pattern: impl Into<String>) -> Self {
+        self.call_site = Some(pattern.into());
+        self
+    }
+
+    /// Fails allocations that may fail with a `probability` between 0 and 1.
+    pub fn probability(mut self, probability: f64) -> Self {
+        self.probability = Some(probability);
+        self
+    }
+
+    /// Sets the seed of the random numbers behind
+    /// [`FaultInjection::probability()`]. The same seed fails the same
+    /// allocations of a deterministic program, run after run. Defaults to 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+// the configuration, and what it needs to remember.
+struct Injector {
+    config: FaultInjection,
+    // the number of allocations that may fail seen so far.
+    seen: usize,
+    rng: u64,
+}
+
+impl Injector {
+    // a random number between 0 and 1.
+    fn next_random(&mut self) -> f64 {
+        (random::next(&mut self.rng) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+static INJECTOR: Mutex<Option<Injector>> = Mutex::new(None);
+
+// whether the allocation of `layout` has to fail. must be called while
+// ALLOCATING is nonzero, since capturing a backtrace allocates.
+pub fn inject(layout: Layout) -> bool {
+    let mut injector = INJECTOR.lock().unwrap();
+    let Some(injector) = injector.as_mut() else {
+        return false;
+    };
+
+    let config = &injector.config;
+    if !config.sizes.contains(&layout.size()) {
+        return false;
+    }
+    if let Some(pattern) = &config.call_site {
+        if !Backtrace::force_capture()
+            .to_string()
+            .contains(pattern.as_str())
+        {
+            return false;
+        }
+    }
+
+    injector.seen += 1;
+    let nth = injector.config.every.is_none_or(|n| injector.seen % n == 0);
+    let lucky = match injector.config.probability {
+        Some(probability) => injector.next_random() < probability,
+        None => true,
+    };
+
+    nth && lucky
+}
+
+impl StupidAlloc {
+    /// Sets which subsequent allocations fail on purpose, or none of them with
+    /// [`None`]. Failing allocations return `AllocError` through the
+    /// `Allocator` API, and a null pointer through
+    /// [`GlobalAlloc`](std::alloc::GlobalAlloc), which makes most of
+    /// the standard library abort, except for the likes of
+    /// [`Vec::try_reserve()`]. Growing an allocation counts as an allocation,
+    /// shrinking it doesn't.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{FaultInjection, StupidAlloc};
+    ///
+    /// StupidAlloc.set_fault_injection(Some(FaultInjection::new().every(2)));
+    /// let first = Box::try_new_in(1u32, StupidAlloc);
+    /// let second = Box::try_new_in(2u32, StupidAlloc);
+    /// StupidAlloc.set_fault_injection(None);
+    ///
+    /// assert!(first.is_ok());
+    /// assert!(second.is_err());
+    /// ```
+    pub fn set_fault_injection(&self, faults: Option<FaultInjection>) {
+        let injector = faults.map(|config| Injector {
+            rng: config.seed,
+            config,
+            seen: 0,
+        });
+
+        // the old configuration gets dropped once the lock is released, in
+        // case freeing it needs the allocator.
+        let old = std::mem::replace(&mut *INJECTOR.lock().unwrap(), injector);
+        drop(old);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use crate::{random, StupidAlloc};
+
+/// What the memory of new allocations is filled with before it is handed
+/// out, see [`StupidAlloc::set_fill_pattern()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillPattern {
+    /// Zeroes, like a brand new file.
+    #[default]
+    Zeroes,
+    /// The same byte over and over, like `0xAA`.
+    Byte(u8),
+    /// `0xCD`, the way debug heaps mark memory that was never written to.
+    Uninitialized,
+    /// Random bytes, different for every allocation.
+    Random,
+}
+
+impl fmt::Display for FillPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FillPattern::Zeroes => write!(f, "zeroes"),
+            FillPattern::Byte(byte) => write!(f, "0x{byte:02X}"),
+            FillPattern::Uninitialized => write!(f, "0xCD (uninitialized)"),
+            FillPattern::Random => write!(f, "random"),
+        }
+    }
+}
+
+static PATTERN: RwLock<FillPattern> = RwLock::new(FillPattern::Zeroes);
+
+// the state of the random bytes, shared by every allocation.
+static RNG: AtomicU64 = AtomicU64::new(0);
+
+// the pattern for a new allocation. allocations that were asked to be zeroed
+// stay that way.
+pub fn pattern(zeroed: bool) -> FillPattern {
+    if zeroed {
+        FillPattern::Zeroes
+    } else {
+        *PATTERN.read().unwrap()
+    }
+}
+
+// the state moves along atomically, so that allocating threads don't share
+// numbers.
+fn next_random() -> u64 {
+    let mut state = RNG.fetch_add(random::GAMMA, Ordering::SeqCst);
+    random::next(&mut state)
+}
+
+// fills `data`, which is zeroed already, with `pattern`.
+pub fn fill(data: &mut [u8], pattern: FillPattern) {
+    match pattern {
+        FillPattern::Zeroes => {}
+        FillPattern::Byte(byte) => data.fill(byte),
+        FillPattern::Uninitialized => data.fill(0xCD),
+        FillPattern::Random => {
+            for chunk in data.chunks_mut(8) {
+                let random = next_random().to_ne_bytes();
+                chunk.copy_from_slice(&random[..chunk.len()]);
+            }
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Sets what the memory of subsequent allocations is filled with before
+    /// being handed out, so that the windows and the hexdumps tell memory
+    /// that was never written to apart from data that happens to be zero.
+    /// The pattern is written down in the log of each allocation.
+    ///
+    /// Allocations that are asked to be zeroed, with `allocate_zeroed()` or
+    /// [`GlobalAlloc::alloc_zeroed()`](std::alloc::GlobalAlloc::alloc_zeroed)
+    /// for example, are zeroed no matter the pattern. So is the memory gained
+    /// by growing an allocation.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use std::alloc::{Allocator, Layout};
+    /// use stupidalloc::{FillPattern, StupidAlloc};
+    ///
+    /// StupidAlloc.set_fill_pattern(FillPattern::Byte(0xAA));
+    /// let layout = Layout::new::<[u8; 4]>();
+    /// let block = StupidAlloc.allocate(layout).unwrap();
+    /// StupidAlloc.set_fill_pattern(FillPattern::Zeroes);
+    ///
+    /// assert_eq!(unsafe { block.as_ref() }, [0xAA; 4]);
+    /// unsafe { StupidAlloc.deallocate(block.cast(), layout) };
+    /// ```
+    pub fn set_fill_pattern(&self, pattern: FillPattern) {
+        *PATTERN.write().unwrap() = pattern;
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    ffi::c_void,
+    fs::{File, OpenOptions},
+    io,
+    os::unix::{fs::FileExt, io::AsRawFd},
+    sync::{Arc, MutexGuard, Once, RwLockWriteGuard},
+};
+
+use crate::{
+    budget, manifest, page_size, pool, protect_guard_pages, reaper,
+    registry::{AllShards, StupidMap},
+    system_scope, temp_alloc_file_path, AllocHandle, Freed, FREED, QUARANTINE, STUPID_MAP,
+};
+
+// everything a forked child might need that another thread could be holding
+// at the time of the fork. the registry comes first, like everywhere else, and
+// the quarantine comes before the locks taken while releasing allocations.
+type Held = (
+    AllShards<RwLockWriteGuard<'static, StupidMap>>,
+    RwLockWriteGuard<'static, BTreeMap<usize, usize>>,
+    MutexGuard<'static, VecDeque<Freed>>,
+    MutexGuard<'static, VecDeque<AllocHandle>>,
+    MutexGuard<'static, Vec<pool::Pooled>>,
+    MutexGuard<'static, reaper::Queue>,
+    MutexGuard<'static, Option<(u32, File)>>,
+    MutexGuard<'static, budget::Spilled>,
+);
+
+thread_local! {
+    // held by the forking thread between before_fork and the after_fork
+    // handlers, which all run in that thread.
+    static HELD: RefCell<Option<Held>> = const { RefCell::new(None) };
+}
+
+static REGISTER_HANDLERS: Once = Once::new();
+
+// makes forked children detach from the files of their parent. there is
+// nothing to detach before the first allocation, so that's when it's done.
+// must be called while ALLOCATING is nonzero, since registering allocates.
+pub fn register() {
+    REGISTER_HANDLERS.call_once(|| unsafe {
+        libc::pthread_atfork(
+            Some(before_fork),
+            Some(after_fork_in_parent),
+            Some(after_fork_in_child),
+        );
+    });
+}
+
+extern "C" fn before_fork() {
+    let (shards, index) = STUPID_MAP.lock_everything();
+    let held = (
+        shards,
+        index,
+        FREED.lock().unwrap(),
+        QUARANTINE.lock().unwrap(),
+        pool::lock(),
+        reaper::lock(),
+        manifest::lock(),
+        budget::lock(),
+    );
+    HELD.with(|h| *h.borrow_mut() = Some(held));
+}
+
+extern "C" fn after_fork_in_parent() {
+    HELD.with(|h| h.borrow_mut().take());
+}
+
+extern "C" fn after_fork_in_child() {
+    let Some((mut shards, index, freed, mut quarantined, pool, queue, manifest, spilled)) =
+        HELD.with(|h| h.borrow_mut().take())
+    else {
+        return;
+    };
+
+    system_scope(|| {
+        for handle in shards.values_mut() {
+            if let Err(e) = detach(handle) {
+                // the child is going to mess with the memory of its parent,
+                // which is bad, but not as bad as not forking at all.
+                eprintln!(
+                    "stupidalloc: allocation {} not detached after fork: {e}",
+                    handle.path.display()
+                );
+            }
+        }
+
+        // quarantined allocations are inaccessible anyway, they only need to
+        // leave the parent's files alone.
+        for handle in quarantined.iter_mut() {
+            handle.persistent = true;
+        }
+
+        // the files of the pool and the paths in the queue get freed, while
+        // the registry is still ours.
+        pool::forget(pool);
+        reaper::forget(queue);
+        manifest::forget(manifest);
+    });
+
+    drop((shards, index, freed, quarantined, spilled));
+}
+
+// gives an allocation of a forked child a file of its own, mapped at the very
+// same place so that the pointers the program holds stay valid. the parent
+// keeps the original file.
+fn detach(handle: &mut AllocHandle) -> io::Result<()> {
+    // memory from another allocator was copied by fork already.
+    let Some(map) = handle.map.map() else {
+        return Ok(());
+    };
+
+    // guard pages can't be read, and are zeroes in the file anyway.
+    let (start, end) = if handle.redzones.guard_pages {
+        (page_size(), map.len() - page_size())
+    } else {
+        (0, map.len())
+    };
+
+    let path = temp_alloc_file_path(None, handle.layout.size())?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(&path)?;
+    file.set_len(map.len() as u64)?;
+    file.write_all_at(&map[start..end], start as u64)?;
+
+    let ptr = unsafe {
+        libc::mmap(
+            map.as_ptr() as *mut c_void,
+            map.len(),
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_FIXED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    if handle.redzones.guard_pages {
+        protect_guard_pages(map);
+    }
+
+    // the file stays open, since it can't be opened again by path.
+    std::fs::remove_file(&path)?;
+    handle.path = path;
+    handle.file = Some(Arc::new(file));
+    handle.persistent = false;
+
+    // locks in RAM don't survive fork either.
+    if handle.pinned {
+        handle.pinned = false;
+        let _ = handle.set_pinned(true);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::{
+    cell::Cell,
+    sync::{Condvar, Mutex},
+};
+
+use crate::StupidAlloc;
+
+// who froze the world, and how many operations are underway.
+struct World {
+    // the marker of the thread that froze the world, or 0 if it isn't frozen.
+    freezer: usize,
+    busy: usize,
+}
+
+static WORLD: Mutex<World> = Mutex::new(World {
+    freezer: 0,
+    busy: 0,
+});
+
+// notified when the world thaws, and when an operation ends while it's frozen.
+static CHANGED: Condvar = Condvar::new();
+
+thread_local! {
+    // threads are told apart by the address of their own marker. asking std
+    // for the current thread may allocate, which isn't an option in here.
+    static MARKER: u8 = const { 0 };
+    // how many operations of this thread are underway, since freeing can
+    // trigger more of them.
+    static MINE: Cell<usize> = const { Cell::new(0) };
+}
+
+fn this_thread() -> usize {
+    MARKER.with(|marker| marker as *const u8 as usize)
+}
+
+// an allocation, free or resize that is underway. the world can't freeze
+// until it's dropped.
+pub struct Operation;
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        MINE.with(|mine| mine.set(mine.get() - 1));
+
+        let mut world = WORLD.lock().unwrap();
+        world.busy -= 1;
+        if world.freezer != 0 {
+            CHANGED.notify_all();
+        }
+    }
+}
+
+// waits for the world to thaw, unless this thread froze it, and then counts an
+// operation as underway until the returned guard is dropped. operations that
+// start during another one of the same thread never wait, since the freezer
+// would wait for the outer one in turn. must not be called with the registry
+// locked.
+pub fn enter() -> Operation {
+    let me = this_thread();
+    let nested = MINE.with(|mine| mine.get() > 0);
+
+    let world = WORLD.lock().unwrap();
+    let mut world = CHANGED
+        .wait_while(world, |world| {
+            !nested && world.freezer != 0 && world.freezer != me
+        })
+        .unwrap();
+    world.busy += 1;
+    MINE.with(|mine| mine.set(mine.get() + 1));
+
+    Operation
+}
+
+// lets every thread go on.
+pub fn thaw() {
+    WORLD.lock().unwrap().freezer = 0;
+    CHANGED.notify_all();
+}
+
+impl StupidAlloc {
+    /// Freezes the heap: stupid allocations, de-allocations and resizes made
+    /// by other threads wait until [`StupidAlloc::thaw_world()`] is called,
+    /// while the calling thread carries on. Once this returns, the operations
+    /// that were underway are over, and nothing changes in the stupid heap
+    /// but what the calling thread does, which leaves all the time in the
+    /// world to look at the windows, or to take a consistent snapshot.
+    ///
+    /// Windows keep running, since they don't use the stupid alloc. Waiting
+    /// for a frozen thread, by joining it for example, waits forever. If
+    /// another thread froze the world already, this waits for it to thaw
+    /// first.
+    ///
+    /// ```
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// StupidAlloc.freeze_world();
+    /// let stats = StupidAlloc.stats();
+    /// StupidAlloc.thaw_world();
+    /// ```
+    pub fn freeze_world(&self) {
+        let me = this_thread();
+        let mine = MINE.with(|mine| mine.get());
+
+        let world = WORLD.lock().unwrap();
+        let mut world = CHANGED
+            .wait_while(world, |world| world.freezer != 0 && world.freezer != me)
+            .unwrap();
+        world.freezer = me;
+
+        // the operations of this thread will never end while it waits.
+        let _world = CHANGED
+            .wait_while(world, |world| world.busy > mine)
+            .unwrap();
+    }
+
+    /// Lets the threads waiting on [`StupidAlloc::freeze_world()`] go on. Does
+    /// nothing if the world isn't frozen.
+    pub fn thaw_world(&self) {
+        thaw();
+    }
+}
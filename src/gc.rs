@@ -0,0 +1,206 @@
+use std::{alloc::Layout, collections::HashSet, path::PathBuf};
+
+#[cfg(feature = "graphics")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    graph::{containing, pointer_graph},
+    system_scope, StupidAlloc,
+};
+
+#[cfg(feature = "graphics")]
+use crate::{graphics, STUPID_MAP};
+
+/// A live allocation that can't be reached from the roots given to
+/// [`StupidAlloc::gc_report()`], and which is probably leaked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    /// The address of the allocation.
+    pub addr: usize,
+    /// The layout of the allocation.
+    pub layout: Layout,
+    /// The path of the allocation file.
+    pub path: PathBuf,
+}
+
+/// The outcome of [`StupidAlloc::gc_report()`].
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    /// The number of live allocations reachable from the roots.
+    pub reachable: usize,
+    /// The live allocations that are not reachable from the roots, sorted by
+    /// address.
+    pub leaks: Vec<Leak>,
+}
+
+// whether the windows of unreachable allocations get tinted red by reports.
+#[cfg(feature = "graphics")]
+static TINT_LEAKS: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "graphics")]
+const RED: u32 = 0x00FF0000;
+
+// marks every allocation reachable from `roots` through the pointers between
+// allocations, and sweeps the others into the report. must be called in
+// system_scope.
+fn mark_and_sweep(roots: Vec<usize>) -> GcReport {
+    let (nodes, edges) = pointer_graph();
+
+    let mut marked = HashSet::new();
+    let mut pending = roots
+        .into_iter()
+        .filter_map(|root| containing(&nodes, root))
+        .collect::<Vec<_>>();
+
+    while let Some(addr) = pending.pop() {
+        if marked.insert(addr) {
+            // the edges are sorted by the allocation they start from.
+            let first = edges.partition_point(|edge| edge.from < addr);
+            pending.extend(
+                edges[first..]
+                    .iter()
+                    .take_while(|edge| edge.from == addr)
+                    .map(|edge| edge.to),
+            );
+        }
+    }
+
+    let leaks = nodes
+        .into_iter()
+        .filter(|node| !marked.contains(&node.addr))
+        .map(|node| Leak {
+            addr: node.addr,
+            layout: node.layout,
+            path: node.path,
+        })
+        .collect();
+
+    GcReport {
+        reachable: marked.len(),
+        leaks,
+    }
+}
+
+// colors the windows of the leaks red, and the other ones white. must be called
+// in system_scope.
+#[cfg(feature = "graphics")]
+fn tint(report: &GcReport) {
+    for (addr, handle) in STUPID_MAP.read_all().iter() {
+        if let Some(window) = &handle.window {
+            let leaked = report
+                .leaks
+                .binary_search_by_key(addr, |leak| leak.addr)
+                .is_ok();
+            let color = if leaked { RED } else { graphics::WHITE };
+            let _ = window.tx.send(graphics::Message::Tint { color });
+        }
+    }
+}
+
+// the words on the stack of the calling thread, from here to its bottom.
+// whatever the callers keep in registers is missed, which is why it's only a
+// demo. must be called in system_scope.
+#[cfg(target_os = "linux")]
+#[inline(never)]
+fn stack_words() -> Vec<usize> {
+    use std::{mem::size_of, mem::MaybeUninit};
+
+    let here = 0usize;
+    let here = std::ptr::addr_of!(here) as usize;
+
+    let mut attr = MaybeUninit::<libc::pthread_attr_t>::uninit();
+    let (mut base, mut size) = (std::ptr::null_mut(), 0);
+    unsafe {
+        if libc::pthread_getattr_np(libc::pthread_self(), attr.as_mut_ptr()) != 0 {
+            return Vec::new();
+        }
+        libc::pthread_attr_getstack(attr.as_ptr(), &mut base, &mut size);
+        libc::pthread_attr_destroy(attr.as_mut_ptr());
+    }
+
+    // SAFETY: the stack is mapped all the way from here to its bottom. the
+    // words may be in the middle of being used by the callers, hence volatile.
+    (here..base as usize + size)
+        .step_by(size_of::<usize>())
+        .map(|word| unsafe { std::ptr::read_volatile(word as *const usize) })
+        .collect()
+}
+
+// makes a report from the roots listed by `roots`, which is called in
+// system_scope, tints the windows if asked to, and copies the report for the
+// user.
+fn report(roots: impl FnOnce() -> Vec<usize>) -> GcReport {
+    let report = system_scope(|| {
+        let report = mark_and_sweep(roots());
+
+        #[cfg(feature = "graphics")]
+        if TINT_LEAKS.load(Ordering::SeqCst) {
+            tint(&report);
+        }
+
+        report
+    });
+
+    let user_report = report.clone();
+    system_scope(|| drop(report));
+    user_report
+}
+
+impl StupidAlloc {
+    /// Looks for leaks, like a conservative garbage collector would: starting
+    /// from the allocations containing the `roots`, every allocation that a
+    /// pointer-sized value in a reachable allocation points into is reachable
+    /// too. The live allocations that are not reachable are reported as likely
+    /// leaks. Nothing gets freed, it's only a report.
+    ///
+    /// Any value that looks like a pointer is taken for one, so a leak can hide
+    /// behind an integer that happens to look like its address.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let list = Box::new_in(Box::new_in(7u64, StupidAlloc), StupidAlloc);
+    /// let leaked: *mut u64 = Box::leak(Box::new_in(42u64, StupidAlloc));
+    ///
+    /// let root = &*list as *const _ as *const u8;
+    /// let report = StupidAlloc.gc_report(&[root]);
+    ///
+    /// assert_eq!(report.reachable, 2);
+    /// assert_eq!(report.leaks.len(), 1);
+    /// assert_eq!(report.leaks[0].addr, leaked as usize);
+    /// ```
+    pub fn gc_report(&self, roots: &[*const u8]) -> GcReport {
+        report(|| roots.iter().map(|&root| root as usize).collect())
+    }
+
+    /// Like [`StupidAlloc::gc_report()`], with every pointer-sized value on
+    /// the stack of the calling thread as an extra root: the allocations held
+    /// by local variables of the callers are reachable, without having to
+    /// list them. Values the compiler keeps in registers are missed, so some
+    /// of the reported leaks may still be in use, especially in release
+    /// builds. The stacks of other threads are not scanned.
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(feature = "nightly", doc(cfg(target_os = "linux")))]
+    pub fn gc_report_with_stack(&self, roots: &[*const u8]) -> GcReport {
+        report(|| {
+            let mut roots = roots.iter().map(|&root| root as usize).collect::<Vec<_>>();
+            roots.extend(stack_words());
+            roots
+        })
+    }
+
+    /// Enables or disables tinting leaks red, for a garbage collection demo.
+    /// When enabled, the set bits of the windows of allocations reported as
+    /// leaks by [`StupidAlloc::gc_report()`] are drawn in red, and those of the
+    /// other ones in white again. Disabling it turns every window white.
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    #[cfg(feature = "graphics")]
+    pub fn set_gc_tint(&self, value: bool) {
+        TINT_LEAKS.store(value, Ordering::SeqCst);
+
+        if !value {
+            system_scope(|| tint(&GcReport::default()));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    alloc::Layout,
+    fmt::Write as _,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{harvest::words, system_scope, StupidAlloc, STUPID_MAP};
+
+// a live allocation.
+pub struct Node {
+    pub addr: usize,
+    pub layout: Layout,
+    pub path: PathBuf,
+}
+
+// a pointer found in an allocation, to some place inside of another one.
+pub struct Edge {
+    pub from: usize,
+    // where the pointer is in the allocation it was found in.
+    pub field: usize,
+    pub to: usize,
+    // where it points to in the allocation it points to.
+    pub target: usize,
+}
+
+// the allocation of `nodes` containing `value`, if any.
+pub fn containing(nodes: &[Node], value: usize) -> Option<usize> {
+    let i = nodes
+        .partition_point(|node| node.addr <= value)
+        .checked_sub(1)?;
+    let node = &nodes[i];
+    (value < node.addr + node.layout.size()).then_some(node.addr)
+}
+
+// the live allocations sorted by address, and the pointers between them. must
+// be called in system_scope.
+pub fn pointer_graph() -> (Vec<Node>, Vec<Edge>) {
+    let map = STUPID_MAP.read_all();
+
+    let mut nodes = map
+        .iter()
+        .map(|(&addr, handle)| Node {
+            addr,
+            layout: handle.layout,
+            path: handle.path.clone(),
+        })
+        .collect::<Vec<_>>();
+    nodes.sort_by_key(|node| node.addr);
+
+    let mut edges = Vec::new();
+    for (&from, handle) in map.iter() {
+        let offset = handle.offset();
+        let data = &handle.map[offset..offset + handle.layout.size()];
+
+        for (field, value) in words(from, data) {
+            if let Some(to) = containing(&nodes, value) {
+                edges.push(Edge {
+                    from,
+                    field,
+                    to,
+                    target: value - to,
+                });
+            }
+        }
+    }
+    edges.sort_by_key(|edge| (edge.from, edge.field));
+
+    (nodes, edges)
+}
+
+// the name of the file of an allocation, which is enough to tell them apart.
+fn name(node: &Node) -> String {
+    let name = node.path.file_name().unwrap_or_default();
+    name.to_string_lossy().into_owned()
+}
+
+fn dot(nodes: &[Node], edges: &[Edge]) -> String {
+    let mut out = String::from("digraph stupidalloc {\n    node [shape=box fontname=monospace];\n");
+
+    // can't fail, it's a string
+    for node in nodes {
+        let _ = writeln!(
+            out,
+            "    \"0x{addr:x}\" [label=\"0x{addr:x}\\n{size} bytes\\n{name}\"];",
+            addr = node.addr,
+            size = node.layout.size(),
+            name = name(node)
+        );
+    }
+    for edge in edges {
+        let _ = writeln!(
+            out,
+            "    \"0x{:x}\" -> \"0x{:x}\" [label=\"{}\"];",
+            edge.from,
+            edge.to,
+            edge_label(edge)
+        );
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn mermaid(nodes: &[Node], edges: &[Edge]) -> String {
+    let mut out = String::from("graph LR\n");
+
+    // can't fail, it's a string
+    for node in nodes {
+        let _ = writeln!(
+            out,
+            "    a{addr:x}[\"0x{addr:x}<br/>{size} bytes<br/>{name}\"]",
+            addr = node.addr,
+            size = node.layout.size(),
+            name = name(node)
+        );
+    }
+    for edge in edges {
+        let _ = writeln!(
+            out,
+            "    a{:x} -->|\"{}\"| a{:x}",
+            edge.from,
+            edge_label(edge),
+            edge.to
+        );
+    }
+
+    out
+}
+
+// the offset of the pointer, and of what it points to if it's not the start.
+fn edge_label(edge: &Edge) -> String {
+    match edge.target {
+        0 => format!("+{}", edge.field),
+        target => format!("+{} → +{target}", edge.field),
+    }
+}
+
+impl StupidAlloc {
+    /// Writes a graph of the pointers between the live allocations to the file
+    /// at `path`: every allocation is a node, and every pointer-sized value
+    /// in one of them that happens to fall inside of an allocation is an edge,
+    /// labelled with its offset. Linked lists, trees and `Rc` cycles look like
+    /// what they are.
+    ///
+    /// The graph is written for [Graphviz](https://graphviz.org), or for
+    /// [Mermaid](https://mermaid.js.org) if the extension of `path` is `mmd`.
+    /// Any value that looks like a pointer is taken for one, so the odd
+    /// integer may show up as a pointer too.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let value = Box::new_in(42u64, StupidAlloc);
+    /// let reference = Box::new_in(&*value, StupidAlloc);
+    ///
+    /// let path = std::env::temp_dir().join("stupidalloc_doc_graph.dot");
+    /// StupidAlloc.export_graph(&path).unwrap();
+    ///
+    /// let graph = std::fs::read_to_string(&path).unwrap();
+    /// let edge = format!("\"{:p}\" -> \"{:p}\"", &*reference, &*value);
+    /// assert!(graph.contains(&edge));
+    ///
+    /// // then: dot -Tsvg stupidalloc_doc_graph.dot -o heap.svg
+    /// ```
+    pub fn export_graph(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+
+        // while the registry is locked, nothing must be stupid-allocated.
+        system_scope(|| {
+            let (nodes, edges) = pointer_graph();
+            let graph = match path.extension() {
+                Some(ext) if ext == "mmd" => mermaid(&nodes, &edges),
+                _ => dot(&nodes, &edges),
+            };
+
+            std::fs::write(path, graph)
+        })
+    }
+}
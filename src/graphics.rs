@@ -1,66 +1,331 @@
 use std::{
     alloc::System,
     fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    panic::Location,
     path::Path,
     sync::{
         mpsc::{channel, Sender, TryRecvError},
-        Arc,
+        Arc, Mutex,
     },
     thread::JoinHandle,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use allocator_api2::vec::Vec;
 use memmap2::{MmapMut, MmapOptions};
 use minifb::{Scale, WindowOptions};
 
-// iterator over bits of byte (LSB -> MSB)
-fn bits_as_pixels(byte: u8) -> impl Iterator<Item = u32> {
+use crate::{analysis, edit, error, hud, placement, tamagotchi, StupidAlloc};
+
+// the color of set bits, unless told otherwise.
+pub const WHITE: u32 = 0x00FFFFFF;
+
+// the colors of the unset and set bits of the redzones around the data: guard
+// pages, canaries and padding.
+const REDZONE: (u32, u32) = (0x00400000, 0x00FF4040);
+
+// the stripes drawn over the pages evicted to the file, which aren't read.
+const EVICTED: (u32, u32) = (0x00000000, 0x00204080);
+
+// the colors of the unset and set bits of the spare capacity of a container,
+// once explained.
+const SPARE: (u32, u32) = (0x00302000, 0x00FFB030);
+
+// what the redzones flash between once a canary was found corrupted, and for
+// how long.
+const ALARM: [(u32, u32); 2] = [(0x00FF0000, 0x00FFFF00), REDZONE];
+const ALARM_DURATION: Duration = Duration::from_secs(3);
+
+// how long the bytes added by a resize take to show up, one after the other,
+// and for how long they stay highlighted in a fading green.
+const GROW_REVEAL: Duration = Duration::from_millis(300);
+const GROW_DURATION: Duration = Duration::from_secs(1);
+const GROW_GREEN: f32 = 160.0;
+
+// draws the bytes of `grown` in `pixels`, which were added by a resize
+// `elapsed` ago: the ones that didn't show up yet are black, and the unset
+// bits of the others are green, fading to black.
+fn animate_growth(pixels: &mut [u32], grown: &Range<usize>, elapsed: Duration) {
+    let progress = elapsed.as_secs_f32() / GROW_REVEAL.as_secs_f32();
+    let shown = grown.start + (grown.len() as f32 * progress.min(1.0)) as usize;
+    let fade = 1.0 - elapsed.as_secs_f32() / GROW_DURATION.as_secs_f32();
+    let green = ((GROW_GREEN * fade.max(0.0)) as u32) << 8;
+
+    for byte in grown.clone() {
+        let Some(bits) = pixels.get_mut(8 * byte..8 * byte + 8) else {
+            break;
+        };
+        for bit in bits {
+            if byte >= shown {
+                *bit = 0;
+            } else if *bit == 0 {
+                *bit = green;
+            }
+        }
+    }
+}
+
+// iterator over bits of byte (LSB -> MSB), unset ones being `unset` and set
+// ones `set`
+fn bits_as_pixels(byte: u8, (unset, set): (u32, u32)) -> impl Iterator<Item = u32> {
     let byte = byte.reverse_bits();
 
     (0..8).map(move |i| {
         // minifb pixel format is 0x00RRGGBB
         if byte >> i & 1 == 0 {
-            //00RRGGBB
-            0x00000000
+            unset
         } else {
-            //00RRGGBB
-            0x00FFFFFF
+            set
         }
     })
 }
 
-// code deduplication ugly function
+// iterator over the bits of `bytes`, set ones being `color`, except for the
+// bytes outside of `data` which are drawn in the colors of `redzone`, and the
+// ones from `spare` on, which are spare capacity. the pages that `resident`
+// says are out in the file, if any, are striped instead of being read, which
+// would bring them back.
+fn bytes_as_pixels<'a>(
+    bytes: &'a [u8],
+    color: u32,
+    data: Option<&'a Range<usize>>,
+    redzone: (u32, u32),
+    spare: Option<usize>,
+    resident: &'a [u8],
+) -> impl Iterator<Item = u32> + 'a {
+    let page = crate::page_size();
+    (0..bytes.len()).flat_map(move |i| match data {
+        _ if resident.get(i / page) == Some(&0) => bits_as_pixels(0b0101_0101, EVICTED),
+        Some(data) if !data.contains(&i) => bits_as_pixels(bytes[i], redzone),
+        _ if spare.is_some_and(|spare| i >= spare) => bits_as_pixels(bytes[i], SPARE),
+        _ => bits_as_pixels(bytes[i], (0x00000000, color)),
+    })
+}
+
+// the address and length of the memory of a pass-through allocation, updated
+// by the allocator whenever it moves.
+pub type MemoryView = Arc<Mutex<(usize, usize)>>;
+
+// where the bytes displayed by a window come from
+pub enum Source {
+    // the allocation file, that the window maps on its own
+    File(Arc<File>),
+    // the memory of a pass-through allocation
+    Memory(MemoryView),
+    // the allocation file of another process, that can only be read
+    ReadOnly(Arc<File>),
+}
+
+// the bytes displayed by a window
+enum Bytes {
+    Map(MmapMut),
+    Memory(MemoryView),
+    // the other process can shrink the file at any time, which would crash a
+    // memory map, so the file gets read again on every frame instead. clicks
+    // change the copy, and are forgotten on the next frame.
+    Read(Arc<File>, Vec<u8, System>),
+}
+
+impl Bytes {
+    fn len(&self) -> usize {
+        match self {
+            Bytes::Map(map) => map.len(),
+            Bytes::Memory(view) => view.lock().unwrap().1,
+            Bytes::Read(_, buf) => buf.len(),
+        }
+    }
+
+    // gives access to the bytes. pass-through memory can't be moved or freed
+    // by the allocator in the meantime.
+    fn with<R>(&mut self, f: impl FnOnce(&mut [u8]) -> R) -> R {
+        match self {
+            Bytes::Map(map) => f(map),
+            Bytes::Memory(view) => {
+                let view = view.lock().unwrap();
+                let (ptr, len) = *view;
+                if ptr == 0 {
+                    f(&mut [])
+                } else {
+                    f(unsafe { std::slice::from_raw_parts_mut(ptr as *mut u8, len) })
+                }
+            }
+            Bytes::Read(file, buf) => {
+                // whatever isn't in the file anymore shows up as zeroes.
+                buf.fill(0);
+                let mut file = &**file;
+                if file.seek(SeekFrom::Start(0)).is_ok() {
+                    let mut read = 0;
+                    while let Ok(n @ 1..) = file.read(&mut buf[read..]) {
+                        read += n;
+                    }
+                }
+                f(buf)
+            }
+        }
+    }
+
+    // fills `resident` with whether each page of the bytes is in RAM, see
+    // residency::resident, or empties it if nobody knows.
+    fn residency(&self, resident: &mut Vec<u8, System>) {
+        resident.clear();
+        if let Bytes::Map(map) = self {
+            let addr = map.as_ptr() as usize;
+            resident.resize(crate::residency::pages(addr, map.len()), 1);
+            if crate::residency::resident(addr, map.len(), resident).is_err() {
+                resident.clear();
+            }
+        }
+    }
+
+    // lets go of the pages the window read, so that the OS can evict them
+    // from RAM: it leaves the pages of other maps alone. they're still there
+    // for the next frame, unless they were evicted in the meantime.
+    fn release(&self) {
+        #[cfg(target_os = "linux")]
+        if let Bytes::Map(map) = self {
+            unsafe {
+                libc::madvise(
+                    map.as_ptr() as *mut libc::c_void,
+                    map.len(),
+                    libc::MADV_DONTNEED,
+                )
+            };
+        }
+    }
+
+    // the numa node of the page at `offset`. the copy of the bytes of another
+    // process says nothing about where theirs are.
+    fn node(&self, offset: usize) -> Option<usize> {
+        match self {
+            Bytes::Map(map) => (offset < map.len())
+                .then(|| crate::numa::node_at(map.as_ptr() as usize + offset))
+                .flatten(),
+            Bytes::Memory(view) => {
+                let (ptr, len) = *view.lock().unwrap();
+                (ptr != 0 && offset < len)
+                    .then(|| crate::numa::node_at(ptr + offset))
+                    .flatten()
+            }
+            Bytes::Read(..) => None,
+        }
+    }
+}
+
+// the number of columns actually used for `len` bytes when `columns` were
+// asked for. StupidAlloc::AUTO_COLUMNS picks a power of two that makes the
+// window about square, without making it wider than the monitor.
+fn fit_columns(columns: usize, len: usize) -> usize {
+    if columns != StupidAlloc::AUTO_COLUMNS {
+        return columns;
+    }
+
+    // with Scale::X16, a byte is 8 by 1 bits of 16 by 16 pixels, so a square
+    // has 8 times as many rows as columns.
+    let square = ((len as f64 / 8.0).sqrt().ceil() as usize).next_power_of_two();
+    let (width, height) = placement::monitor_size();
+    let tall_enough = len.div_ceil((height / 16).max(1)).next_power_of_two();
+    let widest = 1 << (width / (8 * 16)).max(1).ilog2();
+
+    square.max(tall_enough).min(widest)
+}
+
+// code deduplication ugly function. the window has `hud_rows` more rows on
+// top of the bits. also returns the number of columns, fitted to the bytes.
+// the window goes at `position`, which is that of the window it replaces, or
+// wherever the placement says if it's a new one.
 fn create_map_window_buffer(
-    file: &File,
+    source: &Source,
     name: &str,
     columns: usize,
-) -> (MmapMut, minifb::Window, Vec<u32, System>) {
-    let map = unsafe { MmapOptions::new().map_mut(file).unwrap() };
+    hud_rows: usize,
+    position: Option<(isize, isize)>,
+) -> (Bytes, minifb::Window, Vec<u32, System>, usize) {
+    let map = match source {
+        Source::File(file) => Bytes::Map(unsafe { MmapOptions::new().map_mut(&**file).unwrap() }),
+        Source::Memory(view) => Bytes::Memory(Arc::clone(view)),
+        Source::ReadOnly(file) => {
+            let len = file.metadata().map_or(0, |m| m.len() as usize);
+            let mut buf = Vec::with_capacity_in(len, System);
+            buf.resize(len, 0);
+            Bytes::Read(Arc::clone(file), buf)
+        }
+    };
+    let columns = fit_columns(columns, map.len());
     let mut window = minifb::Window::new(
         name,
         8 * columns,
-        map.len() / columns,
+        map.len() / columns + hud_rows,
         WindowOptions {
             scale: Scale::X16, // so that bits aren't the size of a pixel of your screen
             ..Default::default()
         },
     )
     .unwrap();
+    if let Some((x, y)) = position.or_else(|| placement::place(window.get_size())) {
+        window.set_position(x, y);
+    }
     window.limit_update_rate(Some(Duration::from_millis(16))); // 60 fps 😎
 
-    let buffer = Vec::with_capacity_in(map.len() * 8, System);
+    let buffer = Vec::with_capacity_in((map.len() + hud_rows * columns) * 8, System);
 
-    (map, window, buffer)
+    (map, window, buffer, columns)
 }
 
 // messages sent by the allocator
 pub enum Message {
-    // grow (or shrink or grow_zeroed actually)
-    Grow,
+    // grow (or shrink or grow_zeroed actually), with the new facts for the
+    // hud
+    Grow {
+        facts: hud::Facts,
+    },
     // dealloc
     Free,
     // new column size
-    Resize { columns: usize },
+    Resize {
+        columns: usize,
+    },
+    // the type of the data is now known, and maybe where it was allocated
+    Retype {
+        type_name: &'static str,
+        location: Option<&'static Location<'static>>,
+    },
+    // a new version of the data was saved, making `count` of them
+    #[cfg(feature = "time-travel")]
+    Versions {
+        count: usize,
+    },
+    // set bits are now drawn in this color
+    Tint {
+        color: u32,
+    },
+    // the data holds a container whose spare capacity starts `used` bytes in,
+    // as `summary` says
+    Explain {
+        used: usize,
+        summary: String,
+    },
+    // the canary was found corrupted
+    Alarm,
+}
+
+// the title of the window of an allocation
+fn title(path: &str, type_name: Option<&str>, location: Option<&Location>) -> String {
+    format!(
+        "Graphical view of {}{} @ {path}",
+        type_name.map_or("memory".into(), |t| format!("`{t}`")),
+        location.map_or(String::new(), |l| format!(" from {l}"))
+    )
+}
+
+// the same title, followed by what the last explanation of the container in
+// the allocation said, if any.
+fn explained_title(title: String, explained: Option<&(usize, String)>) -> String {
+    match explained {
+        Some((_, summary)) => format!("{title} ({summary})"),
+        None => title,
+    }
 }
 
 pub struct Window {
@@ -70,19 +335,71 @@ pub struct Window {
 }
 
 impl Window {
-    pub fn new(path: &Path, file: Arc<File, System>, columns: usize) -> Self {
-        let name = format!("Graphical view of memory @ {}", path.to_string_lossy());
+    // `born` is when the allocation was made.
+    pub fn new(
+        path: &Path,
+        type_name: Option<&'static str>,
+        location: Option<&'static Location<'static>>,
+        source: Source,
+        columns: usize,
+        born: Instant,
+        facts: hud::Facts,
+    ) -> Self {
+        #[cfg(feature = "time-travel")]
+        let file_path = path.to_path_buf();
+        let mut label = hud::label(path, type_name);
+        let path = path.to_string_lossy().into_owned();
+        let mut name = title(&path, type_name, location);
 
         let (tx, rx) = channel::<Message>();
 
-        let handle = std::thread::Builder::new()
+        let spawned = std::thread::Builder::new()
             .name(name.clone())
             .spawn(move || {
-                let file = file;
-                let mut columns = columns;
+                crate::threads::internal();
+                #[cfg(feature = "valgrind")]
+                crate::valgrind::disable_errors_in_thread();
 
-                let (mut map, mut window, mut buffer) =
-                    create_map_window_buffer(&file, &name, columns);
+                let source = source;
+                // the columns that were asked for, which may be auto, and the
+                // ones actually used.
+                let mut setting = columns;
+                let mut columns;
+                let mut facts = facts;
+                let mut hud_rows = if hud::enabled() { hud::HEIGHT } else { 0 };
+                let mut color = WHITE;
+                // when the canary was last found corrupted.
+                let mut alarm = None::<Instant>;
+                // how the allocation is doing, and what the title says about
+                // it, if anything.
+                let mut pet = tamagotchi::Pet::new(born);
+                let mut status = None;
+                // the bytes added by the last resize, and when it happened,
+                // for as long as they're animated.
+                let mut grown = None::<(Range<usize>, Instant)>;
+                // where the spare capacity of the container in the data
+                // starts, in bytes from its start, and what the title says
+                // about it, until it's resized.
+                let mut explained = None::<(usize, String)>;
+                // the value being typed after a middle click, and the title
+                // that shows it, if it's up.
+                let mut edit = None::<edit::Edit>;
+                let mut edit_title = None::<String>;
+                let (mut type_name, mut location) = (type_name, location);
+                // the number of saved versions, the one being looked at with
+                // the arrow keys, if it's not the live data, and its bytes.
+                // versions never change once saved, so they're read once.
+                #[cfg(feature = "time-travel")]
+                let (mut versions, mut viewing, mut old) = (0, None::<usize>, Vec::new_in(System));
+
+                let (mut map, mut window, mut buffer, fitted) =
+                    create_map_window_buffer(&source, &name, setting, hud_rows, None);
+                columns = fitted;
+                // pass-through memory can change size before the window is
+                // told about it, so remember the size the window was made for.
+                let mut len = map.len();
+                // which pages of the bytes are in RAM, on this frame.
+                let mut resident = Vec::new_in(System);
 
                 loop {
                     if !window.is_open() {
@@ -91,71 +408,323 @@ impl Window {
 
                     match rx.try_recv() {
                         Err(TryRecvError::Empty) => {}
-                        Ok(Message::Free) | Err(TryRecvError::Disconnected) => {
+                        Ok(Message::Free) => {
+                            if tamagotchi::enabled() {
+                                let size = (8 * columns, len / columns + hud_rows);
+                                tamagotchi::die(&mut window, &mut buffer, size, &name, &pet);
+                            }
                             break;
                         }
-                        Ok(Message::Grow) => {
-                            let (new_map, new_window, new_buffer) =
-                                create_map_window_buffer(&file, &name, columns);
+                        Err(TryRecvError::Disconnected) => break,
+                        Ok(Message::Grow { facts: f }) => {
+                            // the data may also have moved in the map, if the
+                            // redzones changed size.
+                            let sizes = facts.layout.zip(f.layout);
+                            grown = sizes.and_then(|(old, new)| {
+                                let added = f.offset + old.size()..f.offset + new.size();
+                                (!added.is_empty()).then(|| (added, Instant::now()))
+                            });
+                            facts = f;
+                            // the explanation is out of date.
+                            if explained.take().is_some() {
+                                name = title(&path, type_name, location);
+                            }
+                            let (new_map, new_window, new_buffer, fitted) =
+                                create_map_window_buffer(
+                                    &source,
+                                    &name,
+                                    setting,
+                                    hud_rows,
+                                    Some(window.get_position()),
+                                );
                             map = new_map;
                             window = new_window;
                             buffer = new_buffer;
+                            columns = fitted;
+                            len = map.len();
+                            status = None;
+                            edit_title = None;
                         }
                         Ok(Message::Resize { columns: c }) => {
-                            columns = c;
-                            let (new_map, new_window, new_buffer) =
-                                create_map_window_buffer(&file, &name, columns);
+                            setting = c;
+                            let (new_map, new_window, new_buffer, fitted) =
+                                create_map_window_buffer(
+                                    &source,
+                                    &name,
+                                    setting,
+                                    hud_rows,
+                                    Some(window.get_position()),
+                                );
                             map = new_map;
                             window = new_window;
                             buffer = new_buffer;
+                            columns = fitted;
+                            len = map.len();
+                            status = None;
+                            edit_title = None;
+                        }
+                        Ok(Message::Retype {
+                            type_name: t,
+                            location: l,
+                        }) => {
+                            (type_name, location) = (Some(t), l);
+                            name = explained_title(
+                                title(&path, type_name, location),
+                                explained.as_ref(),
+                            );
+                            label = t.into();
+                            window.set_title(&name);
+                            status = None;
+                            edit_title = None;
+                        }
+                        #[cfg(feature = "time-travel")]
+                        Ok(Message::Versions { count }) => versions = count,
+                        Ok(Message::Tint { color: c }) => color = c,
+                        Ok(Message::Explain { used, summary }) => {
+                            explained = Some((used, summary));
+                            name = explained_title(
+                                title(&path, type_name, location),
+                                explained.as_ref(),
+                            );
+                            window.set_title(&name);
+                            status = None;
+                            edit_title = None;
                         }
+                        Ok(Message::Alarm) => alarm = Some(Instant::now()),
                     }
 
-                    // really proud of these two lines
-                    buffer.clear();
-                    buffer.extend(map.iter().flat_map(|b| bits_as_pixels(*b)));
+                    // the hud was switched on or off, which takes a window of
+                    // another height.
+                    if hud::enabled() != (hud_rows > 0) {
+                        hud_rows = if hud::enabled() { hud::HEIGHT } else { 0 };
+                        let (new_map, new_window, new_buffer, fitted) = create_map_window_buffer(
+                            &source,
+                            &name,
+                            setting,
+                            hud_rows,
+                            Some(window.get_position()),
+                        );
+                        map = new_map;
+                        window = new_window;
+                        buffer = new_buffer;
+                        columns = fitted;
+                        len = map.len();
+                        status = None;
+                        edit_title = None;
+                    }
 
-                    window
-                        .update_with_buffer(&buffer, 8 * columns, map.len() / columns)
-                        .unwrap();
+                    // where the data is, if known, and the colors of the
+                    // redzones around it, flashing for a while after an alarm.
+                    let data = facts
+                        .layout
+                        .map(|layout| facts.offset..facts.offset + layout.size());
+                    if hud_rows > 0 {
+                        facts.node = map.node(facts.offset);
+                    }
+                    let spare = data
+                        .as_ref()
+                        .zip(explained.as_ref())
+                        .map(|(data, (used, _))| data.start + used);
+                    let redzone = match alarm.map(|since| since.elapsed()) {
+                        Some(elapsed) if elapsed < ALARM_DURATION => {
+                            ALARM[(elapsed.as_millis() / 250) as usize % 2]
+                        }
+                        _ => REDZONE,
+                    };
 
-                    // i've been writing this feature for like 9 hours i'm too tired to try and de-duplicate this code
-                    // future me or anyone else you're welcome to but i'd rather go to bed than try and do that
-                    if window.get_mouse_down(minifb::MouseButton::Left) {
-                        // set bit
-                        if let Some((x, y)) = window.get_mouse_pos(minifb::MouseMode::Discard) {
-                            let x = x.floor() as usize;
-                            let y = y.floor() as usize;
+                    #[cfg(feature = "time-travel")]
+                    {
+                        use minifb::{Key, KeyRepeat};
 
-                            let bit = x % 8;
-                            let byte = (x / 8) + (y * columns);
+                        let previous = viewing;
+                        if window.is_key_pressed(Key::Left, KeyRepeat::Yes) && versions > 0 {
+                            viewing = Some(viewing.map_or(versions - 1, |v| v.saturating_sub(1)));
+                        } else if window.is_key_pressed(Key::Right, KeyRepeat::Yes) {
+                            viewing = viewing.and_then(|v| (v + 1 < versions).then_some(v + 1));
+                        }
 
-                            let mask = 1 << (7 - bit);
+                        if viewing != previous {
+                            name = match viewing {
+                                Some(v) => format!(
+                                    "{} (version {v}/{})",
+                                    title(&path, type_name, location),
+                                    versions - 1
+                                ),
+                                None => explained_title(
+                                    title(&path, type_name, location),
+                                    explained.as_ref(),
+                                ),
+                            };
+                            window.set_title(&name);
+                            status = None;
+                            edit_title = None;
+                            old.clear();
+                            if let Some(v) = viewing {
+                                let path = crate::history::version_path(&file_path, v);
+                                old.extend_from_slice(&std::fs::read(path).unwrap_or_default());
+                            }
+                        }
+
+                        // old versions are only there to be looked at, so
+                        // clicking does nothing.
+                        if viewing.is_some() {
+                            buffer.clear();
+                            if hud_rows > 0 {
+                                let width = 8 * columns;
+                                hud::draw(&mut buffer, width, color, facts, &label, born, len);
+                            }
+                            let old = &old[..len.min(old.len())];
+                            buffer.extend(bytes_as_pixels(
+                                old,
+                                color,
+                                data.as_ref(),
+                                redzone,
+                                spare,
+                                &[],
+                            ));
+                            buffer.resize(8 * (len + hud_rows * columns), 0);
 
-                            map[byte] |= mask;
+                            window
+                                .update_with_buffer(&buffer, 8 * columns, len / columns + hud_rows)
+                                .unwrap();
+                            continue;
+                        }
+                    }
+
+                    let tamagotchi = tamagotchi::enabled();
+                    let color = if tamagotchi { pet.color(color) } else { color };
+
+                    map.residency(&mut resident);
+                    map.with(|map| {
+                        buffer.clear();
+                        if hud_rows > 0 {
+                            let width = 8 * columns;
+                            hud::draw(&mut buffer, width, color, facts, &label, born, len);
+                        }
+                        // really proud of these two lines
+                        let bytes = &map[..len.min(map.len())];
+                        buffer.extend(bytes_as_pixels(
+                            bytes,
+                            color,
+                            data.as_ref(),
+                            redzone,
+                            spare,
+                            &resident,
+                        ));
+                        buffer.resize(8 * (len + hud_rows * columns), 0);
+
+                        if let Some((added, at)) = &grown {
+                            let bits = &mut buffer[8 * columns * hud_rows..];
+                            animate_growth(bits, added, at.elapsed());
+                        }
+                        if grown
+                            .as_ref()
+                            .is_some_and(|(_, at)| at.elapsed() > GROW_DURATION)
+                        {
+                            grown = None;
                         }
-                    } else if window.get_mouse_down(minifb::MouseButton::Right) {
-                        // clear bit
-                        if let Some((x, y)) = window.get_mouse_pos(minifb::MouseMode::Discard) {
+
+                        if analysis::overlay() {
+                            let data = data.clone().and_then(|data| bytes.get(data));
+                            let bits = &mut buffer[8 * columns * hud_rows..];
+                            analysis::draw_overlay(bits, 8 * columns, data.unwrap_or(bytes));
+                        }
+
+                        // the byte under the mouse, and the mask of the bit.
+                        // clicks on the hud don't count.
+                        let clicked = || {
+                            let (x, y) = window.get_mouse_pos(minifb::MouseMode::Discard)?;
                             let x = x.floor() as usize;
-                            let y = y.floor() as usize;
+                            let y = (y.floor() as usize).checked_sub(hud_rows)?;
 
-                            let bit = x % 8;
-                            let byte = (x / 8) + (y * columns);
+                            Some(((x / 8) + (y * columns), 1 << (7 - x % 8)))
+                        };
 
-                            let mask = 1 << (7 - bit);
+                        if window.get_mouse_down(minifb::MouseButton::Left) {
+                            // set bit
+                            if let Some((byte, mask)) = clicked() {
+                                if let Some(byte) = map.get_mut(byte) {
+                                    *byte |= mask;
+                                }
+                            }
+                        } else if window.get_mouse_down(minifb::MouseButton::Right) {
+                            // clear bit
+                            if let Some((byte, mask)) = clicked() {
+                                if let Some(byte) = map.get_mut(byte) {
+                                    *byte &= !mask;
+                                }
+                            }
+                        }
+
+                        // a middle click starts typing a value to write there,
+                        // see edit::parse.
+                        if window.get_mouse_down(minifb::MouseButton::Middle) {
+                            if let Some((byte, _)) = clicked() {
+                                if edit.as_ref().is_none_or(|edit| edit.at != byte) {
+                                    edit = Some(edit::Edit::new(byte));
+                                }
+                            }
+                        }
+                        if let Some(current) = &mut edit {
+                            let keys = window.get_keys_pressed(minifb::KeyRepeat::Yes);
+                            match current.keys(&keys) {
+                                edit::Step::Typing => {}
+                                edit::Step::Cancelled => edit = None,
+                                edit::Step::Done(bytes) => {
+                                    match map.get_mut(current.at..current.at + bytes.len()) {
+                                        Some(region) => {
+                                            region.copy_from_slice(&bytes);
+                                            edit = None;
+                                        }
+                                        None => current.fail("doesn't fit"),
+                                    }
+                                }
+                            }
+                        }
 
-                            map[byte] &= !mask;
+                        // after the clicks, which count as writes.
+                        if tamagotchi {
+                            pet.watch(&map[..len.min(map.len())]);
+                        } else {
+                            pet.forget();
                         }
+                    });
+                    map.release();
+
+                    let wanted = tamagotchi.then(|| pet.status());
+                    if edit.is_none() && wanted != status {
+                        status = wanted;
+                        match status {
+                            Some(status) => window.set_title(&tamagotchi::title(&name, status)),
+                            None => window.set_title(&name),
+                        }
+                    }
+
+                    let wanted = edit.as_ref().map(|edit| edit.title(&name));
+                    if wanted != edit_title {
+                        match &wanted {
+                            Some(title) => window.set_title(title),
+                            // the status goes back up on the next frame.
+                            None => {
+                                window.set_title(&name);
+                                status = None;
+                            }
+                        }
+                        edit_title = wanted;
                     }
+
+                    window
+                        .update_with_buffer(&buffer, 8 * columns, len / columns + hud_rows)
+                        .unwrap();
                 }
-            })
-            .unwrap();
+            });
 
-        Window {
-            handle: Some(handle),
-            tx,
-        }
+        // no window is not worth failing the allocation over.
+        let handle = spawned
+            .map_err(|e| error::backend(format!("couldn't spawn a window thread: {e}")))
+            .ok();
+
+        Window { handle, tx }
     }
 
     pub fn close(mut self) {
@@ -164,6 +733,30 @@ impl Window {
         }
     }
 
+    // closes the window right away, tamagotchi or not, and waits for its
+    // thread, which stops once nobody can send it anything anymore.
+    pub fn shut_down(mut self) {
+        let handle = self.handle.take();
+        drop(self);
+        if let Some(handle) = handle {
+            let _ = handle.join();
+        }
+    }
+
+    // lets the thread of the window be, without joining it.
+    pub fn abandon(mut self) {
+        drop(self.handle.take());
+    }
+
+    // tells the window its allocation is gone. the death of a tamagotchi takes
+    // a while, and the program doesn't have to wait for it.
+    pub fn free(mut self) {
+        let _ = self.tx.send(Message::Free);
+        if tamagotchi::enabled() {
+            drop(self.handle.take());
+        }
+    }
+
     pub fn is_finished(&self) -> bool {
         self.handle
             .as_ref()
@@ -0,0 +1,94 @@
+// wasi has neither windows nor threads to run them in: the graphics feature
+// keeps compiling there, so that the same code runs everywhere, but windows
+// are never shown.
+
+use std::{
+    convert::Infallible,
+    fs::File,
+    panic::Location,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+// the color of set bits, unless told otherwise.
+pub const WHITE: u32 = 0x00FFFFFF;
+
+// the address and length of the memory of a pass-through allocation, updated
+// by the allocator whenever it moves.
+pub type MemoryView = Arc<Mutex<(usize, usize)>>;
+
+// where the bytes displayed by a window would come from
+#[allow(dead_code)]
+pub enum Source {
+    File(Arc<File>),
+    Memory(MemoryView),
+    ReadOnly(Arc<File>),
+}
+
+// messages sent by the allocator, which nobody reads
+#[allow(dead_code)]
+pub enum Message {
+    Grow {
+        facts: crate::hud::Facts,
+    },
+    Free,
+    Resize {
+        columns: usize,
+    },
+    Retype {
+        type_name: &'static str,
+        location: Option<&'static Location<'static>>,
+    },
+    #[cfg(feature = "time-travel")]
+    Versions {
+        count: usize,
+    },
+    Tint {
+        color: u32,
+    },
+    Explain {
+        used: usize,
+        summary: String,
+    },
+    Alarm,
+}
+
+// where the messages go, which is nowhere.
+pub struct Nowhere;
+
+impl Nowhere {
+    pub fn send(&self, _message: Message) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+pub struct Window {
+    pub tx: Nowhere,
+}
+
+impl Window {
+    pub fn new(
+        _path: &Path,
+        _type_name: Option<&'static str>,
+        _location: Option<&'static Location<'static>>,
+        _source: Source,
+        _columns: usize,
+        _born: Instant,
+        _facts: crate::hud::Facts,
+    ) -> Self {
+        Window { tx: Nowhere }
+    }
+
+    pub fn close(self) {}
+
+    pub fn shut_down(self) {}
+
+    pub fn abandon(self) {}
+
+    pub fn free(self) {}
+
+    pub fn is_finished(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,116 @@
+use std::mem::size_of;
+
+use crate::{StupidAlloc, STUPID_MAP};
+
+/// A pointer to a live allocation found inside of another one, returned by
+/// [`StupidAlloc::pointers_of()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapPointer {
+    /// Where the pointer is, in bytes from the start of the allocation it was
+    /// found in.
+    pub offset: usize,
+    /// The pointer itself.
+    pub value: usize,
+    /// The address of the allocation it points to.
+    pub target: usize,
+}
+
+// the aligned words of `data`, which starts at `addr`, with where they are in
+// it. pointers are aligned, at least the ones worth looking at.
+pub fn words(addr: usize, data: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let first = addr.next_multiple_of(size_of::<usize>()) - addr;
+    data.get(first..)
+        .unwrap_or_default()
+        .chunks_exact(size_of::<usize>())
+        .enumerate()
+        .map(move |(i, word)| {
+            let value = usize::from_ne_bytes(word.try_into().unwrap());
+            (first + i * size_of::<usize>(), value)
+        })
+}
+
+// the runs of printable ascii in `data` of at least `min_len` characters, with
+// where they start, like strings(1) finds them.
+fn strings(data: &[u8], min_len: usize) -> Vec<(usize, String)> {
+    let printable = |byte: &u8| byte.is_ascii_graphic() || *byte == b' ' || *byte == b'\t';
+
+    let mut strings = Vec::new();
+    let mut start = 0;
+    for run in data.split(|byte| !printable(byte)) {
+        if !run.is_empty() && run.len() >= min_len {
+            // only ascii in there.
+            strings.push((start, String::from_utf8_lossy(run).into_owned()));
+        }
+        start += run.len() + 1;
+    }
+    strings
+}
+
+impl StupidAlloc {
+    // the contents of the allocation containing `value` and its address, in
+    // a buffer of the user's.
+    pub(crate) fn contents_of<T: ?Sized>(&self, value: &T) -> Option<(usize, Vec<u8>)> {
+        let addr = value as *const T as *const u8 as usize;
+        // the buffer may very well be stupid-allocated, so it can't be created
+        // while the registry is locked.
+        let (start, size) = STUPID_MAP
+            .handle_of(addr)
+            .map(|handle| (handle.start, handle.layout.size()))?;
+        let mut bytes = vec![0; size];
+        let len = self.copy_bytes_of(value, &mut bytes)?;
+        bytes.truncate(len);
+        Some((start, bytes))
+    }
+
+    /// Returns the printable ASCII strings of at least `min_len` characters
+    /// found in the allocation containing `value`, with where they start in
+    /// it, like `strings` does for files, or [`None`] if `value` wasn't
+    /// allocated with the stupid alloc.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in(*b"\x01\x02hello\0stupid world\xff", StupidAlloc);
+    /// let strings = StupidAlloc.strings_of(&*stupid_box, 4).unwrap();
+    /// assert_eq!(strings, [(2, "hello".into()), (8, "stupid world".into())]);
+    /// ```
+    pub fn strings_of<T: ?Sized>(&self, value: &T, min_len: usize) -> Option<Vec<(usize, String)>> {
+        let (_, bytes) = self.contents_of(value)?;
+        Some(strings(&bytes, min_len.max(1)))
+    }
+
+    /// Returns the pointers to live stupid allocations found in the
+    /// allocation containing `value`, in the order they're in, or [`None`]
+    /// if `value` wasn't allocated with the stupid alloc. Like the pointer
+    /// graph of [`StupidAlloc::export_graph()`], only aligned words are
+    /// looked at, and any word that happens to look like a pointer is one.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let target = Box::new_in([1u32; 4], StupidAlloc);
+    /// let pointers = Box::new_in([0, &target[2] as *const u32 as usize], StupidAlloc);
+    ///
+    /// let found = StupidAlloc.pointers_of(&*pointers).unwrap();
+    /// assert_eq!(found.len(), 1);
+    /// assert_eq!(found[0].offset, std::mem::size_of::<usize>());
+    /// assert_eq!(found[0].target, &*target as *const _ as usize);
+    /// ```
+    pub fn pointers_of<T: ?Sized>(&self, value: &T) -> Option<Vec<HeapPointer>> {
+        let (start, bytes) = self.contents_of(value)?;
+        let pointers = words(start, &bytes)
+            .filter(|&(_, value)| value != 0)
+            .filter_map(|(offset, value)| {
+                let target = STUPID_MAP.handle_of(value)?.start;
+                Some(HeapPointer {
+                    offset,
+                    value,
+                    target,
+                })
+            })
+            .collect();
+        Some(pointers)
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt::Write as _;
+
+#[cfg(unix)]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Once,
+};
+
+use crate::{system_scope, temp_alloc_dir, StupidAlloc, Timestamp, STUPID_MAP};
+
+// the width of the bar of the biggest allocation.
+const BAR_WIDTH: usize = 32;
+
+// whether the heap map gets printed when the program exits.
+#[cfg(unix)]
+static AT_EXIT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+static REGISTER_AT_EXIT: Once = Once::new();
+
+// the width of the bar of an allocation of `size` bytes, when the biggest one
+// is `max` bytes. sizes are all over the place, so the scale is logarithmic.
+fn bar_width(size: usize, max: usize) -> usize {
+    let scale = |n: usize| (n as f64 + 1.0).log2();
+    let width = (BAR_WIDTH as f64 * scale(size) / scale(max)).ceil();
+    (width as usize).clamp(1, BAR_WIDTH)
+}
+
+// draws the map. must be called in system_scope.
+fn draw() -> String {
+    let map = STUPID_MAP.read_all();
+    let mut handles = map.iter().collect::<Vec<_>>();
+    handles.sort_by_key(|(&addr, _)| addr);
+
+    let total = handles.iter().map(|(_, h)| h.layout.size()).sum::<usize>();
+    let max = handles.iter().map(|(_, h)| h.layout.size()).max();
+
+    let mut out = format!(
+        "stupid heap: {} allocations, {total} bytes, at {}\n",
+        handles.len(),
+        Timestamp::now()
+    );
+
+    // can't fail, it's a string
+    for (&addr, handle) in handles {
+        let size = handle.layout.size();
+        let width = bar_width(size, max.unwrap_or(size));
+
+        // arenas and persistent allocations have folders of their own, which
+        // are worth showing.
+        let file = match handle.has_file() {
+            true => {
+                let dir = temp_alloc_dir();
+                let path = handle.path.strip_prefix(&dir).unwrap_or(&handle.path);
+                path.to_string_lossy().into_owned()
+            }
+            false => "(no file)".into(),
+        };
+
+        let _ = write!(
+            out,
+            "0x{addr:016x} |{:<BAR_WIDTH$}| {size:>10} B  ",
+            "#".repeat(width)
+        );
+        if let Some(type_name) = handle.type_name {
+            let _ = write!(out, "{type_name}  ");
+        }
+        if let Some(location) = handle.location {
+            let _ = write!(out, "{location}  ");
+        }
+        let _ = writeln!(out, "{file}");
+    }
+
+    out
+}
+
+#[cfg(unix)]
+extern "C" fn print_at_exit() {
+    if AT_EXIT.load(Ordering::SeqCst) {
+        StupidAlloc.print_heap_map();
+    }
+}
+
+impl StupidAlloc {
+    /// Returns a diagram of all the live allocations, one per line, sorted by
+    /// address: a bar showing the size of each one, on a logarithmic scale,
+    /// followed by the size, the type when it is known (see
+    /// [`StupidAlloc::type_name_of()`]), and the file.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in([0u8; 100], StupidAlloc);
+    /// let heap_map = StupidAlloc.heap_map();
+    ///
+    /// assert!(heap_map.starts_with("stupid heap: 1 allocations, 100 bytes"));
+    /// assert!(heap_map.contains(&format!("{:016x}", &*stupid_box as *const _ as usize)));
+    /// ```
+    pub fn heap_map(&self) -> String {
+        let map = system_scope(draw);
+
+        // the map was drawn in System, the user gets their own copy.
+        let user_map = map.as_str().to_owned();
+        system_scope(|| drop(map));
+        user_map
+    }
+
+    /// Prints the diagram of [`StupidAlloc::heap_map()`] to the standard
+    /// output, for a quick look at the heap from a terminal or a CI log.
+    pub fn print_heap_map(&self) {
+        system_scope(|| print!("{}", draw()));
+    }
+
+    /// Enables or disables printing the heap map when the program exits,
+    /// which shows what was never freed.
+    #[cfg(unix)]
+    #[cfg_attr(feature = "nightly", doc(cfg(unix)))]
+    pub fn set_heap_map_at_exit(&self, value: bool) {
+        AT_EXIT.store(value, Ordering::SeqCst);
+        REGISTER_AT_EXIT.call_once(|| unsafe {
+            libc::atexit(print_at_exit);
+        });
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "logging")]
+use std::io::Write;
+
+use crate::{system_scope, user_error, AllocHandle, StupidAlloc, STUPID_MAP};
+
+// where version `version` of the allocation with the file at `path` is saved.
+// pass-through allocations have no file, but their versions go to the same
+// place all the same.
+pub fn version_path(path: &Path, version: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".v{version}"));
+    PathBuf::from(path)
+}
+
+impl AllocHandle {
+    // saves a copy of the data as a new version. must be called in
+    // system_scope, like everything else touching files.
+    pub(crate) fn save_version(&mut self) -> io::Result<()> {
+        let copy = self.copy_data(&version_path(&self.path, self.versions))?;
+
+        #[cfg(feature = "logging")]
+        writeln!(
+            self.log_file,
+            "## Version {}\nCopied with: {copy}\n",
+            self.versions
+        )?;
+        #[cfg(not(feature = "logging"))]
+        let _ = copy;
+
+        self.versions += 1;
+
+        #[cfg(feature = "graphics")]
+        if let Some(window) = self.window.as_ref() {
+            // the window might be closed already, and that's fine.
+            let _ = window.tx.send(crate::graphics::Message::Versions {
+                count: self.versions,
+            });
+        }
+
+        Ok(())
+    }
+
+    // removes the saved versions, once the allocation is gone. needs to be
+    // called while DEALLOCATING is nonzero.
+    pub(crate) fn remove_versions(&self) {
+        for version in 0..self.versions {
+            crate::reaper::remove(version_path(&self.path, version));
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Returns the number of versions saved for the allocation containing
+    /// `value`. A version is saved every time the allocation is resized (with
+    /// the contents from before the resize) and every time it is flushed with
+    /// [`StupidAlloc::flush_of()`] or [`StupidAlloc::flush_all()`].
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "time-travel")))]
+    pub fn versions_of<T: ?Sized>(&self, value: &T) -> usize {
+        let addr = value as *const T as *const u8 as usize;
+        STUPID_MAP
+            .handle_of(addr)
+            .map_or(0, |handle| handle.versions)
+    }
+
+    /// Writes version `version` (counting from 0, the oldest) of the allocation
+    /// containing `value` back into memory. If the allocation was resized since,
+    /// only the bytes that fit are written back.
+    ///
+    /// Returns an error of kind [`NotFound`](io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc, or if there is no such
+    /// version.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let mut stupid_box = Box::new_in(1u32, StupidAlloc);
+    /// StupidAlloc.flush_of(&*stupid_box).unwrap();
+    /// *stupid_box = 2;
+    ///
+    /// StupidAlloc.rewind_of(&*stupid_box, 0).unwrap();
+    /// assert_eq!(*stupid_box, 1);
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "time-travel")))]
+    pub fn rewind_of<T: ?Sized>(&self, value: &T, version: usize) -> io::Result<()> {
+        let addr = value as *const T as *const u8 as usize;
+
+        system_scope(|| {
+            let mut handle = STUPID_MAP
+                .handle_of_mut(addr)
+                .ok_or(io::ErrorKind::NotFound)?;
+
+            if version >= handle.versions {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no version {version}, only {} saved", handle.versions),
+                ));
+            }
+
+            let contents = std::fs::read(version_path(&handle.path, version))?;
+            let len = contents.len().min(handle.layout.size());
+            let offset = handle.offset();
+            handle.map[offset..offset + len].copy_from_slice(&contents[..len]);
+
+            // that's the allocator's own doing, not an external edit.
+            #[cfg(feature = "watch")]
+            {
+                handle.touched = std::time::Instant::now();
+            }
+
+            #[cfg(feature = "logging")]
+            writeln!(handle.log_file, "## Rewind\nBack to version {version}\n")?;
+
+            Ok(())
+        })
+        .map_err(user_error)
+    }
+}
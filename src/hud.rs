@@ -0,0 +1,199 @@
+// there are no windows for the hud to be drawn in on wasi, only the switch.
+#![cfg_attr(target_os = "wasi", allow(dead_code))]
+
+use std::{
+    alloc::{Layout, System},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+use allocator_api2::vec::Vec;
+
+use crate::StupidAlloc;
+
+// whether the windows show the hud above the bits.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+const LINES: usize = 6;
+
+// glyphs are 3 by 5 pixels, with a pixel of space after each of them.
+const GLYPH_WIDTH: usize = 4;
+pub const LINE_HEIGHT: usize = 6;
+
+const SEPARATOR: u32 = 0x00404040;
+
+// the number of rows of pixels taken by the hud, separator included.
+pub const HEIGHT: usize = LINES * LINE_HEIGHT + 1;
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+// what the hud says about an allocation, besides its age and its label, and
+// where its data starts. only the allocator knows those, so they're sent to
+// the window when they change.
+#[derive(Debug, Clone, Copy)]
+pub struct Facts {
+    // unknown for the allocations of other processes.
+    pub layout: Option<Layout>,
+    pub resizes: usize,
+    // the bytes before it are redzones: a guard page and padding.
+    pub offset: usize,
+    // the numa node of the page the data starts on. the OS moves pages around
+    // without telling the allocator, so the window looks it up on every frame.
+    pub node: Option<usize>,
+}
+
+// the pixels of `c`, one row per byte, the leftmost pixel being the third bit.
+// lowercase letters look like uppercase ones, and anything unknown like a
+// question mark.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ';' => [0b000, 0b010, 0b000, 0b010, 0b100],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+        ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+        '&' => [0b010, 0b101, 0b010, 0b101, 0b011],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '@' => [0b010, 0b101, 0b111, 0b100, 0b011],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+// the lines of the hud. `len` is the length of the bytes in the window, for
+// when the layout is unknown.
+fn lines(facts: Facts, label: &str, born: Instant, len: usize) -> [String; LINES] {
+    [
+        format!("size {}", facts.layout.map_or(len, |layout| layout.size())),
+        match facts.layout {
+            Some(layout) => format!("align {}", layout.align()),
+            None => "align ?".into(),
+        },
+        format!("age {}s", born.elapsed().as_secs()),
+        format!("resizes {}", facts.resizes),
+        match facts.node {
+            Some(node) => format!("node {node}"),
+            None => "node ?".into(),
+        },
+        label.into(),
+    ]
+}
+
+// the label of an allocation: its type if known, the name of its file
+// otherwise.
+pub fn label(path: &Path, type_name: Option<&str>) -> String {
+    match type_name {
+        Some(type_name) => type_name.into(),
+        None => path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+    }
+}
+
+// writes `text` in `pixels`, `width` pixels wide, with its top left corner at
+// `(x, y)`, in `color`. text that doesn't fit is cut off.
+pub fn write(pixels: &mut [u32], width: usize, (x, y): (usize, usize), text: &str, color: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i * GLYPH_WIDTH;
+        if left + GLYPH_WIDTH > width {
+            break;
+        }
+
+        for (dy, row) in glyph(c).into_iter().enumerate() {
+            for dx in 0..3 {
+                if row >> (2 - dx) & 1 == 1 {
+                    if let Some(pixel) = pixels.get_mut((y + dy) * width + left + dx) {
+                        *pixel = color;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// appends the hud to `buffer`, `width` pixels wide and HEIGHT rows high, in
+// `color`. text that doesn't fit is cut off.
+pub fn draw(
+    buffer: &mut Vec<u32, System>,
+    width: usize,
+    color: u32,
+    facts: Facts,
+    label: &str,
+    born: Instant,
+    len: usize,
+) {
+    let start = buffer.len();
+    buffer.resize(start + width * HEIGHT, 0);
+    let hud = &mut buffer[start..];
+
+    for (line, text) in lines(facts, label, born, len).iter().enumerate() {
+        write(hud, width, (0, line * LINE_HEIGHT), text, color);
+    }
+
+    hud[(HEIGHT - 1) * width..].fill(SEPARATOR);
+}
+
+impl StupidAlloc {
+    /// Enables or disables the HUD of the graphical windows: a strip at the
+    /// top of each window showing the size, alignment, age, number of resizes
+    /// and NUMA node of its allocation, and its type, or the name of its file
+    /// when the type is unknown. Windows that are already open pick it up on the
+    /// next frame.
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub fn set_window_hud(&self, value: bool) {
+        ENABLED.store(value, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{path::PathBuf, sync::RwLock};
+
+use crate::{page_size, AllocHandle, StupidAlloc};
+
+/// Huge pages for big allocations, set with [`StupidAlloc::set_huge_pages()`].
+///
+/// Memory is handed out by the OS in pages, 4 KiB on most machines, and every
+/// page the program touches takes an entry in the TLB, which is small. Huge
+/// pages, 2 MiB on most machines, take a lot fewer of them for the same
+/// memory. [`HeapInspector::info()`](crate::HeapInspector::info) tells the
+/// size of the pages an allocation actually got.
+///
+/// ```
+/// use stupidalloc::HugePages;
+///
+/// // allocations of 4 MiB and more ask for transparent huge pages.
+/// let huge_pages = HugePages::transparent().threshold(4 << 20);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HugePages {
+    backing: Backing,
+    threshold: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Backing {
+    Transparent,
+    Hugetlbfs(PathBuf),
+}
+
+impl HugePages {
+    /// Asks for transparent huge pages with `madvise`, which the OS may or may
+    /// not grant, depending on its mood and on
+    /// `/sys/kernel/mm/transparent_hugepage`. The files of the allocations
+    /// stay where they are, but only the ones on `tmpfs` stand a chance on
+    /// most kernels.
+    pub fn transparent() -> Self {
+        Self {
+            backing: Backing::Transparent,
+            threshold: 2 << 20,
+        }
+    }
+
+    /// Puts the files of the allocations in the folder `dir`, which must be on
+    /// a `hugetlbfs` mount like `/dev/hugepages`, with huge pages reserved in
+    /// `/proc/sys/vm/nr_hugepages`. Their sizes are rounded up to whole huge
+    /// pages, and allocations fail when the reserved ones run out. Unlike the
+    /// allocation folder, nobody cleans up after crashed runs there.
+    pub fn hugetlbfs(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            backing: Backing::Hugetlbfs(dir.into()),
+            threshold: 2 << 20,
+        }
+    }
+
+    /// Only gives huge pages to allocations of at least `bytes` bytes, 2 MiB
+    /// by default.
+    pub fn threshold(mut self, bytes: usize) -> Self {
+        self.threshold = bytes;
+        self
+    }
+}
+
+static HUGE_PAGES: RwLock<Option<HugePages>> = RwLock::new(None);
+
+// the folder on hugetlbfs for a new allocation of `size` bytes, if it goes
+// there, and the size of the huge pages of the mount. must be called while
+// ALLOCATING is nonzero, the path is the handle's.
+pub fn hugetlbfs(size: usize) -> Option<(PathBuf, usize)> {
+    #[cfg(target_os = "linux")]
+    {
+        let huge_pages = HUGE_PAGES.read().unwrap();
+        let Some(HugePages {
+            backing: Backing::Hugetlbfs(dir),
+            threshold,
+        }) = huge_pages.as_ref()
+        else {
+            return None;
+        };
+        if size < *threshold {
+            return None;
+        }
+
+        // the block size of hugetlbfs is the size of its pages.
+        let path = std::ffi::CString::new(dir.as_os_str().as_encoded_bytes()).ok()?;
+        let mut stats = unsafe { std::mem::zeroed::<libc::statfs>() };
+        if unsafe { libc::statfs(path.as_ptr(), &mut stats) } != 0 {
+            return None;
+        }
+
+        let dir = dir.join(crate::process_id().to_string());
+        Some((dir, (stats.f_bsize as usize).max(page_size())))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = size;
+        None
+    }
+}
+
+impl AllocHandle {
+    // asks for transparent huge pages for the map, if it's big enough. the OS
+    // is free to ignore it, and so are we. must be called in system_scope.
+    pub(crate) fn advise_huge_pages(&self) {
+        #[cfg(target_os = "linux")]
+        {
+            let huge_pages = HUGE_PAGES.read().unwrap();
+            let Some(HugePages {
+                backing: Backing::Transparent,
+                threshold,
+            }) = huge_pages.as_ref()
+            else {
+                return;
+            };
+
+            if let Some(map) = self.map.map().filter(|_| self.layout.size() >= *threshold) {
+                unsafe {
+                    libc::madvise(
+                        map.as_ptr() as *mut libc::c_void,
+                        map.len(),
+                        libc::MADV_HUGEPAGE,
+                    )
+                };
+            }
+        }
+    }
+}
+
+// the memory maps of the process, and the size of their pages, from
+// /proc/self/smaps. a map partly backed by transparent huge pages counts as
+// huge. must be called in system_scope.
+#[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+pub fn page_sizes() -> Vec<(usize, usize, usize)> {
+    let mut maps = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(smaps) = std::fs::read_to_string("/proc/self/smaps") else {
+            return maps;
+        };
+        let huge = std::fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| {
+                let line = meminfo.lines().find(|l| l.starts_with("Hugepagesize:"))?;
+                kib(line)
+            })
+            .unwrap_or(2 << 20);
+
+        for line in smaps.lines() {
+            let range = line
+                .split_once(' ')
+                .and_then(|(range, _)| range.split_once('-'))
+                .and_then(|(start, end)| {
+                    let start = usize::from_str_radix(start, 16).ok()?;
+                    let end = usize::from_str_radix(end, 16).ok()?;
+                    Some((start, end))
+                });
+
+            match (range, maps.last_mut()) {
+                (Some((start, end)), _) => maps.push((start, end, page_size())),
+                (None, Some((_, _, size))) => {
+                    if line.starts_with("KernelPageSize:") {
+                        *size = kib(line).unwrap_or(*size);
+                    } else if ["AnonHugePages:", "ShmemPmdMapped:", "FilePmdMapped:"]
+                        .iter()
+                        .any(|field| line.starts_with(field))
+                        && kib(line).is_some_and(|bytes| bytes > 0)
+                    {
+                        *size = (*size).max(huge);
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    maps
+}
+
+// the number of bytes of a line of /proc like `Hugepagesize:    2048 kB`.
+#[cfg(target_os = "linux")]
+fn kib(line: &str) -> Option<usize> {
+    let kib = line.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+    Some(kib * 1024)
+}
+
+// the size of the pages of the map containing `addr`, among `maps`.
+pub fn page_size_at(maps: &[(usize, usize, usize)], addr: usize) -> usize {
+    maps.iter()
+        .find(|&&(start, end, _)| (start..end).contains(&addr))
+        .map_or_else(page_size, |&(_, _, size)| size)
+}
+
+impl StupidAlloc {
+    /// Gives huge pages to subsequent allocations big enough for them, as
+    /// described by [`HugePages`], or stops with [`None`]. Allocations growing
+    /// past the threshold get transparent huge pages too, but stay where they
+    /// are otherwise. Huge pages are only available on Linux.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{HeapInspector, HugePages, StupidAlloc};
+    ///
+    /// StupidAlloc.set_huge_pages(Some(HugePages::transparent()));
+    /// let mut big = Vec::with_capacity_in(4 << 20, StupidAlloc);
+    /// big.resize(4 << 20, 1u8);
+    /// StupidAlloc.set_huge_pages(None);
+    ///
+    /// // the OS decides whether it's any good.
+    /// let info = StupidAlloc.info(big.as_ptr() as usize).unwrap();
+    /// println!("{} bytes in pages of {} bytes", info.size, info.page_size);
+    /// ```
+    pub fn set_huge_pages(&self, huge_pages: Option<HugePages>) {
+        *HUGE_PAGES.write().unwrap() = huge_pages;
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    alloc::Layout,
+    cell::Cell,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use crate::{huge, numa, system_scope, viewer, AllocHandle, StupidAlloc, STUPID_MAP};
+
+/// What a [`HeapInspector`] knows about one allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationInfo {
+    /// The address of the data.
+    pub addr: usize,
+    /// The size of the data, in bytes.
+    pub size: usize,
+    /// The alignment of the data, unknown for the files of another process.
+    pub align: Option<usize>,
+    /// The file holding the data.
+    pub path: PathBuf,
+    /// The type of the data, if it was recorded.
+    pub type_name: Option<&'static str>,
+    /// The size of the memory pages holding the data, bigger than usual if
+    /// it got huge pages, see [`StupidAlloc::set_huge_pages()`].
+    pub page_size: usize,
+    /// The NUMA node of the memory page the data starts on, if it's in memory
+    /// and the OS tells, see [`StupidAlloc::numa_node_of()`].
+    pub numa_node: Option<usize>,
+}
+
+/// Something that happened to the heap, reported to the subscribers of a
+/// [`HeapInspector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeapEvent {
+    /// Memory was allocated at `addr`.
+    Allocated {
+        /// The address of the new allocation.
+        addr: usize,
+        /// Its layout.
+        layout: Layout,
+    },
+    /// The allocation at `addr` was freed.
+    Freed {
+        /// The address the allocation had.
+        addr: usize,
+        /// The layout it had.
+        layout: Layout,
+    },
+    /// The allocation at `old_addr` was resized, and now lives at `new_addr`.
+    Resized {
+        /// The address of the allocation before the resize.
+        old_addr: usize,
+        /// The address of the allocation after the resize, which may be the
+        /// same.
+        new_addr: usize,
+        /// The layout of the allocation before the resize.
+        old_layout: Layout,
+        /// The layout of the allocation after the resize.
+        new_layout: Layout,
+    },
+}
+
+/// A subscription to the events of a [`HeapInspector`], to end it with
+/// [`HeapInspector::unsubscribe()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription(u64);
+
+/// A callback subscribed to the events of a [`HeapInspector`].
+pub type HeapCallback = Box<dyn Fn(HeapEvent) + Send + Sync>;
+
+/// A way to look at a heap, for tools that don't want to depend on how it's
+/// done: graphical viewers, debuggers, or whatever comes next. Implemented by
+/// [`StupidAlloc`], and usable as a trait object.
+///
+/// ```
+/// #![feature(allocator_api)]
+/// use stupidalloc::{HeapInspector, StupidAlloc};
+///
+/// fn biggest(inspector: &dyn HeapInspector) -> Option<usize> {
+///     inspector.list().iter().map(|allocation| allocation.size).max()
+/// }
+///
+/// let stupid_box = Box::new_in([0u8; 1234], StupidAlloc);
+/// assert!(biggest(&StupidAlloc).unwrap() >= 1234);
+/// ```
+pub trait HeapInspector {
+    /// Returns every live allocation, sorted by address.
+    fn list(&self) -> Vec<AllocationInfo>;
+
+    /// Returns the allocation containing `addr`, if there is one.
+    fn info(&self, addr: usize) -> Option<AllocationInfo>;
+
+    /// Dumps the contents of the allocation containing `addr` like `xxd`
+    /// does, if there is one.
+    fn hexdump(&self, addr: usize) -> Option<String>;
+
+    /// Calls `callback` on every subsequent allocation, free and resize, from
+    /// any thread, until [`HeapInspector::unsubscribe()`] is called.
+    fn subscribe(&self, callback: HeapCallback) -> Subscription;
+
+    /// Stops calling the callback of `subscription`. Does nothing if it was
+    /// stopped already.
+    fn unsubscribe(&self, subscription: Subscription);
+}
+
+static SUBSCRIBERS: RwLock<Vec<(Subscription, Arc<HeapCallback>)>> = RwLock::new(Vec::new());
+
+// the number of subscribers, so that nobody locks anything when there are
+// none.
+static SUBSCRIBED: AtomicUsize = AtomicUsize::new(0);
+
+static NEXT_SUBSCRIPTION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // whether this thread is running the callbacks. what they allocate isn't
+    // reported, or a callback that allocates would never stop being called.
+    static NOTIFYING: Cell<bool> = const { Cell::new(false) };
+}
+
+// runs `f` without reporting what it allocates and frees, since it's busy with
+// the subscribers already.
+fn quietly<R>(f: impl FnOnce() -> R) -> R {
+    let notifying = NOTIFYING.with(|notifying| notifying.replace(true));
+    let result = f();
+    NOTIFYING.with(|n| n.set(notifying));
+    result
+}
+
+// tells the subscribers about `event`. must be called like drop_orphans: not
+// while ALLOCATING or DEALLOCATING is nonzero, and with the registry unlocked,
+// since the callbacks are the program's own code.
+pub fn notify(event: HeapEvent) {
+    if SUBSCRIBED.load(Ordering::SeqCst) == 0 || NOTIFYING.with(Cell::get) {
+        return;
+    }
+
+    quietly(|| {
+        // the callbacks run unlocked, so that they can subscribe and
+        // unsubscribe.
+        let callbacks = SUBSCRIBERS
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, callback)| Arc::clone(callback))
+            .collect::<Vec<_>>();
+        for callback in callbacks {
+            callback(event);
+        }
+    })
+}
+
+impl AllocHandle {
+    // what an inspector tells about the allocation starting at `start`, given
+    // the page sizes of huge::page_sizes(). must be called in system_scope.
+    pub(crate) fn info(&self, start: usize, pages: &[(usize, usize, usize)]) -> AllocationInfo {
+        AllocationInfo {
+            addr: start,
+            size: self.layout.size(),
+            align: Some(self.layout.align()),
+            path: self.path.clone(),
+            type_name: self.type_name,
+            page_size: huge::page_size_at(pages, start),
+            numa_node: numa::node_at(start),
+        }
+    }
+}
+
+impl HeapInspector for StupidAlloc {
+    fn list(&self) -> Vec<AllocationInfo> {
+        // like state, collected in System and then copied for the user.
+        let list = system_scope(|| {
+            // read before locking the registry, it takes a while.
+            let pages = huge::page_sizes();
+            let mut list = STUPID_MAP
+                .read_all()
+                .iter()
+                .map(|(&addr, handle)| AllocationInfo {
+                    addr,
+                    size: handle.layout.size(),
+                    align: Some(handle.layout.align()),
+                    path: handle.path.clone(),
+                    type_name: handle.type_name,
+                    page_size: huge::page_size_at(&pages, addr),
+                    numa_node: numa::node_at(addr),
+                })
+                .collect::<Vec<_>>();
+
+            list.extend(
+                viewer::ATTACHED
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(&addr, attached)| AllocationInfo {
+                        addr,
+                        size: attached.map.len(),
+                        align: None,
+                        path: attached.path.clone(),
+                        type_name: None,
+                        page_size: huge::page_size_at(&pages, addr),
+                        numa_node: numa::node_at(addr),
+                    }),
+            );
+
+            list.sort_by_key(|info| info.addr);
+            list
+        });
+
+        let user_list = list.to_vec();
+        system_scope(|| drop(list));
+        user_list
+    }
+
+    fn info(&self, addr: usize) -> Option<AllocationInfo> {
+        let info = system_scope(|| {
+            let pages = huge::page_sizes();
+            match STUPID_MAP.handle_of(addr) {
+                Some(handle) => Some(handle.info(handle.start, &pages)),
+                None => viewer::ATTACHED
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .find(|(&start, attached)| (start..start + attached.map.len()).contains(&addr))
+                    .map(|(&start, attached)| AllocationInfo {
+                        addr: start,
+                        size: attached.map.len(),
+                        align: None,
+                        path: attached.path.clone(),
+                        type_name: None,
+                        page_size: huge::page_size_at(&pages, start),
+                        numa_node: numa::node_at(start),
+                    }),
+            }
+        });
+
+        let user_info = info.clone();
+        system_scope(|| drop(info));
+        user_info
+    }
+
+    fn hexdump(&self, addr: usize) -> Option<String> {
+        // hexdump_of only looks at the address, and a reference to a
+        // zero-sized value may point anywhere but null.
+        let value = unsafe { std::ptr::NonNull::new(addr as *mut ())?.as_ref() };
+        self.hexdump_of(value)
+    }
+
+    fn subscribe(&self, callback: HeapCallback) -> Subscription {
+        let subscription = Subscription(NEXT_SUBSCRIPTION.fetch_add(1, Ordering::SeqCst));
+        // growing the list may well be a stupid resize, which must not be
+        // reported while it's locked.
+        quietly(|| {
+            SUBSCRIBERS
+                .write()
+                .unwrap()
+                .push((subscription, Arc::new(callback)))
+        });
+        SUBSCRIBED.fetch_add(1, Ordering::SeqCst);
+        subscription
+    }
+
+    fn unsubscribe(&self, subscription: Subscription) {
+        // the callback gets dropped once the lock is released, in case
+        // freeing it needs the allocator.
+        let removed = {
+            let mut subscribers = SUBSCRIBERS.write().unwrap();
+            let index = subscribers.iter().position(|(s, _)| *s == subscription);
+            index.map(|index| subscribers.remove(index))
+        };
+        if removed.is_some() {
+            SUBSCRIBED.fetch_sub(1, Ordering::SeqCst);
+        }
+        drop(removed);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{sync::Mutex, time::Duration};
+
+use crate::StupidAlloc;
+
+/// Artificial delays added to every stupid allocation, de-allocation and
+/// resize, on top of the ones the stupid alloc manages on its own. Set with
+/// [`StupidAlloc::set_latency()`].
+///
+/// A delay is a fixed part plus a part proportional to the size of the
+/// allocation, its new size when resized.
+///
+/// ```
+/// use std::time::Duration;
+/// use stupidalloc::Latency;
+///
+/// // 2ms per operation, and 1ms more per kibibyte.
+/// let molasses = Latency::new()
+///     .fixed(Duration::from_millis(2))
+///     .per_kib(Duration::from_millis(1));
+/// assert_eq!(molasses.delay(4096), Duration::from_millis(6));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Latency {
+    fixed: Duration,
+    per_kib: Duration,
+}
+
+impl Latency {
+    /// No delay at all, until told otherwise by the other methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the part of the delay that doesn't depend on the size.
+    pub fn fixed(mut self, delay: Duration) -> Self {
+        self.fixed = delay;
+        self
+    }
+
+    /// Sets the part of the delay that grows with the size, per kibibyte.
+    pub fn per_kib(mut self, delay: Duration) -> Self {
+        self.per_kib = delay;
+        self
+    }
+
+    /// Returns the delay added to an operation on an allocation of `size`
+    /// bytes.
+    pub fn delay(&self, size: usize) -> Duration {
+        let per_byte = self.per_kib.as_nanos() * size as u128 / 1024;
+        self.fixed + Duration::from_nanos(per_byte.try_into().unwrap_or(u64::MAX))
+    }
+}
+
+static LATENCY: Mutex<Option<Latency>> = Mutex::new(None);
+
+// waits as long as the latency says for an allocation of `size` bytes. must not
+// be called with the registry locked, or every other thread would wait too.
+pub fn wait(size: usize) {
+    let latency = *LATENCY.lock().unwrap();
+    if let Some(latency) = latency {
+        std::thread::sleep(latency.delay(size));
+    }
+}
+
+impl StupidAlloc {
+    /// Sets the artificial delay of subsequent stupid allocations,
+    /// de-allocations and resizes, or removes it with [`None`]. Allocating
+    /// is never free, and this makes it feel like it: code that allocates in
+    /// a loop gets painfully slow, while code that allocates everything up
+    /// front barely notices. The registry is not locked while waiting, so
+    /// other threads can keep allocating in the meantime.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use std::time::{Duration, Instant};
+    /// use stupidalloc::{Latency, StupidAlloc};
+    ///
+    /// let start = Instant::now();
+    /// StupidAlloc.set_latency(Some(Latency::new().fixed(Duration::from_millis(50))));
+    /// let stupid_box = Box::new_in(42u32, StupidAlloc);
+    /// StupidAlloc.set_latency(None);
+    ///
+    /// assert!(start.elapsed() >= Duration::from_millis(50));
+    /// ```
+    pub fn set_latency(&self, latency: Option<Latency>) {
+        *LATENCY.lock().unwrap() = latency;
+    }
+}
@@ -18,6 +18,17 @@
 //! let stupid_box = Box::new_in(1u32, StupidAlloc);
 //! ```
 //!
+//! Or, without enabling `allocator_api`, with the [`stupid_box!`] and
+//! [`stupid_vec!`] macros, which also record the type of the allocation and
+//! the line that made it for the logs and the windows:
+//!
+//! ```
+//! use stupidalloc::{stupid_box, stupid_vec};
+//!
+//! let stupid_box = stupid_box!(1u32);
+//! let stupid_vec = stupid_vec![1u32, 2, 3];
+//! ```
+//!
 //! Use the allocator as the global allocator. Warning: funky stuff may happen,
 //! such as allocations before main!
 //! ```
@@ -31,10 +42,23 @@
 //! }
 //! ```
 //!
+//! ## Stable Rust
+//! The `nightly` feature, enabled by default, implements the unstable
+//! [`Allocator`] trait of the standard library. Without it, the crate builds on
+//! stable: the allocator can be used as the global allocator, and implements
+//! the [`Allocator`] trait of the `allocator-api2` crate instead.
+//!
+//! Either way, the API names the types of `allocator-api2`, re-exported as
+//! [`allocator_api2`], like [`StupidBox`] and [`StupidVec`]: `nightly` only
+//! adds the implementations of the trait of the standard library, so crates
+//! that disable it keep building when another one in the same program
+//! enables it.
+//!
 //! ## Interactivty
 //! By default, the allocator will silently and automatically allocate memory
 //! (as you would expect), by opening files in a temporary folder (as dictated
-//! by [`std::env::temp_dir()`]). A feature flag, `interactive`, will enable
+//! by [`std::env::temp_dir()`], in a `stupidalloc` folder, in a folder named
+//! after the id of the process). A feature flag, `interactive`, will enable
 //! confirmation and file picking dialogs to pop up during allocations and
 //! de-allocations. More specifically:
 //! - On allocation, a confirmation message detailling the [`Layout`] needed for
@@ -56,6 +80,13 @@
 //! in memory, while using the right mouse button will clear it. You can thus
 //! directly modify memory using only your mouse, and directly see the results!
 //!
+//! For anything bigger than a flag, click with the middle mouse button on a
+//! byte and type a value, written like a Rust literal, optionally followed by
+//! the byte order: `42`, `-1i16`, `0xdeadbeef u32 be` or `1.5f32`. The title
+//! of the window shows what you type. Enter writes the value starting at that
+//! byte, escape gives up. Integers without a type take as few bytes as they
+//! fit in, floats are `f64`s, and the byte order is little endian by default.
+//!
 //! ### Creation
 //! By default, allocating memory doesn't create a corresponding graphical window.
 //! To create a window, you can use `StupidAlloc::open_window_of()`, and you
@@ -75,11 +106,46 @@
 //!
 //!     // I changed my mind, I want 4 columns instead.
 //!     StupidAlloc.set_columns_of(&*stupid_box, 4);
+//!
+//!     // Whatever fits, really.
+//!     StupidAlloc.set_columns_of(&*stupid_box, StupidAlloc::AUTO_COLUMNS);
 //! }
 //! ```
 //!
+//! When an allocation grows, its window grows with it, and the new bytes show
+//! up one after the other and stay green for a second, so that a `Vec`
+//! doubling its capacity is hard to miss.
+//!
 //! If the `always-graphics` feature is enabled, then every allocation will be
 //! displayed automatically, without the need to call `open_window_of()`.
+//! `StupidAlloc::set_window_filter()` narrows that down to the allocations
+//! that matter, by size, by type or by call site. The windows of allocations
+//! made with `stupid_box!` and `stupid_vec!` show one element per row, like
+//! 4 bytes for a `u32` or 8 for an `f64`, as long as that's between 2 and 16
+//! bytes.
+//!
+//! ### Placement
+//! `StupidAlloc::set_window_placement()` picks the monitor new windows open on,
+//! given as the rectangle it covers on the desktop, and whether they're
+//! centered, cascaded or tiled there. Handy when the windows belong on a
+//! projector.
+//!
+//! ### Tamagotchi mode
+//! `StupidAlloc::set_tamagotchi()` turns every window into a virtual pet: the
+//! title shows the age of the allocation and a few hearts. Allocations that
+//! aren't written to for a while go hungry, lose their hearts and fade away,
+//! and freeing one makes it die, dramatically.
+//!
+//! ### HUD
+//! `StupidAlloc::set_window_hud()` adds a strip to the top of every window,
+//! written in a tiny font of its own: the size, alignment, age and number of
+//! resizes of the allocation, and its type. Clicks on the strip don't change
+//! any bits.
+//!
+//! ### Usage chart
+//! `StupidAlloc::open_usage_chart()` opens one more window, charting the bytes
+//! and the number of live allocations over the last few seconds, with a red
+//! line wherever an allocation at least as big as the given size was made.
 //!
 //! ## Logging
 //! If the `logging` feature is enabled, each allocation will be accompanied by
@@ -88,157 +154,1915 @@
 //! written as the allocation is interacted with:
 //! - Metadata, such as corresponding allocation file, the [`Layout`], ...
 //! - Allocation and deallocation backtraces (requires the `RUST_BACKTRACE`
-//!   environment variable to be set accordingly)
+//!   environment variable to be set accordingly, or a policy set with
+//!   [`StupidAlloc::set_backtraces()`])
 //! - Every grow or shrink, with new [`Layout`] and corresponding backtrace
 //!
+//! Every event comes with a [`Timestamp`]: the time on the wall clock, and the
+//! time since the first stupid allocation, like `+12.345s`, which makes the
+//! logs of several runs easy to line up. The heap map, the report card and the
+//! diagnostics show it too.
+//!
 //! Log files won't be deleted when the corresponding memory is freed, but they
 //! might get overwritten, either by you when using the `interactive` feature
 //! and specifying the same file name as a previous allocation's, or by
 //! subsequent executions of a program that uses this allocator.
 //!
+//! ## Teaching
+//! The `teaching` feature, which enables `logging`, adds plain explanations of
+//! what happens to the logs, and to the dialogs of the `interactive` feature:
+//! what the size and alignment of a [`Layout`] mean, why a `Vec` just doubled
+//! in size, and what reallocating or freeing memory implies for the pointers
+//! to it. `StupidAlloc::set_verbosity()` picks between brief and detailed
+//! explanations, or none at all.
+//!
+//! Whatever the features, [`StupidAlloc::explain_vec()`] and
+//! [`StupidAlloc::explain_string()`] tell which bytes of the buffer of a `Vec`
+//! or a `String` hold its elements, and which ones are spare capacity, len
+//! versus capacity in real memory. With `logging`, the explanation goes to the
+//! log too, and with `graphics`, the window of the buffer draws the spare
+//! capacity in amber.
+//!
+//! ## Guard pages
+//! Calling [`StupidAlloc::set_guard_pages()`] makes every subsequent allocation
+//! be surrounded by inaccessible pages of memory, a bit like Electric Fence.
+//! The data is placed right before the trailing guard page, so that the first
+//! out-of-bounds access makes the program crash instead of quietly corrupting
+//! whatever comes after. The allocation files get bigger accordingly: a page
+//! on each side, and padding before the data.
+//!
+//! ```
+//! #![feature(allocator_api)]
+//! use stupidalloc::StupidAlloc;
+//!
+//! StupidAlloc.set_guard_pages(true);
+//!
+//! let mut stupid_vec = Vec::new_in(StupidAlloc);
+//! stupid_vec.extend_from_slice(&[1u8, 2, 3]);
+//! stupid_vec.push(4);
+//! assert_eq!(stupid_vec, [1, 2, 3, 4]);
+//! ```
+//!
+//! ## Canaries
+//! Calling [`StupidAlloc::set_canaries()`] makes every subsequent allocation
+//! be followed by a known pattern of bytes, the canary. It is checked when the
+//! allocation is resized or de-allocated, and any modification is reported:
+//! something wrote past the end of the data! Unlike guard pages, this doesn't
+//! crash anything, and it catches overruns of a few bytes even when they land
+//! in the padding of a page.
+//!
+//! Graphical windows draw canaries, guard pages and the padding around the
+//! data in red, and make them flash when a corrupted canary is found.
+//!
+//! ## Fill patterns
+//! New allocations are zeroed, like the files they live in. With
+//! [`StupidAlloc::set_fill_pattern()`] they're filled with `0xAA`, `0xCD` or
+//! random bytes instead, so that reading memory before writing it shows in a
+//! hex editor or graphical window, rather than looking like a legitimate zero.
+//!
+//! ## Quarantine
+//! [`StupidAlloc::set_quarantine()`] makes freed allocations stay around for a
+//! while before really being released. Their contents are replaced by a
+//! `0xDE 0xAD` pattern and they're made inaccessible, so that using memory after
+//! freeing it is either a crash or an obvious sight in a hex editor or graphical
+//! window.
+//!
+//! ## Double frees
+//! Freeing memory that was already freed, or that was never allocated by this
+//! allocator, makes the allocator print what it knows about the pointer to the
+//! standard error before aborting the process: where the allocation was made,
+//! where it was first freed, and where the second free happened. The
+//! backtraces follow the `RUST_BACKTRACE` environment variable.
+//!
+//! ## Persistence
+//! Since allocations are files, nothing prevents them from outliving the
+//! program. [`StupidAlloc::persist_of()`] gives a name to an allocation and
+//! keeps its file around, and [`StupidAlloc::restore()`] maps it back, even
+//! in a later run of the program:
+//!
+//! ```
+//! use stupidalloc::StupidAlloc;
+//!
+//! // SAFETY: a u64 is valid for any bit pattern.
+//! let mut runs = unsafe { StupidAlloc.restore_or_persist("doc_runs", 0u64) };
+//! *runs += 1;
+//!
+//! println!("This program ran {runs} times");
+//! ```
+//!
+//! ### Crash recovery
+//! A program that crashes leaves its allocation files behind. When the first
+//! allocation is made, the folders of processes that are not running anymore
+//! are removed, which [`StupidAlloc::cleanup_stale()`] also does on demand.
+//! Setting the `STUPIDALLOC_STALE` environment variable to `keep` leaves them
+//! alone, and setting it to `recover` makes their allocations persistent
+//! instead, for [`StupidAlloc::restore()`] to bring them back, which
+//! [`StupidAlloc::recover_stale()`] also does on demand.
+//!
+//! Allocations take an advisory lock on their files, for other processes to
+//! know not to touch them: [`StupidAlloc::is_file_in_use()`] checks for it,
+//! and so does `stupidctl clean`.
+//!
+//! What each file was only lives in the memory of the program, so it also
+//! goes into a manifest next to the files, see
+//! [`StupidAlloc::manifest_path()`]: a journal of the allocations, resizes,
+//! types and frees, with their addresses and timestamps. `stupidctl manifest`
+//! replays it to tell what the files of a crashed run were, as long as they
+//! are still around: with `STUPIDALLOC_STALE` set to `keep`, or to `recover`,
+//! which moves the manifest along with the files.
+//!
+//! ### Checksums
+//! Files can also be replaced behind the back of their allocation, cut short,
+//! or rot on the disk between two runs. With [`StupidAlloc::set_checksums()`],
+//! flushing remembers a checksum of each allocation, which the next flush and
+//! the free check the file against, and persistent allocations keep theirs
+//! for [`StupidAlloc::restore()`] to check.
+//!
+//! ## Sharing
+//! Files can be opened by other processes too. `StupidAlloc::share_of()`
+//! returns a [`ShareToken`] describing an allocation, which another process
+//! running the stupid alloc can pass to `StupidAlloc::attach()` to map the same
+//! file. Both processes then see each other's writes live.
+//!
+//! To watch over a whole process instead, `StupidAlloc::attach_readonly()`
+//! maps all the allocation files of a folder read-only. They show up in
+//! `StupidAlloc::state()` and can be displayed in windows, without the watched
+//! process having to do anything but use the stupid alloc.
+//!
+//! ## Snapshots
+//! [`StupidAlloc::snapshot_heap()`] copies the contents of every live
+//! allocation into a folder, along with a manifest describing them. Two
+//! snapshots can then be compared with [`Snapshot::diff()`], to see which
+//! allocations appeared, disappeared, or had their bytes changed in between:
+//!
+//! ```
+//! #![feature(allocator_api)]
+//! use stupidalloc::StupidAlloc;
+//!
+//! let mut stupid_box = Box::new_in(1u32, StupidAlloc);
+//! let dir = std::env::temp_dir().join("stupidalloc_doc_snapshots");
+//!
+//! let before = StupidAlloc.snapshot_heap(dir.join("before")).unwrap();
+//! *stupid_box = 2;
+//! let after = StupidAlloc.snapshot_heap(dir.join("after")).unwrap();
+//!
+//! let diff = before.diff(&after).unwrap();
+//! assert!(diff.added.is_empty() && diff.removed.is_empty());
+//! assert_eq!(diff.changed[0].ranges, [0..1]);
+//! ```
+//!
+//! [`StupidAlloc::snapshot_consistent()`] makes allocations and frees wait
+//! while it copies, and only puts the snapshot in place once it's complete, so
+//! that the manifest and the contents always match.
+//!
+//! ## File names
+//! Allocation files are named after a counter by default, which is hard to
+//! tell apart when talking about them. [`StupidAlloc::set_file_naming()`]
+//! picks another [`FileNaming`]: an adjective and an animal, like
+//! `brave-otter.mem`, or the size of the allocation in emoji.
+//!
+//! ## Heap map
+//! For a look at the heap without windows, [`StupidAlloc::print_heap_map()`]
+//! prints every live allocation on a line of its own, with a bar showing its
+//! size, its type and its file. On Unix platforms,
+//! `StupidAlloc::set_heap_map_at_exit()` prints it when the program exits, to
+//! show what was never freed, in a terminal or in CI logs.
+//!
+//! For a look at the heap as a whole, [`StupidAlloc::save_heap_overview()`]
+//! draws the bits of every live allocation in a picture. With the `wallpaper`
+//! feature, `StupidAlloc::set_wallpaper()` makes that picture the desktop
+//! wallpaper, updated every few seconds, so that the heap is always there
+//! behind your editor. [`StupidAlloc::set_overview_age_scale()`] dims
+//! allocations as they age, so that the long-lived ones stand out from the
+//! churn.
+//!
+//! ## Report card
+//! The allocator keeps count of everything it did: [`StupidAlloc::totals()`]
+//! has the number of allocations, frees and resizes since the program
+//! started, along with the largest allocation, the most resized one and the
+//! longest-lived one. Those counters unlock silly
+//! [achievements](StupidAlloc::achievements()), and
+//! [`StupidAlloc::print_report_card()`] prints them all. On Unix platforms,
+//! `StupidAlloc::set_report_card_at_exit()` prints it when the program exits.
+//!
+//! The longest-lived allocation is one thing, the ones that barely lived are
+//! another: [`StupidAlloc::lifetime_report()`] tells how long freed
+//! allocations lived, at the 50th, 90th and 99th percentiles, overall and by
+//! call site, and points out the call sites that would be better off with the
+//! stack or an [`Arena`].
+//!
+//! Allocations that never stop growing are no better:
+//! [`StupidAlloc::churn_report()`] finds the ones that were resized more than
+//! a given number of times, which is what a `Vec` filled without reserving
+//! its capacity does, and tells where they were made. With the `logging`
+//! feature, `StupidAlloc::set_churn_warning()` writes a warning in their logs
+//! as it happens.
+//!
+//! ## Pointer graphs
+//! [`StupidAlloc::export_graph()`] looks for pointers to allocations inside of
+//! every live allocation, and writes the graph they form to a file, for
+//! Graphviz or Mermaid. A linked list shows up as a chain of boxes, and an
+//! `Rc` cycle as, well, a cycle.
+//!
+//! The same pointers make for a leak detector: [`StupidAlloc::gc_report()`]
+//! marks the allocations reachable from a few roots, the way a conservative
+//! garbage collector would, and reports the other ones as likely leaks. On
+//! Linux, `StupidAlloc::gc_report_with_stack()` takes the stack of the
+//! calling thread as roots too. With the `graphics` feature,
+//! `StupidAlloc::set_gc_tint()` draws the windows of the leaks in red.
+//!
+//! ## Contents
+//! Allocations being files, their contents are there for the taking.
+//! [`StupidAlloc::dedup_report()`] finds the allocations holding the exact same
+//! bytes, and how much they waste, to answer "why do I have 500 copies of the
+//! same string?".
+//!
+//! For a single allocation, [`StupidAlloc::analyze_of()`] counts its bytes,
+//! measures their entropy, and guesses what they are: zeroes, text, floats,
+//! pointers, or anything else. With the `graphics` feature,
+//! `StupidAlloc::set_window_histogram()` draws the counts over the bits of
+//! every window.
+//!
+//! [`StupidAlloc::strings_of()`] pulls the printable strings out of an
+//! allocation, like `strings` does for files, and
+//! [`StupidAlloc::pointers_of()`] the pointers it holds to other allocations,
+//! the same ones the pointer graph is made of.
+//!
+//! The other way around, [`StupidAlloc::search()`] looks for bytes in every
+//! live allocation, and `StupidAlloc::search_regex()` for text matching a
+//! regular expression with the `regex` feature, to answer "which allocation
+//! holds this string?" from the memory itself, rather than from files that may
+//! not be up to date.
+//!
+//! ## Bug reports
+//! [`StupidAlloc::export_bundle()`] writes all of the above into a folder, in
+//! files made for scripts rather than people: the statistics as JSON, and the
+//! allocations, the pointers between them and a few histograms as CSV, with
+//! hexdumps of every allocation if asked. Attach it to the bug report.
+//!
+//! Bundles of two runs, or their manifests, can be compared with
+//! [`StupidAlloc::compare_runs()`], or `stupidctl compare`: the allocations
+//! one run has and the other doesn't, by call site. That's how "this version
+//! leaks 40 more buffers from `parser.rs`" gets found.
+//!
+//! ## Watching
+//! Since allocations are files, other programs can modify them. Enabling the
+//! `watch` feature makes the allocator notice when that happens: the edit is
+//! recorded in the log file (with the `logging` feature), and a callback can be
+//! set with `StupidAlloc::on_external_edit()`. Graphical windows display the
+//! new contents of the memory as soon as the edit is done.
+//!
+//! The other way around, on Unix platforms, [`StupidAlloc::stream_of()`]
+//! streams the contents of an allocation to a named pipe as they change, for
+//! scripts and tools to follow a buffer live without linking against anything.
+//!
+//! ## Time travel
+//! With the `time-travel` feature, a copy of the contents of an allocation is
+//! saved as a new version every time it gets resized or flushed.
+//! `StupidAlloc::rewind_of()` writes an old version back into memory, and the
+//! left and right arrow keys step through the versions in graphical windows.
+//!
+//! ## Bit mode
+//! One file per allocation is a bit much, but not quite enough. With the
+//! `bit-mode` feature, every bit of every allocation gets a file of its own,
+//! holding `0` or `1`, in a folder next to the allocation file:
+//! `alloc_N.mem.bits/0000000003.7` is the most significant bit of the fourth
+//! byte. The bits are written when the allocation is made, resized or flushed
+//! with [`StupidAlloc::flush_of()`], and they are as up to date as the memory
+//! right after that. Needless to say, don't.
+//!
+//! ## Valgrind
+//! Memcheck has no idea that memory-mapped files are allocations. With the
+//! `valgrind` feature, the allocator tells it, so that allocations that are
+//! never freed show up as leaks, and accesses past the end of the data or
+//! after the allocation was freed get reported. Outside of Valgrind, this does
+//! nothing.
+//!
+//! ## Fault injection
+//! Running out of memory is rare enough that the code handling it is rarely
+//! tested. [`StupidAlloc::set_fault_injection()`] makes allocations fail on
+//! purpose: every `n`th one, the ones of some sizes or made from some
+//! function, or at random, as described by a [`FaultInjection`].
+//!
+//! ## Budgets
+//! Every allocation takes a file, and disks fill up.
+//! [`StupidAlloc::set_budget()`] limits the bytes and the number of the live
+//! allocations, and [`StupidAlloc::set_thread_budget()`] those of a single
+//! thread: allocations over a [`Budget`] go to System or fail, and the error
+//! handler hears of it.
+//!
+//! ## Latency
+//! Allocations backed by files are slow, but not slow enough to make a point
+//! in a demo. [`StupidAlloc::set_latency()`] adds a delay to every stupid
+//! allocation, de-allocation and resize, fixed or proportional to the size of
+//! the allocation, as described by a [`Latency`].
+//!
+//! ## Paging
+//! The memory of an allocation is paged in from its file, and out to it.
+//! [`StupidAlloc::pin_of()`] keeps it in RAM, and [`StupidAlloc::advise_of()`]
+//! hints the OS about what comes next with `madvise`: evicting the pages with
+//! [`Advice::PageOut`] makes the next read go to the file, which shows in the
+//! timings. Shrinking an allocation drops the pages past its new end.
+//! [`StupidAlloc::residency_of()`] tells which pages are in RAM at the moment,
+//! and the graphical windows draw the other ones in blue stripes, without
+//! reading them back.
+//!
+//! Big allocations can get huge pages with [`StupidAlloc::set_huge_pages()`],
+//! either transparent ones asked for with `madvise`, or files on a `hugetlbfs`
+//! mount, as described by [`HugePages`]. [`AllocationInfo::page_size`] tells
+//! what the OS actually gave them.
+//!
+//! On machines with several NUMA nodes, [`StupidAlloc::numa_node_of()`] tells
+//! which one the memory of an allocation ended up on, and
+//! [`StupidAlloc::bind_of()`] moves it to another one, to see for yourself
+//! what it does to the timings.
+//!
+//! [`StupidAlloc::profile_accesses_of()`] samples which pages of an allocation
+//! the program accesses over time, by making them inaccessible every now and
+//! then and counting the faults, like an [`AccessProfiler`] says.
+//! [`StupidAlloc::access_profile_of()`] returns the samples, which print as a
+//! heatmap.
+//!
+//! ## Morse code
+//! With the `morse` feature, `StupidAlloc::set_morse()` beeps the size of big
+//! allocations in Morse code on the terminal bell, or on anything else that
+//! can make a sound, as described by a `Morse`. You'll never need to look at
+//! the logs again, provided you can read Morse code.
+//!
+//! ## Heap music
+//! With the `midi` feature, `StupidAlloc::set_midi()` turns the allocator into
+//! an instrument: every allocation plays a note until it's freed, as raw MIDI
+//! written to a device, or as OSC messages sent over UDP. Big allocations play
+//! higher notes, strongly aligned ones louder, and every thread gets a channel
+//! of its own.
+//!
+//! ## Pass-through
+//! Files are slow. With `StupidAlloc::set_pass_through()`, the memory of new
+//! allocations comes from another allocator (like [`System`]) instead, while
+//! everything else keeps working: logs, windows, canaries, the quarantine,
+//! hexdumps and snapshots. Pass-through allocations have no file, so they
+//! can't be persisted nor watched, and don't get guard pages.
+//!
+//! On Windows, [`SharedMemory`] makes a fine allocator for this: the memory is
+//! named shared memory, which other processes can open by name, without a file
+//! that Windows would refuse to remove while it's mapped.
+//!
+//! ### Fake allocations
+//! With the `fake` feature, every allocation is a pass-through allocation
+//! served by [`System`]: no files, no memory maps, but the registry, the logs,
+//! and everything that looks at the state keep working. Crates using the
+//! stupid alloc in their examples and tests can then run them in sandboxed CI,
+//! or under Miri, which gets fake allocations without asking since it can't
+//! map files. Logging still needs a folder to write to. Whatever is about the
+//! files, like [`StupidAlloc::file_of()`], finds none, and the examples of
+//! this documentation that need them are not run.
+//!
+//! If it's creating the files that is too slow, `StupidAlloc::fill_file_pool()`
+//! creates a bunch of them ahead of time, for new allocations to claim.
+//!
+//! ## C API
+//! Rust programs shouldn't have all the fun. With the `c-api` feature, the
+//! crate exports `stupid_malloc`, `stupid_calloc`, `stupid_realloc`,
+//! `stupid_free` and `stupid_state_json` from its `cdylib`, built with
+//! `cargo rustc --lib --crate-type cdylib --features c-api`, for C and C++
+//! programs to link. See the [`capi`] module.
+//!
+//! On Linux, the `preload` feature goes one step further and replaces `malloc`
+//! itself, for programs started with `LD_PRELOAD`. See the [`preload`] module.
+//!
+//! The `python` feature exposes the inspection functions to Python instead, to
+//! look at allocations from a notebook. See the [`python`] module.
+//!
+//! ## Control socket
+//! On Unix platforms, `StupidAlloc::start_control_socket()` listens for
+//! commands from other programs on a Unix domain socket: listing allocations,
+//! dumping them, opening windows for them, taking snapshots and turning the
+//! stupid alloc off and on, while the program runs.
+//!
+//! The crate comes with `stupidctl`, a command line tool speaking to that
+//! socket. It also works on the allocation folder directly: listing, dumping
+//! and drawing allocation files, following their logs, and cleaning up after
+//! programs that crashed without removing their files.
+//!
+//! ## Inspection
+//! Tools that look at the heap from the inside, like a debugger or a viewer of
+//! their own, can be written against the [`HeapInspector`] trait instead of
+//! the methods of `StupidAlloc`: listing the allocations, describing and
+//! dumping them, and subscribing to allocations, frees and resizes as they
+//! happen.
+//!
+//! Viewers running somewhere else can speak the protocol of the `protocol`
+//! feature: a handshake settling the version and the capabilities of each
+//! end, then heap events and chunks of allocation contents, as serde types.
+//!
+//! ## Arenas
+//! A flat pile of files gets confusing quickly. `StupidAlloc::arena()` creates
+//! an [`Arena`], an allocator whose allocation files go to a folder of their
+//! own. Its allocations can be counted with [`Arena::stats()`] and displayed
+//! together, and leaked ones are freed when the arena is dropped.
+//!
+//! A [`StupidScope`] goes one step further, like a region allocator: freeing
+//! its allocations does nothing, and they are all freed at once when the scope
+//! is dropped.
+//!
 //! ## Multi-threading
-//! Internally, the allocator uses a [`RwLock`] when allocating and de-allocating.
-//! As such, using this in a multi-threaded context will yield even more awful
-//! performance. Performance is not the goal, but be warned nonetheless.
-
-#![feature(allocator_api)]
-#![feature(ptr_metadata)]
-#![feature(doc_cfg)]
+//! Internally, the allocator keeps track of allocations in a registry split in
+//! shards, each behind its own [`RwLock`](std::sync::RwLock), along with an
+//! index to find the allocation a pointer belongs to. As such, using this in a
+//! multi-threaded context will yield even more awful performance. Performance
+//! is not the goal, but be warned nonetheless.
+//!
+//! The files of freed allocations are removed by a background thread, so they
+//! can linger for a moment. `StupidAlloc::flush_reaper()` waits until they're
+//! all gone.
+//!
+//! When the program exits, [`StupidAlloc::shut_down()`] closes the windows,
+//! stops the background threads and waits for the files to be removed. Frees
+//! that come after `main`, from the destructors of thread locals for example,
+//! then remove their files on the spot, and allocations go to System, with a
+//! line on the standard error the first time. This happens on its own on Unix
+//! platforms, elsewhere it's up to the end of `main`. [`StupidAlloc::phase()`]
+//! tells whether it happened already.
+//!
+//! [`StupidAlloc::freeze_world()`] stops every other thread at its next
+//! stupid allocation, free or resize until [`StupidAlloc::thaw_world()`], for
+//! a look at a heap that holds still.
+//!
+//! Only the first thread that allocates uses the stupid alloc by default, the
+//! others go to System until they call [`StupidAlloc::enable_in_thread()`].
+//! [`StupidAlloc::set_new_thread_default()`] makes new threads stupid from the
+//! start instead, and [`StupidAlloc::spawn_stupid()`] spawns a single one.
+//! Stupid allocations can be freed from any thread.
+//! [`StupidAlloc::fallbacks()`] counts the allocations that went to System,
+//! and why.
+//! [`StupidAlloc::set_thread_dirs()`] gives each thread a folder of its own,
+//! and groups the allocations by thread in the heap overview.
+//!
+//! ## WASI
+//! The crate builds for `wasm32-wasip1`, for the demos to run in `wasmtime`
+//! and in browser playgrounds. WASI has no memory maps: the memory of each
+//! allocation is a copy of its file instead, read when the allocation is made
+//! and written back to the file when it's flushed, with
+//! [`StupidAlloc::flush_all()`] for example. WASI has no temp folder either,
+//! so the files go to the folder in `TMPDIR`, or `/tmp`, which the runtime must
+//! give access to:
+//!
+//! ```sh
+//! cargo build --target wasm32-wasip1 --no-default-features --features logging
+//! wasmtime --dir /tmp target/wasm32-wasip1/debug/my_demo.wasm
+//! ```
+//!
+//! Logging and looking at the state work as usual. There are no windows, so
+//! the `graphics` feature does nothing, and no threads, so the files of freed
+//! allocations are removed on the spot.
+//!
+//! ## Forking
+//! On Unix platforms, a forked child would share the memory maps of its
+//! parent, and both would write to the same files, then remove them twice.
+//! Instead, the child gets a copy of every allocation in a file of its own,
+//! mapped at the same address so that pointers stay valid. These files are
+//! removed right away, since processes tend to call `exec` right after forking,
+//! and would leave them behind forever. Only the thread that forked makes it to
+//! the child: the files of its freed allocations still get removed, but the
+//! likes of the control socket and the wallpaper stay with the parent.
+//!
+//! ## Diagnostics
+//! [`StupidAlloc::diagnostics()`] tells where the files go, which features
+//! and backend are in use, whether the current thread uses the stupid alloc
+//! at all, and how many files and how much disk space are left. Setting the
+//! `STUPIDALLOC_BANNER` environment variable to `1`, or calling
+//! [`StupidAlloc::set_banner_at_startup()`], prints it all at the first
+//! allocation.
+//!
+//! ## Errors
+//! Creating, resizing and mapping files can fail, and an allocator has no way
+//! to say why: the allocation fails, which usually aborts the program with a
+//! laconic "memory allocation failed". The reason goes to the handler set
+//! with [`StupidAlloc::set_error_handler()`], and is kept around for
+//! [`StupidAlloc::last_error()`], as a [`StupidError`]. Failing to log or to
+//! open a window doesn't fail the allocation, but is reported all the same.
+
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+#![cfg_attr(feature = "nightly", feature(doc_cfg))]
 #![warn(missing_docs)]
 
+use backing::{Backing, InnerAlloc, PASS_THROUGH};
+use bench::Operation;
 use core::fmt;
-use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
+use hashbrown::HashMap;
 use lazy_static::lazy_static;
+#[cfg(not(target_os = "wasi"))]
 use memmap2::{MmapMut, MmapOptions};
+use registry::Registry;
 use std::{
-    alloc::{AllocError, Allocator, GlobalAlloc, Layout, System},
-    fs::{File, OpenOptions},
+    alloc::{GlobalAlloc, Layout, System},
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fs::{File, OpenOptions, TryLockError},
+    panic::Location,
     path::PathBuf,
     ptr::NonNull,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc, Once, RwLock,
+        Arc, Mutex, Once,
     },
 };
+#[cfg(target_os = "wasi")]
+use wasi::{MmapMut, MmapOptions};
+
+// the allocator api of allocator-api2, whatever the features, so that the
+// public types don't change with them. see nightly.rs for the one of std.
+use allocator_api2::alloc::{AllocError, Allocator};
 
 #[cfg(feature = "interactive")]
 use native_dialog::{FileDialog, MessageDialog, MessageType};
 
 #[cfg(feature = "logging")]
-use std::{backtrace::Backtrace, io::Write};
-
+use std::io::Write;
+
+use std::time::Instant;
+
+mod access;
+mod achievements;
+mod advice;
+mod analysis;
+mod arena;
+mod backing;
+mod backtraces;
+mod batch;
+pub mod bench;
+#[cfg(feature = "bit-mode")]
+mod bits;
+mod budget;
+mod bundle;
+#[cfg(feature = "c-api")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "c-api")))]
+pub mod capi;
+#[cfg(feature = "graphics")]
+mod chart;
+mod checksum;
+mod churn;
+mod clock;
+#[cfg(unix)]
+mod control;
+mod dedup;
+mod diagnostics;
+#[cfg(all(feature = "graphics", not(target_os = "wasi")))]
+mod edit;
+mod error;
+mod explain;
+mod fault;
+mod fill;
+#[cfg(unix)]
+mod fork;
+mod freeze;
+mod gc;
+mod graph;
 #[cfg(feature = "graphics")]
+#[cfg_attr(target_os = "wasi", path = "graphics_stub.rs")]
 mod graphics;
+mod harvest;
+mod heapmap;
+#[cfg(feature = "time-travel")]
+mod history;
+#[cfg(feature = "graphics")]
+mod hud;
+mod huge;
+mod inspect;
+mod latency;
+mod lifetimes;
+mod manifest;
+#[cfg(feature = "midi")]
+mod midi;
+#[cfg(feature = "morse")]
+mod morse;
+mod naming;
+#[cfg(feature = "nightly")]
+mod nightly;
+mod numa;
+mod overview;
+mod persist;
+#[cfg(feature = "graphics")]
+mod placement;
+mod pool;
+#[cfg(all(feature = "preload", target_os = "linux", target_env = "gnu"))]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "preload")))]
+pub mod preload;
+#[cfg(feature = "protocol")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "protocol")))]
+pub mod protocol;
+#[cfg(feature = "python")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "python")))]
+pub mod python;
+mod random;
+mod reaper;
+mod reflink;
+mod registry;
+mod residency;
+mod resizes;
+mod runs;
+mod scope;
+mod search;
+mod share;
+#[cfg(windows)]
+mod shm;
+mod shutdown;
+mod snapshot;
+mod stale;
+mod stats;
+mod stream;
+#[cfg(feature = "graphics")]
+mod tamagotchi;
+#[cfg(feature = "teaching")]
+mod teaching;
+mod threads;
+mod typed;
+mod user_data;
+#[cfg(feature = "valgrind")]
+mod valgrind;
+mod viewer;
+#[cfg(feature = "wallpaper")]
+mod wallpaper;
+#[cfg(target_os = "wasi")]
+mod wasi;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "always-graphics")]
+mod window_filter;
+
+pub use access::{AccessProfile, AccessProfiler};
+pub use achievements::Achievement;
+pub use advice::Advice;
+pub use allocator_api2;
+pub use analysis::{Analysis, ContentKind};
+pub use arena::Arena;
+pub use backtraces::Backtraces;
+pub use budget::{Budget, BudgetAction};
+pub use churn::{ChurnReport, ChurnSite};
+pub use clock::Timestamp;
+pub use dedup::{DedupReport, DuplicateGroup};
+pub use diagnostics::{Backend, Diagnostics};
+pub use error::StupidError;
+pub use explain::VecLike;
+pub use fault::FaultInjection;
+pub use fill::FillPattern;
+pub use gc::{GcReport, Leak};
+pub use harvest::HeapPointer;
+pub use huge::HugePages;
+pub use inspect::{AllocationInfo, HeapCallback, HeapEvent, HeapInspector, Subscription};
+pub use latency::Latency;
+pub use lifetimes::{LifetimeReport, Lifetimes, Percentiles, SiteLifetimes};
+pub use manifest::ManifestEntry;
+#[cfg(feature = "midi")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "midi")))]
+pub use midi::Midi;
+#[cfg(feature = "morse")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "morse")))]
+pub use morse::Morse;
+pub use naming::FileNaming;
+#[cfg(feature = "graphics")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+pub use placement::{WindowLayout, WindowPlacement};
+pub use reflink::CopyMethod;
+pub use resizes::ResizeRecord;
+pub use runs::{RunDiff, SiteDiff};
+pub use scope::StupidScope;
+pub use search::SearchHit;
+pub use share::ShareToken;
+#[cfg(windows)]
+#[cfg_attr(feature = "nightly", doc(cfg(windows)))]
+pub use shm::SharedMemory;
+pub use shutdown::Phase;
+pub use snapshot::{ChangedAllocation, Snapshot, SnapshotAllocation, SnapshotDiff};
+pub use stats::{Fallbacks, Stats, Totals};
+#[cfg(feature = "teaching")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "teaching")))]
+pub use teaching::Verbosity;
+pub use threads::ThreadDefault;
+pub use typed::{StupidBox, StupidVec};
+pub use user_data::UserData;
+#[cfg(feature = "wallpaper")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "wallpaper")))]
+pub use wallpaper::Wallpaper;
+#[cfg(feature = "always-graphics")]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "always-graphics")))]
+pub use window_filter::WindowFilter;
+
+#[doc(hidden)]
+pub use typed::{new_box, new_vec, vec_from_array, vec_from_elem};
 
 // tuples are so 2016 let's use a struct instead
 struct AllocHandle {
-    // memory map of the data
-    map: MmapMut,
+    // memory map of the data, or memory from another allocator in
+    // pass-through mode
+    map: Backing,
     // we use an arc so that we can share the handle with the graphical display
-    // thread.
-    file: Arc<File, System>,
+    // thread. none in pass-through mode, there is no file, and when files are
+    // closed after mapping.
+    file: Option<Arc<File>>,
     // the path to the data-holding file.
     path: PathBuf,
+    // the layout of the data, as requested by the last allocation or resize.
+    layout: Layout,
+    // the safety padding around the data.
+    redzones: Redzones,
+    // where the allocation was made, to explain what went wrong when it gets
+    // freed twice.
+    backtrace: Backtrace,
+    // whether the file must be kept when the allocation is freed.
+    persistent: bool,
+    // whether the data is locked in RAM.
+    pinned: bool,
+    // the type of the data, when it was allocated through a typed entry point.
+    type_name: Option<&'static str>,
+    // the line of code that called the typed entry point, which is a lot
+    // cheaper than a backtrace.
+    location: Option<&'static Location<'static>>,
+    // the id of the arena the allocation belongs to, if any.
+    arena: Option<usize>,
+    // the number of the thread that made the allocation.
+    thread: usize,
+    // when the allocation was made, and the number of times it was resized
+    // since.
+    created: Instant,
+    resizes: usize,
+    // every resize so far, oldest first.
+    history: Vec<resizes::Resize>,
+    // whatever the program attached to the allocation.
+    user_data: Option<UserData>,
+    // the number of versions of the data saved so far.
+    #[cfg(feature = "time-travel")]
+    versions: usize,
+    // the checksum of the data at the last flush, if checksums were enabled.
+    checksum: Option<u64>,
     // the thread handle to the graphics thread, if enabled
     #[cfg(feature = "graphics")]
     window: Option<graphics::Window>,
     // the file handle of the logging file
     #[cfg(feature = "logging")]
     log_file: File,
+    // the last time the allocator itself modified the file
+    #[cfg(feature = "watch")]
+    touched: Instant,
+    // whether another program was heard modifying the file.
+    #[cfg(feature = "watch")]
+    edited: bool,
+}
+
+impl AllocHandle {
+    // where the data starts inside of the memory map. only nonzero if the
+    // allocation is surrounded by guard pages.
+    fn offset(&self) -> usize {
+        mapping_layout(self.layout, self.redzones).1
+    }
+
+    // whether the data lives in a file, rather than in memory from another
+    // allocator.
+    fn has_file(&self) -> bool {
+        self.map.map().is_some()
+    }
+
+    // the allocation file, opened again by path if it was closed after
+    // mapping. must be called in system_scope, and only if has_file.
+    fn file(&self) -> std::io::Result<Arc<File>> {
+        match &self.file {
+            Some(file) => Ok(Arc::clone(file)),
+            None => OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.path)
+                .map(Arc::new),
+        }
+    }
+
+    // makes sure the file is up to date with the contents of the data.
+    fn flush(&self) -> std::io::Result<()> {
+        // the flushed pages go beyond the data, and memcheck checks them all.
+        #[cfg(feature = "valgrind")]
+        self.valgrind_expose();
+
+        let result = self.map.flush_range(self.offset(), self.layout.size());
+
+        #[cfg(feature = "valgrind")]
+        self.valgrind_hide();
+
+        #[cfg(feature = "bit-mode")]
+        let result = result.and_then(|()| self.write_bits(self.layout.size()));
+
+        result
+    }
+
+    // appends a line to the log of the allocation. losing a line is not worth
+    // failing over, it only gets reported. must be called in system_scope.
+    #[cfg(feature = "logging")]
+    fn log(&mut self, line: fmt::Arguments) {
+        if let Err(e) = writeln!(self.log_file, "{line}") {
+            let _ = error::check::<()>(Err(e), "write to the log", &self.path.with_extension("md"));
+        }
+    }
+
+    // what a graphical window of this allocation should look at.
+    #[cfg(feature = "graphics")]
+    fn window_source(&self) -> graphics::Source {
+        match self.map.view() {
+            Some(view) => graphics::Source::Memory(Arc::clone(view)),
+            None => graphics::Source::File(self.file().unwrap()),
+        }
+    }
+
+    // what the hud of the window of the allocation shows.
+    #[cfg(feature = "graphics")]
+    fn hud_facts(&self) -> hud::Facts {
+        hud::Facts {
+            layout: Some(self.layout),
+            resizes: self.resizes,
+            offset: self.offset(),
+            node: None,
+        }
+    }
+
+    // opens a window for the allocation, unless one is already open.
+    #[cfg(feature = "graphics")]
+    fn open_window(&mut self, columns: usize) {
+        // no window yet, or the previous one was closed. no new ones once the
+        // stupid alloc has shut down, nobody would close them.
+        if self.window.as_ref().is_none_or(|w| w.is_finished()) && !shutdown::late() {
+            self.window = Some(bench::measure(Operation::Graphics, || {
+                graphics::Window::new(
+                    &self.path,
+                    self.type_name,
+                    self.location,
+                    self.window_source(),
+                    columns,
+                    self.created,
+                    self.hud_facts(),
+                )
+            }));
+        }
+    }
+
+    // locks the data in RAM, or lets it go back to being paged out.
+    #[allow(unused_variables)]
+    fn set_pinned(&mut self, pinned: bool) -> std::io::Result<()> {
+        #[cfg(unix)]
+        {
+            // mlock wants a page-aligned start.
+            let offset = self.offset();
+            let start = offset / page_size() * page_size();
+            let addr = unsafe { self.map.as_ptr().add(start) } as *const libc::c_void;
+            let len = offset - start + self.layout.size();
+
+            let result = if pinned {
+                unsafe { libc::mlock(addr, len) }
+            } else {
+                unsafe { libc::munlock(addr, len) }
+            };
+
+            if result != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            self.pinned = pinned;
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    // fills the data with poison and makes the whole map inaccessible, so
+    // that any use-after-free either crashes or shows an obvious pattern.
+    fn poison(&mut self) {
+        // memcheck was told the data was freed already.
+        #[cfg(feature = "valgrind")]
+        self.valgrind_expose();
+
+        let offset = self.offset();
+        self.map[offset..offset + self.layout.size()]
+            .iter_mut()
+            .zip(POISON.iter().cycle())
+            .for_each(|(b, p)| *b = *p);
+
+        // memory from another allocator is not ours to protect.
+        #[cfg(unix)]
+        if let Some(map) = self.map.map() {
+            // SAFETY: the map is page-aligned, and nobody is supposed to use
+            // it anymore.
+            unsafe {
+                libc::mprotect(
+                    map.as_ptr() as *mut libc::c_void,
+                    map.len(),
+                    libc::PROT_NONE,
+                );
+            }
+        }
+    }
+
+    // gets rid of the memory, the window and the file for good. this needs to
+    // be called while DEALLOCATING is nonzero.
+    fn release(self) {
+        // pass-through allocations have no file to remove.
+        let has_file = self.has_file();
+        let remove_file = has_file && !self.persistent;
+
+        // the maps of wasi are only copies, which must make it to the files
+        // that are kept.
+        #[cfg(target_os = "wasi")]
+        if self.persistent && self.has_file() {
+            let _ = self.flush();
+        }
+
+        #[cfg(feature = "time-travel")]
+        self.remove_versions();
+
+        #[cfg(feature = "bit-mode")]
+        if remove_file {
+            self.remove_bits();
+        }
+
+        // close graphical window
+        #[cfg(feature = "graphics")]
+        {
+            // if there is a window, we need to destroy that first. windows
+            // are closed by the shutdown, and whatever window is left after
+            // that can't be joined anymore: its thread may have been killed
+            // without its consent, and join would panic.
+            if let Some(window) = self.window {
+                if shutdown::late() {
+                    window.abandon();
+                } else {
+                    bench::measure(Operation::Graphics, || window.free());
+                }
+            }
+        }
+
+        drop(self.map); // the map needs to be dropped first
+        drop(self.file); // and then afterwards the file handle
+
+        // dropped once the free is over, see user_data.rs.
+        if let Some(data) = self.user_data {
+            user_data::orphan(data);
+        }
+
+        //std::thread::sleep(std::time::Duration::from_millis(1000));
+
+        // this needs to be done during a time where DEALLOCATING is true,
+        // since it allocates and you'd end up in an infinite recursion.
+        #[cfg(feature = "watch")]
+        watch::unwatch(&self.path);
+
+        if has_file {
+            manifest::freed(&self.path);
+        }
+
+        if remove_file {
+            reaper::remove(self.path);
+        }
+    }
+
+    // checks that the canary after the data is still intact, and complains
+    // loudly otherwise. `addr` is the address of the data.
+    fn check_canary(&mut self, addr: usize) {
+        if !self.redzones.canaries {
+            return;
+        }
+
+        let start = self.offset() + self.layout.size();
+        let canary = &self.map[start..start + CANARY.len()];
+
+        if canary != CANARY {
+            eprintln!(
+                "stupidalloc: canary of allocation 0x{addr:08x} ({}) was corrupted, something wrote past its end!",
+                self.path.to_string_lossy()
+            );
+
+            #[cfg(feature = "logging")]
+            let logged = writeln!(
+                self.log_file,
+                "## Canary corrupted\nSomething wrote past the end of the data (see the allocation backtrace above). Expected:\n```\n{}```\nFound:\n```\n{}```\nDetected at:\n```\n{}\n```\n",
+                HexDump { bytes: &CANARY, addr: addr + self.layout.size() },
+                HexDump { bytes: canary, addr: addr + self.layout.size() },
+                Backtrace::capture()
+            );
+            #[cfg(feature = "logging")]
+            let _ = error::check(logged, "write to the log", &self.path.with_extension("md"));
+
+            #[cfg(feature = "graphics")]
+            if let Some(window) = self.window.as_ref() {
+                let _ = window.tx.send(graphics::Message::Alarm);
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref STUPID_MAP: Registry = Registry::new();
+
+    // the last freed allocations, oldest first.
+    // like everything else in the handles, their contents only ever get
+    // allocated and freed while DEALLOCATING is nonzero.
+    static ref FREED: Mutex<VecDeque<Freed>> = Mutex::new(VecDeque::new());
+
+    // freed allocations that are not released yet, oldest first.
+    static ref QUARANTINE: Mutex<VecDeque<AllocHandle>> = Mutex::new(VecDeque::new());
+}
+
+// these are thread_local because they must not interfere with other threads.
+thread_local! {
+    // currently allocating? nonzero = yes.
+    static ALLOCATING: AtomicUsize = const { AtomicUsize::new(0) };
+    // currently de-allocating? nonzero = yes.
+    static DEALLOCATING: AtomicUsize = const { AtomicUsize::new(0) };
+    // thread-local inhibition boolean, true = use system.
+    static LOCAL_SWITCH_OFF: AtomicBool = {
+        // if init was completed, current thread is not main thread, disabling
+        // by default unless told otherwise. the threads of the stupid alloc
+        // itself run in system_scope, so that, when using `always-graphics`,
+        // their internals don't get allocated in recursion.
+        if INIT_DETECTOR.is_completed() {
+            AtomicBool::new(!threads::new_threads_enabled())
+        } else {
+            // the init once was not called, so this is main thread (or more
+            // generally the first thread that tries to use stupid alloc). allowing
+            // stupid alloc by default.
+            INIT_DETECTOR.call_once(|| {});
+            AtomicBool::new(false)
+        }
+    }
+}
+
+// if this Once is not initialized, this means we are between program entry point
+// and the first access to LOCAL_SWITCH_OFF (aka first stupid allocation).
+static INIT_DETECTOR: Once = Once::new();
+
+// the first thread to look at its switch becomes the main thread, so this must
+// be called before spawning a thread of our own, or it could steal the title.
+fn claim_main_thread() {
+    let _ = LOCAL_SWITCH_OFF.try_with(|_| ());
+}
+
+// whether a stupid allocation or de-allocation is in progress in this thread.
+// threads being torn down can't tell anymore, so they count as busy: the c api
+// gets called from threads rust knows nothing about, at any time.
+fn busy() -> bool {
+    ALLOCATING
+        .try_with(|a| a.load(Ordering::SeqCst) != 0)
+        .unwrap_or(true)
+        || DEALLOCATING
+            .try_with(|d| d.load(Ordering::SeqCst) != 0)
+            .unwrap_or(true)
+}
+
+// whether the stupid alloc must stay out of it and let System do the job,
+// because it's busy or disabled in this thread.
+fn use_system() -> bool {
+    busy()
+        || LOCAL_SWITCH_OFF
+            .try_with(|l| l.load(Ordering::SeqCst))
+            .unwrap_or(true)
+}
+
+// runs `f` as if an allocation was in progress, so that everything `f`
+// allocates comes from System. nothing allocated in there may be freed outside
+// of such a scope, or be handed to the user!
+fn system_scope<R>(f: impl FnOnce() -> R) -> R {
+    ALLOCATING.with(|a| a.fetch_add(1, Ordering::SeqCst));
+    let result = f();
+    ALLOCATING.with(|a| a.fetch_sub(1, Ordering::SeqCst));
+    result
+}
+
+// io errors with a message hold an allocation, so the ones made inside of a
+// system_scope need to be made again before being handed to the user.
+fn user_error(error: std::io::Error) -> std::io::Error {
+    if error.get_ref().is_none() {
+        return error;
+    }
+
+    let user_error = std::io::Error::new(error.kind(), error.to_string());
+    system_scope(|| drop(error));
+    user_error
+}
+
+// the number of byte columns used by default when opening a window for a new
+// allocation. default to 8 bytes (64 bits) per line.
+#[cfg(feature = "always-graphics")]
+static DEFAULT_GRAPHICS_COLUMNS: AtomicUsize = AtomicUsize::new(8);
+
+// whether new allocations get surrounded by guard pages.
+static GUARD_PAGES: AtomicBool = AtomicBool::new(false);
+
+// whether new allocations get canary bytes after their data.
+static CANARIES: AtomicBool = AtomicBool::new(false);
+
+// the pattern written right after the data of allocations with canaries.
+// exactly 16 bytes so that it's a full line in a hex editor, and readable in
+// case you wonder what that is.
+const CANARY: [u8; 16] = *b"~stupid canary~!";
+
+// whether new allocations close their file once it is mapped.
+static CLOSE_FILES: AtomicBool = AtomicBool::new(false);
+
+// the file handle kept by a new allocation, if files aren't closed after
+// mapping, with a shared lock for other processes to know that the file is in
+// use. closed files can't hold locks. must be called while ALLOCATING is
+// nonzero, for the arc.
+fn keep_open(file: File) -> Option<Arc<File>> {
+    // the maps of wasi are copies, that need the file to be written back to.
+    let close = CLOSE_FILES.load(Ordering::SeqCst) && cfg!(not(target_os = "wasi"));
+
+    (!close).then(|| {
+        // locks are only advisory, and not every file system has them. the
+        // exclusive lock of a new file has to go first, some platforms would
+        // keep it along with the shared one.
+        let _ = file.unlock();
+        let _ = file.try_lock_shared();
+        Arc::new(file)
+    })
+}
+
+// the number of freed allocations kept in quarantine before really being
+// released. zero means no quarantine at all.
+static QUARANTINE_LEN: AtomicUsize = AtomicUsize::new(0);
+
+// the pattern freed allocations are filled with when put in quarantine.
+const POISON: [u8; 2] = [0xDE, 0xAD];
+
+// the number of freed allocations remembered to diagnose double frees.
+const FREED_HISTORY_LEN: usize = 1024;
+
+// what's left of an allocation after it was freed.
+struct Freed {
+    addr: usize,
+    layout: Layout,
+    path: PathBuf,
+    allocated: Backtrace,
+    freed: Backtrace,
+}
+
+// moves the stupid allocation at `ptr` to a new one of `new_layout` from
+// System, with as much of its contents as fits.
+unsafe fn move_to_system(
+    ptr: NonNull<u8>,
+    old_layout: Layout,
+    new_layout: Layout,
+) -> Result<NonNull<[u8]>, AllocError> {
+    let new = system_scope(|| System.allocate_zeroed(new_layout))?;
+    std::ptr::copy_nonoverlapping(
+        ptr.as_ptr(),
+        new.as_ptr() as *mut u8,
+        old_layout.size().min(new_layout.size()),
+    );
+    free_registered(ptr.as_ptr() as usize, old_layout);
+    Ok(new)
+}
+
+// prints everything we know about a pointer that the allocator was asked to
+// free or resize, but doesn't own, and aborts the process. `operation` is what
+// was attempted, like "free".
+fn bad_pointer(addr: usize, operation: &str) -> ! {
+    // the diagnostic allocates, make sure it doesn't end up here again.
+    DEALLOCATING.with(|d| d.fetch_add(1, Ordering::SeqCst));
+
+    // live allocations first: addresses get reused, and the freed allocation
+    // that used to be there has nothing to do with this one.
+    if let Some(handle) = STUPID_MAP.handle_of(addr) {
+        eprintln!(
+            "stupidalloc: attempted to {operation} 0x{addr:08x}, which points {} bytes into an allocation instead of its start!\n\
+            - Allocation: 0x{:08x} ({}), {:?}\n\
+            - Allocated at:\n{}\n\
+            - Attempted to {operation} at:\n{}",
+            addr - handle.start,
+            handle.start,
+            handle.path.to_string_lossy(),
+            handle.layout,
+            handle.backtrace,
+            Backtrace::capture()
+        );
+    } else if let Some(freed) = FREED
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|f| (f.addr..f.addr + f.layout.size().max(1)).contains(&addr))
+    {
+        eprintln!(
+            "stupidalloc: attempted to {operation} 0x{addr:08x}, which was already freed!\n\
+            - Allocation: 0x{:08x} ({}), {:?}\n\
+            - Allocated at:\n{}\n\
+            - First freed at:\n{}\n\
+            - Attempted to {operation} at:\n{}",
+            freed.addr,
+            freed.path.to_string_lossy(),
+            freed.layout,
+            freed.allocated,
+            freed.freed,
+            Backtrace::capture()
+        );
+    } else {
+        eprintln!(
+            "stupidalloc: attempted to {operation} 0x{addr:08x}, which was never allocated by stupid alloc!\n\
+            - Attempted to {operation} at:\n{}",
+            Backtrace::capture()
+        );
+    }
+
+    // unwinding out of an allocator is a recipe for disaster.
+    std::process::abort()
+}
+
+// the padding an allocation was created with, used to find where the data
+// is in the allocation file.
+#[derive(Clone, Copy)]
+struct Redzones {
+    guard_pages: bool,
+    canaries: bool,
+    // the size of the pages of hugetlbfs, when the file lives there: its
+    // length must be a multiple of it.
+    huge_page: Option<usize>,
+}
+
+impl Redzones {
+    // the redzones of new allocations
+    fn current() -> Self {
+        Self {
+            guard_pages: GUARD_PAGES.load(Ordering::SeqCst),
+            canaries: CANARIES.load(Ordering::SeqCst),
+            huge_page: None,
+        }
+    }
+}
+
+// the size of a memory page, which is the granularity of guard pages.
+fn page_size() -> usize {
+    #[cfg(unix)]
+    {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+    #[cfg(not(unix))]
+    {
+        // close enough for everything i care about
+        4096
+    }
+}
+
+// returns the length of the file backing an allocation of `layout`, and the
+// offset of the data inside of it. with guard pages, the layout of the file is
+// `[guard page][padding][data][canary][guard page]`: the data is pushed right
+// against the trailing guard page (or canary) so that an overrun hits it on the
+// very first byte past the end. the alignment is respected by growing the
+// padding, which works as long as the alignment is not greater than a page:
+// beyond that, the padding could round down into the leading guard page, so
+// those allocations go without guard pages (memory maps are only page-aligned
+// anyways).
+fn mapping_layout(layout: Layout, redzones: Redzones) -> (usize, usize) {
+    let size = if redzones.canaries {
+        layout.size() + CANARY.len()
+    } else {
+        layout.size()
+    };
+
+    if redzones.guard_pages {
+        let page = page_size();
+        let data_len = size.div_ceil(page) * page;
+        let offset = (page + data_len - size) & !(layout.align() - 1);
+
+        (page + data_len + page, offset)
+    } else if let Some(page) = redzones.huge_page {
+        (size.next_multiple_of(page), 0)
+    } else {
+        (size, 0)
+    }
+}
+
+// formats bytes like `xxd` does, with the addresses starting at `addr`.
+struct HexDump<'a> {
+    bytes: &'a [u8],
+    addr: usize,
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.bytes
+            .chunks(16)
+            .enumerate()
+            .try_for_each(|(i, chunk)| {
+                write!(f, "0x{:08x}: ", self.addr + i * 16)?;
+                (0..16).try_for_each(|j| match chunk.get(j) {
+                    Some(b) => write!(f, "{b:02x} "),
+                    None => write!(f, "   "),
+                })?;
+                write!(f, " ")?;
+                chunk.iter().try_for_each(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        write!(f, "{}", b as char)
+                    } else {
+                        write!(f, ".")
+                    }
+                })?;
+                writeln!(f)
+            })
+    }
+}
+
+// makes the leading and trailing guard pages of a guarded map inaccessible.
+#[allow(unused_variables)]
+fn protect_guard_pages(map: &MmapMut) {
+    #[cfg(unix)]
+    {
+        let page = page_size();
+        let base = map.as_ptr() as *mut libc::c_void;
+
+        // SAFETY: both ranges are page-aligned and inside of the map, and nobody
+        // has been handed a pointer in there.
+        unsafe {
+            libc::mprotect(base, page, libc::PROT_NONE);
+            libc::mprotect(base.add(map.len() - page), page, libc::PROT_NONE);
+        }
+    }
 }
 
-lazy_static! {
-    // use hashbrown map explicitly so that we can directly specify that it lives in
-    // system allocator.
-    static ref STUPID_MAP: RwLock<HashMap<usize, AllocHandle, DefaultHashBuilder, allocator_api2::alloc::System>> =
-        RwLock::new(HashMap::new_in(allocator_api2::alloc::System));
-}
+// returns true if we do allocate something. only does something with the
+// "interactive" feature enabled
+#[allow(unused_variables)]
+fn confirm_alloc(layouts: &[Layout]) -> bool {
+    #[cfg(feature = "interactive")]
+    {
+        // show a lil' confirmation message before throwing you the
+        // file chooser
+        let text = match layouts {
+            [layout] => format!("Choose a file to allocate something for a layout of {layout:?}"),
+            _ => format!(
+                "Choose {} files to allocate things for layouts of {layouts:?}",
+                layouts.len()
+            ),
+        };
+
+        #[cfg(feature = "teaching")]
+        let text = match layouts
+            .first()
+            .and_then(|layout| teaching::allocation(*layout))
+        {
+            Some(explanation) if layouts.len() == 1 => format!("{text}\n\n{explanation}"),
+            _ => text,
+        };
+
+        MessageDialog::new()
+            .set_type(MessageType::Info)
+            .set_title("Stupid allocation time!")
+            .set_text(&text)
+            .show_confirm()
+            .unwrap()
+    }
+
+    #[cfg(not(feature = "interactive"))]
+    {
+        // if we're not interactive we don't ask the user if they want to
+        // allocate stuff lol
+        let _ = layouts;
+        true
+    }
+}
+
+// potentially returns a path to the file of the next allocation, of `size`
+// bytes. must be called while ALLOCATING is nonzero.
+#[allow(unused_variables)]
+fn get_alloc_file_path(arena: Option<&Arena>, size: usize) -> Option<PathBuf> {
+    #[cfg(feature = "interactive")]
+    {
+        // this is the file dialog thing. arenas suggest their own folder.
+        let dialog = FileDialog::new();
+        let dialog = match arena {
+            Some(arena) => dialog.set_location(&arena.dir),
+            None => dialog,
+        };
+        dialog
+            .show_save_single_file()
+            .map_err(|e| {
+                error::report(StupidError::Dialog {
+                    message: e.to_string(),
+                })
+            })
+            .ok()
+            .flatten()
+    }
+    #[cfg(not(feature = "interactive"))]
+    {
+        error::check(
+            temp_alloc_file_path(arena, size),
+            "create the folder",
+            &alloc_dir(arena),
+        )
+    }
+}
+
+// whether allocations are only pretending, with memory from System and no
+// files at all. miri can't map files, so it gets the fake ones too.
+const FAKE: bool = cfg!(any(feature = "fake", miri));
+
+// the folder of the folders of every process.
+fn base_alloc_dir() -> PathBuf {
+    // wasi has no temp folder, it has to be given one by the runtime.
+    #[cfg(target_os = "wasi")]
+    let temp_dir = std::env::var_os("TMPDIR").map_or_else(|| PathBuf::from("/tmp"), PathBuf::from);
+    #[cfg(not(target_os = "wasi"))]
+    let temp_dir = std::env::temp_dir();
+
+    let dir = temp_dir.join("stupidalloc"); // let's just say only one stupidalloc exists huh :)
+
+    #[cfg(windows)]
+    let dir = long_path(dir);
+
+    dir
+}
+
+// windows paths are limited to 260 characters, unless they're verbatim, which
+// only takes a prefix. temp folders can be deep enough for bit-mode files and
+// arenas to hit that limit. verbatim paths must be absolute, which the temp
+// folder is, and network shares are left alone.
+#[cfg(windows)]
+fn long_path(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let disk = matches!(
+        path.components().next(),
+        Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::Disk(_))
+    );
+
+    match disk {
+        true => {
+            let mut verbatim = std::ffi::OsString::from(r"\\?\");
+            verbatim.push(path);
+            verbatim.into()
+        }
+        false => path,
+    }
+}
+
+// the folder of the allocation files, when not picked by the user. every
+// process gets its own, so that processes don't trample each other's files,
+// and so that the files of crashed ones can be told apart, see stale.rs.
+fn temp_alloc_dir() -> PathBuf {
+    base_alloc_dir().join(process_id().to_string())
+}
+
+// the id of this process. wasi doesn't know about processes, as far as it's
+// concerned there's only this one.
+fn process_id() -> u32 {
+    #[cfg(target_os = "wasi")]
+    {
+        0
+    }
+    #[cfg(not(target_os = "wasi"))]
+    {
+        std::process::id()
+    }
+}
+
+// the folder of the files of the allocations of `arena`, or of the ones that
+// don't belong to an arena, which may be the folder of the calling thread.
+// must be called while ALLOCATING is nonzero.
+fn alloc_dir(arena: Option<&Arena>) -> PathBuf {
+    match arena {
+        Some(arena) => arena.dir.clone(),
+        None if threads::dirs() => temp_alloc_dir().join(threads::dir_name()),
+        None => temp_alloc_dir(),
+    }
+}
+
+// create a file with an increasing number for file name in the temp folder, or
+// in the folder of the arena. the name may depend on the `size` of the
+// allocation, see naming.rs. fails if the folder can't be created.
+fn temp_alloc_file_path(arena: Option<&Arena>, size: usize) -> std::io::Result<PathBuf> {
+    alloc_file_path_in(alloc_dir(arena), size)
+}
+
+// same, in the folder at `path`.
+fn alloc_file_path_in(path: PathBuf, size: usize) -> std::io::Result<PathBuf> {
+    use std::sync::atomic::AtomicU64;
+
+    static ALLOC_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    // fake allocations have no files, but the logs still need a folder.
+    if !FAKE || cfg!(feature = "logging") {
+        stale::on_startup();
+        std::fs::create_dir_all(&path)?;
+    }
+
+    Ok(path.join(naming::file_name(
+        ALLOC_FILE_COUNTER.fetch_add(1, Ordering::SeqCst),
+        size,
+    )))
+}
+
+// makes a new allocation for `layout`, without registering it yet. returns
+// none if there is no file to put it in. must be called while ALLOCATING is
+// nonzero.
+fn new_allocation(
+    layout: Layout,
+    arena: Option<&Arena>,
+    zeroed: bool,
+) -> Option<(NonNull<[u8]>, AllocHandle)> {
+    // there are no maps to detach from when faking.
+    #[cfg(unix)]
+    if !FAKE {
+        fork::register();
+    }
+    #[cfg(unix)]
+    shutdown::register();
+    clock::start();
+    diagnostics::on_first_allocation();
+
+    let pass_through = *PASS_THROUGH.read().unwrap();
+    let pass_through = pass_through.or(FAKE.then_some(&System as InnerAlloc));
+
+    // big allocations may go to hugetlbfs, with pages of its own.
+    let hugetlbfs = match pass_through {
+        Some(_) => None,
+        None => huge::hugetlbfs(layout.size()),
+    };
+
+    let mut redzones = Redzones::current();
+    // guard pages need memory of our own to protect, in pages small enough,
+    // and data that doesn't have to start further than a page in.
+    redzones.guard_pages &=
+        pass_through.is_none() && hugetlbfs.is_none() && layout.align() <= page_size();
+    redzones.huge_page = hugetlbfs.as_ref().map(|&(_, page)| page);
+    let (len, offset) = mapping_layout(layout, redzones);
+
+    let backing = match pass_through {
+        // in pass-through mode there is no file to pick, but the path is still
+        // used for the logs and the windows.
+        Some(allocator) => match Backing::allocate(allocator, len, layout.align()) {
+            Some(memory) => error::check(
+                temp_alloc_file_path(arena, layout.size()),
+                "create the folder",
+                &alloc_dir(arena),
+            )
+            .map(|path| (path, memory, None)),
+            None => {
+                error::backend(format!(
+                    "the pass-through allocator failed to allocate {len} bytes"
+                ));
+                None
+            }
+        },
+        None => {
+            // files of the pool are only fit for the default folder, and can't
+            // be picked by the user.
+            let pooled = match arena {
+                None if !cfg!(feature = "interactive")
+                    && !threads::dirs()
+                    && hugetlbfs.is_none() =>
+                {
+                    pool::claim(len)
+                }
+                _ => None,
+            };
+
+            pooled
+                .or_else(|| {
+                    let path = match hugetlbfs {
+                        Some((dir, _)) => error::check(
+                            alloc_file_path_in(dir.clone(), layout.size()),
+                            "create the folder",
+                            &dir,
+                        ),
+                        None => get_alloc_file_path(arena, layout.size()),
+                    };
+                    path.and_then(|path| {
+                        let file = bench::measure(Operation::FileCreation, || {
+                            let file = error::check(
+                                OpenOptions::new()
+                                    .read(true)
+                                    .write(true)
+                                    .create(true)
+                                    .truncate(false)
+                                    .open(&path),
+                                "create the file",
+                                &path,
+                            )?;
+
+                            // the file may belong to a live allocation, here
+                            // or in another process, which truncating it would
+                            // crash. keep_open makes the lock shared again.
+                            if let Err(TryLockError::WouldBlock) = file.try_lock() {
+                                return None;
+                            }
+                            error::check(file.set_len(0), "resize the file", &path)?;
+                            Some(file)
+                        })?;
+                        Some((path, file))
+                    })
+                })
+                .and_then(|(path, file)| {
+                    let map = bench::measure(Operation::FileResize, || {
+                        error::check(file.set_len(len as u64), "resize the file", &path)
+                    })
+                    .and_then(|()| {
+                        bench::measure(Operation::Mmap, || {
+                            error::check(
+                                unsafe { MmapOptions::new().map_mut(&file) },
+                                "map the file",
+                                &path,
+                            )
+                        })
+                    });
+
+                    // a file that can't be used is of no use to anyone.
+                    if map.is_none() {
+                        let _ = std::fs::remove_file(&path);
+                    }
+                    Some((path, Backing::Map(map?), Some(file)))
+                })
+        }
+    };
+
+    let (path, mut map, file) = backing?;
+
+    if redzones.canaries {
+        let start = offset + layout.size();
+        map[start..start + CANARY.len()].copy_from_slice(&CANARY);
+    }
+
+    let fill = fill::pattern(zeroed);
+    fill::fill(&mut map[offset..offset + layout.size()], fill);
+
+    if let (true, Some(map)) = (redzones.guard_pages, map.map()) {
+        protect_guard_pages(map);
+    }
+
+    let ptr = NonNull::slice_from_raw_parts(
+        NonNull::new(unsafe { map.as_mut_ptr().add(offset) }).unwrap(),
+        layout.size(),
+    );
+
+    let backtrace = backtraces::capture(backtraces::Event::Alloc);
+
+    // do some logging if we're told to
+    #[cfg(feature = "logging")]
+    let log_file = bench::measure(Operation::Logging, || {
+        let mut log_path = path.clone();
+        log_path.set_extension("md");
+
+        let mut log_file = error::check(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .truncate(true)
+                .create(true)
+                .open(&log_path),
+            "create the log",
+            &log_path,
+        )?;
+
+        let _ = error::check(
+            writeln!(
+            log_file,
+            "# Metadata\n- Allocation path: {}\n- At: {}\n- Layout: {layout:?}\n- Arena: {}\n- Pass-through: {}\n- Guard pages: {}\n- Canaries: {}\n- Fill: {fill}\n\n# Allocation\n```\n{}\n```\n",
+            path.to_string_lossy(),
+            Timestamp::now(),
+            arena.map_or("none", |arena| &arena.name),
+            if file.is_none() { "yes" } else { "no" },
+            if redzones.guard_pages { "yes" } else { "no" },
+            if redzones.canaries { "yes" } else { "no" },
+            backtrace
+        ),
+            "write to the log",
+            &log_path,
+        );
+
+        #[cfg(feature = "teaching")]
+        if let Some(explanation) = teaching::allocation(layout) {
+            let _ = error::check(
+                writeln!(log_file, "{}", teaching::quote(&explanation)),
+                "write to the log",
+                &log_path,
+            );
+        }
+
+        let _ = error::check(
+            writeln!(log_file, "# Events\n"),
+            "write to the log",
+            &log_path,
+        );
+
+        Some(log_file)
+    });
 
-// these are thread_local because they must not interfere with other threads.
-thread_local! {
-    // currently allocating? nonzero = yes.
-    static ALLOCATING: AtomicUsize = AtomicUsize::new(0);
-    // currently de-allocating? nonzero = yes.
-    static DEALLOCATING: AtomicUsize = AtomicUsize::new(0);
-    // thread-local inhibition boolean, true = use system.
-    static LOCAL_SWITCH_OFF: AtomicBool = {
-        // if init was completed, current thread is not main thread, disabling
-        // by default. were it not for that, when using `always-graphics`, thread
-        // internals would get allocated in recursion and that's the only viable
-        // solution.
-        if INIT_DETECTOR.is_completed() {
-            AtomicBool::new(true)
-        } else {
-            // the init once was not called, so this is main thread (or more
-            // generally the first thread that tries to use stupid alloc). allowing
-            // stupid alloc by default.
-            INIT_DETECTOR.call_once(|| {});
-            AtomicBool::new(false)
+    // the allocation is of no use without its log either.
+    #[cfg(feature = "logging")]
+    let Some(log_file) = log_file
+    else {
+        if file.is_some() {
+            let _ = std::fs::remove_file(&path);
         }
+        return None;
+    };
+
+    // ALLOCATING is nonzero, so this arc lives in System.
+    let file = file.and_then(keep_open);
+
+    #[cfg(feature = "watch")]
+    if map.map().is_some() && !shutdown::late() {
+        watch::watch(&path);
     }
+
+    let handle = AllocHandle {
+        file,
+        map,
+        path,
+        layout,
+        redzones,
+        backtrace,
+        persistent: false,
+        pinned: false,
+        type_name: None,
+        location: None,
+        arena: arena.map(|arena| arena.id),
+        thread: threads::number(),
+        created: Instant::now(),
+        resizes: 0,
+        history: Vec::new(),
+        user_data: None,
+        #[cfg(feature = "time-travel")]
+        versions: 0,
+        checksum: None,
+        #[cfg(feature = "graphics")]
+        window: None,
+        #[cfg(feature = "logging")]
+        log_file,
+        #[cfg(feature = "watch")]
+        touched: Instant::now(),
+        #[cfg(feature = "watch")]
+        edited: false,
+    };
+
+    // every bit gets a file of its own. running out of files is not worth
+    // failing the allocation over, the bits catch up on the next flush.
+    #[cfg(feature = "bit-mode")]
+    let _ = handle.write_bits(0);
+
+    // we have graphics and the feature is enabled: go wild!
+    #[cfg(feature = "always-graphics")]
+    let handle = AllocHandle {
+        window: (!shutdown::late() && window_filter::matches(layout.size(), &handle.path)).then(
+            || {
+                bench::measure(Operation::Graphics, || {
+                    graphics::Window::new(
+                        &handle.path,
+                        None,
+                        None,
+                        handle.window_source(),
+                        DEFAULT_GRAPHICS_COLUMNS.load(Ordering::SeqCst),
+                        handle.created,
+                        handle.hud_facts(),
+                    )
+                })
+            },
+        ),
+        ..handle
+    };
+
+    #[cfg(feature = "valgrind")]
+    handle.valgrind_malloclike();
+
+    if handle.has_file() {
+        manifest::allocated(ptr.as_ptr() as *mut u8 as usize, layout, &handle.path);
+    }
+    handle.advise_huge_pages();
+
+    stats::record_allocation(layout.size());
+
+    #[cfg(feature = "morse")]
+    morse::beep(layout.size());
+
+    #[cfg(feature = "midi")]
+    midi::play(midi::Event::Alloc(layout));
+
+    Some((ptr, handle))
 }
 
-// if this Once is not initialized, this means we are between program entry point
-// and the first access to LOCAL_SWITCH_OFF (aka first stupid allocation).
-static INIT_DETECTOR: Once = Once::new();
+// everything that happens to an allocation once it's removed from the
+// registry to be freed. needs to be called while DEALLOCATING is nonzero.
+fn dispose(addr: usize, mut handle: AllocHandle) {
+    access::disarm(addr);
+    #[cfg(feature = "valgrind")]
+    handle.valgrind_expose();
+
+    handle.check_canary(addr);
+    handle.verify_checksum();
+    // the file of a persistent allocation outlives it, and so does its
+    // checksum.
+    if handle.persistent {
+        handle.store_checksum();
+    }
+    stats::record_free(&handle);
+    let call_site = backtraces::call_site(&handle);
+    lifetimes::record(&handle, &call_site);
+    churn::record(&handle, &call_site);
 
-// the number of byte columns used by default when opening a window for a new
-// allocation. default to 8 bytes (64 bits) per line.
-#[cfg(feature = "always-graphics")]
-static DEFAULT_GRAPHICS_COLUMNS: AtomicUsize = AtomicUsize::new(8);
+    #[cfg(feature = "midi")]
+    midi::play(midi::Event::Free(handle.layout));
 
-// returns true if we do allocate something. only does something with the
-// "interactive" feature enabled
-#[allow(unused_variables)]
-fn confirm_alloc(layout: Layout) -> bool {
-    #[cfg(feature = "interactive")]
+    let backtrace = backtraces::capture(backtraces::Event::Free);
+
+    // log deallocation
+    #[cfg(feature = "logging")]
+    bench::measure(Operation::Logging, || {
+        handle.log(format_args!(
+            "# Deallocation\nAt: {}\n```\n{backtrace}\n```",
+            Timestamp::now()
+        ));
+
+        #[cfg(feature = "teaching")]
+        if let Some(explanation) = teaching::deallocation(handle.layout) {
+            handle.log(format_args!("\n{}", teaching::quote(&explanation)));
+        }
+    });
+
+    // remember it, in case someone tries to free it again.
     {
-        // show a lil' confirmation message before throwing you the
-        // file chooser
-        MessageDialog::new()
-            .set_type(MessageType::Info)
-            .set_title("Stupid allocation time!")
-            .set_text(&format!(
-                "Choose a file to allocate something for a layout of {layout:?}"
-            ))
-            .show_confirm()
-            .unwrap()
+        let mut freed = FREED.lock().unwrap();
+        freed.push_back(Freed {
+            addr,
+            layout: handle.layout,
+            path: handle.path.clone(),
+            allocated: std::mem::replace(&mut handle.backtrace, Backtrace::disabled()),
+            freed: backtrace,
+        });
+        if freed.len() > FREED_HISTORY_LEN {
+            freed.pop_front();
+        }
     }
 
-    #[cfg(not(feature = "interactive"))]
-    {
-        // if we're not interactive we don't ask the user if they want to
-        // allocate stuff lol
-        true
+    #[cfg(feature = "valgrind")]
+    valgrind::freelike(addr);
+
+    // either keep the corpse around for a while, or get rid of it
+    // right away.
+    let quarantine = QUARANTINE_LEN.load(Ordering::SeqCst);
+    let mut quarantined = QUARANTINE.lock().unwrap();
+    if quarantine > 0 {
+        handle.poison();
+        quarantined.push_back(handle);
+    } else {
+        handle.release();
+    }
+
+    // the quarantine shrinks down to its length, which may have been lowered
+    // since, even to zero.
+    while quarantined.len() > quarantine {
+        quarantined.pop_front().unwrap().release();
     }
 }
 
-// potentially returns a path to the file of the next allocation
-fn get_alloc_file_path() -> Option<PathBuf> {
+// frees the stupid allocation starting at `addr`, which must be in the
+// registry.
+fn free_registered(addr: usize, layout: Layout) {
+    let _world = freeze::enter();
+    latency::wait(layout.size());
+
+    // tell thread we're deallocating
+    DEALLOCATING.with(|d| d.fetch_add(1, Ordering::SeqCst));
+
+    // remove handle from map
+    let handle = STUPID_MAP.remove(addr).unwrap();
+
+    // the log outlives the allocation, for the dialog to point to.
+    #[cfg(all(feature = "interactive", feature = "teaching"))]
+    let log_path = handle.path.with_extension("md");
+
+    dispose(addr, handle);
+
+    // show a lil confirmation message box
     #[cfg(feature = "interactive")]
     {
-        // this is the file dialog thing
-        FileDialog::new().show_save_single_file().unwrap()
-    }
-    #[cfg(not(feature = "interactive"))]
-    {
-        use std::sync::atomic::AtomicU64;
-
-        // create a file with an increasing number for file name in the temp
-        // folder.
-        static ALLOC_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
-        let path = std::env::temp_dir().join("stupidalloc"); // let's just say only one stupidalloc exists huh :)
-        match std::fs::create_dir(&path) {
-            Ok(_) => {}
-            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
-            Err(e) => panic!("stupidalloc temp dir creation failed: {e}"),
-        };
+        #[allow(unused_mut)]
+        let mut text = format!("Allocation of layout {layout:?} at address 0x{addr:08x} free'd!");
+
+        #[cfg(feature = "teaching")]
+        if let Some(explanation) = teaching::deallocation(layout) {
+            text = format!(
+                "{text}\n\n{explanation}\n\nThe whole story of the allocation is in {}",
+                log_path.display()
+            );
+        }
 
-        Some(path.join(format!(
-            "alloc_{:010}.mem",
-            ALLOC_FILE_COUNTER.fetch_add(1, Ordering::SeqCst)
-        )))
+        let _ = MessageDialog::new()
+            .set_type(MessageType::Info)
+            .set_title("Stupid deallocation done!")
+            .set_text(&text)
+            .show_confirm()
+            .unwrap();
     }
+
+    // tell thread we're done deallocating
+    DEALLOCATING.with(|a| a.fetch_sub(1, Ordering::SeqCst));
+
+    user_data::drop_orphans();
+    inspect::notify(HeapEvent::Freed { addr, layout });
 }
 
 /// The stupid allocator.
@@ -254,116 +2078,351 @@ impl StupidAlloc {
         LOCAL_SWITCH_OFF.with(|l| l.store(!value, Ordering::SeqCst));
     }
 
+    /// Enables or disables guard pages for subsequent allocations, depending
+    /// on the value passed as parameter. When enabled, the data of each
+    /// allocation is surrounded by inaccessible memory pages, and placed right
+    /// before the trailing one: reading or writing past the end of the data
+    /// will crash the program on the spot, instead of silently messing with
+    /// the bytes of the allocation file. Existing allocations are not affected.
+    ///
+    /// Guard pages are only made inaccessible on Unix platforms. Elsewhere,
+    /// they still take space in the allocation files, but protect nothing.
+    /// Allocations aligned to more than a page go without them.
+    pub fn set_guard_pages(&self, value: bool) {
+        GUARD_PAGES.store(value, Ordering::SeqCst);
+    }
+
+    /// Enables or disables canaries for subsequent allocations, depending on
+    /// the value passed as parameter. When enabled, a known pattern of bytes is
+    /// written right after the data of each allocation, and checked when the
+    /// allocation is resized or de-allocated. If it was modified, something
+    /// wrote past the end of the data: a message is printed to the standard
+    /// error and, with the `logging` feature, a report with a hexdump of the
+    /// damage is added to the log file. Existing allocations are not affected.
+    pub fn set_canaries(&self, value: bool) {
+        CANARIES.store(value, Ordering::SeqCst);
+    }
+
+    /// Sets the number of freed allocations kept in quarantine. When nonzero,
+    /// de-allocated memory isn't released right away: it is filled with a
+    /// `0xDE 0xAD` poison pattern, made inaccessible (on Unix platforms), and
+    /// its file and window are kept around until `len` more allocations have
+    /// been freed. Using memory after it was freed then either crashes, or
+    /// shows the pattern in the allocation file and window. Passing `0`
+    /// disables the quarantine; allocations that are already in there are
+    /// released as other allocations get freed.
+    pub fn set_quarantine(&self, len: usize) {
+        QUARANTINE_LEN.store(len, Ordering::SeqCst);
+    }
+
+    /// Enables or disables closing allocation files once they are mapped, for
+    /// subsequent allocations. Each allocation normally keeps its file open,
+    /// which limits the number of simultaneous allocations to the number of
+    /// file descriptors a process may have open. When enabled, the file is
+    /// only opened again, by path, when it is needed: to resize the
+    /// allocation, or to open a window for it. Existing allocations are not
+    /// affected.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// StupidAlloc.set_close_files(true);
+    /// let mut stupid_vec = Vec::new_in(StupidAlloc);
+    /// stupid_vec.extend(0..1000u32);
+    /// StupidAlloc.set_close_files(false);
+    ///
+    /// assert!(StupidAlloc.file_of(&*stupid_vec).is_some());
+    /// assert_eq!(stupid_vec.iter().sum::<u32>(), 499500);
+    /// ```
+    pub fn set_close_files(&self, value: bool) {
+        CLOSE_FILES.store(value, Ordering::SeqCst);
+    }
+
+    /// Sets the allocator servicing subsequent allocations. With
+    /// [`Some`] allocator, the stupid alloc only wraps it: the memory comes
+    /// from `allocator` instead of a file, but logging, windows, canaries,
+    /// the quarantine and the other helpers keep working. Guard pages are not
+    /// available in this mode. Passing [`None`] goes back to file-backed
+    /// allocations, or to [`System`] with the `fake` feature. Existing
+    /// allocations are not affected.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use std::alloc::System;
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// StupidAlloc.set_pass_through(Some(&System));
+    /// let stupid_box = Box::new_in(42u32, StupidAlloc);
+    /// StupidAlloc.set_pass_through(None);
+    ///
+    /// assert!(StupidAlloc.file_of(&*stupid_box).is_none());
+    /// assert!(StupidAlloc.hexdump_of(&*stupid_box).is_some());
+    /// ```
+    pub fn set_pass_through(&self, allocator: Option<&'static (dyn GlobalAlloc + Sync)>) {
+        *PASS_THROUGH.write().unwrap() = allocator;
+    }
+
     /// Return a [`HashMap`] where the key is an address of an allocation and
     /// the value is a [`PathBuf`].
+    ///
+    /// Files of another process attached with [`StupidAlloc::attach_readonly()`]
+    /// are included, keyed by the address where they are mapped.
     pub fn state(&self) -> HashMap<usize, PathBuf> {
-        STUPID_MAP
-            .read()
-            .unwrap()
+        // allocating with the registry locked would deadlock when the stupid
+        // alloc is the global allocator, so the state is collected in System.
+        let state = system_scope(|| {
+            let mut state = STUPID_MAP
+                .read_all()
+                .iter()
+                .map(|(&addr, handle)| (addr, handle.path.clone()))
+                .collect::<Vec<_>>();
+
+            state.extend(
+                viewer::ATTACHED
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(&addr, attached)| (addr, attached.path.clone())),
+            );
+
+            state
+        });
+
+        let user_state = state
             .iter()
-            .map(|(&addr, handle)| (addr, handle.path.clone()))
-            .collect()
+            .map(|(addr, path)| (*addr, path.clone()))
+            .collect();
+        system_scope(|| drop(state));
+        user_state
     }
 
     /// Returns the [`PathBuf`] of the allocation of an element if it has been
-    /// allocated with the stupid alloc.
+    /// allocated with the stupid alloc. Pass-through allocations have no file,
+    /// so this returns [`None`] for them.
     pub fn file_of<T: ?Sized>(&self, value: &T) -> Option<PathBuf> {
-        STUPID_MAP
-            .read()
-            .unwrap()
-            .iter()
-            .find_map(|(&addr, handle)| {
-                if (addr..addr + handle.map.len())
-                    .contains(&(value as *const T as *const u8 as usize))
-                {
-                    Some(handle.path.clone())
-                } else {
-                    None
-                }
+        let addr = value as *const T as *const u8 as usize;
+
+        // the path gets copied in System while the registry is locked, and
+        // copied again for the user afterwards.
+        let path = system_scope(|| {
+            let handle = STUPID_MAP.handle_of(addr)?;
+            handle.has_file().then(|| handle.path.clone())
+        })?;
+
+        let user_path = path.as_path().to_path_buf();
+        system_scope(|| drop(path));
+
+        Some(user_path)
+    }
+
+    /// Copies the contents of the allocation containing `value` into `buf`,
+    /// starting from the beginning of the allocation, and returns the number of
+    /// bytes copied: the smallest of the allocation's size and `buf`'s length.
+    /// Returns [`None`] if `value` wasn't allocated with the stupid alloc.
+    pub fn copy_bytes_of<T: ?Sized>(&self, value: &T, buf: &mut [u8]) -> Option<usize> {
+        let addr = value as *const T as *const u8 as usize;
+        let handle = STUPID_MAP.handle_of(addr)?;
+
+        let len = handle.layout.size().min(buf.len());
+        let offset = handle.offset();
+        buf[..len].copy_from_slice(&handle.map[offset..offset + len]);
+
+        Some(len)
+    }
+
+    /// Returns a hexdump of the contents of the allocation containing `value`,
+    /// in the style of `xxd`, or [`None`] if `value` wasn't allocated with the
+    /// stupid alloc. The files of another process attached with
+    /// [`StupidAlloc::attach_readonly()`] are dumped whole.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in(*b"stupid", StupidAlloc);
+    /// let dump = StupidAlloc.hexdump_of(&*stupid_box).unwrap();
+    ///
+    /// assert!(dump.contains("73 74 75 70 69 64"));
+    /// assert!(dump.trim_end().ends_with("stupid"));
+    /// ```
+    pub fn hexdump_of<T: ?Sized>(&self, value: &T) -> Option<String> {
+        let addr = value as *const T as *const u8 as usize;
+
+        // the buffer may very well be stupid-allocated, so it can't be created
+        // while the registry is locked.
+        let Some((start, len)) = STUPID_MAP
+            .handle_of(addr)
+            .map(|handle| (handle.start, handle.layout.size()))
+        else {
+            return viewer::hexdump_attached(addr);
+        };
+        let mut bytes = vec![0; len];
+        let len = self.copy_bytes_of(value, &mut bytes)?;
+
+        Some(
+            HexDump {
+                bytes: &bytes[..len],
+                addr: start,
+            }
+            .to_string(),
+        )
+    }
+
+    /// Makes sure that the file of the allocation containing `value` is up to
+    /// date with the contents of the memory. Writes to memory reach the file
+    /// whenever the OS feels like it; flushing forces it to happen right now,
+    /// which is handy before copying the file elsewhere.
+    ///
+    /// Returns an error of kind [`NotFound`](std::io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc.
+    ///
+    /// With the `time-travel` feature, flushing also saves a new version of the
+    /// allocation. With the `bit-mode` feature, it writes every bit to its own
+    /// file.
+    pub fn flush_of<T: ?Sized>(&self, value: &T) -> std::io::Result<()> {
+        let addr = value as *const T as *const u8 as usize;
+
+        system_scope(|| {
+            // only written to with some features
+            #[allow(unused_mut)]
+            let mut handle = STUPID_MAP
+                .handle_of_mut(addr)
+                .ok_or(std::io::ErrorKind::NotFound)?;
+            handle.verify_checksum();
+            handle.flush()?;
+            handle.store_checksum();
+
+            #[cfg(feature = "time-travel")]
+            handle.save_version()?;
+
+            Ok(())
+        })
+        .map_err(user_error)
+    }
+
+    /// Makes sure that the files of all allocations are up to date with the
+    /// contents of the memory, like [`StupidAlloc::flush_of()`].
+    pub fn flush_all(&self) -> std::io::Result<()> {
+        system_scope(|| {
+            STUPID_MAP.write_all().values_mut().try_for_each(|handle| {
+                handle.verify_checksum();
+                handle.flush()?;
+                handle.store_checksum();
+
+                #[cfg(feature = "time-travel")]
+                handle.save_version()?;
+
+                Ok(())
             })
+        })
+        .map_err(user_error)
+    }
+
+    /// Pins the allocation containing `value` in RAM, so that it can't be paged
+    /// out to its file, using `mlock`. The allocation stays pinned when it is
+    /// resized, until [`StupidAlloc::unpin_of()`] is called.
+    ///
+    /// The OS usually limits how much memory can be pinned, in which case an
+    /// error is returned. Pinning is only supported on Unix platforms.
+    pub fn pin_of<T: ?Sized>(&self, value: &T) -> std::io::Result<()> {
+        let addr = value as *const T as *const u8 as usize;
+
+        STUPID_MAP
+            .handle_of_mut(addr)
+            .ok_or(std::io::ErrorKind::NotFound)?
+            .set_pinned(true)
+    }
+
+    /// Lets the allocation containing `value` be paged out again, after it was
+    /// pinned with [`StupidAlloc::pin_of()`].
+    pub fn unpin_of<T: ?Sized>(&self, value: &T) -> std::io::Result<()> {
+        let addr = value as *const T as *const u8 as usize;
+
+        STUPID_MAP
+            .handle_of_mut(addr)
+            .ok_or(std::io::ErrorKind::NotFound)?
+            .set_pinned(false)
     }
 
+    /// A number of columns for [`StupidAlloc::open_window_of()`] and
+    /// [`StupidAlloc::set_columns_of()`] that fits them to the allocation: a
+    /// power of two that makes the window about square without making it
+    /// wider than the screen, picked again whenever the allocation is
+    /// resized.
+    #[cfg(feature = "graphics")]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub const AUTO_COLUMNS: usize = 0;
+
     /// Opens a graphical window displaying the memory contents of the data
     /// passed as a parameter, if it was allocated with stupid alloc. You must also
     /// specify the number of bytes displayed on each row using the `columns`
-    /// parameter.
+    /// parameter, or [`StupidAlloc::AUTO_COLUMNS`].
     #[cfg(feature = "graphics")]
-    #[doc(cfg(feature = "graphics"))]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
     pub fn open_window_of<T: ?Sized>(&self, value: &T, columns: usize) {
-        STUPID_MAP
-            .write()
-            .unwrap()
-            .iter_mut()
-            .for_each(|(&addr, handle)| {
-                if (addr..addr + handle.map.len())
-                    .contains(&(value as *const T as *const u8 as usize))
-                {
-                    if let Some(window) = handle.window.as_mut() {
-                        if window.is_finished() {
-                            *window = graphics::Window::new(
-                                &handle.path,
-                                Arc::clone(&handle.file),
-                                columns,
-                            );
-                        }
-                    } else {
-                        handle.window = Some(graphics::Window::new(
-                            &handle.path,
-                            Arc::clone(&handle.file),
-                            columns,
-                        ));
-                    }
-                }
-            })
+        let addr = value as *const T as *const u8 as usize;
+
+        if let Some(mut handle) = STUPID_MAP.handle_of_mut(addr) {
+            handle.open_window(columns);
+        }
+
+        viewer::with_attached(addr, |attached| attached.open_window(columns))
     }
 
     /// If a graphical window is currently open for `value`, this sets its
-    /// number of columns: the number of bytes (or groups of 8 bits) on each row.
+    /// number of columns: the number of bytes (or groups of 8 bits) on each row,
+    /// or [`StupidAlloc::AUTO_COLUMNS`].
     #[cfg(feature = "graphics")]
-    #[doc(cfg(feature = "graphics"))]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
     pub fn set_columns_of<T: ?Sized>(&self, value: &T, columns: usize) {
-        STUPID_MAP
-            .write()
-            .unwrap()
-            .iter_mut()
-            .for_each(|(&addr, handle)| {
-                if (addr..addr + handle.map.len())
-                    .contains(&(value as *const T as *const u8 as usize))
-                {
-                    if let Some(window) = handle.window.as_mut() {
-                        window
-                            .tx
-                            .send(graphics::Message::Resize { columns })
-                            .unwrap();
-                    }
-                }
-            })
+        let addr = value as *const T as *const u8 as usize;
+
+        if let Some(window) = STUPID_MAP
+            .handle_of(addr)
+            .as_ref()
+            .and_then(|handle| handle.window.as_ref())
+        {
+            window
+                .tx
+                .send(graphics::Message::Resize { columns })
+                .unwrap();
+        }
+
+        viewer::with_attached(addr, |attached| attached.set_columns(columns))
     }
 
     /// Closes any graphical window associated with `value`.
     #[cfg(feature = "graphics")]
     pub fn close_graphics_of<T: ?Sized>(&self, value: &T) {
-        STUPID_MAP
-            .write()
-            .unwrap()
-            .iter_mut()
-            .for_each(|(&addr, handle)| {
-                if (addr..addr + handle.map.len())
-                    .contains(&(value as *const T as *const u8 as usize))
-                {
-                    if let Some(window) = handle.window.take() {
-                        window.close()
-                    }
-                }
-            })
+        let addr = value as *const T as *const u8 as usize;
+
+        if let Some(window) = STUPID_MAP
+            .handle_of_mut(addr)
+            .and_then(|mut handle| handle.window.take())
+        {
+            window.close()
+        }
+
+        viewer::with_attached(addr, |attached| attached.close_window())
     }
 
     // this function abstracts Allocator::allocate and Allocator::allocate_zeroed
     // since the only way to allocate memory with stupid alloc is to have the
-    // contents zeroed already. in the spirit of not duplicating code, the
-    // fallback (either System::allocate or System::allocate_zeroed) is passed
-    // as a parameter.
-    fn inner_allocate<F>(&self, layout: Layout, fallback: F) -> Result<NonNull<[u8]>, AllocError>
+    // contents zeroed already, until the fill pattern says otherwise. in the
+    // spirit of not duplicating code, the fallback (either System::allocate or
+    // System::allocate_zeroed) is passed as a parameter, along with whether
+    // the memory has to stay zeroed.
+    //
+    // allocations made through an arena get tracked as part of it.
+    fn inner_allocate<F>(
+        &self,
+        layout: Layout,
+        arena: Option<&Arena>,
+        zeroed: bool,
+        fallback: F,
+    ) -> Result<NonNull<[u8]>, AllocError>
     where
         F: Fn(Layout) -> Result<NonNull<[u8]>, AllocError>,
     {
@@ -371,104 +2430,46 @@ impl StupidAlloc {
         // - we're allowed to
         // - we're not currently allocating with stupid alloc
         // - we're not currently de-allocating something from stupid alloc
-        if LOCAL_SWITCH_OFF.with(|l| l.load(Ordering::SeqCst))
-            || DEALLOCATING.with(|d| d.load(Ordering::SeqCst)) != 0
-            || ALLOCATING.with(|a| a.load(Ordering::SeqCst)) != 0
-        {
+        if use_system() {
             // THIS IS STUPIDALLOC BITCH!!! we clown in this muthafucka betta
             // take yo sensitive ass back to System
+            stats::record_fallback();
             fallback(layout)
+        } else if shutdown::late() {
+            // whatever System allocates, like malloc with the `preload`
+            // feature, must not come back here.
+            stats::record_fallback();
+            system_scope(|| {
+                shutdown::warn_late(layout);
+                fallback(layout)
+            })
+        } else if let Some(action) = budget::check(layout.size(), 1) {
+            match action {
+                BudgetAction::System => {
+                    stats::record_over_budget();
+                    let ptr = system_scope(|| fallback(layout))?;
+                    budget::spill(ptr.as_ptr() as *mut u8 as usize);
+                    Ok(ptr)
+                }
+                BudgetAction::Fail => Err(AllocError),
+            }
         } else {
+            let _world = freeze::enter();
+            latency::wait(layout.size());
+
             // okay so first we tell the thread that we're allocating.
             // no recursive allocation allowed this bricked my PC twice already.
             ALLOCATING.with(|a| a.fetch_add(1, Ordering::SeqCst));
             let result = {
-                if confirm_alloc(layout) {
-                    let path = get_alloc_file_path();
-
-                    if let Some(path) = path {
-                        let file = OpenOptions::new()
-                            .read(true)
-                            .write(true)
-                            .truncate(true)
-                            .create(true)
-                            .open(&path)
-                            .unwrap();
-
-                        file.set_len(layout.size() as u64).unwrap();
-                        let map = unsafe { MmapOptions::new().map_mut(&file).unwrap() };
-
-                        let ptr = NonNull::from_raw_parts(
-                            NonNull::new(map.as_ptr() as _).unwrap(),
-                            layout.size(),
-                        );
-
-                        // do some logging if we're told to
-                        #[cfg(feature = "logging")]
-                        let log_file = {
-                            let mut log_path = path.clone();
-                            log_path.set_extension("md");
-
-                            let mut log_file = OpenOptions::new()
-                                .read(true)
-                                .write(true)
-                                .truncate(true)
-                                .create(true)
-                                .open(log_path)
-                                .unwrap();
-
-                            writeln!(
-                                log_file,
-                                "# Metadata\n- Allocation path: {}\n- Layout: {layout:?}\n\n# Allocation\n```\n{}\n```\n\n# Events\n",
-                                path.to_string_lossy(),
-                                Backtrace::capture()
-                            )
-                            .unwrap();
-
-                            log_file
-                        };
-
-                        // it's probably not necessary to specify System for
-                        // this arc, but better be safe.
-                        let file = Arc::new_in(file, System);
-
-                        // we have graphics? decide if we start with a window
-                        // for this alloc.
-                        #[cfg(feature = "graphics")]
-                        let window = {
-                            // the feature is enabled: go wild!
-                            #[cfg(feature = "always-graphics")]
-                            {
-                                Some(graphics::Window::new(
-                                    &path,
-                                    Arc::clone(&file),
-                                    DEFAULT_GRAPHICS_COLUMNS.load(Ordering::SeqCst),
-                                ))
-                            }
-                            // or not: no
-                            #[cfg(not(feature = "always-graphics"))]
-                            {
-                                None
-                            }
-                        };
-
-                        STUPID_MAP.write().unwrap().insert(
-                            ptr.as_ptr() as *mut u8 as usize,
-                            AllocHandle {
-                                file,
-                                map,
-                                path,
-                                #[cfg(feature = "graphics")]
-                                window,
-                                #[cfg(feature = "logging")]
-                                log_file,
-                            },
-                        );
-
-                        Ok(ptr)
-                    } else {
-                        Err(AllocError)
-                    }
+                if fault::inject(layout) {
+                    Err(AllocError)
+                } else if confirm_alloc(&[layout]) {
+                    new_allocation(layout, arena, zeroed)
+                        .map(|(ptr, handle)| {
+                            STUPID_MAP.insert(ptr.as_ptr() as *mut u8 as usize, handle);
+                            ptr
+                        })
+                        .ok_or(AllocError)
                 } else {
                     Err(AllocError)
                 }
@@ -478,6 +2479,13 @@ impl StupidAlloc {
             // back to zero we can potentially stupid alloc again!
             ALLOCATING.with(|a| a.fetch_sub(1, Ordering::SeqCst));
 
+            if let Ok(ptr) = result {
+                inspect::notify(HeapEvent::Allocated {
+                    addr: ptr.as_ptr() as *mut u8 as usize,
+                    layout,
+                });
+            }
+
             result
         }
     }
@@ -497,73 +2505,293 @@ impl StupidAlloc {
     {
         let addr: usize = ptr.as_ptr() as usize;
 
-        // same as allocate; if any of these is nonzero / true, we're guaranteed
-        // the data was allocated by system.
-        if LOCAL_SWITCH_OFF.with(|l| l.load(Ordering::SeqCst))
-            || DEALLOCATING.with(|d| d.load(Ordering::SeqCst)) != 0
-            || ALLOCATING.with(|a| a.load(Ordering::SeqCst)) != 0
-        {
+        // same as allocate; if we're busy, the data was allocated by system.
+        if busy() {
             fallback(ptr, old_layout, new_layout)
-        } else if STUPID_MAP.read().unwrap().contains_key(&addr) {
-            let handle = STUPID_MAP.write().unwrap().remove(&addr).unwrap();
+        } else if budget::unspill(addr) {
+            // allocated by System, for going over a budget, and it stays there.
+            let new = system_scope(|| fallback(ptr, old_layout, new_layout));
+            budget::spill(new.map_or(addr, |new| new.as_ptr() as *mut u8 as usize));
+            new
+        } else if use_system() || shutdown::late() {
+            if STUPID_MAP.contains(addr) {
+                // a stupid allocation, resized in a thread where the stupid
+                // alloc is disabled or after it shut down: it moves to System.
+                stats::record_fallback();
+                move_to_system(ptr, old_layout, new_layout)
+            } else {
+                system_scope(|| fallback(ptr, old_layout, new_layout))
+            }
+        } else if let Some(action) = new_layout
+            .size()
+            .checked_sub(old_layout.size())
+            .filter(|&grown| grown > 0)
+            .and_then(|grown| budget::check(grown, 0))
+        {
+            match action {
+                BudgetAction::System => {
+                    stats::record_over_budget();
+                    let new = move_to_system(ptr, old_layout, new_layout)?;
+                    budget::spill(new.as_ptr() as *mut u8 as usize);
+                    Ok(new)
+                }
+                BudgetAction::Fail => Err(AllocError),
+            }
+        } else if new_layout.size() > old_layout.size()
+            && system_scope(|| fault::inject(new_layout))
+        {
+            Err(AllocError)
+        } else {
+            let _world = freeze::enter();
+            latency::wait(new_layout.size());
+
+            // the allocation stays in the registry the whole time, so that
+            // other threads don't see it missing halfway through. everything
+            // in there runs in system_scope, as the registry is locked.
+            let result = STUPID_MAP
+                .update(addr, |handle| {
+                    #[cfg(feature = "valgrind")]
+                    handle.valgrind_expose();
+
+                    access::disarm(addr);
+                    handle.check_canary(addr);
+
+                    // losing a version is not worth failing the resize over.
+                    #[cfg(feature = "time-travel")]
+                    let _ = handle.save_version();
+
+                    let (old_len, old_offset) = mapping_layout(old_layout, handle.redzones);
+                    let (new_len, new_offset) = mapping_layout(new_layout, handle.redzones);
+                    let kept = old_layout.size().min(new_layout.size());
+
+                    // the new memory, or none if the old one could be resized
+                    // where it is. the old allocation is still valid if this
+                    // fails, so nothing is moved or chopped off before.
+                    let resized = if handle.has_file() {
+                        let path = handle.path.clone();
+                        error::check(handle.file(), "open the file", &path).and_then(|file| {
+                            // the new map is read from the file, which has to
+                            // be up to date first when maps are only copies.
+                            #[cfg(target_os = "wasi")]
+                            error::check(
+                                handle.map.flush_range(0, old_len),
+                                "write to the file",
+                                &path,
+                            )?;
+
+                            // growing zeroes stuff out, and has to come
+                            // first for the new map to fit in the file.
+                            if new_len > old_len {
+                                bench::measure(Operation::FileResize, || {
+                                    error::check(
+                                        file.set_len(new_len as u64),
+                                        "resize the file",
+                                        &path,
+                                    )
+                                })?;
+                            }
 
-            // grow or shrink, and growing zeroes stuff out.
-            handle.file.set_len(new_layout.size() as u64).unwrap();
+                            // the guard page at the end would end up in the
+                            // middle of the data, so those always move.
+                            let moved = bench::measure(Operation::Mmap, || {
+                                if !handle.redzones.guard_pages
+                                    && handle.map.remap_in_place(new_len)
+                                {
+                                    Some(None)
+                                } else {
+                                    // new memory mapping to reflect new size.
+                                    error::check(
+                                        unsafe {
+                                            MmapOptions::new().len(new_len).map_mut(
+                                                &*file as &File, /* thanks, memmap2 (sarcasm) */
+                                            )
+                                        },
+                                        "map the file",
+                                        &path,
+                                    )
+                                    .map(|map| Some(Backing::Map(map)))
+                                }
+                            });
+
+                            match moved {
+                                // the old map must not reach past the end of
+                                // the file, or touching it crashes.
+                                None if new_len > old_len => {
+                                    let _ = file.set_len(old_len as u64);
+                                }
+                                // shrinking can't fail anymore: the data fits
+                                // in the new map, and the pages past its end
+                                // are of no use to anyone.
+                                Some(_) if new_len < old_len => {
+                                    let _ = handle.advise(
+                                        Advice::DontNeed,
+                                        new_len.next_multiple_of(page_size()),
+                                        old_len,
+                                    );
+                                    let _ = bench::measure(Operation::FileResize, || {
+                                        error::check(
+                                            file.set_len(new_len as u64),
+                                            "resize the file",
+                                            &path,
+                                        )
+                                    });
+                                }
+                                _ => {}
+                            }
+                            moved
+                        })
+                    } else {
+                        // pass-through: ask the other allocator for new
+                        // memory.
+                        let allocator = handle.map.allocator().unwrap();
+                        match Backing::allocate(allocator, new_len, new_layout.align()) {
+                            Some(mut memory) => {
+                                // same as resizing the file: whatever fits
+                                // stays in place.
+                                let len = old_len.min(new_len);
+                                memory[..len].copy_from_slice(&handle.map[..len]);
 
-            // new memory mapping to reflect new size.
-            let map = unsafe {
-                MmapOptions::new()
-                    .map_mut(&handle.file as &File /* thanks, memmap2 (sarcasm) */)
-                    .unwrap()
-            };
+                                #[cfg(feature = "graphics")]
+                                memory.moved_from(&mut handle.map);
 
-            // tell the window the size has changed
-            #[cfg(feature = "graphics")]
-            let window = {
-                let mut window = handle.window;
-                if let Some(window) = window.as_mut() {
-                    window.tx.send(graphics::Message::Grow).unwrap();
-                }
-                window
-            };
+                                Some(Some(memory))
+                            }
+                            None => {
+                                error::backend(format!(
+                                    "the pass-through allocator failed to allocate {new_len} bytes"
+                                ));
+                                None
+                            }
+                        }
+                    };
+
+                    let Some(mut moved) = resized else {
+                        return Err(AllocError);
+                    };
+                    let map = moved.as_mut().unwrap_or(&mut handle.map);
+
+                    // the data only moves once the new memory is there. it
+                    // fits even when shrinking: there's less than two pages in
+                    // front of it, and the new layout has two pages on top of
+                    // the data.
+                    if old_offset != new_offset {
+                        map.copy_within(old_offset..old_offset + kept, new_offset);
+                    }
 
-            // log the event
-            #[cfg(feature = "logging")]
-            let log_file = {
-                let mut log_file = handle.log_file;
-                writeln!(
-                    log_file,
-                    "## Resize\nNew layout: {new_layout:?}\n```\n{}\n```\n",
-                    Backtrace::capture()
-                )
-                .unwrap();
-                log_file
-            };
+                    if handle.redzones.guard_pages || handle.redzones.canaries {
+                        // moving the data around and the old canary leave stale
+                        // bytes behind, and the grown part must be zeroed.
+                        map[..new_offset].fill(0);
+                        map[new_offset + kept..].fill(0);
 
-            let ptr = NonNull::from_raw_parts(
-                NonNull::new(map.as_ptr() as _).unwrap(),
-                new_layout.size(),
-            );
+                        if handle.redzones.canaries {
+                            let start = new_offset + new_layout.size();
+                            map[start..start + CANARY.len()].copy_from_slice(&CANARY);
+                        }
+
+                        if let (true, Some(map)) = (handle.redzones.guard_pages, map.map()) {
+                            protect_guard_pages(map);
+                        }
+                    }
+
+                    // the old map goes away, and takes its pins with it.
+                    if let Some(map) = moved {
+                        drop(std::mem::replace(&mut handle.map, map));
+                    }
+                    handle.layout = new_layout;
+                    handle.resizes += 1;
+                    stats::record_resize(handle);
+
+                    #[cfg(feature = "midi")]
+                    midi::play(midi::Event::Resize(old_layout, new_layout));
+
+                    // losing bits is not worth failing the resize over either.
+                    #[cfg(feature = "bit-mode")]
+                    let _ = handle.write_bits(old_layout.size());
+
+                    // like realloc, which memcheck sees as a free and a malloc.
+                    #[cfg(feature = "valgrind")]
+                    {
+                        valgrind::freelike(addr);
+                        handle.valgrind_malloclike();
+                    }
+
+                    if handle.pinned {
+                        handle.pinned = false;
+                        // the data is still there, only the pin is lost: not
+                        // worth failing the resize over it, but worth a report.
+                        let pinned = handle.set_pinned(true);
+                        let _ = error::check(pinned, "pin the memory", &handle.path);
+                    }
+
+                    #[cfg(feature = "watch")]
+                    {
+                        handle.touched = Instant::now();
+                    }
 
-            STUPID_MAP.write().unwrap().insert(
-                ptr.as_ptr() as *mut u8 as usize,
-                AllocHandle {
-                    file: handle.file,
-                    map,
-                    path: handle.path,
+                    // tell the window the size has changed
                     #[cfg(feature = "graphics")]
-                    window,
+                    if let Some(window) = handle.window.as_ref() {
+                        let facts = handle.hud_facts();
+                        bench::measure(Operation::Graphics, || {
+                            // the window may have been closed already.
+                            let _ = window.tx.send(graphics::Message::Grow { facts });
+                        });
+                    }
+
+                    let new_ptr = unsafe { handle.map.as_mut_ptr().add(new_offset) };
+                    if handle.has_file() {
+                        manifest::resized(new_ptr as usize, new_layout, &handle.path);
+                    }
+                    handle.advise_huge_pages();
+                    let backtrace = backtraces::capture(backtraces::Event::Resize);
+
+                    // log the event
                     #[cfg(feature = "logging")]
-                    log_file,
-                },
-            );
+                    bench::measure(Operation::Logging, || {
+                        handle.log(format_args!(
+                            "## Resize\nAt: {}\nNew layout: {new_layout:?}\n```\n{backtrace}\n```\n",
+                            Timestamp::now()
+                        ));
+                        if let Some(warning) = churn::warning(handle) {
+                            handle.log(format_args!("{warning}"));
+                        }
 
-            Ok(ptr)
-        } else {
-            // this really shouldn't happen i think.
-            unreachable!(
-                "invariants specify stupid alloc resize, but pointer not in stupid alloc registry"
-            )
+                        #[cfg(feature = "teaching")]
+                        if let Some(explanation) = teaching::resize(
+                            old_layout,
+                            new_layout,
+                            (new_ptr as usize != addr).then_some((addr, new_ptr as usize)),
+                        ) {
+                            handle.log(format_args!("{}", teaching::quote(&explanation)));
+                        }
+                    });
+
+                    handle.history.push(resizes::Resize {
+                        old_layout,
+                        new_layout,
+                        at: Instant::now(),
+                        backtrace,
+                    });
+
+                    Ok(NonNull::slice_from_raw_parts(
+                        NonNull::new(new_ptr).unwrap(),
+                        new_layout.size(),
+                    ))
+                })
+                // this really shouldn't happen if the program is correct.
+                .unwrap_or_else(|| bad_pointer(addr, "resize"));
+
+            if let Ok(ptr) = result {
+                inspect::notify(HeapEvent::Resized {
+                    old_addr: addr,
+                    new_addr: ptr.as_ptr() as *mut u8 as usize,
+                    old_layout,
+                    new_layout,
+                });
+            }
+
+            result
         }
     }
 }
@@ -580,7 +2808,7 @@ impl fmt::Display for StupidAlloc {
 
 unsafe impl Allocator for StupidAlloc {
     fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
-        self.inner_allocate(layout, |layout| System.allocate(layout))
+        self.inner_allocate(layout, None, false, |layout| System.allocate(layout))
     }
 
     unsafe fn grow(
@@ -641,84 +2869,25 @@ unsafe impl Allocator for StupidAlloc {
     }
 
     fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
-        self.inner_allocate(layout, |layout| System.allocate_zeroed(layout))
+        self.inner_allocate(layout, None, true, |layout| System.allocate_zeroed(layout))
     }
 
     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
         let addr: usize = ptr.as_ptr() as usize;
 
-        // same as allocate, if any of these is nonzero / true, the data was
-        // allocated by system.
-        if LOCAL_SWITCH_OFF.with(|l| l.load(Ordering::SeqCst))
-            || DEALLOCATING.with(|d| d.load(Ordering::SeqCst)) != 0
-            || ALLOCATING.with(|a| a.load(Ordering::SeqCst)) != 0
-        {
+        // same as allocate, if we're busy the data was allocated by system.
+        // otherwise it may come from any thread, stupid or not.
+        if busy() {
             System.deallocate(ptr, layout);
-        } else if STUPID_MAP.read().unwrap().contains_key(&addr) {
-            // tell thread we're deallocating
-            DEALLOCATING.with(|d| d.fetch_add(1, Ordering::SeqCst));
-
-            // remove handle from map
-            let handle = STUPID_MAP.write().unwrap().remove(&addr).unwrap();
-
-            // log deallocation
-            #[cfg(feature = "logging")]
-            {
-                let mut log_file = handle.log_file;
-                writeln!(
-                    log_file,
-                    "# Deallocation\n```\n{}\n```",
-                    Backtrace::capture()
-                )
-                .unwrap();
-            }
-
-            // close graphical window
-            #[cfg(feature = "graphics")]
-            {
-                // if there is a window, we need to destroy that first
-                if let Some(window) = handle.window {
-                    window.tx.send(graphics::Message::Free).unwrap();
-                    // originally i wanted to join the thread of the window
-                    // because that's what good people do, but since de-allocation
-                    // after main has ended means the threads were already killed,
-                    // we run into a weird issue where join panics because
-                    // its thread has been sweeped under itself and killed
-                    // without its consent. so for now, until i find a good way
-                    // of properly join a thread after main, let's just leave
-                    // them be. they're all going to terminate because of the
-                    // free message anyways.
-                    //
-                    // FIXME: find a way to join a thread even when it has been
-                    //        killed by the end of process function.
-                    //window.close();
-                }
-            }
-
-            drop(handle.map); // the map needs to be dropped first
-            drop(handle.file); // and then afterwards the file handle
-
-            //std::thread::sleep(std::time::Duration::from_millis(1000));
-
-            // this needs to be done during a time where DEALLOCATING is true,
-            // since it allocates and you'd end up in an infinite recursion.
-            std::fs::remove_file(handle.path).unwrap();
-
-            // show a lil confirmation message box
-            #[cfg(feature = "interactive")]
-            let _ = MessageDialog::new()
-                .set_type(MessageType::Info)
-                .set_title("Stupid deallocation done!")
-                .set_text(&format!(
-                    "Allocation of layout {layout:?} at address 0x{addr:08x} free'd!"
-                ))
-                .show_confirm()
-                .unwrap();
-
-            // tell thread we're done deallocating
-            DEALLOCATING.with(|a| a.fetch_sub(1, Ordering::SeqCst));
+        } else if STUPID_MAP.contains(addr) {
+            free_registered(addr, layout);
+        } else if budget::unspill(addr) || use_system() || shutdown::late() {
+            // allocated by System, for going over a budget, in a thread where
+            // the stupid alloc is disabled, or after it shut down.
+            system_scope(|| System.deallocate(ptr, layout));
         } else {
-            unreachable!("invariants specify stupid alloc deallocation, but data not present in stupid alloc registry")
+            // double free, or a pointer that doesn't come from here at all.
+            bad_pointer(addr, "free")
         }
     }
 }
@@ -726,8 +2895,7 @@ unsafe impl Allocator for StupidAlloc {
 unsafe impl GlobalAlloc for StupidAlloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         <Self as Allocator>::allocate(self, layout)
-            .unwrap()
-            .as_ptr() as _
+            .map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr() as _)
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
@@ -736,7 +2904,29 @@ unsafe impl GlobalAlloc for StupidAlloc {
 
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
         <Self as Allocator>::allocate_zeroed(self, layout)
-            .unwrap()
-            .as_ptr() as _
+            .map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr() as _)
+    }
+
+    // the default one allocates a new file, copies and frees the old one, when
+    // the file can just be resized.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+        self.grow_or_shrink(
+            NonNull::new(ptr).unwrap(),
+            layout,
+            new_layout,
+            |ptr, old_layout, new_layout| {
+                NonNull::new(GlobalAlloc::realloc(
+                    &System,
+                    ptr.as_ptr(),
+                    old_layout,
+                    new_layout.size(),
+                ))
+                .map(|ptr| NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+                .ok_or(AllocError)
+            },
+        )
+        .map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr() as _)
     }
 }
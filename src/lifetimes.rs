@@ -0,0 +1,264 @@
+use std::{collections::BTreeMap, fmt, sync::Mutex, time::Duration};
+
+use crate::{random, system_scope, AllocHandle, StupidAlloc};
+
+// the number of lifetimes kept for each call site, and for all of them. past
+// that, they're sampled.
+const SAMPLES: usize = 4096;
+
+// the lifetime under which 90% of the allocations of a call site must fall for
+// it to be short-lived.
+const SHORT_LIVED: Duration = Duration::from_millis(1);
+
+/// How long allocations lived, at the 50th, 90th and 99th percentiles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Percentiles {
+    /// The median.
+    pub p50: Duration,
+    /// What 90% of them lived less than.
+    pub p90: Duration,
+    /// What 99% of them lived less than.
+    pub p99: Duration,
+}
+
+/// The lifetimes of freed allocations, from the moment they were allocated to
+/// the moment they were freed, see [`StupidAlloc::lifetime_report()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lifetimes {
+    /// The number of freed allocations.
+    pub count: u64,
+    /// Their total size in bytes, as requested by their last layouts.
+    pub bytes: u64,
+    /// The percentiles of the allocations: half of them lived less than
+    /// `by_count.p50`.
+    pub by_count: Percentiles,
+    /// The percentiles of the bytes: half of the bytes lived less than
+    /// `by_bytes.p50`. Big allocations weigh more.
+    pub by_bytes: Percentiles,
+}
+
+impl Lifetimes {
+    /// Whether 90% of the allocations lived less than a millisecond, which is
+    /// about as long as it takes to make one with the stupid alloc. They would
+    /// be better off on the stack, or in an [`Arena`](crate::Arena).
+    pub fn is_short_lived(&self) -> bool {
+        self.count > 0 && self.by_count.p90 < SHORT_LIVED
+    }
+}
+
+/// The lifetimes of the allocations made at a call site.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteLifetimes {
+    /// Where the allocations were made: the file and line of the
+    /// [`stupid_box!`](crate::stupid_box) or [`stupid_vec!`](crate::stupid_vec)
+    /// that made them, or else the first function outside of the standard
+    /// library and the stupid alloc in their backtrace, or `unknown` if
+    /// backtraces are disabled.
+    pub call_site: String,
+    /// The lifetimes of the allocations made there.
+    pub lifetimes: Lifetimes,
+}
+
+/// The outcome of [`StupidAlloc::lifetime_report()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LifetimeReport {
+    /// The lifetimes of all the freed allocations.
+    pub overall: Lifetimes,
+    /// The lifetimes by call site, the one with the most allocations first.
+    pub sites: Vec<SiteLifetimes>,
+}
+
+impl LifetimeReport {
+    /// The call sites whose allocations are short-lived, see
+    /// [`Lifetimes::is_short_lived()`].
+    pub fn short_lived(&self) -> impl Iterator<Item = &SiteLifetimes> {
+        self.sites
+            .iter()
+            .filter(|site| site.lifetimes.is_short_lived())
+    }
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50 {:.2?}, p90 {:.2?}, p99 {:.2?}",
+            self.p50, self.p90, self.p99
+        )
+    }
+}
+
+impl fmt::Display for LifetimeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "lifetimes of {} freed allocations, {} bytes",
+            self.overall.count, self.overall.bytes
+        )?;
+        writeln!(f, "  by count: {}", self.overall.by_count)?;
+        writeln!(f, "  by bytes: {}", self.overall.by_bytes)?;
+        for site in &self.sites {
+            let lifetimes = &site.lifetimes;
+            write!(
+                f,
+                "  {}: {} allocations, {} bytes",
+                site.call_site, lifetimes.count, lifetimes.bytes
+            )?;
+            if lifetimes.is_short_lived() {
+                write!(f, ", short-lived, try the stack or an arena")?;
+            }
+            writeln!(f)?;
+            writeln!(f, "      by count: {}", lifetimes.by_count)?;
+            writeln!(f, "      by bytes: {}", lifetimes.by_bytes)?;
+        }
+        Ok(())
+    }
+}
+
+// the lifetimes and sizes of some of the allocations freed so far, with the
+// totals of all of them.
+struct Samples {
+    count: u64,
+    bytes: u64,
+    samples: Vec<(Duration, usize)>,
+}
+
+impl Samples {
+    const fn new() -> Self {
+        Self {
+            count: 0,
+            bytes: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    // reservoir sampling: the kept lifetimes are as good as any others.
+    fn add(&mut self, lifetime: Duration, size: usize, random: u64) {
+        self.count += 1;
+        self.bytes += size as u64;
+        if self.samples.len() < SAMPLES {
+            self.samples.push((lifetime, size));
+        } else if let Some(sample) = self.samples.get_mut((random % self.count) as usize) {
+            *sample = (lifetime, size);
+        }
+    }
+
+    // sorts the samples, which is fine since nobody cares about their order.
+    fn lifetimes(&mut self) -> Lifetimes {
+        self.samples.sort_unstable();
+
+        let count = self.samples.len();
+        let by_count = |p: usize| {
+            // the smallest lifetime that at least p% of the samples are under.
+            let index = (count * p).div_ceil(100).max(1) - 1;
+            self.samples.get(index).map_or(Duration::ZERO, |&(l, _)| l)
+        };
+
+        let bytes = self.samples.iter().map(|&(_, size)| size).sum::<usize>();
+        let by_bytes = |p: usize| {
+            let wanted = (bytes * p).div_ceil(100);
+            let mut seen = 0;
+            self.samples
+                .iter()
+                .find(|&&(_, size)| {
+                    seen += size;
+                    seen >= wanted && seen > 0
+                })
+                .map_or(Duration::ZERO, |&(l, _)| l)
+        };
+
+        Lifetimes {
+            count: self.count,
+            bytes: self.bytes,
+            by_count: Percentiles {
+                p50: by_count(50),
+                p90: by_count(90),
+                p99: by_count(99),
+            },
+            by_bytes: Percentiles {
+                p50: by_bytes(50),
+                p90: by_bytes(90),
+                p99: by_bytes(99),
+            },
+        }
+    }
+}
+
+struct Recorder {
+    overall: Samples,
+    sites: BTreeMap<String, Samples>,
+    rng: u64,
+}
+
+static RECORDER: Mutex<Recorder> = Mutex::new(Recorder {
+    overall: Samples::new(),
+    sites: BTreeMap::new(),
+    rng: 0,
+});
+
+// records the lifetime of an allocation made at `call_site` that is being
+// freed. must be called while DEALLOCATING is nonzero.
+pub fn record(handle: &AllocHandle, call_site: &str) {
+    let lifetime = handle.created.elapsed();
+    let size = handle.layout.size();
+
+    let mut recorder = RECORDER.lock().unwrap();
+    let random = random::next(&mut recorder.rng);
+    recorder.overall.add(lifetime, size, random);
+    if !recorder.sites.contains_key(call_site) {
+        recorder.sites.insert(call_site.into(), Samples::new());
+    }
+    if let Some(samples) = recorder.sites.get_mut(call_site) {
+        samples.add(lifetime, size, random);
+    }
+}
+
+fn report() -> LifetimeReport {
+    let mut recorder = RECORDER.lock().unwrap();
+    let overall = recorder.overall.lifetimes();
+    let mut sites = recorder
+        .sites
+        .iter_mut()
+        .map(|(call_site, samples)| SiteLifetimes {
+            call_site: call_site.clone(),
+            lifetimes: samples.lifetimes(),
+        })
+        .collect::<Vec<_>>();
+    sites.sort_by_key(|site| std::cmp::Reverse(site.lifetimes.count));
+
+    LifetimeReport { overall, sites }
+}
+
+impl StupidAlloc {
+    /// Reports how long the allocations freed so far lived, from the moment
+    /// they were allocated to the moment they were freed, at the 50th, 90th
+    /// and 99th percentiles, overall and by call site. Call sites whose
+    /// allocations barely lived are highlighted: making a file for a value
+    /// that's gone a few microseconds later is stupid, even by our standards.
+    ///
+    /// Allocations made with [`stupid_box!`](crate::stupid_box) and
+    /// [`stupid_vec!`](crate::stupid_vec) know their call site. The others
+    /// are grouped by their backtrace, if backtraces are enabled, see
+    /// [`StupidAlloc::set_backtraces()`]. Past a few thousand allocations, the
+    /// percentiles are computed from a random sample of them.
+    ///
+    /// ```
+    /// use stupidalloc::{stupid_box, StupidAlloc};
+    ///
+    /// for i in 0..10u32 {
+    ///     drop(stupid_box!(i));
+    /// }
+    ///
+    /// let report = StupidAlloc.lifetime_report();
+    /// assert!(report.overall.count >= 10);
+    /// assert!(report.short_lived().any(|site| site.lifetimes.count == 10));
+    /// println!("{report}");
+    /// ```
+    pub fn lifetime_report(&self) -> LifetimeReport {
+        // collected in System and then copied for the user.
+        let report = system_scope(report);
+        let user_report = report.clone();
+        system_scope(|| drop(report));
+        user_report
+    }
+}
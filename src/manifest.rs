@@ -0,0 +1,298 @@
+use std::{
+    alloc::Layout,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    panic::Location,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{process_id, system_scope, temp_alloc_dir, user_error, StupidAlloc, Timestamp};
+
+// the name of the manifest, in the folder of the process.
+const NAME: &str = "manifest.tsv";
+
+// the file of the manifest, and the process it belongs to: a forked child
+// gets a manifest of its own.
+static MANIFEST: Mutex<Option<(u32, File)>> = Mutex::new(None);
+
+pub fn lock() -> MutexGuard<'static, Option<(u32, File)>> {
+    MANIFEST.lock().unwrap()
+}
+
+// the manifest is the parent's, the forked child starts its own.
+#[cfg(unix)]
+pub fn forget(mut manifest: MutexGuard<'static, Option<(u32, File)>>) {
+    *manifest = None;
+}
+
+// a line of a manifest about `event`.
+fn line(event: &str, fields: fmt::Arguments) -> String {
+    format!("{}\t{event}\t{fields}\n", Timestamp::now())
+}
+
+// appends a line about `event` to the manifest of this process, opening it if
+// needed. the manifest is only there in case of a crash, so nothing is worth
+// failing over it, and nobody's listening for errors. must be called while
+// ALLOCATING or DEALLOCATING is nonzero.
+fn record(event: &str, fields: fmt::Arguments) {
+    let line = line(event, fields);
+    let pid = process_id();
+
+    let mut manifest = lock();
+    if manifest.as_ref().is_none_or(|(of, _)| *of != pid) {
+        let dir = temp_alloc_dir();
+        *manifest = std::fs::create_dir_all(&dir)
+            .and_then(|()| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(dir.join(NAME))
+            })
+            .and_then(|mut file| {
+                if file.metadata()?.len() == 0 {
+                    writeln!(file, "# stupidalloc manifest of process {pid}")?;
+                    writeln!(file, "# at\tevent\tfields...\tpath")?;
+                }
+                Ok((pid, file))
+            })
+            .ok();
+    }
+
+    if let Some((_, file)) = manifest.as_mut() {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+// the allocation at `addr` was made, in the file at `path`.
+pub fn allocated(addr: usize, layout: Layout, path: &Path) {
+    record(
+        "alloc",
+        format_args!(
+            "0x{addr:x}\t{}\t{}\t{}",
+            layout.size(),
+            layout.align(),
+            path.display()
+        ),
+    );
+}
+
+// the allocation in the file at `path` was resized, and is now at `addr`.
+pub fn resized(addr: usize, layout: Layout, path: &Path) {
+    record(
+        "resize",
+        format_args!(
+            "0x{addr:x}\t{}\t{}\t{}",
+            layout.size(),
+            layout.align(),
+            path.display()
+        ),
+    );
+}
+
+// the allocation in the file at `path` holds a `type_name`, made at
+// `location`.
+pub fn typed(path: &Path, type_name: &str, location: &Location) {
+    record(
+        "type",
+        format_args!("{type_name}\t{location}\t{}", path.display()),
+    );
+}
+
+// the file of an allocation moved from `old` to `new`.
+pub fn moved(old: &Path, new: &Path) {
+    record("move", format_args!("{}\t{}", old.display(), new.display()));
+}
+
+// the allocation in the file at `path` was freed. persistent allocations keep
+// their file.
+pub fn freed(path: &Path) {
+    record("free", format_args!("{}", path.display()));
+}
+
+// the files of the dead process `pid` were moved from its folder `dir` to the
+// folder of persistent allocations, `old` to `new` for each of `moves`. its
+// manifest goes with them, as `<pid>-manifest.tsv`, with a move line for each
+// file so that it still tells what they were.
+pub fn recover(pid: u32, dir: &Path, moves: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    let path = dir.join(NAME);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new().read(true).append(true).open(&path)?;
+    // the last line may have been cut short by the crash, and the next one
+    // must not end up on it.
+    let mut last = *b"\n";
+    if file.seek(SeekFrom::End(0))? > 0 {
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+    }
+    if last[0] != b'\n' {
+        file.write_all(b"\n")?;
+    }
+    for (old, new) in moves {
+        let fields = format_args!("{}\t{}", old.display(), new.display());
+        file.write_all(line("move", fields).as_bytes())?;
+    }
+    drop(file);
+
+    let name = format!("{pid}-{NAME}");
+    std::fs::rename(&path, crate::persist::persistent_dir()?.join(name))
+}
+
+/// An allocation that was still live when a manifest was last written, as
+/// read by [`StupidAlloc::read_manifest()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// The path of its file, wherever the file was last moved.
+    pub path: PathBuf,
+    /// Its address, in the process that wrote the manifest.
+    pub address: usize,
+    /// Its size in bytes.
+    pub size: usize,
+    /// Its alignment in bytes.
+    pub align: usize,
+    /// The type it holds, for the allocations made with
+    /// [`stupid_box!`](crate::stupid_box) and [`stupid_vec!`](crate::stupid_vec).
+    pub type_name: Option<String>,
+    /// Where it was made, for the same allocations.
+    pub location: Option<String>,
+    /// When it was made, as written in the manifest.
+    pub allocated: String,
+    /// When it was last resized, if it was.
+    pub resized: Option<String>,
+}
+
+// replays a manifest: the allocations that were still live when it was last
+// written, by path. lines that don't make sense are skipped, the last one may
+// well have been cut short by a crash.
+fn replay(manifest: &str) -> Vec<ManifestEntry> {
+    let mut live = std::collections::BTreeMap::new();
+    let address = |addr: &str| usize::from_str_radix(addr.strip_prefix("0x")?, 16).ok();
+
+    for line in manifest.lines().filter(|line| !line.starts_with('#')) {
+        match line.split('\t').collect::<Vec<_>>()[..] {
+            [at, "alloc", addr, size, align, path] => {
+                let (Some(address), Ok(size), Ok(align)) =
+                    (address(addr), size.parse(), align.parse())
+                else {
+                    continue;
+                };
+                let entry = ManifestEntry {
+                    path: PathBuf::from(path),
+                    address,
+                    size,
+                    align,
+                    type_name: None,
+                    location: None,
+                    allocated: at.into(),
+                    resized: None,
+                };
+                live.insert(entry.path.clone(), entry);
+            }
+            [at, "resize", addr, size, align, path] => {
+                let (Some(entry), Some(address), Ok(size), Ok(align)) = (
+                    live.get_mut(Path::new(path)),
+                    address(addr),
+                    size.parse(),
+                    align.parse(),
+                ) else {
+                    continue;
+                };
+                entry.address = address;
+                entry.size = size;
+                entry.align = align;
+                entry.resized = Some(at.into());
+            }
+            [_, "type", type_name, location, path] => {
+                if let Some(entry) = live.get_mut(Path::new(path)) {
+                    entry.type_name = Some(type_name.into());
+                    entry.location = Some(location.into());
+                }
+            }
+            [_, "move", old, new] => {
+                if let Some(mut entry) = live.remove(Path::new(old)) {
+                    entry.path = PathBuf::from(new);
+                    live.insert(entry.path.clone(), entry);
+                }
+            }
+            [_, "free", path] => {
+                live.remove(Path::new(path));
+            }
+            _ => {}
+        }
+    }
+
+    live.into_values().collect()
+}
+
+// the live allocations of the manifest at `path`, or in the folder `path`. must
+// be called in system_scope.
+pub fn read(path: &Path) -> io::Result<Vec<ManifestEntry>> {
+    let manifest = match path.is_dir() {
+        true => path.join(NAME),
+        false => path.to_path_buf(),
+    };
+    Ok(replay(&std::fs::read_to_string(manifest)?))
+}
+
+impl StupidAlloc {
+    /// Returns the path of the manifest of this process, in its allocation
+    /// folder. The manifest is a tab-separated journal of everything that
+    /// happens to allocation files: one line per allocation, resize, type,
+    /// move and free, each with a [`Timestamp`] and the path of the file.
+    /// Replaying it tells what each file was, which the program forgets when
+    /// it crashes: `stupidctl manifest` does that for the files left behind.
+    /// It goes away with the folder when the files of dead processes are
+    /// cleaned up, see [`StupidAlloc::cleanup_stale()`], except when they are
+    /// recovered with [`StupidAlloc::recover_stale()`]: then it is moved next
+    /// to them, as `<pid>-manifest.tsv`.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let value = Box::new_in(42u64, StupidAlloc);
+    /// let path = StupidAlloc.file_of(&*value).unwrap();
+    ///
+    /// let manifest = std::fs::read_to_string(StupidAlloc.manifest_path()).unwrap();
+    /// assert!(manifest.contains(&format!("\talloc\t{:p}\t8\t8\t{}\n", &*value, path.display())));
+    /// ```
+    pub fn manifest_path(&self) -> PathBuf {
+        temp_alloc_dir().join(NAME)
+    }
+
+    /// Reads the manifest at `path`, or the one in the allocation folder of a
+    /// process if `path` is a folder, and returns the allocations that were
+    /// still live when it was last written, sorted by path. This is what
+    /// `stupidctl manifest` prints, and what
+    /// [`StupidAlloc::compare_runs()`] compares for manifests. Lines that
+    /// don't make sense are skipped: the last one may well have been cut short
+    /// by a crash.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let value = Box::new_in(42u64, StupidAlloc);
+    /// let path = StupidAlloc.file_of(&*value).unwrap();
+    ///
+    /// let live = StupidAlloc.read_manifest(StupidAlloc.manifest_path()).unwrap();
+    /// let entry = live.iter().find(|entry| entry.path == path).unwrap();
+    /// assert_eq!(entry.address, &*value as *const u64 as usize);
+    /// assert_eq!(entry.size, 8);
+    /// ```
+    pub fn read_manifest(&self, path: impl AsRef<Path>) -> io::Result<Vec<ManifestEntry>> {
+        let path = path.as_ref();
+
+        // read in System and then copied for the user, like the reports.
+        let live = system_scope(|| read(path)).map_err(user_error)?;
+        let user_live = live.clone();
+        system_scope(|| drop(live));
+        Ok(user_live)
+    }
+}
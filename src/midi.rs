@@ -0,0 +1,216 @@
+use std::{
+    alloc::Layout,
+    collections::hash_map::DefaultHasher,
+    fs::{File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    net::{SocketAddr, UdpSocket},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use crate::StupidAlloc;
+
+/// Where the music of the allocator goes, set with
+/// [`StupidAlloc::set_midi()`].
+///
+/// Every allocation plays a note, which is released when the allocation is
+/// freed. The pitch goes up with the base-2 logarithm of the size, the
+/// velocity with the alignment, and the channel depends on the thread.
+/// Resizing releases the note of the old size and plays the one of the new
+/// size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Midi {
+    target: Target,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Osc(SocketAddr),
+    Device(PathBuf),
+}
+
+impl Midi {
+    /// Sends an OSC message over UDP to `addr` for every event, to
+    /// `/stupidalloc/alloc`, `/stupidalloc/free` or `/stupidalloc/resize`.
+    /// The arguments are four integers: the size, the note, the velocity and
+    /// the channel. Resizes have the new size, and the note of the new size.
+    pub fn osc(addr: SocketAddr) -> Self {
+        Self {
+            target: Target::Osc(addr),
+        }
+    }
+
+    /// Writes raw MIDI messages to the file at `path`, like a raw MIDI device
+    /// of ALSA, `/dev/snd/midiC1D0` or so, or a named pipe read by a
+    /// synthesizer.
+    pub fn device(path: impl Into<PathBuf>) -> Self {
+        Self {
+            target: Target::Device(path.into()),
+        }
+    }
+}
+
+enum Output {
+    Osc(UdpSocket, SocketAddr),
+    Device(File),
+}
+
+static OUTPUT: Mutex<Option<Output>> = Mutex::new(None);
+
+// what happened to an allocation, as far as music is concerned.
+#[derive(Clone, Copy)]
+pub enum Event {
+    Alloc(Layout),
+    Free(Layout),
+    Resize(Layout, Layout),
+}
+
+// low notes for small allocations, starting from C2 and going up a whole tone
+// per power of two.
+fn note(size: usize) -> u8 {
+    (36 + 2 * size.checked_ilog2().unwrap_or(0)).min(127) as u8
+}
+
+// strongly aligned allocations are played louder.
+fn velocity(align: usize) -> u8 {
+    (16 * (align.ilog2() + 1)).min(127) as u8
+}
+
+thread_local! {
+    // only there to have an address of its own in every thread.
+    static THREAD: u8 = const { 0 };
+}
+
+// one of the 16 channels, always the same for a thread. asking for the current
+// thread would allocate, which is why it's done this way.
+fn channel() -> u8 {
+    let mut hasher = DefaultHasher::new();
+    THREAD
+        .with(|thread| thread as *const u8 as usize)
+        .hash(&mut hasher);
+    (hasher.finish() % 16) as u8
+}
+
+// an OSC message with integer arguments.
+fn osc_message(address: &str, args: &[i32]) -> Vec<u8> {
+    // strings are null-terminated and padded to a multiple of 4 bytes.
+    fn push_str(out: &mut Vec<u8>, s: &str) {
+        out.extend(s.as_bytes());
+        out.resize((out.len() + 4) / 4 * 4, 0);
+    }
+
+    let mut out = Vec::new();
+    push_str(&mut out, address);
+    push_str(&mut out, &format!(",{}", "i".repeat(args.len())));
+    for arg in args {
+        out.extend(arg.to_be_bytes());
+    }
+    out
+}
+
+impl Output {
+    fn play(&mut self, event: Event) -> io::Result<()> {
+        let channel = channel();
+
+        match self {
+            Output::Osc(socket, addr) => {
+                let (address, layout) = match event {
+                    Event::Alloc(layout) => ("/stupidalloc/alloc", layout),
+                    Event::Free(layout) => ("/stupidalloc/free", layout),
+                    Event::Resize(_, layout) => ("/stupidalloc/resize", layout),
+                };
+                let args = [
+                    layout.size().try_into().unwrap_or(i32::MAX),
+                    note(layout.size()).into(),
+                    velocity(layout.align()).into(),
+                    channel.into(),
+                ];
+                socket.send_to(&osc_message(address, &args), *addr)?;
+            }
+            Output::Device(file) => {
+                let on = |layout: Layout| {
+                    [
+                        0x90 | channel,
+                        note(layout.size()),
+                        velocity(layout.align()),
+                    ]
+                };
+                let off = |layout: Layout| {
+                    [
+                        0x80 | channel,
+                        note(layout.size()),
+                        velocity(layout.align()),
+                    ]
+                };
+                match event {
+                    Event::Alloc(layout) => file.write_all(&on(layout))?,
+                    Event::Free(layout) => file.write_all(&off(layout))?,
+                    Event::Resize(old, new) => file.write_all(&[off(old), on(new)].concat())?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// plays `event`, if there's anyone listening. must be called in system_scope,
+// or while ALLOCATING or DEALLOCATING is nonzero.
+pub fn play(event: Event) {
+    if let Some(output) = OUTPUT.lock().unwrap().as_mut() {
+        // the show must go on.
+        let _ = output.play(event);
+    }
+}
+
+impl StupidAlloc {
+    /// Plays subsequent allocator events as MIDI notes or OSC messages, as
+    /// described by a [`Midi`], or stops with [`None`]. Returns an error if the
+    /// socket or the device can't be opened, in which case nothing changes.
+    ///
+    /// Notes are released by the thread that frees the allocation, on its own
+    /// channel: allocations passed around between threads make for notes that
+    /// never end. It's a feature.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use std::net::UdpSocket;
+    /// use stupidalloc::{Midi, StupidAlloc};
+    ///
+    /// let synthesizer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    /// StupidAlloc.set_midi(Some(Midi::osc(synthesizer.local_addr().unwrap()))).unwrap();
+    /// let stupid_box = Box::new_in(42u64, StupidAlloc);
+    /// StupidAlloc.set_midi(None).unwrap();
+    ///
+    /// let mut message = [0; 64];
+    /// let len = synthesizer.recv(&mut message).unwrap();
+    /// assert!(message[..len].starts_with(b"/stupidalloc/alloc\0"));
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "midi")))]
+    pub fn set_midi(&self, midi: Option<Midi>) -> io::Result<()> {
+        // sockets and files hold no memory, so there's no need for
+        // system_scope.
+        let output = midi
+            .map(|midi| -> io::Result<_> {
+                Ok(match midi.target {
+                    Target::Osc(addr) => {
+                        let any = match addr {
+                            SocketAddr::V4(_) => "0.0.0.0:0",
+                            SocketAddr::V6(_) => "[::]:0",
+                        };
+                        Output::Osc(UdpSocket::bind(any)?, addr)
+                    }
+                    Target::Device(path) => {
+                        Output::Device(OpenOptions::new().write(true).open(path)?)
+                    }
+                })
+            })
+            .transpose()?;
+
+        // the old output gets closed once the lock is released.
+        let old = std::mem::replace(&mut *OUTPUT.lock().unwrap(), output);
+        drop(old);
+        Ok(())
+    }
+}
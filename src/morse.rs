@@ -0,0 +1,185 @@
+use std::{
+    io::Write,
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+use crate::{claim_main_thread, shutdown, system_scope, threads, StupidAlloc};
+
+/// Beeps the size of big allocations in Morse code, set with
+/// [`StupidAlloc::set_morse()`].
+///
+/// The beeps go to the terminal bell, which always rings the same way: the
+/// rhythm tells dots and dashes apart, a dash leaving three times as much
+/// silence after it as a dot. Anything that can make a sound longer can take
+/// over with [`Morse::output()`].
+///
+/// ```
+/// use std::time::Duration;
+/// use stupidalloc::Morse;
+///
+/// // allocations of a mebibyte and more, at about 10 words per minute.
+/// let morse = Morse::new()
+///     .threshold(1 << 20)
+///     .unit(Duration::from_millis(120));
+///
+/// assert_eq!(Morse::encode(42), "....- ..---");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Morse {
+    threshold: usize,
+    unit: Duration,
+    output: fn(bool),
+}
+
+impl Default for Morse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// rings the terminal bell when the sound starts.
+fn bell(on: bool) {
+    if on {
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(b"\x07");
+        let _ = stderr.flush();
+    }
+}
+
+// the number of sizes waiting to be beeped. beeping a number takes seconds, so
+// the ones that don't fit are dropped rather than beeped long after the fact.
+const QUEUE: usize = 4;
+
+impl Morse {
+    /// Beeps allocations of 64 KiB and more on the terminal bell, with a dot
+    /// lasting 60 milliseconds, which is about 20 words per minute.
+    pub fn new() -> Self {
+        Self {
+            threshold: 64 * 1024,
+            unit: Duration::from_millis(60),
+            output: bell,
+        }
+    }
+
+    /// Only beeps allocations of at least `threshold` bytes.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the length of a dot. Dashes and the silence between letters are
+    /// three of them, and the silence between sizes seven.
+    pub fn unit(mut self, unit: Duration) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Sets what makes the sound: `output` is called with `true` when a dot
+    /// or a dash starts, and with `false` when it ends, from a background
+    /// thread where the stupid alloc is disabled.
+    pub fn output(mut self, output: fn(bool)) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Returns `size` in Morse code, with a space between digits.
+    pub fn encode(size: usize) -> String {
+        size.to_string()
+            .bytes()
+            .map(|digit| {
+                let digit = (digit - b'0') as usize;
+                // 1 is .----, 6 is -...., and 0 is -----.
+                (0..5)
+                    .map(|i| match digit {
+                        1..=5 if i < digit => '.',
+                        6..=9 if i >= digit - 5 => '.',
+                        _ => '-',
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn play(&self, code: &str) {
+        let units = |n| std::thread::sleep(self.unit * n);
+
+        for symbol in code.chars() {
+            match symbol {
+                // the silence after the last element makes one unit already.
+                ' ' => units(2),
+                _ => {
+                    (self.output)(true);
+                    units(if symbol == '.' { 1 } else { 3 });
+                    (self.output)(false);
+                    units(1);
+                }
+            }
+        }
+        units(6);
+    }
+}
+
+static MORSE: Mutex<Option<Morse>> = Mutex::new(None);
+
+// the sizes waiting for the beeper thread.
+static BEEPER: OnceLock<SyncSender<usize>> = OnceLock::new();
+
+fn beeper(sizes: Receiver<usize>) {
+    for size in sizes {
+        let morse = *MORSE.lock().unwrap();
+        if let Some(morse) = morse {
+            morse.play(&Morse::encode(size));
+        }
+    }
+}
+
+// beeps `size` if it's big enough, and if the beeper isn't too busy already.
+// must be called while ALLOCATING is nonzero.
+pub fn beep(size: usize) {
+    let big = MORSE
+        .lock()
+        .unwrap()
+        .is_some_and(|morse| size >= morse.threshold);
+
+    // the beeper may be gone once the program is exiting.
+    if let (true, Some(sender), false) = (big, BEEPER.get(), shutdown::late()) {
+        let _ = sender.try_send(size);
+    }
+}
+
+impl StupidAlloc {
+    /// Beeps the size of subsequent allocations in Morse code, as described
+    /// by a [`Morse`], or stops with [`None`]. The beeps are played on a
+    /// background thread, and the allocations don't wait for them: when it
+    /// falls behind, sizes are skipped.
+    ///
+    /// ```no_run
+    /// use stupidalloc::{Morse, StupidAlloc};
+    ///
+    /// StupidAlloc.set_morse(Some(Morse::new()));
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "morse")))]
+    pub fn set_morse(&self, morse: Option<Morse>) {
+        *MORSE.lock().unwrap() = morse;
+
+        system_scope(|| {
+            BEEPER.get_or_init(|| {
+                claim_main_thread();
+                let (sender, sizes) = sync_channel(QUEUE);
+                std::thread::Builder::new()
+                    .name("stupidalloc morse".into())
+                    .spawn(move || {
+                        threads::internal();
+                        beeper(sizes)
+                    })
+                    .unwrap();
+                sender
+            });
+        });
+    }
+}
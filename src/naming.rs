@@ -0,0 +1,98 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::StupidAlloc;
+
+/// How the files of new allocations are named, set with
+/// [`StupidAlloc::set_file_naming()`]. Every scheme gives a different name to
+/// every allocation of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileNaming {
+    /// A zero-padded counter, like `alloc_0000000042.mem`.
+    #[default]
+    Counter,
+    /// An adjective and an animal, like `brave-otter.mem`. Once they are all
+    /// taken, a number gets added, like `brave-otter-2.mem`.
+    Words,
+    /// The size of the allocation in emoji, one per digit, followed by the
+    /// counter, like `🐣🦁-42.mem` for 17 bytes.
+    Emoji,
+}
+
+static NAMING: AtomicU8 = AtomicU8::new(FileNaming::Counter as u8);
+
+const ADJECTIVES: [&str; 32] = [
+    "brave", "calm", "clever", "cosy", "curious", "dizzy", "eager", "fancy", "fluffy", "fuzzy",
+    "gentle", "giddy", "grumpy", "happy", "jolly", "lazy", "lucky", "mighty", "noisy", "plucky",
+    "proud", "quick", "quiet", "shiny", "shy", "silly", "sleepy", "sneaky", "spicy", "sturdy",
+    "tiny", "wobbly",
+];
+
+const ANIMALS: [&str; 32] = [
+    "badger", "beaver", "bison", "crab", "crow", "dingo", "duck", "ferret", "finch", "fox",
+    "gecko", "goose", "hedgehog", "heron", "koala", "lemur", "lynx", "marmot", "moose", "newt",
+    "otter", "owl", "panda", "puffin", "quokka", "raccoon", "seal", "sloth", "stoat", "toad",
+    "walrus", "wombat",
+];
+
+// one per digit, growing with it. none of them is made of several code points,
+// which some file systems and terminals don't take well.
+const DIGITS: [&str; 10] = ["🥚", "🐣", "🐥", "🐔", "🦊", "🐺", "🐻", "🦁", "🐘", "🐋"];
+
+fn naming() -> FileNaming {
+    match NAMING.load(Ordering::SeqCst) {
+        0 => FileNaming::Counter,
+        1 => FileNaming::Words,
+        _ => FileNaming::Emoji,
+    }
+}
+
+// the name of the file of the `n`th allocation, which is `size` bytes. only
+// lowercase letters, digits, dashes and emoji, which every file system takes.
+pub fn file_name(n: u64, size: usize) -> String {
+    match naming() {
+        FileNaming::Counter => format!("alloc_{n:010}.mem"),
+        FileNaming::Words => {
+            let (adjectives, animals) = (ADJECTIVES.len() as u64, ANIMALS.len() as u64);
+            // the adjective changes first, so that consecutive allocations
+            // don't all end up being otters.
+            let adjective = ADJECTIVES[(n % adjectives) as usize];
+            let animal = ANIMALS[(n / adjectives % animals) as usize];
+            match n / (adjectives * animals) {
+                0 => format!("{adjective}-{animal}.mem"),
+                round => format!("{adjective}-{animal}-{}.mem", round + 1),
+            }
+        }
+        FileNaming::Emoji => {
+            let size = size
+                .to_string()
+                .bytes()
+                .map(|digit| DIGITS[(digit - b'0') as usize])
+                .collect::<String>();
+            format!("{size}-{n}.mem")
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Sets how the files of subsequent allocations are named, from
+    /// [`FileNaming::Counter`], the default, to names that are easier to say
+    /// out loud: "go look at `brave-otter.mem`". Files picked by hand with the
+    /// `interactive` feature keep the name they were given.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{FileNaming, StupidAlloc};
+    ///
+    /// StupidAlloc.set_file_naming(FileNaming::Words);
+    /// let stupid_box = Box::new_in(42u32, StupidAlloc);
+    /// StupidAlloc.set_file_naming(FileNaming::Counter);
+    ///
+    /// let path = StupidAlloc.file_of(&*stupid_box).unwrap();
+    /// let name = path.file_stem().unwrap().to_str().unwrap();
+    /// assert!(name.contains('-'));
+    /// ```
+    pub fn set_file_naming(&self, naming: FileNaming) {
+        NAMING.store(naming as u8, Ordering::SeqCst);
+    }
+}
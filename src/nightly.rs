@@ -0,0 +1,61 @@
+// the allocators implement the allocator api of allocator-api2 whatever the
+// features, which is what the rest of the crate names. with the nightly
+// feature, they implement the one of std as well, by forwarding to it, for
+// `Box::new_in()` and the likes.
+
+use std::{
+    alloc::{AllocError, Layout},
+    ptr::NonNull,
+};
+
+use crate::{Allocator, Arena, StupidAlloc, StupidScope};
+
+macro_rules! forward_allocator {
+    ($($allocator:ty),*) => {
+        $(
+            unsafe impl std::alloc::Allocator for $allocator {
+                fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                    Allocator::allocate(self, layout).map_err(|_| AllocError)
+                }
+
+                fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                    Allocator::allocate_zeroed(self, layout).map_err(|_| AllocError)
+                }
+
+                unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                    Allocator::deallocate(self, ptr, layout)
+                }
+
+                unsafe fn grow(
+                    &self,
+                    ptr: NonNull<u8>,
+                    old_layout: Layout,
+                    new_layout: Layout,
+                ) -> Result<NonNull<[u8]>, AllocError> {
+                    Allocator::grow(self, ptr, old_layout, new_layout).map_err(|_| AllocError)
+                }
+
+                unsafe fn grow_zeroed(
+                    &self,
+                    ptr: NonNull<u8>,
+                    old_layout: Layout,
+                    new_layout: Layout,
+                ) -> Result<NonNull<[u8]>, AllocError> {
+                    Allocator::grow_zeroed(self, ptr, old_layout, new_layout)
+                        .map_err(|_| AllocError)
+                }
+
+                unsafe fn shrink(
+                    &self,
+                    ptr: NonNull<u8>,
+                    old_layout: Layout,
+                    new_layout: Layout,
+                ) -> Result<NonNull<[u8]>, AllocError> {
+                    Allocator::shrink(self, ptr, old_layout, new_layout).map_err(|_| AllocError)
+                }
+            }
+        )*
+    };
+}
+
+forward_allocator!(StupidAlloc, Arena, StupidScope);
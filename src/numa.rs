@@ -0,0 +1,170 @@
+use std::{io, mem::MaybeUninit};
+
+use crate::{page_size, StupidAlloc, STUPID_MAP};
+
+// the number of nodes bind_of can name, the same as the kernel's default.
+#[cfg(target_os = "linux")]
+const MAX_NODES: usize = 1024;
+
+// from linux/mempolicy.h, which libc doesn't have: move the pages that are
+// already there to the new node.
+#[cfg(target_os = "linux")]
+const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+
+// the node of the memory page containing `addr`, which must be in memory
+// already: asking doesn't bring it in. no error messages, the registry may
+// well be locked.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn query(addr: usize) -> io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        let page = (addr / page_size() * page_size()) as *mut libc::c_void;
+        let mut status: libc::c_int = 0;
+        // without a list of nodes, move_pages only tells where the pages are.
+        let moved = unsafe {
+            libc::syscall(
+                libc::SYS_move_pages,
+                0,
+                1usize,
+                &page,
+                std::ptr::null::<libc::c_int>(),
+                &mut status,
+                0,
+            )
+        };
+        if moved != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if status < 0 {
+            return Err(io::Error::from_raw_os_error(-status));
+        }
+        Ok(status as usize)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+// the node of the memory page containing `addr`, if it's in memory and the
+// OS tells.
+pub fn node_at(addr: usize) -> Option<usize> {
+    query(addr).ok()
+}
+
+// reads a byte of every page from `start` to `end`, for them to be in memory,
+// on whatever node the OS likes.
+fn touch(start: usize, end: usize) {
+    let mut addr = start;
+    while addr < end {
+        // the bytes may well be uninitialized, which is fine as long as
+        // nobody looks at them.
+        unsafe { std::ptr::read_volatile(addr as *const MaybeUninit<u8>) };
+        addr = (addr / page_size() + 1) * page_size();
+    }
+}
+
+impl StupidAlloc {
+    /// Returns the NUMA node of the memory page `value` starts on, reading it
+    /// first so that it's in memory. On machines with a single node, or
+    /// without NUMA support in the kernel, it's always 0.
+    ///
+    /// Returns an error of kind [`NotFound`](io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc, and of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported) outside of Linux.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let data = Box::new_in([42u8; 1 << 16], StupidAlloc);
+    /// if cfg!(target_os = "linux") {
+    ///     println!("on node {}", StupidAlloc.numa_node_of(&*data).unwrap());
+    /// }
+    /// ```
+    pub fn numa_node_of<T: ?Sized>(&self, value: &T) -> io::Result<usize> {
+        let addr = value as *const T as *const u8 as usize;
+
+        let handle = STUPID_MAP.handle_of(addr).ok_or(io::ErrorKind::NotFound)?;
+        let start = handle.start;
+        drop(handle);
+
+        touch(start, start + 1);
+        query(start)
+    }
+
+    /// Binds the memory of the allocation containing `value` to the NUMA node
+    /// `node`, using `mbind`: its pages are read in, then moved to the node,
+    /// and the ones read back from the file later on are supposed to land
+    /// there too. Pages that another map of the file shares, like the one of
+    /// its graphical window, stay where they are.
+    ///
+    /// Returns an error of kind [`NotFound`](io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc, of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported) for pass-through
+    /// allocations, and outside of Linux, and of kind
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) if there's no such node.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let data = Box::new_in([42u8; 1 << 16], StupidAlloc);
+    /// if cfg!(target_os = "linux") {
+    ///     // every machine has a node 0.
+    ///     StupidAlloc.bind_of(&*data, 0).unwrap();
+    ///     assert_eq!(StupidAlloc.numa_node_of(&*data).unwrap(), 0);
+    /// }
+    /// ```
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    pub fn bind_of<T: ?Sized>(&self, value: &T, node: usize) -> io::Result<()> {
+        let addr = value as *const T as *const u8 as usize;
+
+        let handle = STUPID_MAP.handle_of(addr).ok_or(io::ErrorKind::NotFound)?;
+        // only file-backed memory is bound: the pages of memory from another
+        // allocator are shared with its other allocations.
+        if handle.map.map().is_none() {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        let (start, end) = (handle.start, handle.start + handle.layout.size());
+        drop(handle);
+
+        #[cfg(target_os = "linux")]
+        {
+            if node >= MAX_NODES {
+                return Err(io::ErrorKind::InvalidInput.into());
+            }
+            let mut mask = [0 as libc::c_ulong; MAX_NODES / libc::c_ulong::BITS as usize];
+            let bits = libc::c_ulong::BITS as usize;
+            mask[node / bits] |= 1 << (node % bits);
+
+            touch(start, end);
+            let page = start / page_size() * page_size();
+            let bound = unsafe {
+                libc::syscall(
+                    libc::SYS_mbind,
+                    page,
+                    end - page,
+                    libc::MPOL_BIND,
+                    mask.as_ptr(),
+                    // the kernel wants one more than the number of bits.
+                    MAX_NODES + 1,
+                    MPOL_MF_MOVE,
+                )
+            };
+            if bound != 0 {
+                let error = io::Error::last_os_error();
+                return Err(match error.raw_os_error() {
+                    Some(libc::EINVAL) => io::ErrorKind::InvalidInput.into(),
+                    _ => error,
+                });
+            }
+            Ok(())
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+}
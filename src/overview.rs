@@ -0,0 +1,189 @@
+use std::{
+    io,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{system_scope, threads, user_error, StupidAlloc, STUPID_MAP};
+
+const WHITE: u32 = 0x00FFFFFF;
+// how bright the set bits of the oldest allocations get, out of 255.
+const DIMMEST: f64 = 64.0;
+// the color of the gaps between allocations.
+const GAP: u32 = 0x00303030;
+// the colors of the gaps when allocations are grouped by thread, one per
+// thread, over and over.
+const THREAD_GAPS: [u32; 6] = [
+    0x00A03030, 0x0030A030, 0x003030A0, 0x00A0A030, 0x00A030A0, 0x0030A0A0,
+];
+
+// how quickly allocations dim with age in the overview, in nanoseconds. zero
+// means they don't.
+static AGE_SCALE: AtomicU64 = AtomicU64::new(0);
+
+// the color of the set bits of an allocation that is `age` old: white when
+// it's brand new, dimming from there, to half as bright as it gets after a
+// time scale.
+fn age_color(age: Duration) -> u32 {
+    let scale = AGE_SCALE.load(Ordering::SeqCst);
+    if scale == 0 {
+        return WHITE;
+    }
+
+    let fade = (-(age.as_nanos() as f64) / scale as f64 * std::f64::consts::LN_2).exp();
+    let level = (DIMMEST + (255.0 - DIMMEST) * fade) as u32;
+    level << 16 | level << 8 | level
+}
+
+// draws every live allocation in a cell of a grid, sorted by address, with its
+// bits spread over the cell like in a graphical window: white for set bits, or
+// dimmer with age if asked to, black for the others. with a folder per thread,
+// they're grouped by thread first, and the gaps around them take the color of
+// their thread. the pixels are 0x00RRGGBB, row by row. must be called in
+// system_scope.
+pub fn render(width: usize, height: usize) -> Vec<u32> {
+    let mut pixels = vec![0; width * height];
+
+    let map = STUPID_MAP.read_all();
+    let mut handles = map.iter().collect::<Vec<_>>();
+    let by_thread = threads::dirs();
+    match by_thread {
+        true => handles.sort_by_key(|(&addr, handle)| (handle.thread, addr)),
+        false => handles.sort_by_key(|(&addr, _)| addr),
+    }
+    if handles.is_empty() || width == 0 || height == 0 {
+        return pixels;
+    }
+
+    // about as many columns as rows, once stretched to the picture.
+    let count = handles.len();
+    let columns = ((count * width) as f64 / height as f64).sqrt().ceil() as usize;
+    let columns = columns.clamp(1, count);
+    let rows = count.div_ceil(columns);
+    let (cell_width, cell_height) = (width / columns, height / rows);
+
+    for (i, (_, handle)) in handles.into_iter().enumerate() {
+        let (left, top) = (i % columns * cell_width, i / columns * cell_height);
+        let offset = handle.offset();
+        let data = &handle.map[offset..offset + handle.layout.size()];
+        let bits = data.len() * 8;
+
+        // a pixel of gap on each side, when there's room for it.
+        let gap = usize::from(cell_width > 2 && cell_height > 2);
+        let (inner_width, inner_height) = (cell_width - 2 * gap, cell_height - 2 * gap);
+        let area = inner_width * inner_height;
+        let set = age_color(handle.created.elapsed());
+        let gap_color = match by_thread {
+            true => THREAD_GAPS[handle.thread % THREAD_GAPS.len()],
+            false => GAP,
+        };
+
+        for y in 0..cell_height {
+            let row = &mut pixels[(top + y) * width + left..][..cell_width];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let inside =
+                    (gap..cell_width - gap).contains(&x) && (gap..cell_height - gap).contains(&y);
+                *pixel = if !inside {
+                    gap_color
+                } else {
+                    // the bits get stretched or squeezed to fill the cell.
+                    let bit = ((y - gap) * inner_width + x - gap) * bits / area;
+                    match data.get(bit / 8) {
+                        Some(byte) if byte >> (7 - bit % 8) & 1 == 1 => set,
+                        _ => 0,
+                    }
+                };
+            }
+        }
+    }
+
+    pixels
+}
+
+// encodes the pixels as a 24-bit BMP file, which every platform knows how to
+// show and which doesn't need a crate.
+pub fn bmp(pixels: &[u32], width: usize, height: usize) -> Vec<u8> {
+    // rows are padded to a multiple of 4 bytes.
+    let stride = (width * 3).div_ceil(4) * 4;
+    let (header, size) = (14 + 40, stride * height);
+
+    let mut out = Vec::with_capacity(header + size);
+    out.extend(b"BM");
+    out.extend(((header + size) as u32).to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((header as u32).to_le_bytes());
+
+    out.extend(40u32.to_le_bytes());
+    out.extend((width as i32).to_le_bytes());
+    out.extend((height as i32).to_le_bytes());
+    out.extend(1u16.to_le_bytes()); // planes
+    out.extend(24u16.to_le_bytes()); // bits per pixel
+    out.extend([0; 4]); // no compression
+    out.extend((size as u32).to_le_bytes());
+    out.extend(2835i32.to_le_bytes()); // 72 dpi, not that anyone cares
+    out.extend(2835i32.to_le_bytes());
+    out.extend([0; 8]);
+
+    // the bottom row comes first.
+    for row in pixels.chunks(width.max(1)).rev() {
+        let start = out.len();
+        for pixel in row {
+            out.extend(&pixel.to_le_bytes()[..3]);
+        }
+        out.resize(start + stride, 0);
+    }
+
+    out
+}
+
+impl StupidAlloc {
+    /// Saves a picture of every live allocation to `path`, as a BMP file of
+    /// `width` by `height` pixels. Each allocation gets a cell of a grid,
+    /// sorted by address, where its bits are drawn like in a graphical window,
+    /// stretched to fill the cell. With [`StupidAlloc::set_thread_dirs()`],
+    /// the cells are grouped by thread, and framed with the color of their
+    /// thread. With [`StupidAlloc::set_overview_age_scale()`], set bits are
+    /// dimmer the older their allocation is.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in(u64::MAX, StupidAlloc);
+    /// let path = std::env::temp_dir().join("stupid_heap.bmp");
+    /// StupidAlloc.save_heap_overview(&path, 64, 48).unwrap();
+    ///
+    /// let bmp = std::fs::read(&path).unwrap();
+    /// assert!(bmp.starts_with(b"BM"));
+    /// ```
+    pub fn save_heap_overview(
+        &self,
+        path: impl AsRef<Path>,
+        width: usize,
+        height: usize,
+    ) -> io::Result<()> {
+        let path = path.as_ref();
+        system_scope(|| std::fs::write(path, bmp(&render(width, height), width, height)))
+            .map_err(user_error)
+    }
+
+    /// Colors the allocations of the heap overview and of the wallpaper by
+    /// age, or not at all with [`None`], which is the default. The set bits
+    /// of new allocations are white, and lose half of their brightness every
+    /// `scale`, down to a dim gray, so that the allocations that stick around
+    /// stand out from the ones that come and go.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// StupidAlloc.set_overview_age_scale(Some(Duration::from_secs(10)));
+    /// ```
+    pub fn set_overview_age_scale(&self, scale: Option<Duration>) {
+        let nanos = scale.map_or(0, |scale| {
+            u64::try_from(scale.as_nanos()).unwrap_or(u64::MAX).max(1)
+        });
+        AGE_SCALE.store(nanos, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    alloc::Layout,
+    fmt,
+    fs::OpenOptions,
+    io,
+    path::{Component, Path, PathBuf},
+    ptr::NonNull,
+};
+
+use allocator_api2::boxed::Box;
+
+#[cfg(feature = "logging")]
+use std::io::Write;
+
+use crate::{
+    backing::Backing, backtraces, keep_open, system_scope, user_error, AllocHandle, MmapOptions,
+    Redzones, StupidAlloc, STUPID_MAP,
+};
+
+// registers the existing file at `path` as an allocation holding a `T`, whose
+// file is kept when it gets freed. `event` starts the section of the log about
+// it. returns none if the file doesn't have the size of a `T`. needs to be
+// called while ALLOCATING is nonzero.
+#[allow(unused_variables)]
+pub fn adopt_file<T>(path: &Path, event: fmt::Arguments) -> Option<NonNull<T>> {
+    let layout = Layout::new::<T>();
+    let file = OpenOptions::new().read(true).write(true).open(path).ok()?;
+
+    if layout.size() == 0 || file.metadata().ok()?.len() != layout.size() as u64 {
+        return None;
+    }
+
+    let mut map = unsafe { MmapOptions::new().map_mut(&file).ok()? };
+    let ptr = NonNull::new(map.as_mut_ptr() as *mut T)?;
+    crate::checksum::check_persistent(path, &map);
+
+    let backtrace = backtraces::capture(backtraces::Event::Alloc);
+
+    #[cfg(feature = "logging")]
+    let log_file = {
+        let mut log_path = path.to_path_buf();
+        log_path.set_extension("md");
+
+        let mut log_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)
+            .ok()?;
+
+        writeln!(
+            log_file,
+            "{event}\n- Layout: {layout:?}\n```\n{backtrace}\n```\n"
+        )
+        .ok()?;
+
+        log_file
+    };
+
+    #[cfg(feature = "watch")]
+    crate::watch::watch(path);
+
+    let addr = ptr.as_ptr() as usize;
+    let mut handle = AllocHandle {
+        map: Backing::Map(map),
+        file: keep_open(file),
+        path: path.to_path_buf(),
+        layout,
+        redzones: Redzones {
+            guard_pages: false,
+            canaries: false,
+            huge_page: None,
+        },
+        backtrace,
+        persistent: true,
+        pinned: false,
+        type_name: Some(std::any::type_name::<T>()),
+        location: None,
+        arena: None,
+        thread: crate::threads::number(),
+        created: std::time::Instant::now(),
+        resizes: 0,
+        history: Vec::new(),
+        user_data: None,
+        #[cfg(feature = "time-travel")]
+        versions: 0,
+        checksum: None,
+        #[cfg(feature = "graphics")]
+        window: None,
+        #[cfg(feature = "logging")]
+        log_file,
+        #[cfg(feature = "watch")]
+        touched: std::time::Instant::now(),
+        #[cfg(feature = "watch")]
+        edited: false,
+    };
+
+    // the next flush checks against what's there now, whatever it was.
+    handle.store_checksum();
+
+    #[cfg(feature = "valgrind")]
+    handle.valgrind_malloclike();
+
+    crate::manifest::allocated(addr, layout, path);
+    STUPID_MAP.insert(addr, handle);
+
+    Some(ptr)
+}
+
+// checks that `name`, which comes from the user, names a file or a folder right
+// inside of one of ours: not empty, without separators, and not `.` or `..`,
+// which would get out of it.
+pub fn check_name(name: &str) -> io::Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(std::path::is_separator) => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name:?} can't be a file name"),
+        )),
+    }
+}
+
+// where the file of a persistent allocation named `key` lives. it has to
+// outlive the folder of the process.
+pub fn persistent_path(key: &str) -> io::Result<PathBuf> {
+    check_name(key)?;
+    Ok(persistent_dir()?.join(format!("{key}.mem")))
+}
+
+// the folder of persistent allocations, created if needed.
+pub fn persistent_dir() -> io::Result<PathBuf> {
+    let dir = crate::base_alloc_dir().join("persistent");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+impl StupidAlloc {
+    /// Makes the allocation containing `value` persistent under the name
+    /// `key`: its file is moved to a dedicated folder, and won't be deleted
+    /// when the allocation is freed, nor when the program exits. On the next
+    /// run, the allocation can be brought back with [`StupidAlloc::restore()`].
+    ///
+    /// `key` is used as a file name, so keep it simple: an error of kind
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) is returned if it's
+    /// empty, `.` or `..`, or has path separators. Persisting an allocation
+    /// under an already existing key replaces the old file.
+    /// Allocations with guard pages or canaries can't be persisted, and
+    /// neither can pass-through allocations.
+    pub fn persist_of<T: ?Sized>(&self, value: &T, key: &str) -> io::Result<()> {
+        // the new path ends up in the handle, so it must come from System.
+        let result = system_scope(|| {
+            let addr = value as *const T as *const u8 as usize;
+            let mut handle = STUPID_MAP
+                .handle_of_mut(addr)
+                .ok_or(io::ErrorKind::NotFound)?;
+
+            if handle.redzones.guard_pages || handle.redzones.canaries {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "allocations with redzones can't be persisted",
+                ));
+            }
+
+            if !handle.has_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "pass-through allocations have no file to persist",
+                ));
+            }
+
+            let path = persistent_path(key)?;
+            std::fs::rename(&handle.path, &path)?;
+            // the file is gone from the old path, so the handle follows it
+            // before anything else can go wrong.
+            let old_path = std::mem::replace(&mut handle.path, path);
+            handle.persistent = true;
+            crate::manifest::moved(&old_path, &handle.path);
+
+            // the log stays where it was, and failing to write to it is not
+            // worth failing the persist over.
+            #[cfg(feature = "logging")]
+            {
+                let handle = &mut *handle;
+                let logged = writeln!(
+                    handle.log_file,
+                    "## Persisted\nKey: `{key}`, moved to {}\n",
+                    handle.path.to_string_lossy()
+                );
+                let log = old_path.with_extension("md");
+                let _ = crate::error::check(logged, "write to the log", &log);
+            }
+
+            // the watcher might be waiting on the registry lock, so it must be
+            // released before talking to the watcher.
+            #[cfg(feature = "watch")]
+            {
+                let path = handle.path.clone();
+                drop(handle);
+                crate::watch::unwatch(&old_path);
+                crate::watch::watch(&path);
+            }
+
+            Ok(())
+        });
+
+        result.map_err(user_error)
+    }
+
+    /// Brings back an allocation that was persisted under the name `key` with
+    /// [`StupidAlloc::persist_of()`], possibly during a previous run of the
+    /// program. The allocation stays persistent: dropping the returned box
+    /// won't delete the file. Returns [`None`] if there is no such allocation,
+    /// if its size doesn't match `T`, or if it's live already in this process,
+    /// which would make two boxes of the same memory.
+    ///
+    /// # Safety
+    /// The contents of the file must be a valid `T`. Since the file can come
+    /// from another run (or another version) of the program, this is only
+    /// reasonable for plain data: no references, no pointers, no heap
+    /// allocations.
+    pub unsafe fn restore<T>(&self, key: &str) -> Option<Box<T, StupidAlloc>> {
+        // same as allocating: everything that ends up in the handle must come
+        // from System.
+        let ptr = system_scope(|| {
+            let path = persistent_path(key).ok()?;
+            if STUPID_MAP
+                .read_all()
+                .iter()
+                .any(|(_, handle)| handle.path == path)
+            {
+                return None;
+            }
+            adopt_file::<T>(&path, format_args!("# Restored\n- Key: `{key}`"))
+        });
+
+        ptr.map(|ptr| unsafe { Box::from_raw_in(ptr.as_ptr(), StupidAlloc) })
+    }
+
+    /// Restores the allocation persisted under the name `key` like
+    /// [`StupidAlloc::restore()`] does, or if there is none, allocates
+    /// `value` and makes it persistent under that name. Handy for values that
+    /// should carry over from one run to the next.
+    ///
+    /// # Safety
+    /// Same as [`StupidAlloc::restore()`].
+    pub unsafe fn restore_or_persist<T>(&self, key: &str, value: T) -> Box<T, StupidAlloc> {
+        unsafe { self.restore(key) }.unwrap_or_else(|| {
+            let value = Box::new_in(value, StupidAlloc);
+            // if it can't be persisted, it's just a normal allocation.
+            let _ = self.persist_of(&*value, key);
+            value
+        })
+    }
+}
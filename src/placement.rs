@@ -0,0 +1,184 @@
+// there are no windows to place on wasi, only the settings.
+#![cfg_attr(target_os = "wasi", allow(dead_code))]
+
+use std::sync::Mutex;
+
+use crate::StupidAlloc;
+
+// the monitor assumed when none was given. minifb can't tell how big the
+// actual one is, and most of them are at least this big.
+const DEFAULT_MONITOR: Monitor = Monitor {
+    x: 0,
+    y: 0,
+    width: 1920,
+    height: 1080,
+};
+
+// how far apart cascaded windows are, in pixels.
+const CASCADE_STEP: isize = 32;
+
+/// How new windows are arranged on their monitor, see
+/// [`WindowPlacement::layout()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowLayout {
+    /// In the middle of the monitor, on top of each other.
+    #[default]
+    Centered,
+    /// From the top left corner of the monitor, each window a bit lower and
+    /// to the right of the previous one.
+    Cascaded,
+    /// Side by side from the top left corner of the monitor, in rows.
+    Tiled,
+}
+
+// a rectangle of the desktop, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Monitor {
+    x: isize,
+    y: isize,
+    width: usize,
+    height: usize,
+}
+
+/// Where new graphical windows open, for when they belong on the projector
+/// rather than on the laptop. Set with [`StupidAlloc::set_window_placement()`].
+///
+/// minifb can't list the monitors, so the one to use is given as the
+/// rectangle it covers on the desktop, as shown by the display settings of
+/// the operating system.
+///
+/// ```
+/// use stupidalloc::{WindowLayout, WindowPlacement};
+///
+/// // a 1280x720 projector, to the right of a 1920x1080 laptop panel.
+/// let placement = WindowPlacement::new()
+///     .monitor(1920, 0, 1280, 720)
+///     .layout(WindowLayout::Tiled);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowPlacement {
+    monitor: Monitor,
+    layout: WindowLayout,
+}
+
+impl Default for WindowPlacement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WindowPlacement {
+    /// Centers windows on a 1920x1080 monitor at the top left of the desktop,
+    /// until told otherwise by the other methods.
+    pub fn new() -> Self {
+        Self {
+            monitor: DEFAULT_MONITOR,
+            layout: WindowLayout::Centered,
+        }
+    }
+
+    /// Opens windows on the monitor whose top left corner is at `x`, `y` on
+    /// the desktop, and which is `width` by `height` pixels.
+    pub fn monitor(mut self, x: isize, y: isize, width: usize, height: usize) -> Self {
+        self.monitor = Monitor {
+            x,
+            y,
+            width,
+            height,
+        };
+        self
+    }
+
+    /// Arranges windows on the monitor according to `layout`.
+    pub fn layout(mut self, layout: WindowLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+}
+
+// the placement, and where the next cascaded or tiled window goes, relative to
+// the monitor: its top left corner, and the height of the row of tiles.
+struct Placer {
+    placement: WindowPlacement,
+    next: (isize, isize),
+    row_height: usize,
+}
+
+static PLACER: Mutex<Option<Placer>> = Mutex::new(None);
+
+// the size of the monitor windows open on, to fit them to it.
+pub fn monitor_size() -> (usize, usize) {
+    let monitor = PLACER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(DEFAULT_MONITOR, |placer| placer.placement.monitor);
+    (monitor.width, monitor.height)
+}
+
+// where a new window of `size` pixels goes on the desktop, or none to let the
+// operating system decide.
+pub fn place((width, height): (usize, usize)) -> Option<(isize, isize)> {
+    let mut placer = PLACER.lock().unwrap();
+    let placer = placer.as_mut()?;
+    let monitor = placer.placement.monitor;
+    let (width, height) = (width as isize, height as isize);
+    let (monitor_width, monitor_height) = (monitor.width as isize, monitor.height as isize);
+
+    let (x, y) = match placer.placement.layout {
+        WindowLayout::Centered => ((monitor_width - width) / 2, (monitor_height - height) / 2),
+        WindowLayout::Cascaded => {
+            // back to the corner once the windows would fall off the monitor.
+            let (mut x, mut y) = placer.next;
+            if x + width > monitor_width || y + height > monitor_height {
+                (x, y) = (0, 0);
+            }
+            placer.next = (x + CASCADE_STEP, y + CASCADE_STEP);
+            (x, y)
+        }
+        WindowLayout::Tiled => {
+            let (mut x, mut y) = placer.next;
+            // a new row once the current one is full, and back to the first
+            // one once the monitor is.
+            if x > 0 && x + width > monitor_width {
+                (x, y) = (0, y + placer.row_height as isize);
+                placer.row_height = 0;
+            }
+            if y > 0 && y + height > monitor_height {
+                (x, y) = (0, 0);
+                placer.row_height = 0;
+            }
+            placer.next = (x + width, y);
+            placer.row_height = placer.row_height.max(height as usize);
+            (x, y)
+        }
+    };
+
+    Some((monitor.x + x.max(0), monitor.y + y.max(0)))
+}
+
+impl StupidAlloc {
+    /// Sets where subsequent graphical windows open, or lets the operating
+    /// system decide with [`None`], which is the default. Windows keep their
+    /// position when they're resized, and windows that are already open stay
+    /// where they are.
+    ///
+    /// The monitor of the placement is also the one windows opened with
+    /// [`StupidAlloc::AUTO_COLUMNS`] are fitted to.
+    ///
+    /// ```
+    /// use stupidalloc::{StupidAlloc, WindowLayout, WindowPlacement};
+    ///
+    /// StupidAlloc.set_window_placement(Some(
+    ///     WindowPlacement::new().layout(WindowLayout::Cascaded),
+    /// ));
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub fn set_window_placement(&self, placement: Option<WindowPlacement>) {
+        *PLACER.lock().unwrap() = placement.map(|placement| Placer {
+            placement,
+            next: (0, 0),
+            row_height: 0,
+        });
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+#[cfg(unix)]
+use std::sync::MutexGuard;
+
+use lazy_static::lazy_static;
+
+use crate::{system_scope, temp_alloc_file_path, user_error, StupidAlloc};
+
+// a file created ahead of time, waiting for an allocation.
+pub struct Pooled {
+    path: PathBuf,
+    file: File,
+    size: usize,
+}
+
+lazy_static! {
+    // like everything in the handles, the pool only ever gets allocated and
+    // freed while ALLOCATING is nonzero.
+    static ref POOL: Mutex<Vec<Pooled>> = Mutex::new(Vec::new());
+}
+
+// locks the pool while forking, so that nobody is in the middle of claiming.
+#[cfg(unix)]
+pub fn lock() -> MutexGuard<'static, Vec<Pooled>> {
+    POOL.lock().unwrap()
+}
+
+// the files of the pool are the parent's, the forked child makes its own.
+#[cfg(unix)]
+pub fn forget(mut pool: MutexGuard<'static, Vec<Pooled>>) {
+    pool.clear();
+}
+
+// takes the smallest file of the pool that can hold `len` bytes, if there is
+// one. must be called while ALLOCATING is nonzero.
+pub fn claim(len: usize) -> Option<(PathBuf, File)> {
+    let mut pool = POOL.lock().unwrap();
+    let index = pool
+        .iter()
+        .enumerate()
+        .filter(|(_, pooled)| pooled.size >= len)
+        .min_by_key(|(_, pooled)| pooled.size)
+        .map(|(index, _)| index)?;
+
+    let pooled = pool.swap_remove(index);
+    Some((pooled.path, pooled.file))
+}
+
+impl StupidAlloc {
+    /// Creates `count` allocation files of `size` bytes ahead of time. New
+    /// allocations of at most `size` bytes then claim one of them instead of
+    /// creating their own, which saves a few system calls. Once the pool is
+    /// empty, files are created on demand again, as usual.
+    ///
+    /// This can be called several times with different sizes, and from another
+    /// thread to fill the pool in the background. Allocations made through an
+    /// [`Arena`](crate::Arena), in pass-through mode, or with the `interactive`
+    /// feature don't use the pool.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// StupidAlloc.fill_file_pool(16, 4096).unwrap();
+    /// let stupid_box = Box::new_in([0u8; 1024], StupidAlloc);
+    ///
+    /// assert!(StupidAlloc.file_of(&*stupid_box).is_some());
+    /// StupidAlloc.empty_file_pool().unwrap();
+    /// ```
+    pub fn fill_file_pool(&self, count: usize, size: usize) -> io::Result<()> {
+        system_scope(|| {
+            for _ in 0..count {
+                let path = temp_alloc_file_path(None, size)?;
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .truncate(true)
+                    .create(true)
+                    .open(&path)?;
+                file.set_len(size as u64)?;
+
+                POOL.lock().unwrap().push(Pooled { path, file, size });
+            }
+
+            Ok(())
+        })
+        .map_err(user_error)
+    }
+
+    /// Removes the files of the pool that weren't claimed by an allocation,
+    /// see [`StupidAlloc::fill_file_pool()`].
+    pub fn empty_file_pool(&self) -> io::Result<()> {
+        system_scope(|| {
+            let pool = std::mem::take(&mut *POOL.lock().unwrap());
+            pool.into_iter()
+                .try_for_each(|pooled| std::fs::remove_file(pooled.path))
+        })
+        .map_err(user_error)
+    }
+}
@@ -0,0 +1,157 @@
+//! Replacements for the C library's allocator, exported from the `cdylib`
+//! build of the crate with the `preload` feature, so that any dynamically
+//! linked program can be subjected to the stupid alloc:
+//!
+//! ```sh
+//! cargo rustc --release --lib --crate-type cdylib --features preload
+//! LD_PRELOAD=target/release/libstupidalloc.so ls
+//! ```
+//!
+//! Only allocations of at least `STUPIDALLOC_THRESHOLD` bytes (128 KiB by
+//! default) get a file, as the program would never boot otherwise. Smaller
+//! ones go to the C library, and so do the allocations of every thread but the
+//! first one making a big allocation, just like with the [C API](crate::capi).
+//! Small allocations growing past the threshold move to a file.
+//!
+//! Forked processes get a copy of the allocations of their parent, in files
+//! of their own. Those are removed right away, since processes tend to call
+//! `exec` right after forking, and would leave them behind forever. Each
+//! process keeps its files in a folder named after its process ID, and the
+//! files of allocations that are still around when a process exits or calls
+//! `exec` stay there.
+//!
+//! This is only available on Linux with glibc. The feature replaces `malloc`
+//! for whatever links the crate, so it's not meant for Rust programs.
+
+use std::{
+    ffi::{c_void, CStr},
+    sync::OnceLock,
+};
+
+use crate::{
+    capi::{
+        real_calloc, real_free, real_malloc, registered_layout, stupid_calloc, stupid_free,
+        stupid_malloc, stupid_realloc,
+    },
+    stats, use_system,
+};
+
+// where glibc starts mapping memory on its own anyway.
+const DEFAULT_THRESHOLD: usize = 128 * 1024;
+
+// the size from which allocations get a file.
+pub(crate) fn threshold() -> usize {
+    static THRESHOLD: OnceLock<usize> = OnceLock::new();
+
+    *THRESHOLD.get_or_init(|| {
+        // std::env::var would allocate, and we are the allocator.
+        let value = unsafe { libc::getenv(c"STUPIDALLOC_THRESHOLD".as_ptr()) };
+        if value.is_null() {
+            return DEFAULT_THRESHOLD;
+        }
+
+        unsafe { CStr::from_ptr(value) }
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(DEFAULT_THRESHOLD)
+    })
+}
+
+// glibc has no other name for malloc_usable_size, so the real one is looked up
+// past this library. dlsym may allocate, which is fine since small allocations
+// don't come back here.
+unsafe fn real_usable_size(ptr: *mut c_void) -> usize {
+    static REAL: OnceLock<usize> = OnceLock::new();
+
+    let real = *REAL.get_or_init(|| unsafe {
+        libc::dlsym(libc::RTLD_NEXT, c"malloc_usable_size".as_ptr()) as usize
+    });
+
+    match real {
+        0 => 0,
+        real => {
+            let real: unsafe extern "C" fn(*mut c_void) -> usize = std::mem::transmute(real);
+            real(ptr)
+        }
+    }
+}
+
+/// Replaces `malloc`.
+///
+/// # Safety
+/// Same as `malloc`.
+#[no_mangle]
+pub unsafe extern "C" fn malloc(size: usize) -> *mut c_void {
+    if size < threshold() {
+        stats::record_below_threshold();
+        real_malloc(size)
+    } else {
+        stupid_malloc(size)
+    }
+}
+
+/// Replaces `calloc`.
+///
+/// # Safety
+/// Same as `calloc`.
+#[no_mangle]
+pub unsafe extern "C" fn calloc(count: usize, size: usize) -> *mut c_void {
+    match count.checked_mul(size) {
+        Some(total) if total >= threshold() => stupid_calloc(count, size),
+        _ => {
+            stats::record_below_threshold();
+            real_calloc(count, size)
+        }
+    }
+}
+
+/// Replaces `realloc`.
+///
+/// # Safety
+/// Same as `realloc`.
+#[no_mangle]
+pub unsafe extern "C" fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return malloc(size);
+    }
+
+    if size >= threshold() && !use_system() && registered_layout(ptr).is_none() {
+        // a small allocation got big: it moves to a file.
+        let new = stupid_malloc(size);
+        if !new.is_null() {
+            let len = real_usable_size(ptr).min(size);
+            std::ptr::copy_nonoverlapping(ptr as *const u8, new as *mut u8, len);
+            real_free(ptr);
+        }
+        return new;
+    }
+
+    stupid_realloc(ptr, size)
+}
+
+/// Replaces `free`.
+///
+/// # Safety
+/// Same as `free`.
+#[no_mangle]
+pub unsafe extern "C" fn free(ptr: *mut c_void) {
+    stupid_free(ptr)
+}
+
+/// Replaces `malloc_usable_size`, which some programs use to find out how
+/// much room they have.
+///
+/// # Safety
+/// Same as `malloc_usable_size`.
+#[no_mangle]
+pub unsafe extern "C" fn malloc_usable_size(ptr: *mut c_void) -> usize {
+    if ptr.is_null() {
+        return 0;
+    }
+
+    match registered_layout(ptr) {
+        Some(layout) => layout.size(),
+        None => real_usable_size(ptr),
+    }
+}
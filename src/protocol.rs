@@ -0,0 +1,258 @@
+//! The protocol spoken between the stupid alloc and remote viewers, with the
+//! `protocol` feature, so that viewers written by someone else keep working
+//! across versions of the crate.
+//!
+//! Everything is a [`Frame`]. Both ends start with a [`Hello`], saying which
+//! versions of the protocol and which [`Capability`] they have, and
+//! [`Hello::negotiate()`] picks what they have in common. Then the stupid
+//! alloc sends [`Event`]s as the heap changes, and the contents of
+//! allocations as [`Chunk`]s.
+//!
+//! The types are [serde](https://serde.rs) types, and the encoding is up to
+//! the transport: JSON over a WebSocket, anything more compact over TCP.
+//! Frames, capabilities and events that a viewer doesn't know about, because
+//! they're newer than it is, decode as `Unknown`, and are meant to be
+//! skipped.
+//!
+//! ```
+//! use stupidalloc::protocol::{Capability, Hello};
+//!
+//! let ours = Hello::new(vec![Capability::Events, Capability::Contents]);
+//! let theirs = Hello {
+//!     software: "my viewer".into(),
+//!     capabilities: vec![Capability::Events],
+//!     ..ours.clone()
+//! };
+//!
+//! let session = ours.negotiate(&theirs).unwrap();
+//! assert_eq!(session.version, stupidalloc::protocol::VERSION);
+//! assert_eq!(session.capabilities, [Capability::Events]);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{process_id, HeapEvent, HeapInspector, StupidAlloc};
+
+/// The version of the protocol spoken by this version of the crate. Goes up
+/// when frames change in a way older viewers can't skip over.
+pub const VERSION: u32 = 1;
+
+/// The oldest version of the protocol this version of the crate still speaks.
+pub const MIN_VERSION: u32 = 1;
+
+/// Something one end of the connection can do, announced in its [`Hello`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Allocations, frees and resizes, as [`Event`]s.
+    Events,
+    /// The contents of allocations, as [`Chunk`]s.
+    Contents,
+    /// The types of allocations, in the [`Event`]s that have them.
+    Types,
+    /// A capability from a newer version of the protocol.
+    #[serde(other)]
+    Unknown,
+}
+
+/// The first frame sent by each end of the connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    /// The newest version of the protocol spoken by this end.
+    pub version: u32,
+    /// The oldest version of the protocol spoken by this end.
+    pub min_version: u32,
+    /// What's at this end, for humans: `stupidalloc 0.2.1`, or the name of a
+    /// viewer.
+    pub software: String,
+    /// The process at this end.
+    pub pid: u32,
+    /// What this end can do.
+    pub capabilities: Vec<Capability>,
+}
+
+/// What both ends of a connection agreed on, returned by
+/// [`Hello::negotiate()`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// The version of the protocol to speak, the newest both ends know.
+    pub version: u32,
+    /// The capabilities both ends have.
+    pub capabilities: Vec<Capability>,
+}
+
+impl Hello {
+    /// The hello of this version of the crate, in this process, offering
+    /// `capabilities`.
+    pub fn new(capabilities: Vec<Capability>) -> Self {
+        Self {
+            version: VERSION,
+            min_version: MIN_VERSION,
+            software: format!("stupidalloc {}", env!("CARGO_PKG_VERSION")),
+            pid: process_id(),
+            capabilities,
+        }
+    }
+
+    /// Picks the newest version of the protocol and the capabilities both
+    /// ends have in common, or [`None`] if they have no version in common.
+    pub fn negotiate(&self, other: &Hello) -> Option<Session> {
+        let version = self.version.min(other.version);
+        if version < self.min_version.max(other.min_version) {
+            return None;
+        }
+
+        let capabilities = self
+            .capabilities
+            .iter()
+            .copied()
+            .filter(|capability| {
+                *capability != Capability::Unknown && other.capabilities.contains(capability)
+            })
+            .collect();
+
+        Some(Session {
+            version,
+            capabilities,
+        })
+    }
+}
+
+/// Something that happened to the heap, like a [`HeapEvent`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Event {
+    /// An allocation was made.
+    Allocated {
+        /// Its address.
+        addr: usize,
+        /// Its size, in bytes.
+        size: usize,
+        /// Its alignment, in bytes.
+        align: usize,
+        /// Its type, when known and asked for with [`Capability::Types`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        type_name: Option<String>,
+    },
+    /// An allocation was freed.
+    Freed {
+        /// The address it had.
+        addr: usize,
+    },
+    /// An allocation was resized.
+    Resized {
+        /// Its address before the resize.
+        old_addr: usize,
+        /// Its address after the resize, which may be the same.
+        new_addr: usize,
+        /// Its size after the resize, in bytes.
+        size: usize,
+    },
+    /// An event from a newer version of the protocol.
+    #[serde(other)]
+    Unknown,
+}
+
+impl From<HeapEvent> for Event {
+    fn from(event: HeapEvent) -> Self {
+        match event {
+            HeapEvent::Allocated { addr, layout } => Event::Allocated {
+                addr,
+                size: layout.size(),
+                align: layout.align(),
+                type_name: None,
+            },
+            HeapEvent::Freed { addr, .. } => Event::Freed { addr },
+            HeapEvent::Resized {
+                old_addr,
+                new_addr,
+                new_layout,
+                ..
+            } => Event::Resized {
+                old_addr,
+                new_addr,
+                size: new_layout.size(),
+            },
+        }
+    }
+}
+
+/// A piece of the contents of an allocation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// The address of the allocation.
+    pub addr: usize,
+    /// Where the piece starts in the allocation, in bytes.
+    pub offset: usize,
+    /// The size of the whole allocation, in bytes, to know when it's all
+    /// there.
+    pub total: usize,
+    /// The bytes of the piece.
+    pub data: Vec<u8>,
+}
+
+/// Everything that goes over the connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Frame {
+    /// The first frame of each end.
+    Hello(Hello),
+    /// A change of the heap.
+    Event(Event),
+    /// A piece of the contents of an allocation.
+    Chunk(Chunk),
+    /// The last frame of each end, before it hangs up.
+    Bye,
+    /// A frame from a newer version of the protocol.
+    #[serde(other)]
+    Unknown,
+}
+
+impl StupidAlloc {
+    /// Returns the contents of the allocation containing `addr` as
+    /// [`Chunk`]s of at most `chunk_size` bytes, or [`None`] if there is no
+    /// allocation there. Allocations of zero bytes still get one empty chunk,
+    /// so that viewers know they're up to date.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in([7u8; 10], StupidAlloc);
+    /// let addr = &*stupid_box as *const _ as usize;
+    ///
+    /// let chunks = StupidAlloc.chunks_of(addr, 4).unwrap();
+    /// assert_eq!(chunks.len(), 3);
+    /// assert_eq!(chunks[2].offset, 8);
+    /// assert_eq!(chunks[2].data, [7, 7]);
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "protocol")))]
+    pub fn chunks_of(&self, addr: usize, chunk_size: usize) -> Option<Vec<Chunk>> {
+        let info = self.info(addr)?;
+        let mut data = vec![0; info.size];
+        let start = unsafe { std::ptr::NonNull::new(info.addr as *mut ())?.as_ref() };
+        let len = self.copy_bytes_of(start, &mut data)?;
+        data.truncate(len);
+
+        if data.is_empty() {
+            return Some(vec![Chunk {
+                addr: info.addr,
+                offset: 0,
+                total: 0,
+                data,
+            }]);
+        }
+
+        let chunks = data
+            .chunks(chunk_size.max(1))
+            .enumerate()
+            .map(|(i, piece)| Chunk {
+                addr: info.addr,
+                offset: i * chunk_size.max(1),
+                total: len,
+                data: piece.to_vec(),
+            })
+            .collect();
+        Some(chunks)
+    }
+}
@@ -0,0 +1,213 @@
+//! Python bindings for the inspection functions of the stupid alloc, to poke
+//! at the stupid heap from a notebook or to drive a demo from the Python REPL.
+//!
+//! With the `python` feature, the `cdylib` build of the crate is a Python
+//! extension module, easiest to build with [maturin](https://www.maturin.rs),
+//! which asks for the `cdylib` on its own:
+//!
+//! ```sh
+//! maturin develop --features python,pyo3/extension-module
+//! ```
+//!
+//! The heap of a Python process is not very stupid, but the allocation files
+//! of a Rust program running next to it can be attached to it read-only, see
+//! [`StupidAlloc::attach_readonly()`](crate::StupidAlloc::attach_readonly):
+//!
+//! ```python
+//! import stupidalloc
+//!
+//! stupidalloc.attach_readonly("/tmp/stupidalloc")
+//! for address, path in stupidalloc.state().items():
+//!     print(path)
+//!     print(stupidalloc.hexdump(address))
+//! ```
+//!
+//! A Rust program embedding Python can also hand its own heap to the
+//! interpreter, by registering the module before starting it:
+//!
+//! ```no_run
+//! use pyo3::prelude::*;
+//! use stupidalloc::python::bindings;
+//!
+//! pyo3::append_to_inittab!(bindings);
+//! Python::initialize();
+//!
+//! Python::attach(|py| py.run(c"import stupidalloc", None, None)).unwrap();
+//! ```
+//!
+//! Allocations are designated by their address everywhere, as returned by
+//! `state()`. Any address inside of an allocation works.
+
+use std::{collections::BTreeMap, path::PathBuf, ptr::NonNull};
+
+use pyo3::{prelude::*, types::PyDict};
+
+use crate::{Snapshot, StupidAlloc};
+
+// the inspection functions only look at the address of the value they're
+// given, and a reference to a zero-sized value may point anywhere but null.
+fn at<'a>(address: usize) -> Option<&'a ()> {
+    NonNull::new(address as *mut ()).map(|ptr| unsafe { ptr.as_ref() })
+}
+
+/// Returns a dictionary of the address and file of every allocation, including
+/// the files attached with `attach_readonly()`.
+#[pyfunction]
+fn state() -> BTreeMap<usize, PathBuf> {
+    StupidAlloc.state().into_iter().collect()
+}
+
+/// The number of live allocations and their total size in bytes, and the
+/// number of allocations that went to System, returned by `stats()`.
+#[pyclass(name = "Stats", module = "stupidalloc", frozen)]
+struct PyStats {
+    #[pyo3(get)]
+    allocations: usize,
+    #[pyo3(get)]
+    bytes: usize,
+    #[pyo3(get)]
+    fallbacks: u64,
+}
+
+#[pymethods]
+impl PyStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "Stats(allocations={}, bytes={}, fallbacks={})",
+            self.allocations, self.bytes, self.fallbacks
+        )
+    }
+}
+
+/// Returns statistics about the live allocations.
+#[pyfunction]
+fn stats() -> PyStats {
+    let stats = StupidAlloc.stats();
+    PyStats {
+        allocations: stats.allocations,
+        bytes: stats.bytes,
+        fallbacks: stats.fallbacks,
+    }
+}
+
+/// Returns a hexdump of the allocation at `address`, in the style of `xxd`,
+/// or `None` if there is no allocation there.
+#[pyfunction]
+fn hexdump(address: usize) -> Option<String> {
+    StupidAlloc.hexdump_of(at(address)?)
+}
+
+/// Opens a window displaying the allocation at `address`, with `columns`
+/// bytes on each row, or as many as fit the allocation with 0. Does nothing if
+/// there is no allocation there.
+#[cfg(feature = "graphics")]
+#[pyfunction]
+#[pyo3(signature = (address, columns = 16))]
+fn open_window(address: usize, columns: usize) {
+    if let Some(value) = at(address) {
+        StupidAlloc.open_window_of(value, columns)
+    }
+}
+
+/// Maps the allocation files of another process, found in the folder `dir`,
+/// read-only. Returns the number of files attached.
+#[pyfunction]
+fn attach_readonly(dir: PathBuf) -> PyResult<usize> {
+    Ok(StupidAlloc.attach_readonly(dir)?)
+}
+
+/// A copy of all the live allocations at some point in time, saved in a
+/// folder by `snapshot()`.
+#[pyclass(name = "Snapshot", module = "stupidalloc", frozen)]
+struct PySnapshot(Snapshot);
+
+#[pymethods]
+impl PySnapshot {
+    /// Loads a snapshot from its folder, possibly saved by another run of the
+    /// program.
+    #[staticmethod]
+    fn load(dir: PathBuf) -> PyResult<Self> {
+        Ok(Self(Snapshot::load(dir)?))
+    }
+
+    /// The folder of the snapshot.
+    #[getter]
+    fn dir(&self) -> PathBuf {
+        self.0.dir().to_path_buf()
+    }
+
+    /// The allocations of the snapshot, as `(address, size, alignment, path)`
+    /// tuples sorted by address.
+    #[getter]
+    fn allocations(&self) -> Vec<(usize, usize, usize, PathBuf)> {
+        self.0
+            .allocations()
+            .iter()
+            .map(|a| (a.addr, a.layout.size(), a.layout.align(), a.path.clone()))
+            .collect()
+    }
+
+    /// Writes the contents of the snapshot back into the allocations that are
+    /// still live at the same address and with the same size. Returns the
+    /// number of allocations that were restored.
+    fn restore(&self) -> PyResult<usize> {
+        Ok(StupidAlloc.restore_snapshot(&self.0)?)
+    }
+
+    /// Compares this snapshot with a `newer` one. Returns a dictionary with
+    /// the addresses of the `added` and `removed` allocations, and the ranges
+    /// of bytes that differ in the `changed` ones, by address.
+    fn diff<'py>(&self, py: Python<'py>, newer: &PySnapshot) -> PyResult<Bound<'py, PyDict>> {
+        let diff = self.0.diff(&newer.0)?;
+        let addresses = |allocations: &[crate::SnapshotAllocation]| {
+            allocations.iter().map(|a| a.addr).collect::<Vec<_>>()
+        };
+
+        let changed = diff
+            .changed
+            .iter()
+            .map(|changed| {
+                let ranges = changed.ranges.iter().map(|r| (r.start, r.end));
+                (changed.after.addr, ranges.collect::<Vec<_>>())
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let dict = PyDict::new(py);
+        dict.set_item("added", addresses(&diff.added))?;
+        dict.set_item("removed", addresses(&diff.removed))?;
+        dict.set_item("changed", changed)?;
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Snapshot({:?}, {} allocations)",
+            self.0.dir(),
+            self.0.allocations().len()
+        )
+    }
+}
+
+/// Saves a copy of all the live allocations in the folder `dir`, and returns
+/// it as a `Snapshot`.
+#[pyfunction]
+fn snapshot(dir: PathBuf) -> PyResult<PySnapshot> {
+    Ok(PySnapshot(StupidAlloc.snapshot_heap(dir)?))
+}
+
+/// The `stupidalloc` Python module. Programs embedding Python register it with
+/// [`pyo3::append_to_inittab!`].
+#[pymodule]
+#[pyo3(name = "stupidalloc")]
+pub fn bindings(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(state, m)?)?;
+    m.add_function(wrap_pyfunction!(stats, m)?)?;
+    m.add_function(wrap_pyfunction!(hexdump, m)?)?;
+    #[cfg(feature = "graphics")]
+    m.add_function(wrap_pyfunction!(open_window, m)?)?;
+    m.add_function(wrap_pyfunction!(attach_readonly, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot, m)?)?;
+    m.add_class::<PyStats>()?;
+    m.add_class::<PySnapshot>()?;
+    Ok(())
+}
@@ -0,0 +1,14 @@
+// splitmix64, which is plenty random for the fault injection, the fill
+// patterns and the sampling of lifetimes, and takes any seed.
+
+// how much the state moves on every number.
+pub const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// the random number after `state`, which it moves along.
+pub fn next(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(GAMMA);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
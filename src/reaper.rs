@@ -0,0 +1,189 @@
+use std::{
+    collections::VecDeque,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Condvar, Mutex,
+    },
+};
+
+#[cfg(any(unix, windows))]
+use std::sync::MutexGuard;
+
+#[cfg(windows)]
+use std::{collections::HashMap, time::Duration};
+
+use lazy_static::lazy_static;
+
+use crate::{error, process_id, shutdown, threads, StupidAlloc};
+
+// the files waiting to be removed, and whether the reaper is busy removing one.
+pub struct Queue {
+    paths: VecDeque<PathBuf>,
+    busy: bool,
+    // the number of times each file that couldn't be removed was tried.
+    #[cfg(windows)]
+    retries: HashMap<PathBuf, usize>,
+}
+
+// how many times, and how often, windows gets asked to remove a file that is
+// still mapped somewhere.
+#[cfg(windows)]
+const RETRIES: usize = 50;
+#[cfg(windows)]
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    // like everything else in the handles, the paths only ever get allocated
+    // while DEALLOCATING is nonzero. the reaper thread frees them, but stupid
+    // alloc is disabled there anyway.
+    static ref QUEUE: (Mutex<Queue>, Condvar) = (
+        Mutex::new(Queue {
+            paths: VecDeque::new(),
+            busy: false,
+            #[cfg(windows)]
+            retries: HashMap::new(),
+        }),
+        Condvar::new(),
+    );
+}
+
+// the process the reaper thread runs in. threads don't survive fork, so a
+// forked child needs a reaper of its own.
+static REAPER_PID: AtomicU32 = AtomicU32::new(0);
+
+// removing a file takes a while, and frees are slow enough already: the files
+// of freed allocations get removed by a background thread instead. needs to be
+// called while DEALLOCATING is nonzero.
+pub fn remove(path: PathBuf) {
+    // wasi has no threads to do it in the background, and the reaper may be
+    // gone once the program is exiting.
+    if cfg!(target_os = "wasi") || shutdown::late() {
+        let _ = remove_now(&path);
+        return;
+    }
+
+    let pid = process_id();
+    if REAPER_PID.swap(pid, Ordering::SeqCst) != pid {
+        let spawned = std::thread::Builder::new()
+            .name("stupidalloc reaper".into())
+            .spawn(|| {
+                threads::internal();
+                reap()
+            });
+
+        // without a reaper, the file is removed right away, and the next free
+        // tries again.
+        if let Err(e) = spawned {
+            REAPER_PID.store(0, Ordering::SeqCst);
+            error::backend(format!("couldn't spawn the reaper thread: {e}"));
+            let _ = remove_now(&path);
+            return;
+        }
+    }
+
+    let (queue, condvar) = &*QUEUE;
+    queue.lock().unwrap().paths.push_back(path);
+    condvar.notify_all();
+}
+
+// locks the queue while forking, so that the reaper isn't holding it.
+#[cfg(unix)]
+pub fn lock() -> MutexGuard<'static, Queue> {
+    QUEUE.0.lock().unwrap()
+}
+
+// the files in the queue are the parent's business, not the forked child's.
+#[cfg(unix)]
+pub fn forget(mut queue: MutexGuard<'static, Queue>) {
+    queue.paths.clear();
+    queue.busy = false;
+}
+
+// puts `path` back in the queue, after a nap so as not to spin while whoever
+// maps it is busy, or gives up on it after enough tries.
+#[cfg(windows)]
+fn retry(mut guard: MutexGuard<'static, Queue>, path: PathBuf) -> MutexGuard<'static, Queue> {
+    let (queue, condvar) = &*QUEUE;
+
+    let tries = guard.retries.entry(path.clone()).or_insert(0);
+    *tries += 1;
+    if *tries > RETRIES {
+        guard.retries.remove(&path);
+        return guard;
+    }
+
+    // the file is still queued while waiting, so flush_reaper waits too.
+    guard.busy = true;
+    drop(guard);
+    std::thread::sleep(RETRY_DELAY);
+
+    let mut guard = queue.lock().unwrap();
+    guard.busy = false;
+    guard.paths.push_back(path);
+    condvar.notify_all();
+    guard
+}
+
+// the bits of bit-mode come with a folder, queued after them.
+fn remove_now(path: &Path) -> io::Result<()> {
+    std::fs::remove_file(path).or_else(|_| std::fs::remove_dir(path))
+}
+
+fn reap() {
+    let (queue, condvar) = &*QUEUE;
+    let mut guard = queue.lock().unwrap();
+
+    loop {
+        match guard.paths.pop_front() {
+            Some(path) => {
+                guard.busy = true;
+                drop(guard);
+
+                // there is nobody to complain to, and the file might have been
+                // removed by someone else anyway.
+                let result = remove_now(&path);
+
+                guard = queue.lock().unwrap();
+                guard.busy = false;
+
+                // windows refuses to remove a file that is still mapped, by a
+                // window or by another process that attached to it. it gets
+                // back in the queue for a while, in case they let go.
+                #[cfg(windows)]
+                match result {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        guard = retry(guard, path)
+                    }
+                    _ => {
+                        guard.retries.remove(&path);
+                    }
+                }
+                #[cfg(not(windows))]
+                let _ = result;
+
+                condvar.notify_all();
+            }
+            None => guard = condvar.wait(guard).unwrap(),
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Waits until the files of freed allocations are actually removed. They
+    /// are removed by a background thread, so they can stick around for a bit
+    /// after the allocation was freed, which tests checking that the files are
+    /// gone might not appreciate.
+    ///
+    /// This is done by [`StupidAlloc::shut_down()`], automatically when the
+    /// program exits on Unix platforms.
+    pub fn flush_reaper(&self) {
+        let (queue, condvar) = &*QUEUE;
+        let _guard = condvar
+            .wait_while(queue.lock().unwrap(), |queue| {
+                !queue.paths.is_empty() || queue.busy
+            })
+            .unwrap();
+    }
+}
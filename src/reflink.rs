@@ -0,0 +1,126 @@
+use std::{fmt, io, path::Path};
+
+use crate::AllocHandle;
+
+/// How the contents of an allocation were copied into a [`Snapshot`](crate::Snapshot),
+/// or a version of the `time-travel` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CopyMethod {
+    /// The copy shares the blocks of the allocation file on disk until either
+    /// of them is written to, with a reflink on Btrfs and XFS, or `clonefile`
+    /// on APFS: nothing was copied, however big the allocation.
+    Reflink,
+    /// The bytes were copied, which is all other file systems can do.
+    Bytes,
+}
+
+impl CopyMethod {
+    // the name of the method in snapshot manifests.
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        match name {
+            "reflink" => Some(CopyMethod::Reflink),
+            "bytes" => Some(CopyMethod::Bytes),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CopyMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CopyMethod::Reflink => "reflink",
+            CopyMethod::Bytes => "bytes",
+        })
+    }
+}
+
+// makes the file at `dest` a reflink of the `len` bytes at `offset` in the file
+// at `src`. the file system decides whether it can: the offset must fall on one
+// of its blocks, and both files must be on it.
+#[cfg_attr(
+    not(any(target_os = "linux", target_os = "macos")),
+    allow(unused_variables)
+)]
+fn reflink(src: &Path, offset: usize, len: usize, dest: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let src = std::fs::File::open(src)?;
+        let dest = std::fs::File::create(dest)?;
+
+        let cloned = if offset == 0 {
+            unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) }
+        } else {
+            // a length of 0 goes to the end of the file, which doesn't have to
+            // fall on a block.
+            let range = libc::file_clone_range {
+                src_fd: src.as_raw_fd().into(),
+                src_offset: offset as u64,
+                src_length: 0,
+                dest_offset: 0,
+            };
+            unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONERANGE, &range) }
+        };
+        if cloned != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // whatever comes after the data, like canaries and guard pages.
+        dest.set_len(len as u64)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+        // clonefile only does whole files.
+        if offset != 0 {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+
+        let to_c = |path: &Path| {
+            CString::new(path.as_os_str().as_bytes()).map_err(|_| io::ErrorKind::InvalidInput)
+        };
+        // and won't replace anything.
+        match std::fs::remove_file(dest) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        if unsafe { libc::clonefile(to_c(src)?.as_ptr(), to_c(dest)?.as_ptr(), 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(dest)?
+            .set_len(len as u64)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl AllocHandle {
+    // copies the data into a new file at `dest`, sharing the blocks of the
+    // allocation file when the file system can, and copying the bytes
+    // otherwise. must be called in system_scope.
+    pub(crate) fn copy_data(&self, dest: &Path) -> io::Result<CopyMethod> {
+        let offset = self.offset();
+        let size = self.layout.size();
+
+        // apfs clones what's on disk, which the map may be ahead of. linux
+        // writes the dirty pages back before cloning them.
+        #[cfg(target_os = "macos")]
+        let flushed = self.map.flush_range(offset, size).is_ok();
+        #[cfg(not(target_os = "macos"))]
+        let flushed = true;
+
+        if flushed && self.has_file() && reflink(&self.path, offset, size, dest).is_ok() {
+            return Ok(CopyMethod::Reflink);
+        }
+
+        std::fs::write(dest, &self.map[offset..offset + size])?;
+        Ok(CopyMethod::Bytes)
+    }
+}
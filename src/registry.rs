@@ -0,0 +1,263 @@
+use std::{
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use allocator_api2::alloc::System;
+use hashbrown::{
+    hash_map::{DefaultHashBuilder, Entry},
+    HashMap,
+};
+
+use crate::{
+    bench::{self, Operation},
+    system_scope, AllocHandle,
+};
+
+// the number of shards of the registry. allocations and frees only lock the
+// shard of their address, so threads only wait on each other when they're
+// unlucky.
+const SHARDS: usize = 16;
+
+pub type StupidMap = HashMap<usize, AllocHandle, DefaultHashBuilder, System>;
+
+// all the live allocations, by address, split in shards.
+pub struct Registry {
+    shards: [RwLock<StupidMap>; SHARDS],
+    // the start and size of every allocation, sorted, to find the allocation
+    // containing a pointer without looking at all of them. it is only ever
+    // locked after a shard, never before, and only touched in system_scope.
+    index: RwLock<BTreeMap<usize, usize>>,
+}
+
+// the allocation starting at `start`, along with the locked shard holding it.
+pub(crate) struct Locked<G> {
+    pub start: usize,
+    shard: G,
+}
+
+impl<G: Deref<Target = StupidMap>> Deref for Locked<G> {
+    type Target = AllocHandle;
+
+    fn deref(&self) -> &AllocHandle {
+        // checked when it was locked
+        self.shard.get(&self.start).unwrap()
+    }
+}
+
+impl<G: DerefMut<Target = StupidMap>> DerefMut for Locked<G> {
+    fn deref_mut(&mut self) -> &mut AllocHandle {
+        self.shard.get_mut(&self.start).unwrap()
+    }
+}
+
+// every shard of the registry, locked at once. the shards are always locked in
+// the same order, so two threads doing this can't deadlock.
+pub struct AllShards<G>([G; SHARDS]);
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            shards: std::array::from_fn(|_| RwLock::new(HashMap::new_in(System))),
+            index: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    fn shard_index(addr: usize) -> usize {
+        // mapped files start on page boundaries, so the low bits are mostly
+        // zeroes. fibonacci hashing mixes the other bits into the top ones.
+        let hash = addr.wrapping_mul(0x9E37_79B9_7F4A_7C15_u64 as usize);
+        hash >> (usize::BITS - SHARDS.ilog2())
+    }
+
+    fn shard(&self, addr: usize) -> &RwLock<StupidMap> {
+        &self.shards[Self::shard_index(addr)]
+    }
+
+    // the shard holding the allocation starting at `addr`, if there is one.
+    pub fn read(&self, addr: usize) -> RwLockReadGuard<'_, StupidMap> {
+        bench::measure(Operation::RegistryLock, || self.shard(addr).read().unwrap())
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        self.read(addr).contains_key(&addr)
+    }
+
+    pub fn insert(&self, addr: usize, handle: AllocHandle) {
+        let mut shard = self.write(addr);
+        let size = handle.layout.size();
+        shard.insert(addr, handle);
+        system_scope(|| self.lock_index().insert(addr, size));
+    }
+
+    // inserts many allocations while locking every shard and the index only
+    // once, instead of once per allocation.
+    pub fn insert_batch(&self, handles: impl IntoIterator<Item = (usize, AllocHandle)>) {
+        let mut shards = self.write_all();
+        system_scope(|| {
+            let mut index = self.lock_index();
+            for (addr, handle) in handles {
+                index.insert(addr, handle.layout.size());
+                shards.0[Self::shard_index(addr)].insert(addr, handle);
+            }
+        })
+    }
+
+    pub fn remove(&self, addr: usize) -> Option<AllocHandle> {
+        let mut shard = self.write(addr);
+        let handle = shard.remove(&addr)?;
+        system_scope(|| self.lock_index().remove(&addr));
+        Some(handle)
+    }
+
+    // runs `f` on the allocation starting at `addr` without ever unlocking its
+    // shard, so that other threads can't see it missing halfway through.
+    // returns none if there is no such allocation. `f` runs in system_scope,
+    // and may move the allocation: the registry follows it to its new address.
+    pub fn update<R>(&self, addr: usize, f: impl FnOnce(&mut AllocHandle) -> R) -> Option<R> {
+        system_scope(|| {
+            let mut shard = self.write(addr);
+            let Entry::Occupied(mut entry) = shard.entry(addr) else {
+                return None;
+            };
+
+            let result = f(entry.get_mut());
+
+            let handle = entry.get();
+            let start = handle.map.as_ptr() as usize + handle.offset();
+            let size = handle.layout.size();
+
+            if start == addr {
+                self.lock_index().insert(addr, size);
+            } else if Self::shard_index(start) == Self::shard_index(addr) {
+                let handle = entry.remove();
+                shard.insert(start, handle);
+                self.move_index(addr, start, size);
+            } else if Self::shard_index(start) > Self::shard_index(addr) {
+                // shards are locked in order, like write_all does, and this
+                // one comes after.
+                let mut new = self.write(start);
+                new.insert(start, entry.remove());
+                self.move_index(addr, start, size);
+            } else {
+                // this one comes before, so the old shard is let go of first.
+                // the allocation stays registered at its old address in the
+                // meantime, which is the only one other threads know of until
+                // this returns.
+                drop(shard);
+                let mut new = self.write(start);
+                let mut old = self.write(addr);
+                if let Some(handle) = old.remove(&addr) {
+                    new.insert(start, handle);
+                    self.move_index(addr, start, size);
+                }
+            }
+
+            Some(result)
+        })
+    }
+
+    // the allocation at `from` is now at `to`, with `size` bytes. must be
+    // called with the shards of both locked, in system_scope.
+    fn move_index(&self, from: usize, to: usize, size: usize) {
+        let mut index = self.lock_index();
+        index.remove(&from);
+        index.insert(to, size);
+    }
+
+    // the index, locked for writing. like the shards, waiting for it counts
+    // as registry locking in the benchmarks.
+    fn lock_index(&self) -> RwLockWriteGuard<'_, BTreeMap<usize, usize>> {
+        bench::measure(Operation::RegistryLock, || self.index.write().unwrap())
+    }
+
+    // the start of the allocation containing the byte at `addr`.
+    fn start_of(&self, addr: usize) -> Option<usize> {
+        bench::measure(Operation::RegistryLock, || self.index.read().unwrap())
+            .range(..=addr)
+            .next_back()
+            .filter(|(&start, &size)| (start..start + size).contains(&addr))
+            .map(|(&start, _)| start)
+    }
+
+    // finds the allocation containing the byte at `addr`. it may be freed by
+    // another thread before its shard gets locked, in which case it's not
+    // there anymore.
+    pub fn handle_of(&self, addr: usize) -> Option<Locked<RwLockReadGuard<'_, StupidMap>>> {
+        let start = self.start_of(addr)?;
+        let shard = self.read(start);
+        shard
+            .contains_key(&start)
+            .then_some(Locked { start, shard })
+    }
+
+    // same as handle_of, but mutable.
+    pub fn handle_of_mut(&self, addr: usize) -> Option<Locked<RwLockWriteGuard<'_, StupidMap>>> {
+        let start = self.start_of(addr)?;
+        let shard = self.write(start);
+        shard
+            .contains_key(&start)
+            .then_some(Locked { start, shard })
+    }
+
+    // same as read, but mutable.
+    pub fn write(&self, addr: usize) -> RwLockWriteGuard<'_, StupidMap> {
+        bench::measure(Operation::RegistryLock, || {
+            self.shard(addr).write().unwrap()
+        })
+    }
+
+    // every shard and the index, locked at once, so that no other thread is
+    // in the middle of anything. used around fork.
+    #[cfg(unix)]
+    pub fn lock_everything(
+        &self,
+    ) -> (
+        AllShards<RwLockWriteGuard<'_, StupidMap>>,
+        RwLockWriteGuard<'_, BTreeMap<usize, usize>>,
+    ) {
+        let shards = self.write_all();
+        (shards, self.lock_index())
+    }
+
+    // every shard, for iterating over all the allocations or looking up
+    // pointers that may be inside of an allocation rather than at its start.
+    pub fn read_all(&self) -> AllShards<RwLockReadGuard<'_, StupidMap>> {
+        bench::measure(Operation::RegistryLock, || {
+            AllShards(std::array::from_fn(|i| self.shards[i].read().unwrap()))
+        })
+    }
+
+    // same as read_all, but mutable.
+    pub fn write_all(&self) -> AllShards<RwLockWriteGuard<'_, StupidMap>> {
+        bench::measure(Operation::RegistryLock, || {
+            AllShards(std::array::from_fn(|i| self.shards[i].write().unwrap()))
+        })
+    }
+}
+
+impl<G: Deref<Target = StupidMap>> AllShards<G> {
+    pub fn iter(&self) -> impl Iterator<Item = (&usize, &AllocHandle)> {
+        self.0.iter().flat_map(|shard| shard.iter())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &AllocHandle> {
+        self.0.iter().flat_map(|shard| shard.values())
+    }
+}
+
+impl<G: DerefMut<Target = StupidMap>> AllShards<G> {
+    #[cfg(feature = "watch")]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&usize, &mut AllocHandle)> {
+        self.0.iter_mut().flat_map(|shard| shard.iter_mut())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut AllocHandle> {
+        self.0.iter_mut().flat_map(|shard| shard.values_mut())
+    }
+
+    pub fn get_mut(&mut self, addr: &usize) -> Option<&mut AllocHandle> {
+        self.0.iter_mut().find_map(|shard| shard.get_mut(addr))
+    }
+}
@@ -0,0 +1,88 @@
+use std::io;
+
+use crate::{page_size, StupidAlloc, STUPID_MAP};
+
+// the number of pages the `len` bytes at `addr` are on.
+pub fn pages(addr: usize, len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (addr + len).div_ceil(page_size()) - addr / page_size()
+}
+
+// tells whether each page of the `len` bytes at `addr` is in RAM, one byte per
+// page in `resident` from the one `addr` is on: 1 if it is, 0 if it's out in
+// the file. `resident` must have room for all of them. no error messages, and
+// no allocations, the registry may well be locked.
+#[cfg_attr(not(unix), allow(unused_variables))]
+pub fn resident(addr: usize, len: usize, resident: &mut [u8]) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let count = pages(addr, len);
+        let resident = resident
+            .get_mut(..count)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+
+        // mincore wants a page-aligned start.
+        let start = addr / page_size() * page_size();
+        let len = count * page_size();
+        if unsafe { libc::mincore(start as *mut _, len, resident.as_mut_ptr().cast()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // the other bits are whatever the OS likes.
+        for page in resident {
+            *page &= 1;
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl StupidAlloc {
+    /// Tells which memory pages of the allocation containing `value` are in
+    /// RAM, and which ones are out in its file, using `mincore`, from the page
+    /// the data starts on to the one it ends on. The OS brings pages in when
+    /// they're used and evicts them when it needs the memory, or right away
+    /// with [`Advice::PageOut`](crate::Advice::PageOut). The graphical windows
+    /// draw evicted pages in blue stripes.
+    ///
+    /// Returns an error of kind [`NotFound`](io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc, and of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported) for pass-through
+    /// allocations, and outside of Unix platforms.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{Advice, StupidAlloc};
+    ///
+    /// let data = Box::new_in([42u8; 1 << 16], StupidAlloc);
+    /// if cfg!(target_os = "linux") {
+    ///     assert!(StupidAlloc.residency_of(&*data).unwrap().iter().all(|&page| page));
+    ///
+    ///     StupidAlloc.advise_of(&*data, Advice::PageOut).unwrap();
+    ///     // the OS may keep some, but never all of them.
+    ///     assert!(!StupidAlloc.residency_of(&*data).unwrap().iter().all(|&page| page));
+    ///     assert_eq!(data[1000], 42);
+    /// }
+    /// ```
+    pub fn residency_of<T: ?Sized>(&self, value: &T) -> io::Result<Vec<bool>> {
+        let addr = value as *const T as *const u8 as usize;
+
+        let handle = STUPID_MAP.handle_of(addr).ok_or(io::ErrorKind::NotFound)?;
+        // pass-through memory has no file to be evicted to.
+        if handle.map.map().is_none() {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        let (start, len) = (handle.start, handle.layout.size());
+        drop(handle);
+
+        let mut pages = vec![0; pages(start, len)];
+        resident(start, len, &mut pages)?;
+        Ok(pages.into_iter().map(|page| page == 1).collect())
+    }
+}
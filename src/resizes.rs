@@ -0,0 +1,86 @@
+use std::{
+    alloc::Layout,
+    backtrace::{Backtrace, BacktraceStatus},
+    time::Instant,
+};
+
+use crate::{system_scope, StupidAlloc, STUPID_MAP};
+
+/// One resize of an allocation, see [`StupidAlloc::history_of()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResizeRecord {
+    /// The layout of the allocation before the resize.
+    pub old_layout: Layout,
+    /// The layout of the allocation after the resize.
+    pub new_layout: Layout,
+    /// When the resize happened.
+    pub at: Instant,
+    /// Where the resize was made from, if backtraces are enabled, with
+    /// `RUST_BACKTRACE=1` for example.
+    pub backtrace: Option<String>,
+}
+
+// a resize, as kept by the handle. like everything else in there, it only
+// ever gets allocated and freed in system_scope.
+pub struct Resize {
+    pub old_layout: Layout,
+    pub new_layout: Layout,
+    pub at: Instant,
+    pub backtrace: Backtrace,
+}
+
+impl Resize {
+    // must be called in system_scope, like the handle is.
+    fn record(&self) -> ResizeRecord {
+        ResizeRecord {
+            old_layout: self.old_layout,
+            new_layout: self.new_layout,
+            at: self.at,
+            backtrace: (self.backtrace.status() == BacktraceStatus::Captured)
+                .then(|| self.backtrace.to_string()),
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Returns every resize of the allocation containing `value`, oldest
+    /// first, or [`None`] if `value` isn't in a stupid allocation. Handy to
+    /// check how a buffer grew:
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let mut stupid_vec = Vec::with_capacity_in(1, StupidAlloc);
+    /// for i in 0..5u8 {
+    ///     stupid_vec.push(i);
+    /// }
+    ///
+    /// let capacities = StupidAlloc
+    ///     .history_of(&*stupid_vec)
+    ///     .unwrap()
+    ///     .iter()
+    ///     .map(|resize| resize.new_layout.size())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(capacities, [8]);
+    /// ```
+    pub fn history_of<T: ?Sized>(&self, value: &T) -> Option<Vec<ResizeRecord>> {
+        let addr = value as *const T as *const u8 as usize;
+
+        let history = system_scope(|| {
+            let handle = STUPID_MAP.handle_of(addr)?;
+            Some(
+                handle
+                    .history
+                    .iter()
+                    .map(Resize::record)
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        // the copy was made in System, the user gets their own.
+        let user_history = history.clone();
+        system_scope(|| drop(history));
+        user_history
+    }
+}
@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, fmt, io, path::Path};
+
+use crate::{bundle, manifest, system_scope, user_error, StupidAlloc};
+
+/// How the live allocations made at a call site differ between two runs, see
+/// [`StupidAlloc::compare_runs()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteDiff {
+    /// Where the allocations were made, like
+    /// [`SiteLifetimes::call_site`](crate::SiteLifetimes::call_site), or
+    /// `unknown`.
+    pub call_site: String,
+    /// The number of allocations only present in the first run.
+    pub removed: usize,
+    /// Their total size in bytes.
+    pub removed_bytes: usize,
+    /// The number of allocations only present in the second run.
+    pub added: usize,
+    /// Their total size in bytes.
+    pub added_bytes: usize,
+}
+
+impl SiteDiff {
+    /// The number of allocations the second run has more than the first one,
+    /// negative if it has fewer.
+    pub fn net(&self) -> isize {
+        self.added as isize - self.removed as isize
+    }
+}
+
+/// The differences between the live allocations of two runs, computed by
+/// [`StupidAlloc::compare_runs()`].
+///
+/// Addresses change from one run to the next, so allocations are matched by
+/// call site, type and size instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunDiff {
+    /// The call sites whose allocations differ, the one the second run has
+    /// the most more allocations of first.
+    pub sites: Vec<SiteDiff>,
+}
+
+impl RunDiff {
+    /// The number of allocations the second run has more than the first one,
+    /// negative if it has fewer.
+    pub fn net(&self) -> isize {
+        self.sites.iter().map(SiteDiff::net).sum()
+    }
+}
+
+impl fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:+} allocations in the second run, over {} call sites",
+            self.net(),
+            self.sites.len()
+        )?;
+        for site in &self.sites {
+            writeln!(
+                f,
+                "  {:+} ({} added, {} bytes; {} removed, {} bytes): {}",
+                site.net(),
+                site.added,
+                site.added_bytes,
+                site.removed,
+                site.removed_bytes,
+                site.call_site
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// a live allocation of a run: its call site, type and size.
+type Allocation = (String, String, usize);
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// the live allocations of a bundle written by export_bundle. bundles from
+// before call sites were written make do with the locations.
+fn from_bundle(csv: &str) -> io::Result<Vec<Allocation>> {
+    let mut records = bundle::records(csv).into_iter();
+    let header = records.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|field| field == name);
+
+    let size = column("size").ok_or_else(|| invalid("not a stupidalloc bundle"))?;
+    let (type_name, location, call_site) =
+        (column("type"), column("location"), column("call_site"));
+
+    records
+        .map(|record| {
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|column| record.get(column))
+                    .filter(|field| !field.is_empty())
+            };
+            let call_site = field(call_site)
+                .or(field(location))
+                .map_or("unknown", String::as_str);
+            let size = field(Some(size))
+                .and_then(|size| size.parse().ok())
+                .ok_or_else(|| invalid(format!("invalid bundle line: {record:?}")))?;
+
+            Ok((
+                call_site.to_string(),
+                field(type_name).cloned().unwrap_or_default(),
+                size,
+            ))
+        })
+        .collect()
+}
+
+// the live allocations of the run at `path`: a bundle folder, a manifest, or
+// the allocation folder of a process holding one. must be called in
+// system_scope.
+fn load(path: &Path) -> io::Result<Vec<Allocation>> {
+    if path.join("allocations.csv").exists() {
+        return from_bundle(&std::fs::read_to_string(path.join("allocations.csv"))?);
+    }
+
+    Ok(manifest::read(path)?
+        .into_iter()
+        .map(|live| {
+            let call_site = live.location.unwrap_or_else(|| "unknown".into());
+            (call_site, live.type_name.unwrap_or_default(), live.size)
+        })
+        .collect())
+}
+
+fn compare(a: &Path, b: &Path) -> io::Result<RunDiff> {
+    // how many allocations of each kind each run has.
+    let mut counts = BTreeMap::<Allocation, (usize, usize)>::new();
+    for allocation in load(a)? {
+        counts.entry(allocation).or_default().0 += 1;
+    }
+    for allocation in load(b)? {
+        counts.entry(allocation).or_default().1 += 1;
+    }
+
+    let mut sites = BTreeMap::<String, SiteDiff>::new();
+    for ((call_site, _, size), (in_a, in_b)) in counts.into_iter().filter(|(_, (a, b))| a != b) {
+        let site = sites.entry(call_site.clone()).or_insert(SiteDiff {
+            call_site,
+            removed: 0,
+            removed_bytes: 0,
+            added: 0,
+            added_bytes: 0,
+        });
+        site.removed += in_a.saturating_sub(in_b);
+        site.removed_bytes += in_a.saturating_sub(in_b) * size;
+        site.added += in_b.saturating_sub(in_a);
+        site.added_bytes += in_b.saturating_sub(in_a) * size;
+    }
+
+    let mut sites = sites.into_values().collect::<Vec<_>>();
+    sites.sort_by_key(|site| std::cmp::Reverse(site.net()));
+    Ok(RunDiff { sites })
+}
+
+impl StupidAlloc {
+    /// Compares the live allocations of two runs of a program, `a` and `b`,
+    /// by call site: "this version leaks 40 more buffers from `parser.rs`".
+    /// Each run is either a folder written by [`StupidAlloc::export_bundle()`],
+    /// or a manifest (see [`StupidAlloc::manifest_path()`]), or the allocation
+    /// folder of a process holding its manifest. `stupidctl compare` does the
+    /// same from the command line.
+    ///
+    /// Bundles know the call sites of the allocations made with
+    /// [`stupid_box!`](crate::stupid_box) and [`stupid_vec!`](crate::stupid_vec),
+    /// and of the other ones if backtraces were enabled when the bundle was
+    /// written. Manifests only know the former, and what a manifest calls live
+    /// is what was live when the program exited or crashed.
+    ///
+    /// ```
+    /// use stupidalloc::{stupid_vec, StupidAlloc};
+    ///
+    /// let before = std::env::temp_dir().join("stupidalloc_doc_runs_before");
+    /// StupidAlloc.export_bundle(&before, false).unwrap();
+    ///
+    /// let leaked = (0..40).map(|_| stupid_vec![0u8; 1024]).collect::<Vec<_>>();
+    /// let after = std::env::temp_dir().join("stupidalloc_doc_runs_after");
+    /// StupidAlloc.export_bundle(&after, false).unwrap();
+    ///
+    /// let diff = StupidAlloc.compare_runs(&before, &after).unwrap();
+    /// assert_eq!(diff.sites[0].added, 40);
+    /// assert_eq!(diff.sites[0].added_bytes, 40 * 1024);
+    /// println!("{diff}");
+    /// ```
+    pub fn compare_runs(&self, a: impl AsRef<Path>, b: impl AsRef<Path>) -> io::Result<RunDiff> {
+        let (a, b) = (a.as_ref(), b.as_ref());
+
+        // read in System and then copied for the user, like the reports.
+        let diff = system_scope(|| compare(a, b)).map_err(user_error)?;
+        let user_diff = diff.clone();
+        system_scope(|| drop(diff));
+        Ok(user_diff)
+    }
+}
@@ -0,0 +1,121 @@
+use std::{alloc::Layout, ptr::NonNull};
+
+use crate::{
+    system_scope, temp_alloc_dir, AllocError, Allocator, Arena, Stats, StupidAlloc, STUPID_MAP,
+};
+
+/// An allocator that frees everything at once, when it is dropped.
+///
+/// This is how region allocators work: freeing an allocation made through the
+/// scope does nothing, and its file stays around until the end of the scope.
+/// Watch the files pile up in the temporary folder, and disappear all at once!
+///
+#[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+#[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+/// #![feature(allocator_api)]
+/// use stupidalloc::{StupidAlloc, StupidScope};
+///
+/// let scope = StupidScope::new();
+///
+/// let stupid_box = Box::new_in(1u32, &scope);
+/// let file = StupidAlloc.file_of(&*stupid_box).unwrap();
+/// drop(stupid_box);
+///
+/// // still there!
+/// assert!(file.exists());
+/// assert_eq!(scope.stats().allocations, 1);
+///
+/// drop(scope);
+/// StupidAlloc.flush_reaper();
+/// assert!(!file.exists());
+/// ```
+pub struct StupidScope {
+    // the scope is an arena that doesn't let go of anything.
+    arena: Arena,
+}
+
+impl StupidScope {
+    /// Creates a new scope. The files of its allocations are created along
+    /// with the other allocation files.
+    pub fn new() -> Self {
+        StupidScope {
+            arena: Arena::new("scope", temp_alloc_dir()),
+        }
+    }
+
+    /// Returns statistics about the allocations of the scope, including the
+    /// ones that were freed but are kept until the end of the scope.
+    pub fn stats(&self) -> Stats {
+        self.arena.stats()
+    }
+}
+
+impl Default for StupidScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl Allocator for StupidScope {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.arena.allocate(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.arena.allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let addr = ptr.as_ptr() as usize;
+
+        // stupid allocations stay until the arena is dropped, but the ones
+        // that went to System must be freed like any other.
+        let kept = system_scope(|| {
+            let mut map = STUPID_MAP.write(addr);
+            let Some(_handle) = map
+                .get_mut(&addr)
+                .filter(|handle| handle.arena == Some(self.arena.id))
+            else {
+                return false;
+            };
+
+            #[cfg(feature = "logging")]
+            _handle.log(format_args!(
+                "## Freed\nFreed by its owner, but kept until the end of the scope.\n"
+            ));
+
+            true
+        });
+
+        if !kept {
+            StupidAlloc.deallocate(ptr, layout)
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.arena.grow(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.arena.grow_zeroed(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.arena.shrink(ptr, old_layout, new_layout)
+    }
+}
@@ -0,0 +1,135 @@
+#[cfg(feature = "regex")]
+use std::io;
+use std::ops::Range;
+
+use crate::{huge, system_scope, AllocationInfo, StupidAlloc, STUPID_MAP};
+
+#[cfg(feature = "regex")]
+use crate::user_error;
+
+/// A live allocation holding matches of [`StupidAlloc::search()`], or of
+/// `StupidAlloc::search_regex()` with the `regex` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// What is known about the allocation.
+    pub info: AllocationInfo,
+    /// Where the matches are, in bytes from the start of the allocation,
+    /// in order and without overlapping.
+    pub matches: Vec<Range<usize>>,
+}
+
+// every occurrence of `needle` in `data`, without overlapping.
+fn find_all(data: &[u8], needle: &[u8]) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while let Some(found) = data.get(start..).and_then(|rest| {
+        rest.windows(needle.len())
+            .position(|window| window == needle)
+    }) {
+        matches.push(start + found..start + found + needle.len());
+        start += found + needle.len();
+    }
+    matches
+}
+
+// the live allocations where `find` finds something, sorted by address. must be
+// called in system_scope.
+fn search(find: impl Fn(&[u8]) -> Vec<Range<usize>>) -> Vec<SearchHit> {
+    let pages = huge::page_sizes();
+    let map = STUPID_MAP.read_all();
+
+    let mut hits = Vec::new();
+    for (&addr, handle) in map.iter() {
+        let offset = handle.offset();
+        let matches = find(&handle.map[offset..offset + handle.layout.size()]);
+        if !matches.is_empty() {
+            hits.push(SearchHit {
+                info: handle.info(addr, &pages),
+                matches,
+            });
+        }
+    }
+    hits.sort_by_key(|hit| hit.info.addr);
+    hits
+}
+
+impl StupidAlloc {
+    /// Looks for `needle` in every live allocation, and returns the ones
+    /// holding it, with where. Which allocation holds this string? Grepping
+    /// the allocation folder tells too, but only as of the last time the
+    /// memory was written back to the files, while this reads the memory
+    /// itself. An empty needle finds nothing, and a needle in a stupid
+    /// allocation finds itself.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let haystack = Box::new_in(*b"hay, hay, a needle, and more hay", StupidAlloc);
+    ///
+    /// let hits = StupidAlloc.search(b"needle");
+    /// assert_eq!(hits.len(), 1);
+    /// assert_eq!(hits[0].info.addr, haystack.as_ptr() as usize);
+    /// assert_eq!(hits[0].matches, [12..18]);
+    /// ```
+    pub fn search(&self, needle: &[u8]) -> Vec<SearchHit> {
+        if needle.is_empty() {
+            return Vec::new();
+        }
+
+        // like the reports, found in System and then copied for the user.
+        let hits = system_scope(|| search(|data| find_all(data, needle)));
+        let user_hits = hits.clone();
+        system_scope(|| drop(hits));
+        user_hits
+    }
+
+    /// Like [`StupidAlloc::search()`], with a regular expression matching the
+    /// bytes of text instead, in the syntax of the
+    /// [`regex`](https://docs.rs/regex) crate. `^` and `$` match at the ends
+    /// of the allocations, and of their lines, and empty matches are left out.
+    ///
+    /// Returns an error of kind [`InvalidInput`](io::ErrorKind::InvalidInput)
+    /// if `pattern` isn't a valid regular expression, telling where.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let log = Box::new_in(*b"GET /index.html 200\nGET /secret 403\n", StupidAlloc);
+    ///
+    /// let hits = StupidAlloc.search_regex(r"(?i)get /\w+ 4\d\d$").unwrap();
+    /// assert_eq!(hits[0].info.addr, log.as_ptr() as usize);
+    /// assert_eq!(hits[0].matches, [20..35]);
+    ///
+    /// assert!(StupidAlloc.search_regex("[z-a]").is_err());
+    /// ```
+    #[cfg(feature = "regex")]
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "regex")))]
+    pub fn search_regex(&self, pattern: &str) -> io::Result<Vec<SearchHit>> {
+        // the regex allocates as it goes, so it lives and dies in System.
+        let hits = system_scope(|| {
+            let regex = regex::bytes::RegexBuilder::new(pattern)
+                .multi_line(true)
+                .build()
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid regular expression: {e}"),
+                    )
+                })?;
+            Ok(search(|data| {
+                regex
+                    .find_iter(data)
+                    .filter(|found| !found.is_empty())
+                    .map(|found| found.range())
+                    .collect()
+            }))
+        })
+        .map_err(user_error)?;
+
+        let user_hits = hits.clone();
+        system_scope(|| drop(hits));
+        Ok(user_hits)
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use allocator_api2::boxed::Box;
+
+#[cfg(feature = "logging")]
+use std::io::Write;
+
+use crate::{persist::adopt_file, process_id, system_scope, user_error, StupidAlloc, STUPID_MAP};
+
+/// A description of an allocation shared with [`StupidAlloc::share_of()`],
+/// that another process can [`StupidAlloc::attach()`] to.
+///
+/// To send it to the other process, turn it into a string with its
+/// [`Display`](fmt::Display) implementation, and back with [`str::parse()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareToken {
+    path: PathBuf,
+    size: usize,
+}
+
+impl ShareToken {
+    /// Returns the path of the file of the shared allocation.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the size of the shared allocation, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl fmt::Display for ShareToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.size, self.path.to_string_lossy())
+    }
+}
+
+impl FromStr for ShareToken {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the size goes first, since the path may very well contain colons.
+        let (size, path) = s
+            .split_once(':')
+            .and_then(|(size, path)| Some((size.parse().ok()?, path)))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid share token: {s:?}"),
+                )
+            })?;
+
+        Ok(ShareToken {
+            path: PathBuf::from(path),
+            size,
+        })
+    }
+}
+
+impl StupidAlloc {
+    /// Shares the allocation containing `value` with other processes: the
+    /// returned token lets another process running the stupid alloc map the
+    /// same file with [`StupidAlloc::attach()`]. Both processes then see each
+    /// other's writes live, in memory and in their graphical windows.
+    ///
+    /// The allocation should not be resized while it is shared. Allocations
+    /// with guard pages or canaries can't be shared, and neither can
+    /// pass-through allocations.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{ShareToken, StupidAlloc};
+    ///
+    /// let mut stupid_box = Box::new_in(0u64, StupidAlloc);
+    /// let token = StupidAlloc.share_of(&*stupid_box).unwrap().to_string();
+    ///
+    /// // meanwhile, in another process...
+    /// let token = token.parse::<ShareToken>().unwrap();
+    /// let attached = unsafe { StupidAlloc.attach::<u64>(&token) }.unwrap();
+    ///
+    /// *stupid_box = 42;
+    /// assert_eq!(unsafe { std::ptr::read_volatile(&*attached) }, 42);
+    /// ```
+    pub fn share_of<T: ?Sized>(&self, value: &T) -> io::Result<ShareToken> {
+        let addr = value as *const T as *const u8 as usize;
+
+        // the path gets copied in System while the registry is locked, and
+        // copied again for the user afterwards.
+        let (path, size) = system_scope(|| {
+            // only written to with some features
+            #[allow(unused_mut)]
+            let mut handle = STUPID_MAP
+                .handle_of_mut(addr)
+                .ok_or(io::ErrorKind::NotFound)?;
+
+            if handle.redzones.guard_pages || handle.redzones.canaries {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "allocations with redzones can't be shared",
+                ));
+            }
+
+            if !handle.has_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "pass-through allocations have no file to share",
+                ));
+            }
+
+            #[cfg(feature = "logging")]
+            writeln!(handle.log_file, "## Shared\n- Process: {}\n", process_id())?;
+
+            Ok((handle.path.clone(), handle.layout.size()))
+        })
+        .map_err(user_error)?;
+
+        let token = ShareToken {
+            path: path.as_path().to_path_buf(),
+            size,
+        };
+        system_scope(|| drop(path));
+
+        Ok(token)
+    }
+
+    /// Maps an allocation shared by another process with
+    /// [`StupidAlloc::share_of()`]. The allocation behaves like any other: it
+    /// can be inspected and displayed in a window, and dropping the box won't
+    /// delete the file, which belongs to the other process. Returns [`None`]
+    /// if the file is gone, or if its size doesn't match `T`.
+    ///
+    /// # Safety
+    /// The contents of the allocation must be a valid `T`, and stay so while
+    /// the other process writes to it. Since the other process can be running
+    /// anything, this is only reasonable for plain data: no references, no
+    /// pointers, no heap allocations.
+    pub unsafe fn attach<T>(&self, token: &ShareToken) -> Option<Box<T, StupidAlloc>> {
+        if token.size != std::mem::size_of::<T>() {
+            return None;
+        }
+
+        let ptr = system_scope(|| {
+            adopt_file::<T>(
+                &token.path,
+                format_args!("# Attached\n- Process: {}", process_id()),
+            )
+        });
+
+        ptr.map(|ptr| unsafe { Box::from_raw_in(ptr.as_ptr(), StupidAlloc) })
+    }
+}
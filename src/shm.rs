@@ -0,0 +1,165 @@
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    ffi::c_void,
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use allocator_api2::alloc::System;
+use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
+use lazy_static::lazy_static;
+
+type Handle = *mut c_void;
+
+const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
+const PAGE_READWRITE: u32 = 0x04;
+const FILE_MAP_ALL_ACCESS: u32 = 0x000F_001F;
+
+// views of a mapping start on a multiple of the allocation granularity, which
+// is 64 KiB everywhere windows runs.
+const GRANULARITY: usize = 64 * 1024;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn CreateFileMappingW(
+        file: Handle,
+        attributes: *const c_void,
+        protect: u32,
+        size_high: u32,
+        size_low: u32,
+        name: *const u16,
+    ) -> Handle;
+    fn MapViewOfFile(
+        mapping: Handle,
+        access: u32,
+        offset_high: u32,
+        offset_low: u32,
+        len: usize,
+    ) -> *mut c_void;
+    fn UnmapViewOfFile(addr: *const c_void) -> i32;
+    fn CloseHandle(handle: Handle) -> i32;
+}
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    // the number in the name of each live section, by address. it lives in
+    // System, since the stupid alloc may be the one calling.
+    static ref NAMES: Mutex<HashMap<usize, u64, DefaultHashBuilder, System>> =
+        Mutex::new(HashMap::new_in(System));
+}
+
+// the name of the `n`th section, as a nul-terminated wide string that doesn't
+// need the heap.
+struct WideName {
+    buf: [u16; 64],
+    len: usize,
+}
+
+impl std::fmt::Write for WideName {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        for unit in s.encode_utf16() {
+            // the last one is the terminating nul.
+            if self.len + 1 >= self.buf.len() {
+                return Err(std::fmt::Error);
+            }
+            self.buf[self.len] = unit;
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+fn wide_name(n: u64) -> WideName {
+    let mut name = WideName {
+        buf: [0; 64],
+        len: 0,
+    };
+    // fits in 64 units, whatever the numbers.
+    let _ = write!(name, "Local\\stupidalloc-{}-{n}", std::process::id());
+    name
+}
+
+/// An allocator handing out named shared memory, which is backed by the paging
+/// file rather than by a file of its own. Windows won't remove a file that is
+/// still mapped somewhere, so allocations backed by this leave nothing behind,
+/// while other processes can still open them by name, with
+/// `OpenFileMappingW`.
+///
+/// This is meant to be the allocator of [`StupidAlloc::set_pass_through()`](crate::StupidAlloc::set_pass_through()):
+/// it keeps track of the names with the standard library's `System`, but the
+/// rest of the stupid alloc keeps working as usual.
+///
+/// ```no_run
+/// #![feature(allocator_api)]
+/// use stupidalloc::{SharedMemory, StupidAlloc};
+///
+/// StupidAlloc.set_pass_through(Some(&SharedMemory));
+/// let stupid_box = Box::new_in(42u32, StupidAlloc);
+/// StupidAlloc.set_pass_through(None);
+///
+/// let name = SharedMemory.name_of(&*stupid_box as *const u32 as *const u8);
+/// assert!(name.unwrap().starts_with("Local\\stupidalloc-"));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SharedMemory;
+
+impl SharedMemory {
+    /// Returns the name of the shared memory starting at `ptr`, for other
+    /// processes to open it, or [`None`] if it doesn't come from this
+    /// allocator. The names are `Local\stupidalloc-<pid>-<n>`, numbered in the
+    /// order of allocation.
+    pub fn name_of(&self, ptr: *const u8) -> Option<String> {
+        // the name must be formatted without holding the lock, since it may
+        // well be allocated by us.
+        let n = *NAMES.lock().unwrap().get(&(ptr as usize))?;
+        Some(format!("Local\\stupidalloc-{}-{n}", std::process::id()))
+    }
+}
+
+unsafe impl GlobalAlloc for SharedMemory {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.align() > GRANULARITY {
+            return std::ptr::null_mut();
+        }
+
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let name = wide_name(n);
+        let size = layout.size().max(1) as u64;
+
+        let mapping = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            std::ptr::null(),
+            PAGE_READWRITE,
+            (size >> 32) as u32,
+            size as u32,
+            name.buf.as_ptr(),
+        );
+        if mapping.is_null() {
+            return std::ptr::null_mut();
+        }
+
+        // the view keeps the section alive, and its name with it.
+        let ptr = MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, 0);
+        CloseHandle(mapping);
+        if ptr.is_null() {
+            return std::ptr::null_mut();
+        }
+
+        NAMES.lock().unwrap().insert(ptr as usize, n);
+        ptr as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        NAMES.lock().unwrap().remove(&(ptr as usize));
+        UnmapViewOfFile(ptr as *const c_void);
+    }
+
+    // sections are zeroed when created.
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.alloc(layout)
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    alloc::Layout,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(unix)]
+use std::sync::Once;
+
+use crate::{system_scope, StupidAlloc, INIT_DETECTOR, STUPID_MAP};
+
+/// Where the stupid alloc is in the life of the program, returned by
+/// [`StupidAlloc::phase()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Nothing was allocated yet, and no thread was picked as the one the
+    /// stupid alloc is enabled in.
+    Starting,
+    /// Business as usual.
+    Running,
+    /// The stupid alloc has shut down, usually because the program is
+    /// exiting: see [`StupidAlloc::shut_down()`]. New allocations go to
+    /// System, and the ones from before are freed or moved to System when
+    /// they're resized.
+    Late,
+}
+
+// set once the stupid alloc has shut down, usually because the program is
+// exiting. the threads of the windows and the reaper may be gone by then, so
+// allocations and frees stop talking to them.
+static LATE: AtomicBool = AtomicBool::new(false);
+
+// whether the first late allocation was complained about already.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+static REGISTER_AT_EXIT: Once = Once::new();
+
+// whether the stupid alloc has shut down already.
+pub fn late() -> bool {
+    LATE.load(Ordering::SeqCst)
+}
+
+// says once that allocations come after the shutdown, like the ones of static
+// destructors, and go to System. must be called in system_scope.
+pub fn warn_late(layout: Layout) {
+    if !WARNED.swap(true, Ordering::SeqCst) {
+        eprintln!(
+            "stupidalloc: allocation of {} bytes after the stupid alloc shut down, it and the ones after it go to System",
+            layout.size()
+        );
+    }
+}
+
+// shuts the stupid alloc down when the program exits. atexit handlers run
+// before the threads are killed, and before the frees of thread locals and
+// the like. must be called while ALLOCATING is nonzero, since registering
+// allocates.
+#[cfg(unix)]
+pub fn register() {
+    // miri knows nothing of atexit.
+    if cfg!(miri) {
+        return;
+    }
+
+    REGISTER_AT_EXIT.call_once(|| unsafe {
+        libc::atexit(at_exit);
+    });
+}
+
+#[cfg(unix)]
+extern "C" fn at_exit() {
+    StupidAlloc.shut_down()
+}
+
+impl StupidAlloc {
+    /// Gets the stupid alloc ready for the end of the program: windows are
+    /// closed, the watcher and the wallpaper stop, the files of freed
+    /// allocations are removed, and the logs of the allocations that are
+    /// still alive say so. Frees that come after that, from the destructors
+    /// of thread locals for example, remove their file on the spot and leave
+    /// the background threads alone, since they may be gone already.
+    ///
+    /// Allocations made afterwards, by static destructors for example, go to
+    /// System, and resizing an allocation made before moves it there: the
+    /// first one says so on the standard error. The stupid alloc is then in
+    /// its [`Phase::Late`]. On Unix platforms this is done automatically when
+    /// the program exits, elsewhere it's best called at the end of `main`.
+    pub fn shut_down(&self) {
+        if LATE.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        // nobody would be left to thaw it.
+        crate::freeze::thaw();
+
+        system_scope(|| {
+            #[cfg(feature = "graphics")]
+            {
+                // the windows are joined without holding the locks, since
+                // their threads read the registry.
+                let mut windows = STUPID_MAP
+                    .write_all()
+                    .values_mut()
+                    .filter_map(|handle| handle.window.take())
+                    .collect::<Vec<_>>();
+                windows.extend(
+                    crate::QUARANTINE
+                        .lock()
+                        .unwrap()
+                        .iter_mut()
+                        .filter_map(|handle| handle.window.take()),
+                );
+                for window in windows {
+                    window.shut_down();
+                }
+            }
+
+            #[cfg(feature = "watch")]
+            crate::watch::stop();
+
+            #[cfg(feature = "wallpaper")]
+            crate::wallpaper::stop();
+
+            for handle in STUPID_MAP.write_all().values_mut() {
+                // the next run should find persistent allocations up to date.
+                if handle.persistent {
+                    let _ = handle.flush();
+                }
+
+                #[cfg(feature = "logging")]
+                handle.log(format_args!(
+                    "## Shutdown\nStill allocated when the stupid alloc shut down.\n"
+                ));
+            }
+        });
+
+        self.flush_reaper();
+    }
+
+    /// Returns where the stupid alloc is in the life of the program: before
+    /// the first allocation, running, or shut down and passing everything on
+    /// to System.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{Phase, StupidAlloc};
+    ///
+    /// let stupid_box = Box::new_in(1u8, StupidAlloc);
+    /// assert_eq!(StupidAlloc.phase(), Phase::Running);
+    /// ```
+    pub fn phase(&self) -> Phase {
+        if late() {
+            Phase::Late
+        } else if INIT_DETECTOR.is_completed() {
+            Phase::Running
+        } else {
+            Phase::Starting
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    alloc::Layout,
+    fmt::Write as _,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{system_scope, AllocHandle, CopyMethod, StupidAlloc, STUPID_MAP};
+
+// the name of the file describing the contents of a snapshot folder.
+const MANIFEST: &str = "manifest.txt";
+
+// the first line of a manifest, so that random text files get rejected.
+const MANIFEST_HEADER: &str = "# stupidalloc heap snapshot";
+
+// the file holding the contents of the allocation at `addr` in a snapshot.
+fn contents_path(dir: &Path, addr: usize) -> PathBuf {
+    dir.join(format!("0x{addr:016x}.mem"))
+}
+
+fn invalid_manifest(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid snapshot manifest line: {line:?}"),
+    )
+}
+
+/// An allocation recorded in a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotAllocation {
+    /// The address of the allocation at the time of the snapshot.
+    pub addr: usize,
+    /// The layout of the allocation at the time of the snapshot.
+    pub layout: Layout,
+    /// The path of the allocation file at the time of the snapshot.
+    pub path: PathBuf,
+    /// How its contents were copied into the snapshot.
+    pub copy: CopyMethod,
+}
+
+/// A copy of all the live allocations at some point in time, created with
+/// [`StupidAlloc::snapshot_heap()`].
+///
+/// The snapshot lives in a folder, holding a manifest describing the
+/// allocations and a copy of the contents of each of them.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    dir: PathBuf,
+    allocations: Vec<SnapshotAllocation>,
+}
+
+/// An allocation whose contents differ between two [`Snapshot`]s.
+#[derive(Debug, Clone)]
+pub struct ChangedAllocation {
+    /// The allocation in the older snapshot.
+    pub before: SnapshotAllocation,
+    /// The allocation in the newer snapshot.
+    pub after: SnapshotAllocation,
+    /// The ranges of bytes that differ. If the allocation changed size,
+    /// the bytes that only exist in one of the snapshots count as different.
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// The differences between two [`Snapshot`]s, computed by [`Snapshot::diff()`].
+///
+/// Allocations are matched by address.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    /// Allocations only present in the newer snapshot.
+    pub added: Vec<SnapshotAllocation>,
+    /// Allocations only present in the older snapshot.
+    pub removed: Vec<SnapshotAllocation>,
+    /// Allocations present in both snapshots, but with different contents.
+    pub changed: Vec<ChangedAllocation>,
+}
+
+impl Snapshot {
+    /// Loads a snapshot previously created with [`StupidAlloc::snapshot_heap()`]
+    /// from its folder, possibly by another run of the program.
+    pub fn load(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let manifest = std::fs::read_to_string(dir.join(MANIFEST))?;
+        let mut lines = manifest.lines();
+
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a stupidalloc snapshot manifest",
+            ));
+        }
+
+        let allocations = lines
+            .map(|line| {
+                // address, size, alignment, copy method, and then the path
+                // which may contain spaces
+                let mut fields = line.splitn(5, ' ');
+                let mut next = || fields.next().ok_or_else(|| invalid_manifest(line));
+
+                let addr = usize::from_str_radix(next()?.trim_start_matches("0x"), 16);
+                let size = next()?.parse::<usize>();
+                let align = next()?.parse::<usize>();
+                let copy = CopyMethod::parse(next()?);
+                let path = PathBuf::from(next()?);
+
+                match (addr, size, align, copy) {
+                    (Ok(addr), Ok(size), Ok(align), Some(copy)) => Ok(SnapshotAllocation {
+                        addr,
+                        layout: Layout::from_size_align(size, align)
+                            .map_err(|_| invalid_manifest(line))?,
+                        path,
+                        copy,
+                    }),
+                    _ => Err(invalid_manifest(line)),
+                }
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { dir, allocations })
+    }
+
+    /// Returns the folder of the snapshot.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the allocations recorded in the snapshot, sorted by address.
+    pub fn allocations(&self) -> &[SnapshotAllocation] {
+        &self.allocations
+    }
+
+    /// Reads the contents of one of the allocations of the snapshot.
+    pub fn contents_of(&self, allocation: &SnapshotAllocation) -> io::Result<Vec<u8>> {
+        std::fs::read(contents_path(&self.dir, allocation.addr))
+    }
+
+    /// Compares this snapshot with a `newer` one, both by set of allocations
+    /// and byte by byte.
+    pub fn diff(&self, newer: &Snapshot) -> io::Result<SnapshotDiff> {
+        let mut diff = SnapshotDiff::default();
+
+        for before in &self.allocations {
+            match newer.allocations.iter().find(|a| a.addr == before.addr) {
+                None => diff.removed.push(before.clone()),
+                Some(after) => {
+                    let old = self.contents_of(before)?;
+                    let new = newer.contents_of(after)?;
+                    let ranges = differing_ranges(&old, &new);
+
+                    if !ranges.is_empty() {
+                        diff.changed.push(ChangedAllocation {
+                            before: before.clone(),
+                            after: after.clone(),
+                            ranges,
+                        });
+                    }
+                }
+            }
+        }
+
+        diff.added = newer
+            .allocations
+            .iter()
+            .filter(|after| !self.allocations.iter().any(|a| a.addr == after.addr))
+            .cloned()
+            .collect();
+
+        Ok(diff)
+    }
+}
+
+// the ranges of bytes that are not the same in `old` and `new`.
+pub fn differing_ranges(old: &[u8], new: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    (0..old.len().max(new.len()))
+        .filter(|&i| old.get(i) != new.get(i))
+        .for_each(|i| match ranges.last_mut() {
+            Some(range) if range.end == i => range.end += 1,
+            _ => ranges.push(i..i + 1),
+        });
+
+    ranges
+}
+
+// flushes every allocation of `handles` and writes its contents into `dir`,
+// followed by the manifest. must be called in system_scope, with the
+// registry locked.
+fn write_snapshot<'a>(
+    dir: &Path,
+    handles: impl Iterator<Item = (&'a usize, &'a AllocHandle)>,
+) -> io::Result<()> {
+    let mut handles = handles.collect::<Vec<_>>();
+    handles.sort_by_key(|(&addr, _)| addr);
+
+    let mut manifest = String::from(MANIFEST_HEADER);
+    for (&addr, handle) in handles {
+        handle.flush()?;
+        let copy = handle.copy_data(&contents_path(dir, addr))?;
+
+        // can't fail, it's a string
+        let _ = write!(
+            manifest,
+            "\n0x{addr:016x} {} {} {copy} {}",
+            handle.layout.size(),
+            handle.layout.align(),
+            handle.path.to_string_lossy()
+        );
+    }
+
+    std::fs::write(dir.join(MANIFEST), manifest)
+}
+
+// `dir` with `suffix` appended to its name, as a sibling of it.
+fn sibling(dir: &Path, suffix: &str) -> PathBuf {
+    let mut name = dir.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    dir.with_file_name(name)
+}
+
+impl StupidAlloc {
+    /// Takes a snapshot of the heap: the contents of every live allocation are
+    /// flushed and copied into the folder `dir` (created if needed), along with
+    /// a manifest of their addresses, layouts and files. The snapshot can be
+    /// compared to another one with [`Snapshot::diff()`], or written back into
+    /// memory with [`StupidAlloc::restore_snapshot()`].
+    ///
+    /// On file systems that can, the copies are reflinks of the allocation
+    /// files, which take no time nor space until either is written to, as
+    /// [`SnapshotAllocation::copy`] tells.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in([7u8; 1 << 16], StupidAlloc);
+    /// let dir = std::env::temp_dir().join("stupidalloc_doc_snapshot_heap");
+    ///
+    /// let snapshot = StupidAlloc.snapshot_heap(&dir).unwrap();
+    /// for allocation in snapshot.allocations() {
+    ///     println!("0x{:x}: {}", allocation.addr, allocation.copy);
+    /// }
+    /// ```
+    pub fn snapshot_heap(&self, dir: impl AsRef<Path>) -> io::Result<Snapshot> {
+        let dir = dir.as_ref();
+
+        // while the registry is locked, nothing must be stupid-allocated.
+        system_scope(|| {
+            std::fs::create_dir_all(dir)?;
+            write_snapshot(dir, STUPID_MAP.read_all().iter())
+        })?;
+
+        // outside of the scope, since the snapshot is handed to the user.
+        Snapshot::load(dir)
+    }
+
+    /// Takes a snapshot of the heap like [`StupidAlloc::snapshot_heap()`],
+    /// but one that is never torn: allocations, frees and resizes wait until
+    /// every allocation is flushed and copied, so that the manifest and the
+    /// contents match. The snapshot is written next to `dir` first, and only
+    /// takes its place once complete, replacing whatever was there: a crash
+    /// halfway through leaves the previous snapshot in `dir` untouched.
+    ///
+    /// Memory can still be written to in the meantime, through pointers that
+    /// other threads already hold.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in(1u32, StupidAlloc);
+    /// let dir = std::env::temp_dir().join("stupidalloc_doc_consistent");
+    ///
+    /// // twice, the second one replacing the first.
+    /// StupidAlloc.snapshot_consistent(&dir).unwrap();
+    /// let snapshot = StupidAlloc.snapshot_consistent(&dir).unwrap();
+    ///
+    /// let addr = &*stupid_box as *const u32 as usize;
+    /// assert!(snapshot.allocations().iter().any(|a| a.addr == addr));
+    /// ```
+    pub fn snapshot_consistent(&self, dir: impl AsRef<Path>) -> io::Result<Snapshot> {
+        let dir = dir.as_ref();
+        let partial = sibling(dir, ".partial");
+        let old = sibling(dir, ".old");
+
+        system_scope(|| {
+            // leftovers of a previous crash.
+            for leftover in [&partial, &old] {
+                if leftover.exists() {
+                    std::fs::remove_dir_all(leftover)?;
+                }
+            }
+            std::fs::create_dir_all(&partial)?;
+
+            // the write locks keep everyone out, readers included.
+            write_snapshot(&partial, STUPID_MAP.write_all().iter())?;
+
+            if dir.exists() {
+                std::fs::rename(dir, &old)?;
+            }
+            std::fs::rename(&partial, dir)?;
+            if old.exists() {
+                std::fs::remove_dir_all(&old)?;
+            }
+            Ok::<_, io::Error>(())
+        })?;
+
+        Snapshot::load(dir)
+    }
+
+    /// Writes the contents recorded in `snapshot` back into memory, for every
+    /// allocation of the snapshot that is still live at the same address and
+    /// with the same size. Returns the number of allocations that were
+    /// restored.
+    pub fn restore_snapshot(&self, snapshot: &Snapshot) -> io::Result<usize> {
+        system_scope(|| {
+            let mut map = STUPID_MAP.write_all();
+            let mut restored = 0;
+
+            for allocation in &snapshot.allocations {
+                if let Some(handle) = map
+                    .get_mut(&allocation.addr)
+                    .filter(|handle| handle.layout.size() == allocation.layout.size())
+                {
+                    let contents = snapshot.contents_of(allocation)?;
+                    if contents.len() == allocation.layout.size() {
+                        let offset = handle.offset();
+                        handle.map[offset..offset + contents.len()].copy_from_slice(&contents);
+                        restored += 1;
+                    }
+                }
+            }
+
+            Ok(restored)
+        })
+    }
+}
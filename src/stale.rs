@@ -0,0 +1,245 @@
+use std::{
+    fs::{File, TryLockError},
+    io,
+    path::{Path, PathBuf},
+    sync::Once,
+};
+
+use crate::{
+    base_alloc_dir, claim_main_thread, process_id, system_scope, threads, user_error, StupidAlloc,
+};
+
+static AT_STARTUP: Once = Once::new();
+
+// whether the process `pid` is still running. when there's no telling, it is.
+fn alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // signal 0 only checks that the signal could be sent. a process owned
+        // by someone else is alive, just not ours to signal.
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return true;
+        };
+        let signaled = unsafe { libc::kill(pid, 0) } == 0;
+        signaled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+// whether the file at `path` is locked by a live allocation, in any process.
+// allocations lock their files for as long as they keep them open, see
+// keep_open.
+fn in_use(path: &Path) -> io::Result<bool> {
+    match File::open(path)?.try_lock() {
+        Err(TryLockError::WouldBlock) => Ok(true),
+        // no locks here, no telling.
+        Err(TryLockError::Error(_)) | Ok(()) => Ok(false),
+    }
+}
+
+// whether any file in `dir` or its subfolders, like the ones of arenas, is
+// in use.
+fn any_in_use(dir: &Path) -> io::Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let used = match path.is_dir() {
+            true => any_in_use(&path)?,
+            false => in_use(&path)?,
+        };
+        if used {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// the folders of the processes that are gone, and their pids. an allocation
+// shared with a live process keeps the folder alive too. persistent
+// allocations have a folder of their own, which is not named after a process.
+fn stale_dirs() -> io::Result<Vec<(u32, PathBuf)>> {
+    let dir = base_alloc_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut stale = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let pid = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.parse::<u32>().ok());
+
+        if let Some(pid) = pid {
+            if pid != process_id() && path.is_dir() && !alive(pid) && !any_in_use(&path)? {
+                stale.push((pid, path));
+            }
+        }
+    }
+
+    stale.sort();
+    Ok(stale)
+}
+
+// moves the allocation files of the folder of process `pid` to the folder of
+// persistent allocations, along with its manifest, and returns their keys. the
+// rest of the folder is left alone.
+fn recover(pid: u32, dir: &Path) -> io::Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut moves = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        if path.extension().is_some_and(|ext| ext == "mem") {
+            let key = format!("{pid}-{stem}");
+            let new = crate::persist::persistent_path(&key)?;
+            std::fs::rename(&path, &new)?;
+            moves.push((path, new));
+            keys.push(key);
+        }
+    }
+
+    // the files are recovered either way.
+    let _ = crate::manifest::recover(pid, dir, &moves);
+    Ok(keys)
+}
+
+// what to do with the files of dead processes, decided by the
+// `STUPIDALLOC_STALE` environment variable. nobody's listening for errors.
+fn at_startup() {
+    let mode = std::env::var("STUPIDALLOC_STALE").unwrap_or_default();
+    let Ok(stale) = stale_dirs() else {
+        return;
+    };
+
+    match mode.as_str() {
+        "keep" => {}
+        "recover" => {
+            let keys = stale
+                .iter()
+                .filter_map(|(pid, dir)| recover(*pid, dir).ok())
+                .flatten()
+                .collect::<Vec<_>>();
+            if !keys.is_empty() {
+                eprintln!(
+                    "stupidalloc: recovered the allocations of crashed processes, restore them with StupidAlloc::restore(): {}",
+                    keys.join(", ")
+                );
+            }
+            for (_, dir) in stale {
+                let _ = std::fs::remove_dir_all(dir);
+            }
+        }
+        _ => {
+            for (_, dir) in stale {
+                let _ = std::fs::remove_dir_all(dir);
+            }
+        }
+    }
+}
+
+// looks for the files of dead processes, once, on a background thread: there
+// can be a lot of them. must be called while ALLOCATING is nonzero.
+pub fn on_startup() {
+    AT_STARTUP.call_once(|| {
+        claim_main_thread();
+        let _ = std::thread::Builder::new()
+            .name("stupidalloc cleanup".into())
+            .spawn(|| {
+                threads::internal();
+                at_startup()
+            });
+    });
+}
+
+impl StupidAlloc {
+    /// Removes the allocation files left behind by processes that are not
+    /// running anymore, and returns their pids. Every process has a folder of
+    /// its own, named after its pid, which stays around if it crashes, or if it
+    /// exits without freeing everything.
+    ///
+    /// This is done automatically in the background when the first allocation
+    /// is made, unless the `STUPIDALLOC_STALE` environment variable says
+    /// otherwise: `keep` leaves the files alone, and `recover` does what
+    /// [`StupidAlloc::recover_stale()`] does, printing the keys to the
+    /// standard error. Persistent allocations are never removed. Telling
+    /// whether a process is running only works on Unix platforms, and
+    /// nothing is removed on the others.
+    pub fn cleanup_stale(&self) -> io::Result<Vec<u32>> {
+        let pids = system_scope(|| {
+            let stale = stale_dirs()?;
+            for (_, dir) in &stale {
+                std::fs::remove_dir_all(dir)?;
+            }
+            Ok(stale.into_iter().map(|(pid, _)| pid).collect::<Vec<_>>())
+        })
+        .map_err(user_error)?;
+
+        let user_pids = pids.clone();
+        system_scope(|| drop(pids));
+        Ok(user_pids)
+    }
+
+    /// Returns whether the file at `path` belongs to a live allocation, in
+    /// this process or in any other. Allocations take an advisory lock on
+    /// their files (with `flock` or `LockFileEx`), for tools cleaning up after
+    /// the stupid alloc to know which files they must not touch. Allocations
+    /// whose files were closed with [`StupidAlloc::set_close_files()`] can't
+    /// hold a lock, and neither can the files of file systems without locks.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in(42u32, StupidAlloc);
+    /// let path = StupidAlloc.file_of(&*stupid_box).unwrap();
+    /// assert!(StupidAlloc.is_file_in_use(&path).unwrap());
+    /// ```
+    pub fn is_file_in_use(&self, path: impl AsRef<Path>) -> io::Result<bool> {
+        let path = path.as_ref();
+        system_scope(|| in_use(path)).map_err(user_error)
+    }
+
+    /// Like [`StupidAlloc::cleanup_stale()`], except that the allocation files
+    /// of the dead processes are made persistent before their folders are
+    /// removed, so that their contents can be looked at with
+    /// [`StupidAlloc::restore()`]. Returns their keys, which are the pid of
+    /// the process and the name of the file, like `1234-alloc_0000000042`.
+    /// The manifest of each process goes with them, as `1234-manifest.tsv` in
+    /// the same folder, and still tells what each file was, see
+    /// [`StupidAlloc::manifest_path()`].
+    ///
+    /// ```no_run
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// for key in StupidAlloc.recover_stale().unwrap() {
+    ///     // SAFETY: the program only ever allocates u64s. honest.
+    ///     if let Some(value) = unsafe { StupidAlloc.restore::<u64>(&key) } {
+    ///         println!("{key} was holding {value}");
+    ///     }
+    /// }
+    /// ```
+    pub fn recover_stale(&self) -> io::Result<Vec<String>> {
+        let keys = system_scope(|| {
+            let mut keys = Vec::new();
+            for (pid, dir) in stale_dirs()? {
+                keys.extend(recover(pid, &dir)?);
+                std::fs::remove_dir_all(dir)?;
+            }
+            Ok(keys)
+        })
+        .map_err(user_error)?;
+
+        let user_keys = keys.clone();
+        system_scope(|| drop(keys));
+        Ok(user_keys)
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::{busy, shutdown, AllocHandle, StupidAlloc, STUPID_MAP};
+
+/// Statistics about live allocations, returned by [`StupidAlloc::stats()`]
+/// and [`Arena::stats()`](crate::Arena::stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of live allocations.
+    pub allocations: usize,
+    /// The total size of the live allocations in bytes, as requested by their
+    /// layouts. Redzones and padding are not counted.
+    pub bytes: usize,
+    /// The number of allocations that went to System instead since the
+    /// program started, see [`StupidAlloc::fallbacks()`] for why. Only
+    /// counted by [`StupidAlloc::stats()`], arenas leave it at zero.
+    pub fallbacks: u64,
+}
+
+/// Why allocations went to System instead of getting a file, counted since the
+/// program started and returned by [`StupidAlloc::fallbacks()`]. No file for
+/// your `Box`? One of these went up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fallbacks {
+    /// Allocations made by threads where the stupid alloc is disabled. Only
+    /// the first thread that allocates has it enabled by default, see
+    /// [`StupidAlloc::enable_in_thread()`] and
+    /// [`StupidAlloc::set_new_thread_default()`].
+    pub disabled: u64,
+    /// Allocations made by the stupid alloc itself while it was busy
+    /// allocating or freeing, or by its own threads, or by threads that are
+    /// exiting.
+    pub recursion: u64,
+    /// Allocations of the C programs the crate is preloaded in, that were
+    /// smaller than the threshold of the `preload` feature.
+    pub below_threshold: u64,
+    /// Allocations made after the stupid alloc shut down, see
+    /// [`Phase::Late`](crate::Phase::Late), and allocations from before that
+    /// were moved to System by a resize.
+    pub late: u64,
+    /// Allocations that would have gone over a [`Budget`](crate::Budget),
+    /// and allocations that were moved to System by a resize that would have.
+    pub over_budget: u64,
+}
+
+impl Fallbacks {
+    /// The number of allocations that went to System, for any reason.
+    pub fn total(&self) -> u64 {
+        self.disabled + self.recursion + self.below_threshold + self.late + self.over_budget
+    }
+}
+
+/// Counters of everything the stupid alloc did since the program started,
+/// returned by [`StupidAlloc::totals()`]. Allocations that are still live
+/// count too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    /// The number of allocations made.
+    pub allocations: u64,
+    /// The number of allocations freed.
+    pub frees: u64,
+    /// The number of times allocations were resized.
+    pub resizes: u64,
+    /// The size of the largest allocation in bytes, including the ones that
+    /// grew that large.
+    pub largest: usize,
+    /// The number of times the most resized allocation was resized.
+    pub most_resizes: usize,
+    /// How long the longest-lived allocation lived.
+    pub longest_lived: Duration,
+}
+
+// the counters behind the totals, minus what the live allocations add to them.
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static FREES: AtomicU64 = AtomicU64::new(0);
+static RESIZES: AtomicU64 = AtomicU64::new(0);
+static LARGEST: AtomicUsize = AtomicUsize::new(0);
+static MOST_RESIZES: AtomicUsize = AtomicUsize::new(0);
+static LONGEST_LIVED_NANOS: AtomicU64 = AtomicU64::new(0);
+
+// the allocations that went to System, by reason.
+static DISABLED: AtomicU64 = AtomicU64::new(0);
+static RECURSION: AtomicU64 = AtomicU64::new(0);
+static BELOW_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+static LATE: AtomicU64 = AtomicU64::new(0);
+static OVER_BUDGET: AtomicU64 = AtomicU64::new(0);
+
+pub fn record_allocation(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    LARGEST.fetch_max(size, Ordering::Relaxed);
+}
+
+// counts an allocation that goes to System because use_system said so, or
+// because the stupid alloc shut down.
+pub fn record_fallback() {
+    if busy() {
+        RECURSION.fetch_add(1, Ordering::Relaxed);
+    } else if shutdown::late() {
+        LATE.fetch_add(1, Ordering::Relaxed);
+    } else {
+        DISABLED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// counts an allocation that goes to System because it's over a budget.
+pub fn record_over_budget() {
+    OVER_BUDGET.fetch_add(1, Ordering::Relaxed);
+}
+
+// counts an allocation of a preloaded program that was too small for a file.
+#[cfg(all(feature = "preload", target_os = "linux", target_env = "gnu"))]
+pub fn record_below_threshold() {
+    BELOW_THRESHOLD.fetch_add(1, Ordering::Relaxed);
+}
+
+// must be called once the handle has its new layout.
+pub fn record_resize(handle: &AllocHandle) {
+    RESIZES.fetch_add(1, Ordering::Relaxed);
+    LARGEST.fetch_max(handle.layout.size(), Ordering::Relaxed);
+    MOST_RESIZES.fetch_max(handle.resizes, Ordering::Relaxed);
+}
+
+pub fn record_free(handle: &AllocHandle) {
+    FREES.fetch_add(1, Ordering::Relaxed);
+    let lived = handle
+        .created
+        .elapsed()
+        .as_nanos()
+        .try_into()
+        .unwrap_or(u64::MAX);
+    LONGEST_LIVED_NANOS.fetch_max(lived, Ordering::Relaxed);
+}
+
+// the statistics of the live allocations matching `filter`.
+pub fn stats_of(filter: impl Fn(&AllocHandle) -> bool) -> Stats {
+    STUPID_MAP
+        .read_all()
+        .values()
+        .filter(|handle| filter(handle))
+        .fold(Stats::default(), |stats, handle| Stats {
+            allocations: stats.allocations + 1,
+            bytes: stats.bytes + handle.layout.size(),
+            ..stats
+        })
+}
+
+impl StupidAlloc {
+    /// Returns statistics about all the live allocations of the stupid alloc.
+    /// Freed allocations sitting in quarantine are not counted.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            fallbacks: self.fallbacks().total(),
+            ..stats_of(|_| true)
+        }
+    }
+
+    /// Returns how many allocations went to System instead of getting a file
+    /// since the program started, and why. Their frees and resizes go to
+    /// System too, and aren't counted.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// // the first thread to allocate is the one the stupid alloc is enabled in.
+    /// let stupid_box = Box::new_in(1, StupidAlloc);
+    /// let before = StupidAlloc.fallbacks();
+    /// std::thread::spawn(|| drop(Box::new_in(42, StupidAlloc))).join().unwrap();
+    /// assert!(StupidAlloc.fallbacks().disabled > before.disabled);
+    /// ```
+    pub fn fallbacks(&self) -> Fallbacks {
+        Fallbacks {
+            disabled: DISABLED.load(Ordering::Relaxed),
+            recursion: RECURSION.load(Ordering::Relaxed),
+            below_threshold: BELOW_THRESHOLD.load(Ordering::Relaxed),
+            late: LATE.load(Ordering::Relaxed),
+            over_budget: OVER_BUDGET.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns counters of everything the stupid alloc did since the program
+    /// started: allocations, frees and resizes, and a few records.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let mut stupid_vec = Vec::new_in(StupidAlloc);
+    /// for i in 0..100u8 {
+    ///     stupid_vec.push(i);
+    /// }
+    ///
+    /// let totals = StupidAlloc.totals();
+    /// assert_eq!(totals.allocations, 1);
+    /// assert!(totals.resizes >= 1);
+    /// assert_eq!(totals.most_resizes as u64, totals.resizes);
+    /// assert!(totals.largest >= 100);
+    /// ```
+    pub fn totals(&self) -> Totals {
+        let totals = Totals {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            frees: FREES.load(Ordering::Relaxed),
+            resizes: RESIZES.load(Ordering::Relaxed),
+            largest: LARGEST.load(Ordering::Relaxed),
+            most_resizes: MOST_RESIZES.load(Ordering::Relaxed),
+            longest_lived: Duration::from_nanos(LONGEST_LIVED_NANOS.load(Ordering::Relaxed)),
+        };
+
+        // the live allocations hold records too, they just didn't finish
+        // setting them.
+        STUPID_MAP
+            .read_all()
+            .values()
+            .fold(totals, |totals, handle| Totals {
+                longest_lived: totals.longest_lived.max(handle.created.elapsed()),
+                ..totals
+            })
+    }
+}
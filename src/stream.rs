@@ -0,0 +1,203 @@
+use std::{io, path::Path};
+
+use crate::StupidAlloc;
+
+#[cfg(unix)]
+use std::{fs::File, io::Write, path::PathBuf, time::Duration};
+
+#[cfg(unix)]
+use crate::{error, snapshot::differing_ranges, system_scope, threads, user_error, STUPID_MAP};
+
+// how often streams look at their allocation.
+#[cfg(unix)]
+const INTERVAL: Duration = Duration::from_millis(50);
+
+// opens the fifo at `path` for writing, if anyone is reading it: opening it
+// without blocking fails with ENXIO until then.
+#[cfg(unix)]
+fn open(path: &Path) -> io::Result<Option<File>> {
+    use std::os::unix::{fs::OpenOptionsExt, io::AsRawFd};
+
+    let opened = std::fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path);
+    match opened {
+        Ok(file) => {
+            // writes do block, a slow reader only slows its stream down.
+            let fd = file.as_raw_fd();
+            unsafe {
+                libc::fcntl(
+                    fd,
+                    libc::F_SETFL,
+                    libc::fcntl(fd, libc::F_GETFL) & !libc::O_NONBLOCK,
+                )
+            };
+            Ok(Some(file))
+        }
+        Err(e) if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// the contents of the allocation in the file at `path`, or none once it's
+// freed.
+#[cfg(unix)]
+fn contents(path: &Path) -> Option<Vec<u8>> {
+    let map = STUPID_MAP.read_all();
+    let (_, handle) = map.iter().find(|(_, handle)| handle.path == path)?;
+    let offset = handle.offset();
+    Some(handle.map[offset..offset + handle.layout.size()].to_vec())
+}
+
+// a frame of the stream: the size of the allocation, the offset of the bytes,
+// and their number, as little-endian u64s, followed by the bytes.
+#[cfg(unix)]
+fn write_frame(out: &mut File, size: usize, offset: usize, bytes: &[u8]) -> io::Result<()> {
+    let mut header = [0; 24];
+    header[..8].copy_from_slice(&(size as u64).to_le_bytes());
+    header[8..16].copy_from_slice(&(offset as u64).to_le_bytes());
+    header[16..].copy_from_slice(&(bytes.len() as u64).to_le_bytes());
+    out.write_all(&header)?;
+    out.write_all(bytes)
+}
+
+// streams the allocation in the file at `path` to the fifo at `fifo` until
+// it's freed, or until the fifo is removed.
+#[cfg(unix)]
+fn stream(path: PathBuf, fifo: PathBuf) {
+    threads::internal();
+
+    // what the reader has seen so far, none until there's one.
+    let (mut reader, mut sent) = (None, None::<Vec<u8>>);
+    while let Some(data) = contents(&path) {
+        if reader.is_none() {
+            if !fifo.exists() {
+                return;
+            }
+            reader = open(&fifo).ok().flatten();
+        }
+
+        if let Some(out) = reader.as_mut() {
+            // a new reader starts with everything.
+            let ranges = match &sent {
+                None => std::iter::once(0..data.len()).collect(),
+                Some(sent) => {
+                    let mut ranges = differing_ranges(sent, &data)
+                        .into_iter()
+                        .map(|range| range.start..range.end.min(data.len()))
+                        .filter(|range| !range.is_empty())
+                        .collect::<Vec<_>>();
+                    // shrinking changes no byte, but the size.
+                    if ranges.is_empty() && sent.len() != data.len() {
+                        ranges.push(data.len()..data.len());
+                    }
+                    ranges
+                }
+            };
+
+            let written = ranges
+                .into_iter()
+                .try_for_each(|range| write_frame(out, data.len(), range.start, &data[range]));
+            match written {
+                Ok(()) => sent = Some(data),
+                // the reader left, the next one starts over. rust programs
+                // ignore SIGPIPE, so this is an error rather than a crash.
+                Err(_) => (reader, sent) = (None, None),
+            }
+        }
+
+        std::thread::sleep(INTERVAL);
+    }
+    // dropping the fifo tells the reader it's over.
+}
+
+impl StupidAlloc {
+    /// Streams the contents of the allocation containing `value` to the named
+    /// pipe at `fifo`, created if it doesn't exist, for other programs to
+    /// watch it change live: `pv`, `xxd`, a Python script, without linking
+    /// against anything.
+    ///
+    /// The stream is made of frames, each one being the size of the
+    /// allocation, the offset of some bytes and their number, as three
+    /// little-endian `u64`s, followed by the bytes. A reader first gets the
+    /// whole allocation, then the bytes that changed, looked for every 50
+    /// milliseconds, so it can keep a copy up to date. Readers can come and
+    /// go; the next one starts over. The stream follows the allocation when
+    /// it's resized, and ends when it's freed, or when `fifo` is removed.
+    ///
+    /// Returns an error of kind [`NotFound`](io::ErrorKind::NotFound) if
+    /// `value` wasn't allocated with the stupid alloc, of kind
+    /// [`AlreadyExists`](io::ErrorKind::AlreadyExists) if `fifo` is something
+    /// else than a named pipe, and of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported) for pass-through
+    /// allocations, and outside of Unix platforms.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use std::io::Read;
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let data = Box::new_in(*b"hello, pipe", StupidAlloc);
+    /// if cfg!(unix) {
+    ///     let fifo = std::env::temp_dir().join("stupidalloc_doc_stream");
+    ///     StupidAlloc.stream_of(&*data, &fifo).unwrap();
+    ///
+    ///     // what a script on the other end would do.
+    ///     let mut frame = [0; 24 + 11];
+    ///     let mut reader = std::fs::File::open(&fifo).unwrap();
+    ///     reader.read_exact(&mut frame).unwrap();
+    ///     assert_eq!(frame[..8], 11u64.to_le_bytes());
+    ///     assert_eq!(frame[8..16], 0u64.to_le_bytes());
+    ///     assert_eq!(&frame[24..], b"hello, pipe");
+    /// }
+    /// ```
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    pub fn stream_of<T: ?Sized>(&self, value: &T, fifo: impl AsRef<Path>) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::{ffi::OsStrExt, fs::FileTypeExt};
+
+            let addr = value as *const T as *const u8 as usize;
+            let fifo = fifo.as_ref();
+
+            // the paths belong to the thread, and come from System.
+            system_scope(|| {
+                let handle = STUPID_MAP.handle_of(addr).ok_or(io::ErrorKind::NotFound)?;
+                if handle.map.map().is_none() {
+                    return Err(io::ErrorKind::Unsupported.into());
+                }
+                let (path, fifo) = (handle.path.clone(), fifo.to_path_buf());
+                drop(handle);
+
+                match std::fs::metadata(&fifo) {
+                    Ok(metadata) if metadata.file_type().is_fifo() => {}
+                    Ok(_) => return Err(io::ErrorKind::AlreadyExists.into()),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes())
+                            .map_err(|_| io::ErrorKind::InvalidInput)?;
+                        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                    Err(e) => return Err(e),
+                }
+
+                let spawned = std::thread::Builder::new()
+                    .name("stupidalloc stream".into())
+                    .spawn(move || stream(path, fifo));
+                if let Err(e) = spawned {
+                    error::backend(format!("couldn't spawn the stream thread: {e}"));
+                    return Err(io::ErrorKind::Other.into());
+                }
+                Ok(())
+            })
+            .map_err(user_error)
+        }
+        #[cfg(not(unix))]
+        {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+}
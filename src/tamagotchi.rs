@@ -0,0 +1,164 @@
+// there are no windows for the pets to live in on wasi, only the switch.
+#![cfg_attr(target_os = "wasi", allow(dead_code))]
+
+use std::{
+    alloc::System,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use allocator_api2::vec::Vec;
+
+use crate::StupidAlloc;
+
+// whether the windows show how their allocation is doing.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// how long an allocation stays healthy without being written to, and how long
+// it takes to starve after that.
+const FED: Duration = Duration::from_secs(5);
+const STARVING: Duration = Duration::from_secs(30);
+
+const HEARTS: usize = 5;
+
+const RED: u32 = 0x00FF0000;
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+// multiplies every channel of a 0x00RRGGBB color by `factor`.
+fn scale(color: u32, factor: f64) -> u32 {
+    [16, 8, 0].into_iter().fold(0, |scaled, shift| {
+        let channel = ((color >> shift & 0xFF) as f64 * factor) as u32;
+        scaled | channel.min(0xFF) << shift
+    })
+}
+
+// the life of the allocation of a window. writes are noticed by comparing the
+// bytes with the ones of the previous frame, which only works as long as the
+// window is drawn, but doesn't need anything from the allocator.
+pub struct Pet {
+    born: Instant,
+    last_write: Instant,
+    shadow: Vec<u8, System>,
+}
+
+impl Pet {
+    // `born` is when the allocation was made, which can be long before its
+    // window was opened.
+    pub fn new(born: Instant) -> Self {
+        Self {
+            born,
+            last_write: Instant::now(),
+            shadow: Vec::new_in(System),
+        }
+    }
+
+    // feeds the pet if the bytes changed since the last time.
+    pub fn watch(&mut self, bytes: &[u8]) {
+        if self.shadow[..] != *bytes {
+            self.shadow.clear();
+            self.shadow.extend_from_slice(bytes);
+            self.last_write = Instant::now();
+        }
+    }
+
+    // forgets the bytes, so that the pet is fed again when it next gets
+    // watched. nobody watches it while the mode is disabled, it's not its fault.
+    pub fn forget(&mut self) {
+        self.shadow.clear();
+    }
+
+    // from 1 when it was written to recently, down to 0 when it starved.
+    fn health(&self) -> f64 {
+        let hungry = self.last_write.elapsed().saturating_sub(FED);
+        1.0 - (hungry.as_secs_f64() / STARVING.as_secs_f64()).min(1.0)
+    }
+
+    // the hearts left and the age in seconds, which is what the title shows.
+    pub fn status(&self) -> (usize, u64) {
+        let hearts = (self.health() * HEARTS as f64).ceil() as usize;
+        (hearts, self.born.elapsed().as_secs())
+    }
+
+    // the color of the set bits, which fades as the health goes down.
+    pub fn color(&self, color: u32) -> u32 {
+        scale(color, 0.25 + 0.75 * self.health())
+    }
+}
+
+// the title of a window, with the status of its allocation.
+pub fn title(name: &str, (hearts, age): (usize, u64)) -> String {
+    let mood = if hearts == 0 { " starving!" } else { "" };
+    format!(
+        "{name} {}{} {age}s old{mood}",
+        "♥".repeat(hearts),
+        "♡".repeat(HEARTS - hearts)
+    )
+}
+
+// the allocation was freed: the bits turn red, and crumble to the bottom of the
+// window while fading away. only the pixels are left by then, since the memory
+// is gone.
+#[cfg(not(target_os = "wasi"))]
+pub fn die(
+    window: &mut minifb::Window,
+    buffer: &mut [u32],
+    (width, height): (usize, usize),
+    name: &str,
+    pet: &Pet,
+) {
+    window.set_title(&format!(
+        "{name} ✝ died at {}s old",
+        pet.born.elapsed().as_secs()
+    ));
+
+    let buffer = &mut buffer[..width * height];
+    for pixel in buffer.iter_mut().filter(|pixel| **pixel != 0) {
+        *pixel = RED;
+    }
+
+    for frame in 0..120 {
+        if !window.is_open() {
+            return;
+        }
+
+        // a moment of silence first.
+        if frame >= 20 {
+            // the rows are walked from the bottom, so that a pixel falls one
+            // row per frame.
+            for y in (0..height.saturating_sub(1)).rev() {
+                for x in 0..width {
+                    let (above, below) = (y * width + x, (y + 1) * width + x);
+                    if buffer[above] != 0 && buffer[below] == 0 {
+                        buffer.swap(above, below);
+                    }
+                }
+            }
+            for pixel in buffer.iter_mut() {
+                *pixel = scale(*pixel, 0.96);
+            }
+        }
+
+        if window.update_with_buffer(buffer, width, height).is_err() {
+            return;
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Enables or disables the tamagotchi mode of the graphical windows. When
+    /// enabled, the title of each window shows the age of its allocation, and
+    /// how healthy it is: an allocation that isn't written to goes hungry,
+    /// loses its hearts and fades away. Clicking on the bits counts as
+    /// feeding it. When freed, the allocation dies, dramatically, and its
+    /// window takes a moment to close, without holding up the program.
+    ///
+    /// Writes are noticed by the windows themselves, by comparing the data
+    /// with what they drew on the previous frame.
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "graphics")))]
+    pub fn set_tamagotchi(&self, value: bool) {
+        ENABLED.store(value, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    alloc::Layout,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{page_size, StupidAlloc};
+
+/// How much the `teaching` feature explains in the logs and the dialogs, set
+/// with [`StupidAlloc::set_verbosity()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// No explanations, like without the feature.
+    Quiet,
+    /// A sentence or two about each event.
+    Brief,
+    /// Everything there is to say about each event.
+    #[default]
+    Detailed,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Detailed as u8);
+
+fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::SeqCst) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Brief,
+        _ => Verbosity::Detailed,
+    }
+}
+
+// the explanation, as a quote so that it stands out in the markdown logs.
+pub fn quote(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("> {line}\n"))
+        .collect::<String>()
+}
+
+// what a layout means, before allocating for it.
+pub fn allocation(layout: Layout) -> Option<String> {
+    let (size, align) = (layout.size(), layout.align());
+    let mut text = format!(
+        "A `Layout` of {size} bytes aligned to {align}: the value takes {size} bytes, and must start at an address that is a multiple of {align}."
+    );
+
+    match verbosity() {
+        Verbosity::Quiet => return None,
+        Verbosity::Brief => {}
+        Verbosity::Detailed => text.push_str(&format!(
+            "\n\nThe size is `size_of` the type, padding between fields included, times the number of elements for the likes of `Vec` and `String`. The alignment is the one of the most demanding field: a `u64` wants an address that is a multiple of 8, while a `u8` is happy anywhere.\n\nHere, the allocation gets a file of its own, mapped in memory. Memory maps start at the beginning of a page, {} bytes here, so any alignment up to that comes for free. A real allocator packs allocations next to each other instead, and has to leave gaps to respect the alignment.",
+            page_size()
+        )),
+    }
+
+    Some(text)
+}
+
+// why an allocation changed size, and what it implies. `moved` is where it
+// went, if it didn't stay in place.
+pub fn resize(old: Layout, new: Layout, moved: Option<(usize, usize)>) -> Option<String> {
+    let (old_size, new_size) = (old.size(), new.size());
+    let mut text = if new_size >= old_size {
+        format!("Grown from {old_size} to {new_size} bytes.")
+    } else {
+        format!("Shrunk from {old_size} to {new_size} bytes.")
+    };
+    if new_size == old_size * 2 {
+        text.push_str(" That's twice the size: `Vec` and `String` double their capacity whenever they run out of room.");
+    }
+
+    match verbosity() {
+        Verbosity::Quiet => return None,
+        Verbosity::Brief => return Some(text),
+        Verbosity::Detailed => {}
+    }
+
+    if new_size == old_size * 2 {
+        text.push_str(" Doubling means that pushing n elements only reallocates about log2(n) times, instead of every time.");
+    } else if new_size < old_size {
+        text.push_str(
+            " A `Vec` never gives memory back on its own: this is `shrink_to_fit()` or the likes.",
+        );
+    }
+
+    text.push_str("\n\nReallocating means finding room for the new size, which may be somewhere else entirely: the bytes are copied over, and the old memory is gone. Every pointer into the old place is left dangling, which is why Rust won't let you keep a reference into a `Vec` while pushing to it. Reserving the capacity up front, with `Vec::with_capacity()` or `reserve()`, avoids reallocating at all.\n\n");
+    text.push_str(&match moved {
+        Some((from, to)) => {
+            format!("This time, the allocation moved from 0x{from:08x} to 0x{to:08x}.")
+        }
+        None => {
+            "This time, the file could be resized where it was mapped, so nothing moved.".into()
+        }
+    });
+
+    Some(text)
+}
+
+// what freeing an allocation means.
+pub fn deallocation(layout: Layout) -> Option<String> {
+    let mut text = format!(
+        "Freed: the {} bytes are given back to the allocator.",
+        layout.size()
+    );
+
+    match verbosity() {
+        Verbosity::Quiet => return None,
+        Verbosity::Brief => {}
+        Verbosity::Detailed => text.push_str("\n\nWhoever owned the memory dropped it: a `Box` going out of scope, a `Vec` being dropped or reallocated, or the last `Rc` pointing to it going away. Using the memory after this is a use-after-free, which the ownership rules prevent in safe Rust.\n\nHere, the file is deleted and the memory map goes away, so a dangling pointer would crash the program, instead of quietly reading whatever the next allocation put there."),
+    }
+
+    Some(text)
+}
+
+impl StupidAlloc {
+    /// Sets how much the `teaching` feature explains in the logs and the
+    /// dialogs, from [`Verbosity::Quiet`] to [`Verbosity::Detailed`], the
+    /// default.
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "teaching")))]
+    pub fn set_verbosity(&self, verbosity: Verbosity) {
+        VERBOSITY.store(verbosity as u8, Ordering::SeqCst);
+    }
+}
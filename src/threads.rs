@@ -0,0 +1,175 @@
+use std::{
+    cell::Cell,
+    io,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread::{Builder, JoinHandle},
+};
+
+use crate::{StupidAlloc, ALLOCATING};
+
+/// Whether threads use the stupid alloc from the start, see
+/// [`StupidAlloc::set_new_thread_default()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ThreadDefault {
+    /// New threads use the stupid alloc, like the first one.
+    Enabled,
+    /// New threads use System, until they call
+    /// [`StupidAlloc::enable_in_thread()`].
+    #[default]
+    Disabled,
+}
+
+// whether allocations go in a folder of their thread.
+static THREAD_DIRS: AtomicBool = AtomicBool::new(false);
+
+// whether threads other than the first one start with the stupid alloc
+// enabled.
+static NEW_THREADS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+static NEXT_NUMBER: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    // the number of this thread, in the order threads first allocated. zero
+    // until then. const and without drop, so that it's there until the very
+    // end of the thread.
+    static NUMBER: Cell<usize> = const { Cell::new(0) };
+}
+
+// the number of the calling thread, starting at 1.
+pub fn number() -> usize {
+    NUMBER
+        .try_with(|number| {
+            if number.get() == 0 {
+                number.set(NEXT_NUMBER.fetch_add(1, Ordering::SeqCst));
+            }
+            number.get()
+        })
+        .unwrap_or(0)
+}
+
+// makes the calling thread, one of the stupid alloc's own, busy for good, like
+// in a system_scope that never ends: everything it allocates comes from
+// System, whatever the default of new threads is, and its frees don't look
+// at the registry, which may well be locked by whoever is waiting on it. to
+// be called before anything else in the thread.
+pub fn internal() {
+    let _ = ALLOCATING.try_with(|a| a.fetch_add(1, Ordering::SeqCst));
+}
+
+pub fn new_threads_enabled() -> bool {
+    NEW_THREADS_ENABLED.load(Ordering::SeqCst)
+}
+
+pub fn dirs() -> bool {
+    THREAD_DIRS.load(Ordering::SeqCst)
+}
+
+// the name of the folder of the calling thread: its own name when it has one,
+// with whatever file systems wouldn't take replaced, and its number otherwise.
+// must be called while ALLOCATING is nonzero.
+pub fn dir_name() -> String {
+    match std::thread::current().name() {
+        Some(name) => name
+            .chars()
+            .map(
+                |c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    true => c,
+                    false => '_',
+                },
+            )
+            .collect(),
+        None => format!("thread-{}", number()),
+    }
+}
+
+impl StupidAlloc {
+    /// Enables or disables a folder per thread for subsequent allocations,
+    /// depending on the value passed as parameter. When enabled, the files of
+    /// each thread go in a folder named after it, in the folder of the
+    /// process, and the heap overview groups the allocations by thread, with
+    /// a color each. Allocations of arenas stay in the folder of their arena.
+    ///
+    /// Other threads don't use the stupid alloc by default, see
+    /// [`StupidAlloc::enable_in_thread()`].
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// StupidAlloc.set_thread_dirs(true);
+    /// std::thread::Builder::new()
+    ///     .name("worker".into())
+    ///     .spawn(|| {
+    ///         StupidAlloc.enable_in_thread(true);
+    ///         let stupid_box = Box::new_in(42, StupidAlloc);
+    ///         let path = StupidAlloc.file_of(&*stupid_box).unwrap();
+    ///         assert!(path.parent().unwrap().ends_with("worker"));
+    ///     })
+    ///     .unwrap()
+    ///     .join()
+    ///     .unwrap();
+    /// StupidAlloc.set_thread_dirs(false);
+    /// ```
+    pub fn set_thread_dirs(&self, value: bool) {
+        THREAD_DIRS.store(value, Ordering::SeqCst);
+    }
+    /// Sets whether threads use the stupid alloc from the start. Only the
+    /// first thread that allocates does by default, since libraries and the
+    /// standard library spawn threads of their own, and those would get
+    /// stupid allocations too: with [`ThreadDefault::Enabled`], every thread
+    /// whose first allocation comes after this call is stupid, including
+    /// theirs. The threads of the stupid alloc itself never are. Threads can
+    /// still change their mind with [`StupidAlloc::enable_in_thread()`].
+    ///
+    /// Stupid allocations can be freed from any thread, stupid or not, and
+    /// resizing one from a thread where the stupid alloc is disabled moves it
+    /// to System.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::{StupidAlloc, ThreadDefault};
+    ///
+    /// let stupid_box = Box::new_in(1u8, StupidAlloc);
+    /// StupidAlloc.set_new_thread_default(ThreadDefault::Enabled);
+    /// std::thread::spawn(|| {
+    ///     let worker_box = Box::new_in(42u32, StupidAlloc);
+    ///     assert!(StupidAlloc.file_of(&*worker_box).is_some());
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// StupidAlloc.set_new_thread_default(ThreadDefault::Disabled);
+    /// ```
+    pub fn set_new_thread_default(&self, default: ThreadDefault) {
+        NEW_THREADS_ENABLED.store(default == ThreadDefault::Enabled, Ordering::SeqCst);
+    }
+
+    /// Spawns a thread with `builder` like [`Builder::spawn()`], with the
+    /// stupid alloc enabled in it before `f` runs, whatever
+    /// [`StupidAlloc::set_new_thread_default()`] says.
+    ///
+    #[cfg_attr(any(feature = "fake", miri), doc = "```ignore")]
+    #[cfg_attr(not(any(feature = "fake", miri)), doc = "```")]
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let worker = StupidAlloc
+    ///     .spawn_stupid(std::thread::Builder::new().name("worker".into()), || {
+    ///         let worker_box = Box::new_in(42u32, StupidAlloc);
+    ///         StupidAlloc.file_of(&*worker_box).is_some()
+    ///     })
+    ///     .unwrap();
+    /// assert!(worker.join().unwrap());
+    /// ```
+    pub fn spawn_stupid<F, T>(&self, builder: Builder, f: F) -> io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        builder.spawn(move || {
+            StupidAlloc.enable_in_thread(true);
+            f()
+        })
+    }
+}
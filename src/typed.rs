@@ -0,0 +1,211 @@
+use std::{any::type_name, mem::size_of, panic::Location};
+
+use allocator_api2::{boxed::Box, vec::Vec};
+
+use crate::{system_scope, StupidAlloc, STUPID_MAP};
+
+/// A [`Box`] whose contents are allocated with the stupid alloc. Create one
+/// with [`stupid_box!`](crate::stupid_box).
+pub type StupidBox<T> = Box<T, StupidAlloc>;
+
+/// A [`Vec`] whose contents are allocated with the stupid alloc. Create one
+/// with [`stupid_vec!`](crate::stupid_vec).
+pub type StupidVec<T> = Vec<T, StupidAlloc>;
+
+// the widest a window gets to fit the elements of its allocation, in bytes.
+// wider ones don't fit on the screen.
+#[cfg(feature = "graphics")]
+const MAX_ELEMENT_COLUMNS: usize = 16;
+
+// the number of columns of a window showing elements of `element_size` bytes,
+// one element per row, if that makes sense. any number of columns lines up
+// with bytes.
+#[cfg(feature = "graphics")]
+fn element_columns(element_size: usize) -> Option<usize> {
+    (2..=MAX_ELEMENT_COLUMNS)
+        .contains(&element_size)
+        .then_some(element_size)
+}
+
+// remembers the type of the allocation at `addr`, and the line of code that
+// made it, so that the logs and the windows can show them. windows opened by
+// default show one element of `element_size` bytes per row. does nothing if
+// there is no such allocation, like for zero-sized types.
+#[cfg_attr(not(feature = "graphics"), allow(unused_variables))]
+fn record_type(
+    addr: usize,
+    type_name: &'static str,
+    location: &'static Location<'static>,
+    element_size: usize,
+) {
+    // the log message and the window message may allocate, and the registry
+    // is locked.
+    system_scope(|| {
+        let mut map = STUPID_MAP.write(addr);
+        if let Some(handle) = map.get_mut(&addr) {
+            handle.type_name = Some(type_name);
+            handle.location = Some(location);
+
+            if handle.has_file() {
+                crate::manifest::typed(&handle.path, type_name, location);
+            }
+
+            #[cfg(feature = "logging")]
+            handle.log(format_args!(
+                "## Type\n`{type_name}`, allocated at `{location}`\n"
+            ));
+
+            // a window can only have been opened by default this early, so
+            // it's fine to change its columns.
+            #[cfg(feature = "graphics")]
+            if let Some(window) = handle.window.as_ref() {
+                // the window might be closed already, and that's fine.
+                let _ = window.tx.send(crate::graphics::Message::Retype {
+                    type_name,
+                    location: Some(location),
+                });
+                if let Some(columns) = element_columns(element_size) {
+                    let _ = window.tx.send(crate::graphics::Message::Resize { columns });
+                }
+            }
+
+            #[cfg(feature = "always-graphics")]
+            if handle.window.is_none()
+                && crate::window_filter::matches_type(handle.layout.size(), &handle.path, type_name)
+            {
+                handle.open_window(element_columns(element_size).unwrap_or_else(|| {
+                    crate::DEFAULT_GRAPHICS_COLUMNS.load(std::sync::atomic::Ordering::SeqCst)
+                }));
+            }
+        }
+    })
+}
+
+// the functions below are used by the macros, which can't call `new_in`
+// themselves since it's unstable and they expand in the crate of the user.
+// they track their caller, which is where the macro was used.
+
+#[doc(hidden)]
+#[track_caller]
+pub fn new_box<T>(value: T) -> StupidBox<T> {
+    let boxed = Box::new_in(value, StupidAlloc);
+    record_type(
+        &*boxed as *const T as usize,
+        type_name::<T>(),
+        Location::caller(),
+        size_of::<T>(),
+    );
+    boxed
+}
+
+#[doc(hidden)]
+pub fn new_vec<T>() -> StupidVec<T> {
+    Vec::new_in(StupidAlloc)
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn vec_from_elem<T: Clone>(elem: T, n: usize) -> StupidVec<T> {
+    let mut vec = Vec::with_capacity_in(n, StupidAlloc);
+    vec.resize(n, elem);
+    record_type(
+        vec.as_ptr() as usize,
+        type_name::<[T]>(),
+        Location::caller(),
+        size_of::<T>(),
+    );
+    vec
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn vec_from_array<T, const N: usize>(array: [T; N]) -> StupidVec<T> {
+    let mut vec = Vec::with_capacity_in(N, StupidAlloc);
+    vec.extend(array);
+    record_type(
+        vec.as_ptr() as usize,
+        type_name::<[T]>(),
+        Location::caller(),
+        size_of::<T>(),
+    );
+    vec
+}
+
+/// Allocates a value with the stupid alloc, returning a [`StupidBox`]. The
+/// type of the value is recorded, along with the file and line where the macro
+/// was used, and shown in the log file and the window of the allocation.
+///
+/// ```
+/// use stupidalloc::{stupid_box, StupidAlloc, StupidBox};
+///
+/// let stupid_box: StupidBox<u32> = stupid_box!(42);
+///
+/// assert_eq!(*stupid_box, 42);
+/// assert_eq!(StupidAlloc.type_name_of(&*stupid_box), Some("u32"));
+/// ```
+#[macro_export]
+macro_rules! stupid_box {
+    ($value:expr) => {
+        $crate::new_box($value)
+    };
+}
+
+/// Creates a [`StupidVec`] containing the arguments, with the same syntax as
+/// [`vec!`]. The type of the elements is recorded, along with the file and
+/// line where the macro was used, and shown in the log file and the window of
+/// the allocation.
+///
+/// ```
+/// use stupidalloc::{stupid_vec, StupidAlloc};
+///
+/// let numbers = stupid_vec![1u8, 2, 3];
+/// let zeroes = stupid_vec![0u16; 8];
+///
+/// assert_eq!(numbers, [1, 2, 3]);
+/// assert_eq!(StupidAlloc.type_name_of(&zeroes[0]), Some("[u16]"));
+/// ```
+#[macro_export]
+macro_rules! stupid_vec {
+    () => {
+        $crate::new_vec()
+    };
+    ($elem:expr; $n:expr) => {
+        $crate::vec_from_elem($elem, $n)
+    };
+    ($($x:expr),+ $(,)?) => {
+        $crate::vec_from_array([$($x),+])
+    };
+}
+
+impl StupidAlloc {
+    /// Returns the type of the allocation containing `value`, if it was made
+    /// with [`stupid_box!`](crate::stupid_box), [`stupid_vec!`](crate::stupid_vec)
+    /// or [`StupidAlloc::restore()`].
+    pub fn type_name_of<T: ?Sized>(&self, value: &T) -> Option<&'static str> {
+        let addr = value as *const T as *const u8 as usize;
+        STUPID_MAP
+            .handle_of(addr)
+            .and_then(|handle| handle.type_name)
+    }
+
+    /// Returns the file and line of the [`stupid_box!`](crate::stupid_box) or
+    /// [`stupid_vec!`](crate::stupid_vec) that made the allocation containing
+    /// `value`. Recording it costs next to nothing, unlike a backtrace, so
+    /// backtraces can be turned off with [`StupidAlloc::set_backtraces()`]
+    /// and only turned back on when this isn't enough.
+    ///
+    /// ```
+    /// use stupidalloc::{stupid_box, StupidAlloc};
+    ///
+    /// let stupid_box = stupid_box!(42u32);
+    /// let location = StupidAlloc.location_of(&*stupid_box).unwrap();
+    ///
+    /// assert_eq!(location.line(), line!() - 3);
+    /// ```
+    pub fn location_of<T: ?Sized>(&self, value: &T) -> Option<&'static Location<'static>> {
+        let addr = value as *const T as *const u8 as usize;
+        STUPID_MAP
+            .handle_of(addr)
+            .and_then(|handle| handle.location)
+    }
+}
@@ -0,0 +1,87 @@
+use std::{alloc::System, any::Any, sync::Mutex};
+
+use allocator_api2::vec::Vec;
+use lazy_static::lazy_static;
+
+use crate::{system_scope, StupidAlloc, STUPID_MAP};
+
+/// Whatever a program wants to keep alongside an allocation, see
+/// [`StupidAlloc::set_user_data_of()`].
+pub type UserData = Box<dyn Any + Send + Sync>;
+
+lazy_static! {
+    // the data of freed allocations. it was allocated by the program, maybe
+    // with the stupid alloc itself, so it can't be dropped while freeing: it
+    // waits here until the free is over.
+    static ref ORPHANS: Mutex<Vec<UserData, System>> = Mutex::new(Vec::new_in(System));
+}
+
+// keeps the data of a freed allocation until drop_orphans. needs to be called
+// while DEALLOCATING is nonzero.
+pub fn orphan(data: UserData) {
+    ORPHANS.lock().unwrap().push(data);
+}
+
+// drops the data of freed allocations, as the program would. must not be
+// called while ALLOCATING or DEALLOCATING is nonzero, or with the registry
+// locked. the lock is released first, since dropping the data can free more
+// stupid allocations.
+pub fn drop_orphans() {
+    let orphans = std::mem::replace(&mut *ORPHANS.lock().unwrap(), Vec::new_in(System));
+    drop(orphans);
+}
+
+impl StupidAlloc {
+    /// Attaches `data` to the allocation containing `value`, for frameworks
+    /// building on the stupid alloc to keep their own metadata along with it.
+    /// Returns the data that was attached before, if any, or gives `data`
+    /// back if `value` isn't in a stupid allocation.
+    ///
+    /// The data lives as long as the allocation, and is dropped when it is
+    /// freed.
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// let stupid_box = Box::new_in(42u32, StupidAlloc);
+    /// StupidAlloc.set_user_data_of(&*stupid_box, Box::new("the answer")).unwrap();
+    ///
+    /// assert_eq!(StupidAlloc.user_data_of::<&str, _>(&*stupid_box), Some("the answer"));
+    /// ```
+    pub fn set_user_data_of<T: ?Sized>(
+        &self,
+        value: &T,
+        data: UserData,
+    ) -> Result<Option<UserData>, UserData> {
+        let addr = value as *const T as *const u8 as usize;
+
+        // moving the box around doesn't allocate, and the previous one is
+        // dropped by the caller, unlocked.
+        match STUPID_MAP.handle_of_mut(addr) {
+            Some(mut handle) => Ok(handle.user_data.replace(data)),
+            None => Err(data),
+        }
+    }
+
+    /// Returns a copy of the data attached to the allocation containing
+    /// `value` with [`StupidAlloc::set_user_data_of()`], if there is some and
+    /// it's a `D`.
+    pub fn user_data_of<D: Any + Clone, T: ?Sized>(&self, value: &T) -> Option<D> {
+        let addr = value as *const T as *const u8 as usize;
+
+        // the registry is locked while cloning, so the clone lives in System.
+        let data = system_scope(|| {
+            STUPID_MAP
+                .handle_of(addr)?
+                .user_data
+                .as_ref()?
+                .downcast_ref::<D>()
+                .cloned()
+        });
+
+        let user_data = data.clone();
+        system_scope(|| drop(data));
+        user_data
+    }
+}
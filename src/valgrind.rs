@@ -0,0 +1,111 @@
+use std::arch::asm;
+
+use crate::{page_size, AllocHandle};
+
+// valgrind's client requests, from valgrind.h. the ones of memcheck are
+// numbered from 'M' 'C'.
+const MALLOCLIKE_BLOCK: usize = 0x1301;
+const FREELIKE_BLOCK: usize = 0x1302;
+#[cfg(feature = "graphics")]
+const CHANGE_ERR_DISABLEMENT: usize = 0x1801;
+const MAKE_MEM_NOACCESS: usize = 0x4d43_0000;
+const MAKE_MEM_DEFINED: usize = 0x4d43_0002;
+
+// sends a client request to valgrind: the first element of `args` is the
+// request, and the others are its arguments. valgrind recognizes the magic
+// sequence of rotations that add up to nothing, and the rest of the world runs
+// it as is: it does nothing and returns 0.
+#[cfg(target_arch = "x86_64")]
+fn request(args: [usize; 6]) -> usize {
+    let result;
+    unsafe {
+        asm!(
+            "rol rdi, 3",
+            "rol rdi, 13",
+            "rol rdi, 61",
+            "rol rdi, 51",
+            "xchg rbx, rbx",
+            inout("rdx") 0usize => result,
+            in("rax") args.as_ptr(),
+            inout("rdi") 0usize => _,
+        );
+    }
+    result
+}
+
+#[cfg(target_arch = "aarch64")]
+fn request(args: [usize; 6]) -> usize {
+    let result;
+    unsafe {
+        asm!(
+            "ror x12, x12, #3",
+            "ror x12, x12, #13",
+            "ror x12, x12, #51",
+            "ror x12, x12, #61",
+            "orr x10, x10, x10",
+            inout("x3") 0usize => result,
+            in("x4") args.as_ptr(),
+            inout("x12") 0usize => _,
+        );
+    }
+    result
+}
+
+// valgrind doesn't run anywhere else anyway.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn request(_args: [usize; 6]) -> usize {
+    0
+}
+
+// tells memcheck that the data at `addr` was freed, so that using it gets
+// reported. memcheck wants the address the block was created with.
+pub fn freelike(addr: usize) {
+    request([FREELIKE_BLOCK, addr, 0, 0, 0, 0]);
+}
+
+// the windows read the whole mapping all the time, which memcheck would have a
+// lot to say about.
+#[cfg(feature = "graphics")]
+pub fn disable_errors_in_thread() {
+    request([CHANGE_ERR_DISABLEMENT, 1, 0, 0, 0, 0]);
+}
+
+impl AllocHandle {
+    // the mapping of the allocation, rounded up to whole pages like valgrind
+    // does, and where the data starts in it. none if the memory comes from
+    // another allocator, which valgrind knows about already.
+    fn valgrind_span(&self) -> Option<(usize, usize, usize)> {
+        let map = self.map.map()?;
+        let start = map.as_ptr() as usize;
+        let end = (start + map.len()).div_ceil(page_size()) * page_size();
+        Some((start, start + self.offset(), end))
+    }
+
+    // has memcheck track the data like a block from malloc, so that it gets
+    // reported if it leaks, and makes the rest of the mapping off-limits so
+    // that writing past the end of the data gets reported too. allocation
+    // files are zeroed or hold actual data, so the whole block is defined.
+    pub(crate) fn valgrind_malloclike(&self) {
+        if let Some((_, data, _)) = self.valgrind_span() {
+            request([MALLOCLIKE_BLOCK, data, self.layout.size(), 0, 1, 0]);
+            self.valgrind_hide();
+        }
+    }
+
+    // makes the whole mapping accessible, for the allocator to look at the
+    // redzones or move the data around.
+    pub(crate) fn valgrind_expose(&self) {
+        if let Some((start, _, end)) = self.valgrind_span() {
+            request([MAKE_MEM_DEFINED, start, end - start, 0, 0, 0]);
+        }
+    }
+
+    // makes everything around the data inaccessible again.
+    pub(crate) fn valgrind_hide(&self) {
+        if let Some((start, data, end)) = self.valgrind_span() {
+            let data_end = data + self.layout.size();
+            request([MAKE_MEM_NOACCESS, start, data - start, 0, 0, 0]);
+            request([MAKE_MEM_NOACCESS, data_end, end - data_end, 0, 0, 0]);
+        }
+    }
+}
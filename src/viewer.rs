@@ -0,0 +1,214 @@
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use allocator_api2::alloc::System;
+use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
+use lazy_static::lazy_static;
+use memmap2::{Mmap, MmapOptions};
+
+#[cfg(feature = "graphics")]
+use std::{sync::Arc, time::Instant};
+
+#[cfg(feature = "graphics")]
+use crate::graphics;
+
+use crate::{system_scope, user_error, HexDump, StupidAlloc};
+
+// an allocation file of another process, mapped read-only. like the handles,
+// everything in here only ever gets allocated and freed in system_scope.
+pub struct Attached {
+    pub path: PathBuf,
+    pub map: Mmap,
+    #[cfg(feature = "graphics")]
+    file: Arc<File>,
+    #[cfg(feature = "graphics")]
+    window: Option<graphics::Window>,
+}
+
+#[cfg(feature = "graphics")]
+impl Attached {
+    // opens a window for the file. it belongs to another process, so the
+    // window only ever reads it.
+    pub fn open_window(&mut self, columns: usize) {
+        if self.window.as_ref().is_none_or(|w| w.is_finished()) {
+            self.window = Some(graphics::Window::new(
+                &self.path,
+                None,
+                None,
+                graphics::Source::ReadOnly(Arc::clone(&self.file)),
+                columns,
+                // only the other process knows how old the allocation is, or
+                // what it looks like.
+                Instant::now(),
+                crate::hud::Facts {
+                    layout: None,
+                    resizes: 0,
+                    offset: 0,
+                    node: None,
+                },
+            ));
+        }
+    }
+
+    pub fn set_columns(&self, columns: usize) {
+        if let Some(window) = self.window.as_ref() {
+            let _ = window.tx.send(graphics::Message::Resize { columns });
+        }
+    }
+
+    pub fn close_window(&mut self) {
+        if let Some(window) = self.window.take() {
+            // the window might be closed already, and that's fine.
+            window.free();
+        }
+    }
+}
+
+type AttachedMap = HashMap<usize, Attached, DefaultHashBuilder, System>;
+
+lazy_static! {
+    // the attached files, by the address of their mapping.
+    pub static ref ATTACHED: RwLock<AttachedMap> = RwLock::new(HashMap::new_in(System));
+}
+
+// runs `f` on the attached file containing `addr`, if there is one.
+#[cfg(feature = "graphics")]
+pub fn with_attached(addr: usize, f: impl FnOnce(&mut Attached)) {
+    system_scope(|| {
+        if let Some(attached) = ATTACHED
+            .write()
+            .unwrap()
+            .iter_mut()
+            .find(|(&start, attached)| (start..start + attached.map.len()).contains(&addr))
+            .map(|(_, attached)| attached)
+        {
+            f(attached)
+        }
+    })
+}
+
+// dumps the attached file containing `addr`. the dump may be stupid-allocated
+// while ATTACHED is locked, which is fine since allocating never looks at it.
+pub fn hexdump_attached(addr: usize) -> Option<String> {
+    let attached = ATTACHED.read().unwrap();
+    let (&start, attached) = attached
+        .iter()
+        .find(|(&start, attached)| (start..start + attached.map.len()).contains(&addr))?;
+
+    Some(
+        HexDump {
+            bytes: &attached.map,
+            addr: start,
+        }
+        .to_string(),
+    )
+}
+
+fn map_readonly(path: PathBuf) -> io::Result<Option<(usize, Attached)>> {
+    let file = File::open(&path)?;
+
+    // empty files can't be mapped, and there's nothing to see in them anyway.
+    if file.metadata()?.len() == 0 {
+        return Ok(None);
+    }
+
+    let map = unsafe { MmapOptions::new().map(&file)? };
+
+    Ok(Some((
+        map.as_ptr() as usize,
+        Attached {
+            path,
+            map,
+            #[cfg(feature = "graphics")]
+            file: Arc::new(file),
+            #[cfg(feature = "graphics")]
+            window: None,
+        },
+    )))
+}
+
+impl StupidAlloc {
+    /// Maps the allocation files of another process, found in the folder
+    /// `dir`, read-only. This is made for a "viewer" process watching over a
+    /// "victim" process, which only needs to use the stupid alloc.
+    ///
+    /// The attached files show up in [`StupidAlloc::state()`], keyed by the
+    /// address where they are mapped in this process, and
+    /// [`StupidAlloc::open_window_of()`] opens windows for them, which only
+    /// display the contents: clicking around does nothing. Calling this again
+    /// with the same folder picks up the files that were created in the
+    /// meantime, and forgets the ones that were removed or resized, along with
+    /// their windows. Returns the number of files attached from `dir`.
+    ///
+    /// The contents can also be read through the mapping, although that's
+    /// risky: if the other process shrinks an allocation, reading past the end
+    /// of its file crashes this process.
+    ///
+    /// ```no_run
+    /// use stupidalloc::StupidAlloc;
+    ///
+    /// // the default folder of the victim, when it runs on the same machine,
+    /// // which is named after its pid.
+    /// let pid = 1234;
+    /// let dir = std::env::temp_dir().join("stupidalloc").join(pid.to_string());
+    /// let attached = StupidAlloc.attach_readonly(&dir).unwrap();
+    ///
+    /// for (addr, path) in StupidAlloc.state() {
+    ///     if path.starts_with(&dir) {
+    ///         println!("{} is mapped at {addr:#x}", path.display());
+    ///     }
+    /// }
+    /// # assert!(attached > 0);
+    /// ```
+    pub fn attach_readonly(&self, dir: impl AsRef<Path>) -> io::Result<usize> {
+        let dir = dir.as_ref();
+
+        system_scope(|| {
+            let mut files = Vec::new();
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "mem") {
+                    files.push(path);
+                }
+            }
+
+            let mut attached = ATTACHED.write().unwrap();
+
+            // forget whatever changed since the last time. resized files are
+            // mapped again below.
+            attached.retain(|_, attached| {
+                let keep = attached.path.parent() != Some(dir)
+                    || std::fs::metadata(&attached.path)
+                        .is_ok_and(|m| m.len() == attached.map.len() as u64);
+
+                #[cfg(feature = "graphics")]
+                if !keep {
+                    attached.close_window();
+                }
+
+                keep
+            });
+
+            let mut count = 0;
+            for path in files {
+                if attached.values().any(|attached| attached.path == path) {
+                    count += 1;
+                    continue;
+                }
+
+                // the other process may have freed it in the meantime.
+                if let Ok(Some((addr, new))) = map_readonly(path) {
+                    attached.insert(addr, new);
+                    count += 1;
+                }
+            }
+
+            Ok(count)
+        })
+        .map_err(user_error)
+    }
+}
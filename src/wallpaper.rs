@@ -0,0 +1,216 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io,
+    path::Path,
+    process::Command,
+    sync::{Mutex, OnceLock},
+    thread::Thread,
+    time::Duration,
+};
+
+use crate::{
+    claim_main_thread, overview, shutdown, system_scope, temp_alloc_dir, threads, user_error,
+    StupidAlloc,
+};
+
+/// How the heap overview gets rendered as the desktop wallpaper, set with
+/// [`StupidAlloc::set_wallpaper()`].
+///
+/// ```
+/// use std::time::Duration;
+/// use stupidalloc::Wallpaper;
+///
+/// // a 4K screen, updated every minute.
+/// let wallpaper = Wallpaper::new()
+///     .size(3840, 2160)
+///     .interval(Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wallpaper {
+    width: usize,
+    height: usize,
+    interval: Duration,
+}
+
+impl Default for Wallpaper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wallpaper {
+    /// A 1920 by 1080 wallpaper, updated every 10 seconds.
+    pub fn new() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            interval: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets the size of the picture, in pixels. It should be the one of the
+    /// screen, or the desktop will stretch it.
+    pub fn size(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Sets how long to wait between updates. Desktops don't like having their
+    /// wallpaper changed all the time, so anything below 5 seconds is 5
+    /// seconds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(MIN_INTERVAL);
+        self
+    }
+}
+
+const MIN_INTERVAL: Duration = Duration::from_secs(5);
+
+static WALLPAPER: Mutex<Option<Wallpaper>> = Mutex::new(None);
+
+// the thread updating the wallpaper, which sleeps until it's needed.
+static UPDATER: OnceLock<Thread> = OnceLock::new();
+
+// the hash of the last picture, and which of the two files it went to.
+static LAST: Mutex<(u64, bool)> = Mutex::new((0, false));
+
+// points the desktop to the picture at `path`.
+#[cfg(target_os = "macos")]
+fn set_desktop(path: &Path) -> io::Result<()> {
+    let script = format!(
+        "tell application \"System Events\" to tell every desktop to set picture to {:?}",
+        path.to_string_lossy()
+    );
+    run(Command::new("osascript").args(["-e", &script]))
+}
+
+#[cfg(windows)]
+fn set_desktop(path: &Path) -> io::Result<()> {
+    // SPI_SETDESKWALLPAPER, with SPIF_UPDATEINIFILE | SPIF_SENDCHANGE.
+    let script = format!(
+        "Add-Type -TypeDefinition 'using System.Runtime.InteropServices; public class Desktop {{ [DllImport(\"user32.dll\", CharSet = CharSet.Unicode)] public static extern int SystemParametersInfo(int action, int param, string value, int flags); }}'; [Desktop]::SystemParametersInfo(20, 0, '{}', 3)",
+        path.to_string_lossy()
+    );
+    run(Command::new("powershell").args(["-NoProfile", "-Command", &script]))
+}
+
+// there's a way per desktop environment, and most of them speak GNOME. the
+// others probably have feh.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_desktop(path: &Path) -> io::Result<()> {
+    let uri = format!("file://{}", path.to_string_lossy());
+    let gnome = ["picture-uri", "picture-uri-dark"]
+        .into_iter()
+        .try_for_each(|key| {
+            run(Command::new("gsettings").args(["set", "org.gnome.desktop.background", key, &uri]))
+        });
+
+    gnome.or_else(|_| run(Command::new("feh").arg("--bg-fill").arg(path)))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_desktop(_path: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+fn run(command: &mut Command) -> io::Result<()> {
+    let status = command.status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!("{command:?} failed: {status}"))),
+    }
+}
+
+// renders the overview and makes it the wallpaper, unless it looks exactly
+// like the last one and `force` is false. must be called in system_scope.
+fn update(config: Wallpaper, force: bool) -> io::Result<()> {
+    let (width, height) = (config.width, config.height);
+    let bmp = overview::bmp(&overview::render(width, height), width, height);
+
+    let mut hasher = DefaultHasher::new();
+    bmp.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let mut last = LAST.lock().unwrap();
+    if hash == last.0 && !force {
+        return Ok(());
+    }
+
+    // desktops tend to keep showing the old picture when the file changes but
+    // its name doesn't, so the picture goes back and forth between two files.
+    let path = temp_alloc_dir().join(format!("wallpaper_{}.bmp", u8::from(!last.1)));
+    std::fs::create_dir_all(temp_alloc_dir())?;
+    std::fs::write(&path, bmp)?;
+    set_desktop(&path)?;
+
+    *last = (hash, !last.1);
+    Ok(())
+}
+
+fn updater() {
+    while !shutdown::late() {
+        let config = *WALLPAPER.lock().unwrap();
+        match config {
+            // there is nobody to complain to, and the desktop might come back.
+            Some(config) => {
+                let _ = system_scope(|| update(config, false));
+                std::thread::park_timeout(config.interval);
+            }
+            None => std::thread::park(),
+        }
+    }
+}
+
+// wakes the updater up for it to notice the shutdown, if there is one.
+pub fn stop() {
+    if let Some(updater) = UPDATER.get() {
+        updater.unpark();
+    }
+}
+
+impl StupidAlloc {
+    /// Renders the picture of [`StupidAlloc::save_heap_overview()`] every now
+    /// and then, on a background thread, and sets it as the desktop wallpaper,
+    /// so that the heap is always there behind the editor. [`None`] stops
+    /// updating it, and leaves the last one in place.
+    ///
+    /// The picture is only set again when it changed. On Linux, the GNOME
+    /// settings are tried first, then `feh`. On macOS, every desktop gets it.
+    ///
+    /// ```no_run
+    /// use stupidalloc::{StupidAlloc, Wallpaper};
+    ///
+    /// StupidAlloc.set_wallpaper(Some(Wallpaper::new()));
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "wallpaper")))]
+    pub fn set_wallpaper(&self, wallpaper: Option<Wallpaper>) {
+        *WALLPAPER.lock().unwrap() = wallpaper;
+
+        let updater = system_scope(|| {
+            UPDATER.get_or_init(|| {
+                claim_main_thread();
+                std::thread::Builder::new()
+                    .name("stupidalloc wallpaper".into())
+                    .spawn(|| {
+                        threads::internal();
+                        updater()
+                    })
+                    .unwrap()
+                    .thread()
+                    .clone()
+            })
+        });
+        updater.unpark();
+    }
+
+    /// Sets the desktop wallpaper to the picture of the heap right now, with
+    /// the size of the wallpaper given to [`StupidAlloc::set_wallpaper()`] if
+    /// any, and returns what went wrong if it didn't work.
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "wallpaper")))]
+    pub fn update_wallpaper(&self) -> io::Result<()> {
+        let config = WALLPAPER.lock().unwrap().unwrap_or_default();
+        system_scope(|| update(config, true)).map_err(user_error)
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    os::fd::{AsRawFd, FromRawFd, RawFd},
+    ptr::NonNull,
+};
+
+use crate::page_size;
+
+// there is no mmap on wasi, so the "map" of a file is memory of its own: a copy
+// of the file, read when mapping, and written back to it when flushing. the
+// file is only up to date after a flush, unlike a real memory map.
+pub struct MmapMut {
+    ptr: NonNull<u8>,
+    len: usize,
+    // the file must stay open for as long as the map lives, see keep_open.
+    fd: RawFd,
+}
+
+// SAFETY: the memory is owned by the map, just like a Box would.
+unsafe impl Send for MmapMut {}
+unsafe impl Sync for MmapMut {}
+
+// page-aligned like a real map, for the alignment of the data to come for free.
+fn layout(len: usize) -> Layout {
+    Layout::from_size_align(len.max(1), page_size()).unwrap()
+}
+
+impl MmapMut {
+    // the mapped file, which belongs to someone else.
+    fn file(&self) -> ManuallyDrop<File> {
+        ManuallyDrop::new(unsafe { File::from_raw_fd(self.fd) })
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    pub fn flush_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        let mut file = self.file();
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.write_all(&self[offset..offset + len])
+    }
+}
+
+impl Deref for MmapMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for MmapMut {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for MmapMut {
+    fn drop(&mut self) {
+        // nothing is written back: the map may be replaced by a new one, made
+        // from the file after it was resized.
+        unsafe { System.dealloc(self.ptr.as_ptr(), layout(self.len)) }
+    }
+}
+
+// stands in for the options of memmap2, of which only the length is used.
+pub struct MmapOptions {
+    len: Option<usize>,
+}
+
+impl MmapOptions {
+    pub fn new() -> Self {
+        MmapOptions { len: None }
+    }
+
+    pub fn len(&mut self, len: usize) -> &mut Self {
+        self.len = Some(len);
+        self
+    }
+
+    // copies the file to memory, all of it unless told a length. unsafe for
+    // the sake of looking like memmap2.
+    pub unsafe fn map_mut(&self, file: &File) -> io::Result<MmapMut> {
+        let len = match self.len {
+            Some(len) => len,
+            None => file.metadata()?.len() as usize,
+        };
+        let ptr = NonNull::new(System.alloc_zeroed(layout(len)))
+            .ok_or(io::Error::from(io::ErrorKind::OutOfMemory))?;
+
+        let mut map = MmapMut {
+            ptr,
+            len,
+            fd: file.as_raw_fd(),
+        };
+
+        let mut file = map.file();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut map)?;
+
+        Ok(map)
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    path::Path,
+    sync::{Mutex, RwLock},
+    time::Duration,
+};
+
+use notify::{
+    event::ModifyKind, recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode,
+    Watcher,
+};
+
+use crate::{StupidAlloc, STUPID_MAP};
+
+// modifications made by the allocator itself (like resizing a file) also show
+// up as events. the ones happening this soon after the allocator touched the
+// file are assumed to be ours.
+const OWN_EDIT_DELAY: Duration = Duration::from_millis(100);
+
+// the watcher, created on the first allocation that needs it.
+static WATCHER: Mutex<Option<RecommendedWatcher>> = Mutex::new(None);
+
+// the user's callback for external edits, given the address and path of the
+// allocation.
+type Callback = fn(usize, &Path);
+static CALLBACK: RwLock<Option<Callback>> = RwLock::new(None);
+
+// starts watching the file of an allocation. needs to be called while
+// ALLOCATING is nonzero, since the watcher lives forever.
+pub fn watch(path: &Path) {
+    let mut watcher = WATCHER.lock().unwrap();
+
+    if watcher.is_none() {
+        *watcher = recommended_watcher(on_event).ok();
+    }
+
+    if let Some(watcher) = watcher.as_mut() {
+        // not being able to watch a file is not worth failing an allocation.
+        let _ = watcher.watch(path, RecursiveMode::NonRecursive);
+    }
+}
+
+// stops watching the file of an allocation. needs to be called while
+// ALLOCATING or DEALLOCATING is nonzero.
+pub fn unwatch(path: &Path) {
+    if let Some(watcher) = WATCHER.lock().unwrap().as_mut() {
+        let _ = watcher.unwatch(path);
+    }
+}
+
+// gets rid of the watcher and its thread. needs to be called in system_scope.
+pub fn stop() {
+    let watcher = WATCHER.lock().unwrap().take();
+    drop(watcher);
+}
+
+// runs on the thread of the watcher.
+fn on_event(event: notify::Result<Event>) {
+    // the watcher thread is probably not stupid already, but make sure of it
+    // since we're about to lock the registry.
+    StupidAlloc.enable_in_thread(false);
+
+    let Ok(event) = event else {
+        return;
+    };
+
+    // editors tend to replace the file rather than write to it, which only
+    // shows up as a change of its number of links while it's mapped.
+    if !matches!(
+        event.kind,
+        EventKind::Modify(
+            ModifyKind::Data(_) | ModifyKind::Any | ModifyKind::Name(_) | ModifyKind::Metadata(_)
+        ) | EventKind::Remove(_)
+    ) {
+        return;
+    }
+
+    for path in &event.paths {
+        let edited = STUPID_MAP
+            .write_all()
+            .iter_mut()
+            .find(|(_, handle)| &handle.path == path && handle.touched.elapsed() > OWN_EDIT_DELAY)
+            .map(|(&addr, handle)| {
+                // for the checksums to tell the edit apart from corruption.
+                handle.edited = true;
+
+                #[cfg(feature = "logging")]
+                handle.log(format_args!(
+                    "## External edit\nThe allocation file was modified by another program.\n"
+                ));
+
+                addr
+            });
+
+        if let Some(addr) = edited {
+            if let Some(callback) = *CALLBACK.read().unwrap() {
+                callback(addr, path);
+            }
+        }
+    }
+}
+
+impl StupidAlloc {
+    /// Sets a function to be called whenever an allocation file is modified,
+    /// moved or removed by another program, like a hex editor. It is given the address of the
+    /// allocation and the path of its file, and runs on a background thread.
+    ///
+    /// Edits are detected regardless of this function, and recorded in the
+    /// log file of the allocation if the `logging` feature is enabled.
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "watch")))]
+    pub fn on_external_edit(&self, callback: fn(usize, &Path)) {
+        *CALLBACK.write().unwrap() = Some(callback);
+    }
+}
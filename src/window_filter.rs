@@ -0,0 +1,135 @@
+use std::{
+    backtrace::Backtrace,
+    ops::{Bound, RangeBounds},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{hud, StupidAlloc};
+
+/// Which new allocations get a window of their own with the `always-graphics`
+/// feature, for when a window per allocation is a few thousand windows too
+/// many. Set with [`StupidAlloc::set_window_filter()`].
+///
+/// An allocation needs to match everything that was asked for. With nothing
+/// asked for, all of them match.
+///
+/// ```
+/// use stupidalloc::WindowFilter;
+///
+/// // buffers of at least a page, made by the parser.
+/// let filter = WindowFilter::new()
+///     .sizes(4096..)
+///     .call_site("my_crate::parser");
+/// ```
+#[derive(Debug, Clone)]
+pub struct WindowFilter {
+    sizes: (Bound<usize>, Bound<usize>),
+    label: Option<String>,
+    call_site: Option<String>,
+}
+
+impl Default for WindowFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WindowFilter {
+    /// Matches every allocation, until told otherwise by the other methods.
+    pub fn new() -> Self {
+        Self {
+            sizes: (Bound::Unbounded, Bound::Unbounded),
+            label: None,
+            call_site: None,
+        }
+    }
+
+    /// Only matches allocations with a size in `range`, in bytes.
+    pub fn sizes(mut self, range: impl RangeBounds<usize>) -> Self {
+        self.sizes = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    /// Only matches allocations whose label contains `pattern`. The label is
+    /// the type of the allocation when it's known, and the name of its file
+    /// otherwise. Allocations made with [`stupid_box!`](crate::stupid_box)
+    /// and [`stupid_vec!`](crate::stupid_vec) learn their type right after
+    /// being allocated, and their window opens then.
+    pub fn label(mut self, pattern: impl Into<String>) -> Self {
+        self.label = Some(pattern.into());
+        self
+    }
+
+    /// Only matches allocations whose backtrace contains `pattern`, like the
+    /// name of a function or of a module. Capturing backtraces is slow, so
+    /// is this.
+    pub fn call_site(mut self, pattern: impl Into<String>) -> Self {
+        self.call_site = Some(pattern.into());
+        self
+    }
+
+    fn matches(&self, size: usize, path: &Path, type_name: Option<&str>) -> bool {
+        if !self.sizes.contains(&size) {
+            return false;
+        }
+        if let Some(pattern) = &self.label {
+            if !hud::label(path, type_name).contains(pattern.as_str()) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.call_site {
+            if !Backtrace::force_capture()
+                .to_string()
+                .contains(pattern.as_str())
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+static FILTER: Mutex<Option<WindowFilter>> = Mutex::new(None);
+
+// whether a new allocation of `size` bytes gets a window. must be called while
+// ALLOCATING is nonzero, since capturing a backtrace allocates.
+pub fn matches(size: usize, path: &Path) -> bool {
+    FILTER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_none_or(|filter| filter.matches(size, path, None))
+}
+
+// whether an allocation that just learnt its type gets a window it didn't get
+// when it was allocated. only the label can have changed since then, and the
+// call site is still on the stack. must be called in system_scope.
+pub fn matches_type(size: usize, path: &Path, type_name: &str) -> bool {
+    FILTER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|filter| filter.label.is_some() && filter.matches(size, path, Some(type_name)))
+}
+
+impl StupidAlloc {
+    /// Sets which subsequent allocations get a window of their own with the
+    /// `always-graphics` feature, or all of them with [`None`]. Windows can
+    /// still be opened for any allocation with
+    /// [`StupidAlloc::open_window_of()`].
+    ///
+    /// ```
+    /// use stupidalloc::{StupidAlloc, WindowFilter};
+    ///
+    /// StupidAlloc.set_window_filter(Some(WindowFilter::new().label("Matrix")));
+    /// ```
+    #[cfg_attr(feature = "nightly", doc(cfg(feature = "always-graphics")))]
+    pub fn set_window_filter(&self, filter: Option<WindowFilter>) {
+        // the old filter gets dropped once the lock is released, in case
+        // freeing it needs the allocator.
+        let old = std::mem::replace(&mut *FILTER.lock().unwrap(), filter);
+        drop(old);
+    }
+}